@@ -12,6 +12,7 @@ class CryptoOutput(BaseModel):
     ciphertext: str
     salt: str
     nonce: str
+    kdf_params: str | None = None
 
 class CryptoFactory:
     """
@@ -71,7 +72,8 @@ class CryptoFactory:
                 encrypted.salt,
                 encrypted.nonce,
                 self.master_key,
-                context
+                context,
+                kdf_params=encrypted.kdf_params
             )
             return plaintext
         except Exception as e:
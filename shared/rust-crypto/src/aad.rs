@@ -0,0 +1,91 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyBytes, PyDict, PyInt, PyList, PyString, PyTuple};
+use std::ops::Deref;
+
+// Start the structured encodings. 0xFF never occurs in UTF-8, so no plain string
+// `aad_context` can encode to the same bytes as a dict or a list of parts.
+const TAG_DICT: &[u8] = b"\xffksc-aad-dict\x01";
+const TAG_PARTS: &[u8] = b"\xffksc-aad-parts\x01";
+
+fn push_part(out: &mut Vec<u8>, part: &[u8]) {
+    out.extend_from_slice(&(part.len() as u32).to_be_bytes());
+    out.extend_from_slice(part);
+}
+
+/// Type-tagged bytes of one dict value or part, so `{"row": 1}` and `{"row": "1"}` differ.
+fn encode_value(value: &Bound<'_, PyAny>) -> PyResult<Vec<u8>> {
+    let (tag, bytes) = if let Ok(value) = value.cast::<PyString>() {
+        (b's', value.to_str()?.as_bytes().to_vec())
+    } else if let Ok(value) = value.cast::<PyBytes>() {
+        (b'b', value.as_bytes().to_vec())
+    } else if value.is_instance_of::<PyInt>() && !value.is_instance_of::<PyBool>() {
+        (b'i', value.str()?.to_str()?.as_bytes().to_vec())
+    } else {
+        return Err(pyo3::exceptions::PyTypeError::new_err(format!(
+            "aad_context values and parts must be str, bytes or int, not {}", value.get_type().name()?
+        )));
+    };
+    Ok([&[tag][..], &bytes].concat())
+}
+
+/// Canonical form of a dict `aad_context`: entries sorted by key, each key and
+/// value length-prefixed, so neither key order nor formatting can change it.
+fn encode_dict(dict: &Bound<'_, PyDict>) -> PyResult<Vec<u8>> {
+    let mut entries = Vec::with_capacity(dict.len());
+    for (key, value) in dict.iter() {
+        let key = key.cast::<PyString>()
+            .map_err(|_| pyo3::exceptions::PyTypeError::new_err("aad_context keys must be str"))?;
+        entries.push((key.to_str()?.to_string(), encode_value(&value)?));
+    }
+    entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+    let mut out = TAG_DICT.to_vec();
+    out.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+    for (key, value) in &entries {
+        push_part(&mut out, key.as_bytes());
+        push_part(&mut out, value);
+    }
+    Ok(out)
+}
+
+/// Ordered parts such as `(tenant, record_id, schema_version)`, each length-prefixed
+/// so `("1", "23")` and `("12", "3")` stay distinct.
+fn encode_parts(parts: &Bound<'_, PyAny>) -> PyResult<Vec<u8>> {
+    let parts = parts.try_iter()?.map(|part| encode_value(&part?)).collect::<PyResult<Vec<_>>>()?;
+    let mut out = TAG_PARTS.to_vec();
+    out.extend_from_slice(&(parts.len() as u32).to_be_bytes());
+    for part in &parts {
+        push_part(&mut out, part);
+    }
+    Ok(out)
+}
+
+/// `aad_context` argument: a `str`, used as is (UTF-8); a dict such as
+/// `{"tenant": ..., "table": ..., "row": ...}`, canonicalized so the same dict always
+/// gives the same associated data; or a list/tuple of parts, kept in order.
+pub(crate) struct AadArg(Vec<u8>);
+
+impl Deref for AadArg {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<'a, 'py> FromPyObject<'a, 'py> for AadArg {
+    type Error = PyErr;
+
+    fn extract(obj: Borrowed<'a, 'py, PyAny>) -> PyResult<Self> {
+        if let Ok(context) = obj.cast::<PyString>() {
+            return Ok(AadArg(context.to_str()?.as_bytes().to_vec()));
+        }
+        if let Ok(dict) = obj.cast::<PyDict>() {
+            return encode_dict(&dict).map(AadArg);
+        }
+        if obj.is_instance_of::<PyList>() || obj.is_instance_of::<PyTuple>() {
+            return encode_parts(&obj).map(AadArg);
+        }
+        Err(pyo3::exceptions::PyTypeError::new_err("aad_context must be a str, dict, list or tuple"))
+    }
+}
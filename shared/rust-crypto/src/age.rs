@@ -0,0 +1,102 @@
+use pyo3::prelude::*;
+use ::age::armor::{ArmoredReader, ArmoredWriter, Format};
+use ::age::secrecy::{ExposeSecret, SecretString};
+use ::age::{scrypt, x25519, Decryptor, Encryptor, Identity, Recipient};
+use std::io::{Read, Write};
+
+use crate::audit::Operation;
+use crate::error::Error;
+
+const FIPS_NAME: &str = "age (X25519, scrypt, ChaCha20-Poly1305)";
+
+fn encrypt(plaintext: &[u8], recipients: &[String], passphrase: Option<&str>, armor: bool) -> Result<Vec<u8>, Error> {
+    crate::fips::reject(FIPS_NAME)?;
+    let recipients: Vec<Box<dyn Recipient + Send>> = match passphrase {
+        // age only allows an scrypt stanza on its own
+        Some(_) if !recipients.is_empty() => return Err(Error::crypto("passphrase cannot be combined with recipients")),
+        Some(passphrase) => vec![Box::new(scrypt::Recipient::new(SecretString::from(passphrase.to_string())))],
+        None if recipients.is_empty() => return Err(
+            Error::config("At least one recipient or a passphrase is required")
+        ),
+        None => recipients.iter()
+            .map(|r| r.trim().parse::<x25519::Recipient>()
+                .map(|r| Box::new(r) as Box<dyn Recipient + Send>)
+                .map_err(|e| Error::invalid_format(format!("Invalid age recipient {:?}: {}", r, e))))
+            .collect::<Result<_, _>>()?,
+    };
+
+    let encryptor = Encryptor::with_recipients(recipients.iter().map(|r| r.as_ref() as &dyn Recipient))
+        .map_err(|e| Error::crypto(format!("age encryption failed: {}", e)))?;
+    let format = if armor { Format::AsciiArmor } else { Format::Binary };
+    let io_error = |e: std::io::Error| Error::crypto(format!("age encryption failed: {}", e));
+
+    let mut out = Vec::with_capacity(plaintext.len() + 256);
+    let armored = ArmoredWriter::wrap_output(&mut out, format).map_err(io_error)?;
+    let mut writer = encryptor.wrap_output(armored).map_err(io_error)?;
+    writer.write_all(plaintext).map_err(io_error)?;
+    writer.finish().and_then(|armored| armored.finish()).map_err(io_error)?;
+    Ok(out)
+}
+
+fn decrypt(ciphertext: &[u8], identities: &[String], passphrase: Option<&str>) -> Result<Vec<u8>, Error> {
+    crate::fips::reject(FIPS_NAME)?;
+    let mut keys: Vec<Box<dyn Identity>> = Vec::new();
+    for identity in identities {
+        let identity = identity.trim().parse::<x25519::Identity>()
+            .map_err(|e| Error::invalid_format(format!("Invalid age identity: {}", e)))?;
+        keys.push(Box::new(identity));
+    }
+    if let Some(passphrase) = passphrase {
+        keys.push(Box::new(scrypt::Identity::new(SecretString::from(passphrase.to_string()))));
+    }
+    if keys.is_empty() {
+        return Err(Error::config("At least one identity or a passphrase is required"));
+    }
+
+    // ArmoredReader passes binary files through unchanged
+    let decryptor = Decryptor::new_buffered(ArmoredReader::new(ciphertext))
+        .map_err(|e| Error::invalid_format(format!("Invalid age file: {}", e)))?;
+    let mut reader = decryptor.decrypt(keys.iter().map(|k| k.as_ref()))
+        .map_err(|e| Error::crypto(format!("age decryption failed: {}", e)))?;
+    let mut plaintext = Vec::new();
+    reader.read_to_end(&mut plaintext).map_err(|e| Error::crypto(format!("age decryption failed: {}", e)))?;
+    Ok(plaintext)
+}
+
+/// New X25519 identity for the age format: returns `(identity, recipient)` as the
+/// `AGE-SECRET-KEY-1...` and `age1...` strings `age-keygen` prints.
+#[pyfunction]
+pub fn age_generate_identity() -> (String, String) {
+    let identity = x25519::Identity::generate();
+    (identity.to_string().expose_secret().to_string(), identity.to_public().to_string())
+}
+
+/// Encrypts into an age file that `age -d` can open, either to X25519 `recipients`
+/// (`age1...`) or to a `passphrase` (scrypt). `armor=True` produces the PEM-style
+/// text form (`age -a`).
+#[pyfunction]
+#[pyo3(signature = (plaintext, recipients=Vec::new(), passphrase=None, armor=false))]
+pub fn age_encrypt(
+    py: Python<'_>,
+    plaintext: &[u8],
+    recipients: Vec<String>,
+    passphrase: Option<&str>,
+    armor: bool
+) -> PyResult<Vec<u8>> {
+    py.detach(|| Operation::new("encrypt", "age").run(|| encrypt(plaintext, &recipients, passphrase, armor)))
+        .map_err(crate::error::to_pyerr)
+}
+
+/// Decrypts a binary or armored age file with any of the X25519 `identities`
+/// (`AGE-SECRET-KEY-1...`) or a `passphrase`.
+#[pyfunction]
+#[pyo3(signature = (ciphertext, identities=Vec::new(), passphrase=None))]
+pub fn age_decrypt(
+    py: Python<'_>,
+    ciphertext: &[u8],
+    identities: Vec<String>,
+    passphrase: Option<&str>
+) -> PyResult<Vec<u8>> {
+    py.detach(|| Operation::new("decrypt", "age").run(|| decrypt(ciphertext, &identities, passphrase)))
+        .map_err(crate::error::to_pyerr)
+}
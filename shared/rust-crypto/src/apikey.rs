@@ -0,0 +1,95 @@
+use pyo3::prelude::*;
+use argon2::password_hash::rand_core::OsRng;
+use hmac::{digest::KeyInit, Hmac, Mac};
+use rand::Rng;
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+use crate::error::Error;
+use crate::master_key::MasterKeyArg;
+
+const BASE62: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+/// 30 base62 characters, ~178 bits, like GitHub's tokens.
+const RANDOM_LEN: usize = 30;
+/// CRC-32 in base62 always fits 6 characters.
+const CHECKSUM_LEN: usize = 6;
+/// HMAC key when no pepper is given: the verifier is then a plain keyed hash, which
+/// is enough for keys this random.
+const DEFAULT_PEPPER: &[u8] = b"k-services/api-key";
+const VERIFIER_PREFIX: &str = "hmac-sha256:";
+
+/// CRC-32 (IEEE), for catching typos and for secret scanners; not a security check.
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| if crc & 1 == 1 { crc >> 1 ^ 0xedb8_8320 } else { crc >> 1 })
+    })
+}
+
+fn checksum(body: &str) -> String {
+    let mut value = crc32(body.as_bytes());
+    let mut out = [b'0'; CHECKSUM_LEN];
+    for digit in out.iter_mut().rev() {
+        *digit = BASE62[(value % 62) as usize];
+        value /= 62;
+    }
+    String::from_utf8(out.to_vec()).expect("base62 is ASCII")
+}
+
+/// Whether `key` is `prefix_` + random part + a matching checksum.
+fn well_formed(key: &str) -> bool {
+    let Some((prefix, rest)) = key.rsplit_once('_') else { return false };
+    if !valid_prefix(prefix) || rest.len() != RANDOM_LEN + CHECKSUM_LEN || !rest.bytes().all(|b| BASE62.contains(&b)) {
+        return false;
+    }
+    let (body, sum) = key.split_at(key.len() - CHECKSUM_LEN);
+    checksum(body) == sum
+}
+
+fn valid_prefix(prefix: &str) -> bool {
+    (1..=16).contains(&prefix.len()) && prefix.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_')
+}
+
+fn verifier(key: &str, pepper: Option<&[u8]>) -> String {
+    let mut mac = <Hmac<Sha256> as KeyInit>::new_from_slice(pepper.unwrap_or(DEFAULT_PEPPER))
+        .expect("HMAC accepts keys of any length");
+    mac.update(key.as_bytes());
+    format!("{}{}", VERIFIER_PREFIX, crate::hash::to_hex(&mac.finalize().into_bytes()))
+}
+
+/// Mints an API key `<prefix>_<30 random base62><6 checksum>` (e.g. `ksk_...`, as
+/// GitHub's `ghp_` tokens) and returns `(key, verifier)`. Show the key to the user
+/// once and store only the verifier, an HMAC-SHA256 of the key under `pepper` (a
+/// server-side secret kept out of the database; optional, since the keys are too
+/// random to brute-force). The checksum lets `is_valid_api_key` and secret scanners
+/// reject mistyped or made-up keys without a lookup.
+///
+/// `prefix` is 1-16 lower-case letters, digits or underscores.
+#[pyfunction]
+#[pyo3(signature = (prefix, pepper=None))]
+pub fn generate_api_key(prefix: &str, pepper: Option<MasterKeyArg>) -> PyResult<(String, String)> {
+    if !valid_prefix(prefix) {
+        return Err(crate::error::to_pyerr(
+            Error::config("prefix must be 1-16 lower-case letters, digits or underscores").with_input("prefix")
+        ));
+    }
+    let random: String = (0..RANDOM_LEN).map(|_| BASE62[OsRng.gen_range(0..BASE62.len())] as char).collect();
+    let body = format!("{}_{}", prefix, random);
+    let key = format!("{}{}", body, checksum(&body));
+    let verifier = verifier(&key, pepper.as_deref());
+    Ok((key, verifier))
+}
+
+/// Checks `key` against the verifier `generate_api_key` returned for it, in constant
+/// time, with the same `pepper`. Malformed keys and bad checksums are `False`.
+#[pyfunction]
+#[pyo3(signature = (key, stored_hash, pepper=None))]
+pub fn verify_api_key(key: &str, stored_hash: &str, pepper: Option<MasterKeyArg>) -> bool {
+    well_formed(key) && bool::from(verifier(key, pepper.as_deref()).as_bytes().ct_eq(stored_hash.as_bytes()))
+}
+
+/// Format and checksum check only, with no secret involved: for rejecting garbage
+/// before a database lookup, or scanning text for leaked keys.
+#[pyfunction]
+pub fn is_valid_api_key(key: &str) -> bool {
+    well_formed(key)
+}
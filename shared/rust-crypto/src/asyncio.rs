@@ -0,0 +1,102 @@
+use pyo3::prelude::*;
+use std::sync::Mutex;
+
+use crate::aad::AadArg;
+use crate::cipher::Algorithm;
+use crate::encoding::{Encoded, Encoding};
+use crate::error::Error;
+use crate::master_key::MasterKeyArg;
+
+type Job = Box<dyn FnOnce(Python<'_>) -> PyResult<Py<PyAny>> + Send>;
+
+/// One-shot callable handed to the event loop's executor.
+#[pyclass(frozen)]
+struct ExecutorJob(Mutex<Option<Job>>);
+
+#[pymethods]
+impl ExecutorJob {
+    fn __call__(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        let job = self.0.lock().unwrap().take()
+            .ok_or_else(|| pyo3::exceptions::PyRuntimeError::new_err("Job already ran"))?;
+        job(py)
+    }
+}
+
+/// Runs `job` on the running event loop's default executor and returns the awaitable
+/// future, so Argon2 never runs on (or blocks) the event loop thread. The executor's
+/// threads belong to Python, which joins them at exit; `job` releases the GIL itself
+/// around the heavy work.
+fn run_blocking<'py>(
+    py: Python<'py>,
+    job: impl FnOnce(Python<'_>) -> PyResult<Py<PyAny>> + Send + 'static
+) -> PyResult<Bound<'py, PyAny>> {
+    let event_loop = py.import("asyncio")?.call_method0("get_running_loop")?;
+    let job = ExecutorJob(Mutex::new(Some(Box::new(job))));
+    event_loop.call_method1("run_in_executor", (py.None(), job))
+}
+
+/// Awaitable version of `encrypt_with_context`: `await` it from a coroutine to get
+/// the same dict, while the KDF and cipher run on a background thread.
+#[pyfunction]
+#[pyo3(signature = (
+    plaintext, master_key, aad_context, algorithm=Algorithm::DEFAULT_NAME,
+    memory_cost=None, time_cost=None, parallelism=None, kdf_profile=None, kdf_params=None,
+    detached_tag=false, encoding=Encoding::DEFAULT_NAME
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn encrypt_with_context_async<'py>(
+    py: Python<'py>,
+    plaintext: String,
+    master_key: MasterKeyArg,
+    aad_context: AadArg,
+    algorithm: &str,
+    memory_cost: Option<u32>,
+    time_cost: Option<u32>,
+    parallelism: Option<u32>,
+    kdf_profile: Option<&str>,
+    kdf_params: Option<&str>,
+    detached_tag: bool,
+    encoding: &str
+) -> PyResult<Bound<'py, PyAny>> {
+    let algorithm = Algorithm::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+    let encoding = Encoding::from_name(encoding).map_err(crate::error::to_pyerr)?;
+    let (kdf_params, kdf_profile) = crate::resolve_kdf_params(kdf_params, kdf_profile, memory_cost, time_cost, parallelism)?;
+    run_blocking(py, move |py| {
+        let sealed = py.detach(|| crate::seal(algorithm, kdf_params, plaintext.as_bytes(), &master_key, &aad_context))
+            .map_err(crate::error::to_pyerr)?;
+        crate::sealed_to_dict(py, &sealed, kdf_profile, detached_tag, encoding).map(Py::into_any)
+    })
+}
+
+/// Awaitable version of `decrypt_with_context`.
+#[pyfunction]
+#[pyo3(signature = (
+    ciphertext_b64, salt_b64, nonce_b64, master_key, aad_context, algorithm=Algorithm::DEFAULT_NAME,
+    kdf_params=None, tag_b64=None,
+    encoding=Encoding::DEFAULT_NAME
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn decrypt_with_context_async<'py>(
+    py: Python<'py>,
+    ciphertext_b64: Encoded,
+    salt_b64: Encoded,
+    nonce_b64: Encoded,
+    master_key: MasterKeyArg,
+    aad_context: AadArg,
+    algorithm: &str,
+    kdf_params: Option<&str>,
+    tag_b64: Option<Encoded>,
+    encoding: &str
+) -> PyResult<Bound<'py, PyAny>> {
+    let algorithm = Algorithm::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+    let encoding = Encoding::from_name(encoding).map_err(crate::error::to_pyerr)?;
+    let kdf_params = crate::parse_kdf_params(kdf_params)?;
+    run_blocking(py, move |py| {
+        let plaintext = py.detach(|| crate::open(
+            algorithm, kdf_params, encoding, &ciphertext_b64, &salt_b64, &nonce_b64, tag_b64.as_ref(), &master_key, &aad_context
+        )).map_err(crate::error::to_pyerr)?;
+        let plaintext = String::from_utf8(plaintext)
+            .map_err(|e| crate::error::to_pyerr(Error::invalid_format(format!("Invalid UTF-8: {}", e))))?;
+        Ok(plaintext.into_pyobject(py)?.into_any().unbind())
+    })
+}
@@ -0,0 +1,189 @@
+use pyo3::prelude::*;
+use pyo3::sync::MutexExt;
+use pyo3::types::PyDict;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::error::Error;
+use crate::json::{to_json, to_python};
+
+/// Hex characters of the AAD's SHA-256 kept in records: enough to tell contexts
+/// apart, too few to be a useful oracle for guessing them.
+const AAD_FINGERPRINT_LEN: usize = 16;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static CALLBACK: Mutex<Option<Py<PyAny>>> = Mutex::new(None);
+static CHAIN: Mutex<Chain> = Mutex::new(Chain { sequence: 0, hash: [0; 32] });
+
+/// Sequence number and hash of the last record delivered.
+struct Chain {
+    sequence: u64,
+    hash: [u8; 32],
+}
+
+fn chain_hash(previous: &[u8; 32], record: &Map<String, Value>) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(previous);
+    hasher.update(serde_json::to_vec(record).expect("records serialize"));
+    hasher.finalize().into()
+}
+
+/// An operation to report, described by labels only: never keys, plaintext or AAD.
+pub(crate) struct Operation<'a> {
+    name: &'static str,
+    algorithm: &'a str,
+    key_id: Option<&'a str>,
+    aad_context: Option<&'a [u8]>,
+}
+
+impl<'a> Operation<'a> {
+    pub(crate) const fn new(name: &'static str, algorithm: &'a str) -> Self {
+        Operation { name, algorithm, key_id: None, aad_context: None }
+    }
+
+    pub(crate) const fn with_key_id(mut self, key_id: Option<&'a str>) -> Self {
+        self.key_id = key_id;
+        self
+    }
+
+    pub(crate) const fn with_aad(mut self, aad_context: &'a [u8]) -> Self {
+        self.aad_context = Some(aad_context);
+        self
+    }
+
+    /// Runs `run` and reports how it went. With a callback installed, an operation
+    /// that succeeded fails if the record can't be delivered.
+    pub(crate) fn run<T>(&self, run: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+        self.run_checked(run, |_| None)
+    }
+
+    /// `run` for one step of a longer operation (a stream `update()`): only a failure
+    /// is reported, since the last step reports the operation as a whole.
+    pub(crate) fn run_step<T>(&self, run: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+        if !ENABLED.load(Ordering::Relaxed) {
+            return run();
+        }
+        let start = Instant::now();
+        let result = run();
+        if let Err(error) = &result {
+            // The step's own error is the one worth raising
+            let _ = self.report(error.code(), start.elapsed().as_secs_f64());
+        }
+        result
+    }
+
+    /// `run`, where `rejected` gives the code for results that aren't errors but
+    /// should be reported as failures (a signature that doesn't verify).
+    pub(crate) fn run_checked<T>(
+        &self,
+        run: impl FnOnce() -> Result<T, Error>,
+        rejected: impl FnOnce(&T) -> Option<&'static str>
+    ) -> Result<T, Error> {
+        if !ENABLED.load(Ordering::Relaxed) {
+            return run();
+        }
+        let start = Instant::now();
+        let result = run();
+        let duration = start.elapsed().as_secs_f64();
+        let outcome = match &result {
+            Ok(value) => rejected(value).unwrap_or("ok"),
+            Err(error) => error.code(),
+        };
+        let reported = self.report(outcome, duration);
+        result.and_then(|value| reported.map(|()| value))
+    }
+
+    /// Must not be called with a lock held that a GIL-holding thread might wait on.
+    fn report(&self, outcome: &str, duration: f64) -> Result<(), Error> {
+        Python::attach(|py| {
+            let Some(callback) = CALLBACK.lock().unwrap().as_ref().map(|callback| callback.clone_ref(py)) else {
+                return Ok(());
+            };
+            // Held until the callback returns, so records arrive in chain order
+            let mut chain = CHAIN.lock_py_attached(py).unwrap();
+            let time = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |d| d.as_secs_f64());
+            let aad_fingerprint = self.aad_context
+                .map(|aad| crate::hash::to_hex(&Sha256::digest(aad))[..AAD_FINGERPRINT_LEN].to_string());
+            let mut record = Map::new();
+            record.insert("sequence".to_string(), chain.sequence.into());
+            record.insert("time".to_string(), time.into());
+            record.insert("operation".to_string(), self.name.into());
+            record.insert("algorithm".to_string(), self.algorithm.into());
+            record.insert("key_id".to_string(), self.key_id.into());
+            record.insert("aad_fingerprint".to_string(), aad_fingerprint.into());
+            record.insert("outcome".to_string(), outcome.into());
+            record.insert("duration".to_string(), duration.into());
+            let hash = chain_hash(&chain.hash, &record);
+            record.insert("hash".to_string(), crate::hash::to_hex(&hash).into());
+
+            let record = to_python(py, &Value::Object(record)).map_err(|e| Error::crypto(e.to_string()))?;
+            callback.call1(py, (record,))
+                .map_err(|e| Error::crypto(format!("Audit callback failed: {}", e)).with_code("audit_failed"))?;
+            chain.sequence += 1;
+            chain.hash = hash;
+            Ok(())
+        })
+    }
+}
+
+/// Installs `callback(record)`, called after every encrypt, decrypt, sign and verify,
+/// whatever the format (envelopes, `CryptoContext`, streams and files, session
+/// tokens, Fernet, JWE/JWT, PASETO, Branca, libsodium, age, ...), in the thread that
+/// ran it. A stream is one operation, reported by `finalize()` (and by a failing
+/// `update()`). `record` is a dict: `sequence`, `time` (unix seconds), `operation`,
+/// `algorithm`, `key_id`, `aad_fingerprint` (a short SHA-256 of the AAD, or `None`),
+/// `outcome` (`"ok"` or the error `code`) and `duration` (seconds; the master-key
+/// derivation isn't counted). No record carries keys, plaintext or AAD.
+///
+/// Records form a hash chain (`hash` covers the record and the previous `hash`), so
+/// `verify_audit_chain` detects records that were removed, reordered or edited after
+/// delivery. If the callback raises, the operation fails with `CryptoError`, so
+/// nothing succeeds unrecorded. Installing a callback restarts the chain at
+/// `sequence` 0; `None` removes it.
+#[pyfunction]
+#[pyo3(signature = (callback=None))]
+pub fn set_audit_callback(py: Python<'_>, callback: Option<Py<PyAny>>) -> PyResult<()> {
+    if callback.as_ref().is_some_and(|callback| !callback.bind(py).is_callable()) {
+        return Err(pyo3::exceptions::PyTypeError::new_err("callback must be callable"));
+    }
+    let mut chain = CHAIN.lock_py_attached(py).unwrap();
+    *chain = Chain { sequence: 0, hash: [0; 32] };
+    ENABLED.store(callback.is_some(), Ordering::Relaxed);
+    *CALLBACK.lock().unwrap() = callback;
+    Ok(())
+}
+
+/// Whether `records` are consecutive audit records, unaltered and in order, from the
+/// start of the chain or, with `previous_hash`, from the record after the one with
+/// that `hash`.
+#[pyfunction]
+#[pyo3(signature = (records, previous_hash=None))]
+pub fn verify_audit_chain(records: Vec<Bound<'_, PyDict>>, previous_hash: Option<&str>) -> PyResult<bool> {
+    let mut previous = [0u8; 32];
+    if let Some(previous_hash) = previous_hash {
+        let decoded = crate::encoding::hex_decode(previous_hash)
+            .map_err(|e| crate::error::to_pyerr(e.context("Invalid previous_hash")))?;
+        previous = decoded.try_into()
+            .map_err(|_| crate::error::to_pyerr(
+                Error::invalid_format("Invalid previous_hash: expected a 64-digit SHA-256 hex digest")
+            ))?;
+    }
+    let mut sequence = None;
+    for record in records {
+        let Value::Object(mut record) = to_json(record.as_any())? else { unreachable!("dicts convert to objects") };
+        let Some(Value::String(hash)) = record.remove("hash") else { return Ok(false) };
+        let Some(number) = record.get("sequence").and_then(Value::as_u64) else { return Ok(false) };
+        if sequence.is_some_and(|expected| number != expected) || (previous_hash.is_none() && sequence.is_none() && number != 0) {
+            return Ok(false);
+        }
+        let expected = chain_hash(&previous, &record);
+        if crate::hash::to_hex(&expected) != hash {
+            return Ok(false);
+        }
+        (previous, sequence) = (expected, Some(number + 1));
+    }
+    Ok(true)
+}
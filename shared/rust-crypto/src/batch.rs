@@ -0,0 +1,211 @@
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use argon2::password_hash::rand_core::OsRng;
+use rand::RngCore;
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+use crate::aad::AadArg;
+use crate::buffer::BytesLike;
+use crate::cipher::Algorithm;
+use crate::encoding::{Encoded, Encoding};
+use crate::envelope::{default_extensions, Envelope};
+use crate::error::Error;
+use crate::master_key::MasterKeyArg;
+use crate::progress::{CancellationToken, Progress, DEFAULT_PROGRESS_INTERVAL};
+
+/// Memory that `decrypt_many`'s concurrent key derivations may take together; a
+/// batch of 1 GiB derivations runs them one at a time.
+const KDF_MEMORY_BUDGET: u64 = 1024 * 1024 * 1024;
+
+fn check_lengths(items: usize, aad_list: &[AadArg]) -> PyResult<()> {
+    if items != aad_list.len() {
+        return Err(crate::error::to_pyerr(Error::crypto(format!(
+            "aad_list has {} entries for {} items", aad_list.len(), items
+        ))));
+    }
+    Ok(())
+}
+
+/// Runs `job` on a pool of `threads` workers, or on Rayon's global pool (one
+/// worker per CPU) when `threads` is `None`.
+fn run_on_pool<T: Send>(threads: Option<usize>, job: impl FnOnce() -> Result<T, Error> + Send) -> Result<T, Error> {
+    match threads {
+        Some(0) => Err(Error::config("threads must be at least 1").with_input("threads")),
+        Some(threads) => rayon::ThreadPoolBuilder::new().num_threads(threads).build()
+            .map_err(|e| Error::crypto(format!("Could not start thread pool: {}", e)))?
+            .install(job),
+        None => job(),
+    }
+}
+
+/// Encrypts every plaintext into an envelope token (as `encrypt_to_token` does) in
+/// one call, the i-th bound to `aad_list[i]`. The whole batch shares one salt, so
+/// the KDF runs once instead of once per item; every token still gets its own nonce.
+///
+/// Items are encrypted in parallel with the GIL released, on `threads` workers
+/// (default: one per CPU). `progress(bytes_processed, total)` is called every
+/// `progress_interval` bytes of plaintext, and once at the end. Cancelling `cancel`
+/// stops the batch after the items already in flight. `encoding` is as for
+/// `encrypt_to_token`.
+#[pyfunction]
+#[pyo3(signature = (
+    plaintexts, master_key, aad_list, algorithm=Algorithm::DEFAULT_NAME, kdf_profile=None, key_id=None, threads=None,
+    progress=None, progress_interval=DEFAULT_PROGRESS_INTERVAL, cancel=None, encoding=Encoding::TOKEN_DEFAULT_NAME
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn encrypt_many(
+    py: Python<'_>,
+    plaintexts: Vec<BytesLike>,
+    master_key: MasterKeyArg,
+    aad_list: Vec<AadArg>,
+    algorithm: &str,
+    kdf_profile: Option<&str>,
+    key_id: Option<&str>,
+    threads: Option<usize>,
+    progress: Option<Py<PyAny>>,
+    progress_interval: u64,
+    cancel: Option<PyRef<'_, CancellationToken>>,
+    encoding: &str
+) -> PyResult<Vec<Py<PyAny>>> {
+    check_lengths(plaintexts.len(), &aad_list)?;
+    let encoding = Encoding::from_name(encoding).map_err(crate::error::to_pyerr)?;
+    let total = plaintexts.iter().map(|plaintext| plaintext.len() as u64).sum();
+    let progress = Progress::new(progress, Some(total), progress_interval).with_cancel(cancel.as_deref());
+    let algorithm = Algorithm::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+    let (kdf_params, _) = crate::resolve_kdf_params(None, kdf_profile, None, None, None)?;
+
+    let envelopes = py.detach(|| run_on_pool(threads, || {
+        let mut salt = vec![0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let key = kdf_params.derive(&master_key, &salt)?;
+        plaintexts.par_iter().zip(&aad_list).map(|(plaintext, aad_context)| {
+            let envelope = Envelope::seal_with_key(
+                algorithm, kdf_params, salt.clone(), default_extensions(key_id), &key, plaintext, aad_context
+            )?;
+            progress.advance(plaintext.len())?;
+            envelope.to_bytes()
+        }).collect::<Result<Vec<_>, Error>>()
+        .and_then(|envelopes| progress.finish().map(|_| envelopes))
+    })).map_err(|e| progress.to_pyerr(e))?;
+    Ok(envelopes.iter().map(|envelope| encoding.encode(py, envelope).unbind()).collect())
+}
+
+/// Decrypts a list of envelope tokens in one call, the i-th verified against
+/// `aad_list[i]`. The KDF runs once per distinct salt, so a batch from
+/// `encrypt_many` costs a single run. Fails if any token doesn't open.
+///
+/// Key derivations and items both run in parallel with the GIL released, on
+/// `threads` workers (default: one per CPU), though only as many derivations at
+/// once as fit in 1 GiB. `progress` and `cancel` work as in `encrypt_many`,
+/// counting bytes of the tokens.
+#[pyfunction]
+#[pyo3(signature = (
+    tokens, master_key, aad_list, threads=None, progress=None, progress_interval=DEFAULT_PROGRESS_INTERVAL, cancel=None,
+    encoding=Encoding::TOKEN_DEFAULT_NAME
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn decrypt_many<'py>(
+    py: Python<'py>,
+    tokens: Vec<Encoded>,
+    master_key: MasterKeyArg,
+    aad_list: Vec<AadArg>,
+    threads: Option<usize>,
+    progress: Option<Py<PyAny>>,
+    progress_interval: u64,
+    cancel: Option<PyRef<'_, CancellationToken>>,
+    encoding: &str
+) -> PyResult<Vec<Bound<'py, PyBytes>>> {
+    check_lengths(tokens.len(), &aad_list)?;
+    let encoding = Encoding::from_name(encoding).map_err(crate::error::to_pyerr)?;
+    let total = tokens.iter().map(|token| token.len() as u64).sum();
+    let progress = Progress::new(progress, Some(total), progress_interval).with_cancel(cancel.as_deref());
+    py.detach(|| run_on_pool(threads, || {
+        let envelopes = tokens.par_iter().enumerate()
+            .map(|(index, token)| {
+                encoding.decode(token, "token")
+                    .and_then(|envelope| Envelope::from_bytes(&envelope))
+                    .map_err(|e| e.in_item(index))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        // One derivation per distinct (salt, KDF parameters), blamed on the first item using it
+        let mut pending = HashMap::new();
+        for (index, envelope) in envelopes.iter().enumerate() {
+            pending.entry((envelope.salt.as_slice(), envelope.kdf_params.encode()))
+                .or_insert((index, envelope.kdf_params));
+        }
+        let mut pending: Vec<_> = pending.into_iter().collect();
+        let mut keys = HashMap::with_capacity(pending.len());
+        while !pending.is_empty() {
+            // As many derivations at once as fit in the memory budget, and at least one
+            let mut memory = 0;
+            let batch = pending.iter()
+                .take_while(|(_, (_, kdf_params))| {
+                    memory += kdf_params.memory_bytes();
+                    memory <= KDF_MEMORY_BUDGET
+                })
+                .count()
+                .max(1);
+            let derived = pending.drain(..batch).collect::<Vec<_>>().into_par_iter()
+                .map(|(id, (index, kdf_params))| {
+                    kdf_params.derive(&master_key, id.0).map(|key| (id, key)).map_err(|e| e.in_item(index))
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            keys.extend(derived);
+        }
+
+        envelopes.par_iter().zip(&aad_list).enumerate().map(|(index, (envelope, aad_context))| {
+            let key = &keys[&(envelope.salt.as_slice(), envelope.kdf_params.encode())];
+            let plaintext = envelope.open_with_key(key, aad_context).map_err(|e| e.in_item(index))?;
+            progress.advance(tokens[index].len())?;
+            Ok(plaintext)
+        }).collect::<Result<Vec<_>, Error>>()
+        .and_then(|plaintexts| progress.finish().map(|_| plaintexts))
+    })).map(|plaintexts| plaintexts.iter().map(|plaintext| PyBytes::new(py, plaintext)).collect())
+    .map_err(|e| progress.to_pyerr(e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::encode_token;
+    use crate::kdf::KdfParams;
+
+    const KEY: &[u8] = b"batch test master key";
+
+    fn token(plaintext: &[u8], aad_context: &[u8]) -> Encoded {
+        let kdf_params = KdfParams::new(Some(8), Some(1), Some(1)).unwrap();
+        let envelope = Envelope::seal(Algorithm::AES_256_GCM, kdf_params, Vec::new(), plaintext, KEY, aad_context);
+        Encoded::Text(encode_token(&envelope.unwrap()).unwrap())
+    }
+
+    #[test]
+    fn failures_name_their_item() {
+        Python::initialize();
+        Python::attach(|py| {
+            let decrypt = |tokens: Vec<Encoded>, aad_list: &[&str]| {
+                let master_key: MasterKeyArg = PyBytes::new(py, KEY).extract().unwrap();
+                let aad_list = aad_list.iter().map(|aad| aad.into_pyobject(py).unwrap().extract().unwrap()).collect();
+                let encoding = Encoding::TOKEN_DEFAULT_NAME;
+                decrypt_many(py, tokens, master_key, aad_list, Some(2), None, DEFAULT_PROGRESS_INTERVAL, None, encoding)
+            };
+            let attribute = |err: &PyErr, name: &str| err.value(py).getattr(name).unwrap().to_string();
+
+            let plaintexts = decrypt(vec![token(b"a", b"x"), token(b"b", b"y")], &["x", "y"]).unwrap();
+            assert_eq!(plaintexts.iter().map(|p| p.as_bytes()).collect::<Vec<_>>(), [b"a", b"b"]);
+
+            let tokens = vec![token(b"a", b"x"), token(b"b", b"y"), token(b"c", b"z")];
+            let err = decrypt(tokens, &["x", "y", "x"]).unwrap_err();
+            assert_eq!(attribute(&err, "item"), "2");
+            assert_eq!(attribute(&err, "code"), "authentication_failed");
+            assert!(err.value(py).to_string().starts_with("Item 2: "), "{}", err.value(py));
+
+            let err = decrypt(vec![token(b"a", b"x"), Encoded::Text("not a token".into())], &["x", "x"]).unwrap_err();
+            assert_eq!(attribute(&err, "item"), "1");
+
+            let err = decrypt(vec![token(b"a", b"x")], &["x", "y"]).unwrap_err();
+            assert_eq!(attribute(&err, "item"), "None");
+        });
+    }
+}
@@ -0,0 +1,150 @@
+use pyo3::prelude::*;
+use chacha20poly1305::{aead::{Aead, KeyInit, Payload}, XChaCha20Poly1305};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::audit::Operation;
+use crate::error::Error;
+use crate::random::random_bytes;
+
+const VERSION: u8 = 0xBA;
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 24;
+/// version + timestamp + nonce
+const HEADER_LEN: usize = 1 + 4 + NONCE_LEN;
+const TAG_LEN: usize = 16;
+const FIPS_NAME: &str = "Branca (XChaCha20-Poly1305)";
+const BASE62: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// Big-endian base conversion between byte strings and base62 digits.
+fn convert(digits: &[u8], from: u32, to: u32) -> Vec<u8> {
+    let mut number: Vec<u32> = digits.iter().map(|&d| d as u32).collect();
+    let mut out = Vec::new();
+    while !number.is_empty() {
+        let mut quotient = Vec::with_capacity(number.len());
+        let mut remainder = 0u32;
+        for digit in number {
+            let acc = remainder * from + digit;
+            if !quotient.is_empty() || acc / to > 0 {
+                quotient.push(acc / to);
+            }
+            remainder = acc % to;
+        }
+        out.push(remainder as u8);
+        number = quotient;
+    }
+    out.reverse();
+    out
+}
+
+fn base62_encode(data: &[u8]) -> String {
+    convert(data, 256, 62).into_iter().map(|d| BASE62[d as usize] as char).collect()
+}
+
+fn base62_decode(text: &str) -> Result<Vec<u8>, Error> {
+    let digits = text.bytes()
+        .map(|c| BASE62.iter().position(|&b| b == c).map(|d| d as u8))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| Error::invalid_format("Invalid Branca token: bad base62"))?;
+    Ok(convert(&digits, 62, 256))
+}
+
+fn cipher(key: &[u8]) -> Result<XChaCha20Poly1305, Error> {
+    if key.len() != KEY_LEN {
+        return Err(Error::config(format!("Branca key must be {} bytes, got {}", KEY_LEN, key.len()))
+            .with_code("invalid_length").with_input("key").with_lengths(KEY_LEN, key.len()));
+    }
+    Ok(XChaCha20Poly1305::new(key.into()))
+}
+
+/// Token layout (https://github.com/tuupola/branca-spec), base62-encoded:
+///
+/// ```text
+/// version 0xBA | timestamp u32 | nonce (24) | XChaCha20-Poly1305 ciphertext | tag (16)
+/// ```
+///
+/// The header is the AEAD's associated data.
+pub(crate) fn seal(key: &[u8], payload: &[u8], timestamp: u32) -> Result<String, Error> {
+    crate::fips::reject(FIPS_NAME)?;
+    let cipher = cipher(key)?;
+    let nonce = random_bytes(NONCE_LEN);
+    let mut token = vec![VERSION];
+    token.extend_from_slice(&timestamp.to_be_bytes());
+    token.extend_from_slice(&nonce);
+    let ciphertext = cipher.encrypt(nonce.as_slice().into(), Payload { msg: payload, aad: &token })
+        .map_err(|_| Error::crypto("Branca encryption failed"))?;
+    token.extend_from_slice(&ciphertext);
+    Ok(base62_encode(&token))
+}
+
+pub(crate) fn open(key: &[u8], token: &str, ttl: Option<u64>) -> Result<(Vec<u8>, u32), Error> {
+    crate::fips::reject(FIPS_NAME)?;
+    let cipher = cipher(key)?;
+    let data = base62_decode(token.trim())?;
+    if data.len() < HEADER_LEN + TAG_LEN || data[0] != VERSION {
+        return Err(Error::invalid_format("Invalid Branca token"));
+    }
+    let (header, ciphertext) = data.split_at(HEADER_LEN);
+    let payload = cipher.decrypt(header[5..].into(), Payload { msg: ciphertext, aad: header })
+        .map_err(|_| Error::decryption("Branca token authentication failed"))?;
+
+    let timestamp = u32::from_be_bytes(header[1..5].try_into().unwrap());
+    if let Some(ttl) = ttl {
+        if (timestamp as u64).saturating_add(ttl) < now() {
+            return Err(Error::expired("Branca token has expired"));
+        }
+    }
+    Ok((payload, timestamp))
+}
+
+/// Encodes `payload` into a Branca token under a 32-byte `key`, stamped with
+/// `timestamp` (seconds since the epoch, default now).
+#[pyfunction]
+#[pyo3(signature = (key, payload, timestamp=None))]
+pub fn branca_encode(py: Python<'_>, key: &[u8], payload: &[u8], timestamp: Option<u32>) -> PyResult<String> {
+    let timestamp = timestamp.unwrap_or_else(|| now() as u32);
+    py.detach(|| Operation::new("encrypt", "branca").run(|| seal(key, payload, timestamp)))
+        .map_err(crate::error::to_pyerr)
+}
+
+/// Decodes a Branca token and returns `(payload, timestamp)`. With `ttl` (seconds),
+/// tokens stamped longer ago than that are rejected.
+#[pyfunction]
+#[pyo3(signature = (key, token, ttl=None))]
+pub fn branca_decode(py: Python<'_>, key: &[u8], token: &str, ttl: Option<u64>) -> PyResult<(Vec<u8>, u32)> {
+    py.detach(|| Operation::new("decrypt", "branca").run(|| open(key, token, ttl))).map_err(crate::error::to_pyerr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Kind;
+
+    /// From the Branca spec's test vectors.
+    const SPEC_KEY: &[u8; 32] = b"supersecretkeyyoushouldnotcommit";
+    const SPEC_TOKEN: &str = "875GH233T7IYrxtgXxlQBYiFobZMQdHAT51vChKsAIYCFxZtL1evV54vYqLyZtQ0ekPHt8kJHQp0a";
+
+    #[test]
+    fn opens_the_spec_vector() {
+        assert_eq!(open(SPEC_KEY, SPEC_TOKEN, None).unwrap(), (b"Hello world!".to_vec(), 123206400));
+    }
+
+    #[test]
+    fn rejects_tokens_past_their_ttl() {
+        assert_eq!(open(SPEC_KEY, SPEC_TOKEN, Some(3600)).unwrap_err().kind(), Kind::Expired);
+
+        let fresh = seal(SPEC_KEY, b"payload", now() as u32 - 30).unwrap();
+        assert_eq!(open(SPEC_KEY, &fresh, Some(60)).unwrap().0, b"payload");
+        assert_eq!(open(SPEC_KEY, &fresh, Some(10)).unwrap_err().kind(), Kind::Expired);
+    }
+
+    #[test]
+    fn classifies_bad_tokens() {
+        assert_eq!(open(&[1; KEY_LEN], SPEC_TOKEN, None).unwrap_err().kind(), Kind::Decryption);
+        assert_eq!(open(SPEC_KEY, "not base62!", None).unwrap_err().kind(), Kind::InvalidFormat);
+        assert_eq!(open(SPEC_KEY, &SPEC_TOKEN[..20], None).unwrap_err().kind(), Kind::InvalidFormat);
+    }
+}
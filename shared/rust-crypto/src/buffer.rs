@@ -0,0 +1,144 @@
+use pyo3::buffer::PyUntypedBuffer;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use std::ops::Deref;
+use zeroize::Zeroizing;
+
+use crate::error::Error;
+
+/// Exports `obj`'s buffer, which must be C-contiguous.
+fn contiguous_buffer(obj: Borrowed<'_, '_, PyAny>) -> PyResult<PyUntypedBuffer> {
+    let Ok(buffer) = PyUntypedBuffer::get(&obj) else {
+        return Err(pyo3::exceptions::PyTypeError::new_err(format!(
+            "a bytes-like object is required, not '{}'", obj.get_type().name()?
+        )));
+    };
+    if !buffer.is_c_contiguous() {
+        return Err(crate::error::to_pyerr(Error::config("Buffer must be C-contiguous")));
+    }
+    Ok(buffer)
+}
+
+/// The bytes of an exported buffer.
+///
+/// # Safety
+///
+/// `buffer` must be C-contiguous, and nothing may write to its memory while the
+/// slice is alive.
+unsafe fn buffer_bytes(buffer: &PyUntypedBuffer) -> &[u8] {
+    let len = buffer.len_bytes();
+    if len == 0 {
+        return &[];
+    }
+    // SAFETY: the buffer stays exported, so its memory can't be freed or moved,
+    // for as long as `buffer` is borrowed.
+    unsafe { std::slice::from_raw_parts(buffer.buf_ptr() as *const u8, len) }
+}
+
+/// Binary input argument: `bytes` or any other object exposing a C-contiguous
+/// buffer (`bytearray`, `memoryview`, `mmap`, numpy arrays of any dtype).
+/// `bytes` objects are immutable, so they are read in place rather than copied.
+/// Every other buffer is copied while the GIL is held, since the work runs without
+/// it: a read-only export only stops *this* consumer from writing, and another
+/// thread could still write to the memory or close an `mmap` meanwhile. The copy
+/// is wiped on drop.
+pub(crate) enum BytesLike {
+    Shared(PyUntypedBuffer),
+    Copied(Zeroizing<Vec<u8>>),
+}
+
+impl Deref for BytesLike {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            // SAFETY: only `bytes` exports, which nothing can write to, are shared (see `extract`)
+            BytesLike::Shared(buffer) => unsafe { buffer_bytes(buffer) },
+            BytesLike::Copied(bytes) => bytes,
+        }
+    }
+}
+
+impl<'a, 'py> FromPyObject<'a, 'py> for BytesLike {
+    type Error = PyErr;
+
+    fn extract(obj: Borrowed<'a, 'py, PyAny>) -> PyResult<Self> {
+        let buffer = contiguous_buffer(obj)?;
+        if obj.is_exact_instance_of::<PyBytes>() {
+            return Ok(BytesLike::Shared(buffer));
+        }
+        // SAFETY: Python code can only write to the buffer while some thread holds
+        // the GIL, and this one does until the copy is made
+        let bytes = Zeroizing::new(unsafe { buffer_bytes(&buffer) }.to_vec());
+        Ok(BytesLike::Copied(bytes))
+    }
+}
+
+/// Output argument: any writable C-contiguous buffer (`bytearray`, a writable
+/// `memoryview` slice, numpy arrays, a writable `mmap`) that results are written
+/// into in place. Only touch it with the GIL held, so no Python thread writes to
+/// it at the same time.
+pub(crate) struct WritableBuffer(PyUntypedBuffer);
+
+impl WritableBuffer {
+    pub(crate) fn len(&self) -> usize {
+        self.0.len_bytes()
+    }
+
+    /// Whether `other` shares any memory with this buffer.
+    pub(crate) fn overlaps(&self, other: &[u8]) -> bool {
+        let start = self.0.buf_ptr() as usize;
+        let other_start = other.as_ptr() as usize;
+        start < other_start + other.len() && other_start < start + self.len()
+    }
+
+    /// The buffer's bytes; `_py` proves the GIL is held.
+    pub(crate) fn as_mut_slice(&mut self, _py: Python<'_>) -> &mut [u8] {
+        let len = self.len();
+        if len == 0 {
+            return &mut [];
+        }
+        // SAFETY: the buffer is C-contiguous and stays exported until `self` drops,
+        // it was exported writable, `&mut self` keeps this the only slice over it
+        // on the Rust side, and the GIL is held for as long as it lives.
+        unsafe { std::slice::from_raw_parts_mut(self.0.buf_ptr() as *mut u8, len) }
+    }
+}
+
+impl<'a, 'py> FromPyObject<'a, 'py> for WritableBuffer {
+    type Error = PyErr;
+
+    fn extract(obj: Borrowed<'a, 'py, PyAny>) -> PyResult<Self> {
+        let buffer = contiguous_buffer(obj)?;
+        if buffer.readonly() {
+            return Err(pyo3::exceptions::PyTypeError::new_err(format!(
+                "a writable bytes-like object is required, not '{}'", obj.get_type().name()?
+            )));
+        }
+        Ok(WritableBuffer(buffer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    #[test]
+    fn only_bytes_are_shared() {
+        Python::initialize();
+        Python::attach(|py| {
+            for (expr, shared) in [
+                ("b'data'", true),
+                ("bytearray(b'data')", false),
+                ("memoryview(b'data')", false),
+                ("memoryview(bytearray(b'data')).toreadonly()", false),
+            ] {
+                let obj = py.eval(&CString::new(expr).unwrap(), None, None).unwrap();
+                let bytes: BytesLike = obj.extract().unwrap();
+                assert_eq!(&*bytes, b"data", "{}", expr);
+                assert_eq!(matches!(bytes, BytesLike::Shared(_)), shared, "{}", expr);
+            }
+        });
+    }
+}
@@ -0,0 +1,132 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use blake2::{Blake2b, Digest, digest::consts::U32};
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+use crate::error::Error;
+use crate::kdf::{DerivedKey, KdfParams};
+
+const DEFAULT_MAX_ENTRIES: usize = 1024;
+
+/// Process-wide LRU of derived keys. `None` means caching is disabled (the default):
+/// callers have to opt in explicitly because cached keys outlive the call that made them.
+static KEY_CACHE: Mutex<Option<KeyCache>> = Mutex::new(None);
+
+struct KeyCache {
+    entries: LruCache<[u8; 32], DerivedKey>,
+    hits: u64,
+    misses: u64,
+}
+
+/// The master key itself is never stored: entries are addressed by a hash over
+/// (master key, salt, KDF parameters), each part length-prefixed.
+fn cache_id(master_key: &[u8], salt: &[u8], params: &KdfParams) -> [u8; 32] {
+    let params = params.encode();
+    let mut hasher = Blake2b::<U32>::new();
+    for part in [master_key, salt, params.as_bytes()] {
+        hasher.update((part.len() as u64).to_be_bytes());
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+/// Returns the cached key for this derivation, or runs `derive` and caches its result.
+/// When the cache is disabled this is just `derive()`.
+pub(crate) fn get_or_derive<F>(master_key: &[u8], salt: &[u8], params: &KdfParams, derive: F) -> Result<DerivedKey, Error>
+where
+    F: FnOnce() -> Result<DerivedKey, Error>,
+{
+    let id = cache_id(master_key, salt, params);
+    // Only the lookup runs under the lock: nothing here may take the GIL or run the KDF
+    let lookup = {
+        let mut guard = KEY_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+        guard.as_mut().map(|cache| match cache.entries.get(&id) {
+            Some(key) => {
+                cache.hits += 1;
+                crate::metrics::CACHE_HITS.fetch_add(1, Ordering::Relaxed);
+                Some(key.clone())
+            }
+            None => {
+                cache.misses += 1;
+                crate::metrics::CACHE_MISSES.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        })
+    };
+    let Some(hit) = lookup else {
+        return derive();
+    };
+    // Logged outside the lock, since logging takes the GIL
+    crate::logging::debug(|| format!("Key cache {} for {}", if hit.is_some() { "hit" } else { "miss" }, params.name()));
+    if let Some(key) = hit {
+        return Ok(key);
+    }
+
+    // The lock is not held while Argon2 runs, so concurrent misses don't serialize
+    let key = derive()?;
+    if let Some(cache) = KEY_CACHE.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+        cache.entries.put(id, key.clone());
+    }
+    Ok(key)
+}
+
+/// Turns on the derived-key cache (or resizes it) with room for `max_entries` keys.
+#[pyfunction]
+#[pyo3(signature = (max_entries=DEFAULT_MAX_ENTRIES))]
+pub fn enable_key_cache(max_entries: usize) -> PyResult<()> {
+    let capacity = NonZeroUsize::new(max_entries)
+        .ok_or_else(|| crate::error::to_pyerr(
+            Error::config("max_entries must be positive").with_input("max_entries")
+        ))?;
+
+    let mut guard = KEY_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    match guard.as_mut() {
+        Some(cache) => cache.entries.resize(capacity),
+        None => *guard = Some(KeyCache { entries: LruCache::new(capacity), hits: 0, misses: 0 }),
+    }
+    Ok(())
+}
+
+/// Turns the cache off and drops every cached key.
+#[pyfunction]
+pub fn disable_key_cache() {
+    *KEY_CACHE.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Drops every cached key but leaves the cache enabled.
+#[pyfunction]
+pub fn clear_key_cache() {
+    if let Some(cache) = KEY_CACHE.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+        cache.entries.clear();
+    }
+}
+
+/// Returns `{enabled, size, max_entries, hits, misses}`.
+#[pyfunction]
+pub fn key_cache_info(py: Python<'_>) -> PyResult<Py<PyDict>> {
+    let guard = KEY_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    let result = PyDict::new(py);
+    result.set_item("enabled", guard.is_some())?;
+    result.set_item("size", guard.as_ref().map_or(0, |c| c.entries.len()))?;
+    result.set_item("max_entries", guard.as_ref().map_or(0, |c| c.entries.cap().get()))?;
+    result.set_item("hits", guard.as_ref().map_or(0, |c| c.hits))?;
+    result.set_item("misses", guard.as_ref().map_or(0, |c| c.misses))?;
+    Ok(result.unbind())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_without_holding_the_lock() {
+        let key = get_or_derive(b"master", b"salt", &KdfParams::default(), || {
+            assert!(KEY_CACHE.try_lock().is_ok(), "KEY_CACHE held during derivation");
+            Ok(zeroize::Zeroizing::new([1; 32]))
+        }).unwrap();
+        assert_eq!(*key, [1; 32]);
+    }
+}
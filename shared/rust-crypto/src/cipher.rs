@@ -0,0 +1,216 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use aes_gcm::{
+    aead::{generic_array::GenericArray, AeadInPlace, KeyInit},
+    Aes256Gcm
+};
+use aes_gcm_siv::Aes256GcmSiv;
+use chacha20poly1305::XChaCha20Poly1305;
+use std::time::Instant;
+
+use crate::error::Error;
+
+/// Every supported cipher appends a 16-byte tag.
+pub(crate) const TAG_LEN: usize = 16;
+
+type SealFn = fn(&[u8; 32], &[u8], &[u8], &mut [u8]) -> Result<[u8; TAG_LEN], aes_gcm::Error>;
+type OpenFn = fn(&[u8; 32], &[u8], &[u8], &mut [u8], &[u8; TAG_LEN]) -> Result<(), aes_gcm::Error>;
+
+/// Entry in the cipher registry: an AEAD taking a 32-byte key and producing a
+/// `TAG_LEN`-byte tag.
+#[derive(Debug)]
+pub(crate) struct CipherSpec {
+    /// Name accepted by the `algorithm` argument and reported by `inspect()`.
+    id: &'static str,
+    /// Byte recorded in envelope headers. Never reuse one, even for a removed cipher.
+    envelope_id: u8,
+    nonce_len: usize,
+    /// Encryptions with random nonces one key can safely do, if the nonce is short
+    /// enough for that to matter.
+    max_messages: Option<u64>,
+    fips_approved: bool,
+    /// Encrypts the buffer in place and returns the tag.
+    seal: SealFn,
+    /// Verifies the tag and decrypts the buffer in place.
+    open: OpenFn,
+}
+
+fn seal<C: KeyInit + AeadInPlace>(key: &[u8; 32], nonce: &[u8], aad: &[u8], buffer: &mut [u8]) -> Result<[u8; TAG_LEN], aes_gcm::Error> {
+    let cipher = C::new_from_slice(key).expect("registered ciphers take 32-byte keys");
+    let tag = cipher.encrypt_in_place_detached(GenericArray::from_slice(nonce), aad, buffer)?;
+    Ok(tag.as_slice().try_into().expect("registered ciphers have 16-byte tags"))
+}
+
+fn open<C: KeyInit + AeadInPlace>(key: &[u8; 32], nonce: &[u8], aad: &[u8], buffer: &mut [u8], tag: &[u8; TAG_LEN]) -> Result<(), aes_gcm::Error> {
+    let cipher = C::new_from_slice(key).expect("registered ciphers take 32-byte keys");
+    cipher.decrypt_in_place_detached(GenericArray::from_slice(nonce), aad, buffer, GenericArray::from_slice(tag))
+}
+
+/// A cipher from `ALGORITHMS`, selectable through the `algorithm` argument.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Algorithm(&'static CipherSpec);
+
+impl PartialEq for Algorithm {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.envelope_id == other.0.envelope_id
+    }
+}
+
+impl Eq for Algorithm {}
+
+/// Every cipher the module knows, in the order `list_algorithms()` reports them.
+/// Adding one means adding an entry here (behind a Cargo feature for
+/// organization-specific ciphers); envelope ids 128-255 are left for those.
+const ALGORITHMS: &[Algorithm] = &[
+    Algorithm::AES_256_GCM,
+    Algorithm::XCHACHA20_POLY1305,
+    Algorithm::AES_256_GCM_SIV,
+];
+
+impl Algorithm {
+    pub(crate) const DEFAULT_NAME: &'static str = "aes-256-gcm";
+
+    pub(crate) const AES_256_GCM: Algorithm = Algorithm(&CipherSpec {
+        id: "aes-256-gcm",
+        envelope_id: 1,
+        nonce_len: 12,
+        // SP 800-38D 8.3: random 96-bit nonces keep the collision chance below
+        // 2^-32 for up to 2^32 messages
+        max_messages: Some(1 << 32),
+        fips_approved: true,
+        seal: seal::<Aes256Gcm>,
+        open: open::<Aes256Gcm>,
+    });
+    pub(crate) const XCHACHA20_POLY1305: Algorithm = Algorithm(&CipherSpec {
+        id: "xchacha20-poly1305",
+        envelope_id: 2,
+        nonce_len: 24,
+        max_messages: None,
+        fips_approved: false,
+        seal: seal::<XChaCha20Poly1305>,
+        open: open::<XChaCha20Poly1305>,
+    });
+    /// Nonce-misuse resistant: a repeated nonce only reveals whether two
+    /// messages (with the same AAD) are equal, instead of leaking the key stream.
+    pub(crate) const AES_256_GCM_SIV: Algorithm = Algorithm(&CipherSpec {
+        id: "aes-256-gcm-siv",
+        envelope_id: 3,
+        nonce_len: 12,
+        max_messages: Some(1 << 32),
+        fips_approved: false,
+        seal: seal::<Aes256GcmSiv>,
+        open: open::<Aes256GcmSiv>,
+    });
+
+    pub(crate) fn from_name(name: &str) -> Result<Self, Error> {
+        ALGORITHMS.iter()
+            .find(|algorithm| algorithm.0.id.eq_ignore_ascii_case(name))
+            .copied()
+            .ok_or_else(|| Error::config(format!("Unsupported algorithm: {}", name)).with_code("unsupported_algorithm"))
+    }
+
+    pub(crate) fn from_envelope_id(id: u8) -> Result<Self, Error> {
+        ALGORITHMS.iter()
+            .find(|algorithm| algorithm.0.envelope_id == id)
+            .copied()
+            .ok_or_else(|| Error::invalid_format(format!("Unknown algorithm id in envelope: {}", id))
+                .with_code("unsupported_algorithm"))
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        self.0.id
+    }
+
+    pub(crate) fn envelope_id(self) -> u8 {
+        self.0.envelope_id
+    }
+
+    pub(crate) fn nonce_len(self) -> usize {
+        self.0.nonce_len
+    }
+
+    pub(crate) fn max_messages(self) -> Option<u64> {
+        self.0.max_messages
+    }
+
+    fn check(self, nonce: &[u8]) -> Result<(), Error> {
+        if !self.0.fips_approved {
+            crate::fips::reject(self.name())?;
+        }
+        if nonce.len() != self.nonce_len() {
+            return Err(crate::error::wrong_length("nonce", self.nonce_len(), nonce.len()));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn encrypt(self, key: &[u8; 32], nonce: &[u8], msg: &[u8], aad: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut out = Vec::with_capacity(msg.len() + TAG_LEN);
+        out.extend_from_slice(msg);
+        let tag = self.encrypt_detached(key, nonce, &mut out, aad)?;
+        out.extend_from_slice(&tag);
+        Ok(out)
+    }
+
+    pub(crate) fn decrypt(self, key: &[u8; 32], nonce: &[u8], msg: &[u8], aad: &[u8]) -> Result<Vec<u8>, Error> {
+        self.check(nonce)?;
+        if msg.len() < TAG_LEN {
+            return Err(Error::decryption("Decryption failed: Integrity check failed or context mismatch"));
+        }
+        let (ciphertext, tag) = msg.split_at(msg.len() - TAG_LEN);
+        let mut out = ciphertext.to_vec();
+        self.decrypt_detached(key, nonce, &mut out, aad, tag)?;
+        Ok(out)
+    }
+
+    /// Encrypts `buffer` in place and returns the tag instead of appending it.
+    pub(crate) fn encrypt_detached(self, key: &[u8; 32], nonce: &[u8], buffer: &mut [u8], aad: &[u8]) -> Result<[u8; TAG_LEN], Error> {
+        self.check(nonce)?;
+        let start = Instant::now();
+        let sealed = (self.0.seal)(key, nonce, aad, buffer);
+        crate::metrics::ENCRYPT.record(start.elapsed());
+        sealed.map_err(|e| Error::crypto(format!("Encryption failed: {}", e)))
+    }
+
+    /// Verifies `tag` and decrypts `buffer` in place. On failure `buffer` is left
+    /// encrypted for GCM and ChaCha20; for GCM-SIV its contents are unspecified.
+    pub(crate) fn decrypt_detached(self, key: &[u8; 32], nonce: &[u8], buffer: &mut [u8], aad: &[u8], tag: &[u8]) -> Result<(), Error> {
+        self.check(nonce)?;
+        let tag: &[u8; TAG_LEN] = tag.try_into()
+            .map_err(|_| crate::error::wrong_length("tag", TAG_LEN, tag.len()))?;
+        let start = Instant::now();
+        let opened = (self.0.open)(key, nonce, aad, buffer, tag);
+        crate::metrics::DECRYPT.record(start.elapsed());
+        opened.map_err(|_| Error::decryption("Decryption failed: Integrity check failed or context mismatch"))
+    }
+}
+
+/// The registered ciphers and KDFs, as `{"type", "id", "envelope_id", "fips_approved",
+/// "available"}` dicts (ciphers also carry `nonce_len` and `max_messages`). `id` is what the `algorithm`
+/// argument, `kdf_params` and `inspect()` use; `available` is false for anything
+/// `enable_fips_mode()` currently blocks.
+#[pyfunction]
+pub fn list_algorithms(py: Python<'_>) -> PyResult<Bound<'_, PyList>> {
+    let entries = PyList::empty(py);
+    let fips = crate::fips::enabled();
+    for algorithm in ALGORITHMS {
+        let entry = PyDict::new(py);
+        entry.set_item("type", "cipher")?;
+        entry.set_item("id", algorithm.0.id)?;
+        entry.set_item("envelope_id", algorithm.0.envelope_id)?;
+        entry.set_item("nonce_len", algorithm.0.nonce_len)?;
+        entry.set_item("max_messages", algorithm.0.max_messages)?;
+        entry.set_item("fips_approved", algorithm.0.fips_approved)?;
+        entry.set_item("available", algorithm.0.fips_approved || !fips)?;
+        entries.append(entry)?;
+    }
+    for kdf in crate::kdf::KDFS {
+        let entry = PyDict::new(py);
+        entry.set_item("type", "kdf")?;
+        entry.set_item("id", kdf.id)?;
+        entry.set_item("envelope_id", kdf.envelope_id)?;
+        entry.set_item("fips_approved", kdf.fips_approved)?;
+        entry.set_item("available", kdf.fips_approved || !fips)?;
+        entries.append(entry)?;
+    }
+    Ok(entries)
+}
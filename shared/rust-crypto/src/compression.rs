@@ -0,0 +1,52 @@
+use crate::error::Error;
+
+/// Codec id in the compression extension.
+const CODEC_ZSTD: u8 = 1;
+
+pub(crate) const DEFAULT_COMPRESSION_LEVEL: i32 = zstd::DEFAULT_COMPRESSION_LEVEL;
+
+/// Compresses `data` with zstd at `level`, before encryption. Returns it with the
+/// envelope extension value recording it, `codec u8 | uncompressed_len u64`, so
+/// decryption undoes it unasked and never inflates past the (authenticated) size.
+pub(crate) fn compress(data: &[u8], level: i32) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    let levels = zstd::compression_level_range();
+    if !levels.contains(&level) {
+        return Err(Error::config(format!(
+            "compression_level must be between {} and {}", levels.start(), levels.end()
+        )).with_input("compression_level"));
+    }
+    let compressed = zstd::bulk::compress(data, level)
+        .map_err(|e| Error::crypto(format!("Compression failed: {}", e)))?;
+    let mut record = vec![CODEC_ZSTD];
+    record.extend_from_slice(&(data.len() as u64).to_be_bytes());
+    Ok((compressed, record))
+}
+
+/// Reverses `compress`, given the extension value it returned.
+pub(crate) fn decompress(data: &[u8], record: &[u8]) -> Result<Vec<u8>, Error> {
+    let Some((&codec, len)) = record.split_first() else {
+        return Err(Error::invalid_format("Invalid compression record"));
+    };
+    if codec != CODEC_ZSTD {
+        return Err(
+            Error::config(format!("Unsupported compression codec: {}", codec)).with_code("unsupported_compression")
+        );
+    }
+    let len = <[u8; 8]>::try_from(len).ok()
+        .and_then(|len| usize::try_from(u64::from_be_bytes(len)).ok())
+        .ok_or_else(|| Error::invalid_format("Invalid compression record"))?;
+    let decompressed = zstd::bulk::decompress(data, len)
+        .map_err(|e| Error::invalid_format(format!("Decompression failed: {}", e)).with_code("decompression_failed"))?;
+    if decompressed.len() != len {
+        return Err(Error::invalid_format("Decompression failed: size mismatch").with_code("decompression_failed"));
+    }
+    Ok(decompressed)
+}
+
+/// Codec name for `inspect()`.
+pub(crate) fn codec_name(record: &[u8]) -> Option<&'static str> {
+    match record.first() {
+        Some(&CODEC_ZSTD) => Some("zstd"),
+        _ => None,
+    }
+}
@@ -0,0 +1,324 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use argon2::password_hash::rand_core::OsRng;
+use hkdf::Hkdf;
+use rand::RngCore;
+use sha2::Sha256;
+use std::sync::Mutex;
+use zeroize::Zeroizing;
+
+use crate::aad::AadArg;
+use crate::audit::Operation;
+use crate::buffer::BytesLike;
+use crate::cipher::Algorithm;
+use crate::error::Error;
+use crate::kdf::KdfParams;
+use crate::locked::LockedBytes;
+use crate::master_key::MasterKeyArg;
+use crate::nonce::NonceSequence;
+
+/// Holds a key derived once from the master key and a salt, for bulk jobs where
+/// paying Argon2 per record would dominate. Every record still gets a fresh nonce.
+///
+/// `encrypt()` returns `nonce || ciphertext`. To decrypt later, persist `salt`,
+/// `kdf_params` and `algorithm` once and rebuild the context with the same values.
+/// The derived key is held in locked memory, like `MasterKey`.
+///
+/// Random nonces only stay unique for so many messages under one key (2^32 for the
+/// 12-byte nonces of AES-256-GCM and GCM-SIV), so `encrypt()` counts messages and
+/// bytes per key and refuses once `max_messages` is reached. With `rekey=True` it
+/// instead encrypts under subkeys derived from the key and a random 16-byte ID,
+/// switching to a fresh one at the limit; blobs are then `subkey_id || nonce ||
+/// ciphertext`, and each context instance picks its own subkey, so counts don't need
+/// to be shared between processes. Without `rekey` the count starts over for every
+/// instance built from the same salt; keep it below the limit across all of them.
+///
+/// `nonces` takes a `NonceSequence` for counter nonces instead of random ones (12-byte
+/// nonce ciphers only). Those can't collide, so the cipher's limit doesn't apply then;
+/// only an explicit `max_messages` does.
+#[pyclass]
+pub struct CryptoContext {
+    key: LockedBytes,
+    salt: Vec<u8>,
+    algorithm: Algorithm,
+    kdf_params: KdfParams,
+    rekey: bool,
+    max_messages: Option<u64>,
+    nonces: Option<Py<NonceSequence>>,
+    usage: Mutex<Usage>,
+}
+
+/// Use of the key `encrypt()` currently seals under.
+struct Usage {
+    /// `None` for the derived key itself, when not rekeying.
+    subkey: Option<(SubkeyId, LockedBytes)>,
+    messages: u64,
+    bytes: u64,
+    rekeys: u64,
+}
+
+const SUBKEY_ID_LEN: usize = 16;
+type SubkeyId = [u8; SUBKEY_ID_LEN];
+
+fn derive_subkey(key: &[u8; 32], subkey_id: &[u8]) -> Zeroizing<[u8; 32]> {
+    let mut subkey = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(Some(subkey_id), key)
+        .expand(b"KSC-CONTEXT-SUBKEY", subkey.as_mut())
+        .expect("32 bytes is a valid HKDF length");
+    subkey
+}
+
+fn new_subkey(key: &[u8; 32]) -> (SubkeyId, LockedBytes) {
+    let mut subkey_id = [0u8; SUBKEY_ID_LEN];
+    OsRng.fill_bytes(&mut subkey_id);
+    (subkey_id, LockedBytes::new(derive_subkey(key, &subkey_id).as_slice()))
+}
+
+impl CryptoContext {
+    fn key(&self) -> &[u8; 32] {
+        (*self.key).try_into().expect("derived keys are 32 bytes")
+    }
+
+    /// Counts one more message of `len` bytes against the current key, moving to a
+    /// fresh subkey (or failing) at the limit, and returns the key to seal under with
+    /// the subkey ID to prefix.
+    fn reserve(&self, len: usize) -> Result<(Zeroizing<[u8; 32]>, Option<SubkeyId>), Error> {
+        let mut usage = self.usage.lock().unwrap();
+        let mut rekeyed = false;
+        if self.max_messages.is_some_and(|max| usage.messages >= max) {
+            if !self.rekey {
+                return Err(Error::crypto(format!(
+                    "Key usage limit reached: {} messages under this key; build a new context with a fresh salt, or pass rekey=True",
+                    usage.messages
+                )).with_code("key_usage_exceeded"));
+            }
+            usage.subkey = Some(new_subkey(self.key()));
+            (usage.messages, usage.bytes) = (0, 0);
+            usage.rekeys += 1;
+            rekeyed = true;
+        }
+        usage.messages += 1;
+        usage.bytes = usage.bytes.saturating_add(len as u64);
+        let reserved = match &usage.subkey {
+            Some((subkey_id, subkey)) => (Zeroizing::new((**subkey).try_into().expect("subkeys are 32 bytes")), Some(*subkey_id)),
+            None => (Zeroizing::new(*self.key()), None),
+        };
+        // Logged after the lock is released, since logging takes the GIL and the
+        // `usage` getter takes the lock while holding it
+        drop(usage);
+        if rekeyed {
+            crate::logging::info(|| "CryptoContext switched to a fresh subkey".to_string());
+        }
+        Ok(reserved)
+    }
+
+    /// The key a blob was sealed under, and the rest of the blob.
+    fn open_key<'a>(&self, blob: &'a [u8]) -> Result<(Zeroizing<[u8; 32]>, &'a [u8]), Error> {
+        if !self.rekey {
+            return Ok((Zeroizing::new(*self.key()), blob));
+        }
+        if blob.len() < SUBKEY_ID_LEN {
+            return Err(Error::invalid_format("Ciphertext too short").with_code("truncated"));
+        }
+        let (subkey_id, rest) = blob.split_at(SUBKEY_ID_LEN);
+        Ok((derive_subkey(self.key(), subkey_id), rest))
+    }
+}
+
+#[pymethods]
+impl CryptoContext {
+    /// `max_messages` lowers the per-key limit below the cipher's own (see
+    /// `list_algorithms()`); the cipher's limit can't be raised.
+    #[new]
+    #[pyo3(signature = (
+        master_key, salt=None, algorithm=Algorithm::DEFAULT_NAME, kdf_params=None, kdf_profile=None,
+        rekey=false, max_messages=None, nonces=None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        py: Python<'_>,
+        master_key: MasterKeyArg,
+        salt: Option<Vec<u8>>,
+        algorithm: &str,
+        kdf_params: Option<&str>,
+        kdf_profile: Option<&str>,
+        rekey: bool,
+        max_messages: Option<u64>,
+        nonces: Option<Py<NonceSequence>>
+    ) -> PyResult<Self> {
+        let algorithm = Algorithm::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+        let kdf_params = match (kdf_params, kdf_profile) {
+            (Some(_), Some(_)) => Err(Error::crypto("kdf_params cannot be combined with kdf_profile")),
+            (Some(encoded), None) => KdfParams::parse(encoded),
+            (None, Some(name)) => KdfParams::from_profile(name).map(|(_, params)| params),
+            (None, None) => Ok(KdfParams::default()),
+        }.map_err(crate::error::to_pyerr)?;
+
+        let salt = match salt {
+            Some(salt) if salt.len() < 8 => {
+                return Err(crate::error::to_pyerr(Error::config("Salt must be at least 8 bytes")));
+            }
+            Some(salt) => salt,
+            None => {
+                let mut salt = vec![0u8; 16];
+                OsRng.fill_bytes(&mut salt);
+                salt
+            }
+        };
+
+        let key = py.detach(|| kdf_params.derive(&master_key, &salt))
+            .map(|key| LockedBytes::new(key.as_slice()))
+            .map_err(crate::error::to_pyerr)?;
+
+        if max_messages == Some(0) {
+            return Err(crate::error::to_pyerr(
+                Error::config("max_messages must be at least 1").with_input("max_messages")
+            ));
+        }
+        if nonces.is_some() && algorithm.nonce_len() != crate::nonce::NONCE_LEN {
+            return Err(crate::error::to_pyerr(
+                Error::config(format!("nonces must be used with a 12-byte nonce cipher, not {}", algorithm.name()))
+                    .with_input("nonces")
+            ));
+        }
+        let max_messages = match (max_messages, algorithm.max_messages()) {
+            (given, _) if nonces.is_some() => given,
+            (Some(given), Some(limit)) => Some(given.min(limit)),
+            (given, limit) => given.or(limit),
+        };
+        let subkey = rekey.then(|| new_subkey((*key).try_into().expect("derived keys are 32 bytes")));
+        let usage = Mutex::new(Usage { subkey, messages: 0, bytes: 0, rekeys: 0 });
+        Ok(CryptoContext { key, salt, algorithm, kdf_params, rekey, max_messages, nonces, usage })
+    }
+
+    /// Salt the key was derived with; store it to rebuild the context.
+    #[getter]
+    fn salt(&self) -> Vec<u8> {
+        self.salt.clone()
+    }
+
+    /// KDF parameters in the same encoded notation used elsewhere (`m=..,t=..,p=..`).
+    #[getter]
+    fn kdf_params(&self) -> String {
+        self.kdf_params.encode()
+    }
+
+    #[getter]
+    fn algorithm(&self) -> &'static str {
+        self.algorithm.name()
+    }
+
+    /// Whether the derived key is pinned in RAM; see `MasterKey.locked`.
+    #[getter]
+    fn locked(&self) -> bool {
+        self.key.is_locked()
+    }
+
+    #[getter]
+    fn rekey(&self) -> bool {
+        self.rekey
+    }
+
+    /// Use of the key `encrypt()` currently seals under: `{messages, bytes,
+    /// max_messages, rekeys}`, where `bytes` counts plaintext and `rekeys` the
+    /// switches to a fresh subkey. `max_messages` is `None` for ciphers without a limit.
+    #[getter]
+    fn usage<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let usage = self.usage.lock().unwrap();
+        let result = PyDict::new(py);
+        result.set_item("messages", usage.messages)?;
+        result.set_item("bytes", usage.bytes)?;
+        result.set_item("max_messages", self.max_messages)?;
+        result.set_item("rekeys", usage.rekeys)?;
+        Ok(result)
+    }
+
+    /// Encrypts `plaintext` with a fresh nonce and returns `nonce || ciphertext`
+    /// (prefixed with the subkey ID when rekeying).
+    fn encrypt(&self, py: Python<'_>, plaintext: BytesLike, aad_context: AadArg) -> PyResult<Vec<u8>> {
+        let nonce = self.nonces.as_ref().map(|nonces| nonces.get().next_nonce(py)).transpose()?;
+        py.detach(|| -> Result<Vec<u8>, Error> {
+            let (key, subkey_id) = self.reserve(plaintext.len())?;
+            let mut blob = subkey_id.map_or_else(Vec::new, |subkey_id| subkey_id.to_vec());
+            let nonce = nonce.map_or_else(|| {
+                let mut nonce = vec![0u8; self.algorithm.nonce_len()];
+                OsRng.fill_bytes(&mut nonce);
+                nonce
+            }, |nonce| nonce.to_vec());
+            let operation = Operation::new("encrypt", self.algorithm.name()).with_aad(&aad_context);
+            let ciphertext = operation.run(|| self.algorithm.encrypt(&key, &nonce, &plaintext, &aad_context))?;
+            blob.extend_from_slice(&nonce);
+            blob.extend_from_slice(&ciphertext);
+            Ok(blob)
+        }).map_err(crate::error::to_pyerr)
+    }
+
+    /// Decrypts a blob produced by `encrypt`, verifying `aad_context`. Decryption
+    /// doesn't count towards the usage limit.
+    fn decrypt(&self, py: Python<'_>, blob: BytesLike, aad_context: AadArg) -> PyResult<Vec<u8>> {
+        py.detach(|| {
+            let (key, blob) = self.open_key(&blob)?;
+            let nonce_len = self.algorithm.nonce_len();
+            if blob.len() < nonce_len {
+                return Err(Error::invalid_format("Ciphertext too short").with_code("truncated"));
+            }
+            let (nonce, ciphertext) = blob.split_at(nonce_len);
+            let operation = Operation::new("decrypt", self.algorithm.name()).with_aad(&aad_context);
+            operation.run(|| self.algorithm.decrypt(&key, nonce, ciphertext, &aad_context))
+        }).map_err(crate::error::to_pyerr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(rekey: bool, max_messages: u64) -> CryptoContext {
+        let key = [7u8; 32];
+        let subkey = rekey.then(|| new_subkey(&key));
+        CryptoContext {
+            key: LockedBytes::new(&key),
+            salt: vec![0; 16],
+            algorithm: Algorithm::AES_256_GCM,
+            kdf_params: KdfParams::default(),
+            rekey,
+            max_messages: Some(max_messages),
+            nonces: None,
+            usage: Mutex::new(Usage { subkey, messages: 0, bytes: 0, rekeys: 0 }),
+        }
+    }
+
+    #[test]
+    fn refuses_past_the_limit_without_rekey() {
+        let ctx = context(false, 2);
+        assert_eq!(ctx.reserve(10).unwrap().1, None);
+        ctx.reserve(5).unwrap();
+        assert_eq!(ctx.reserve(1).unwrap_err().code(), "key_usage_exceeded");
+        let usage = ctx.usage.lock().unwrap();
+        assert_eq!((usage.messages, usage.bytes, usage.rekeys), (2, 15, 0));
+    }
+
+    #[test]
+    fn rekeys_at_the_limit() {
+        let ctx = context(true, 2);
+        let (first_key, first_id) = ctx.reserve(10).unwrap();
+        let (second_key, second_id) = ctx.reserve(10).unwrap();
+        assert_eq!((&first_key, first_id), (&second_key, second_id));
+        let (third_key, third_id) = ctx.reserve(3).unwrap();
+        assert_ne!(third_id, first_id);
+        assert_ne!(third_key, first_key);
+        {
+            let usage = ctx.usage.lock().unwrap();
+            assert_eq!((usage.messages, usage.bytes, usage.rekeys), (1, 3, 1));
+        }
+
+        // Blobs under the old subkey still open: the ID in front picks the key
+        for (key, subkey_id) in [(first_key, first_id.unwrap()), (third_key, third_id.unwrap())] {
+            let mut blob = subkey_id.to_vec();
+            blob.extend_from_slice(b"rest");
+            let (opened, rest) = ctx.open_key(&blob).unwrap();
+            assert_eq!((opened, rest), (key, b"rest".as_slice()));
+        }
+        assert_eq!(ctx.open_key(&[0; SUBKEY_ID_LEN - 1]).unwrap_err().code(), "truncated");
+    }
+}
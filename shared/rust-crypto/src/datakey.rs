@@ -0,0 +1,197 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use argon2::password_hash::rand_core::OsRng;
+use rand::RngCore;
+use std::sync::Arc;
+use zeroize::Zeroizing;
+
+use crate::aad::AadArg;
+use crate::audit::Operation;
+use crate::buffer::BytesLike;
+use crate::cipher::Algorithm;
+use crate::encoding::{Encoded, Encoding};
+use crate::envelope::{default_extensions, encode_token, Envelope};
+use crate::error::Error;
+use crate::kdf::KdfParams;
+use crate::master_key::MasterKeyArg;
+use crate::provider::{self, KeyProvider, Provider};
+
+/// AAD context of the envelope a data key is wrapped in, so a wrapped key can't be
+/// confused with (or substituted by) an ordinary token under the same master key.
+const DATA_KEY_CONTEXT: &[u8] = b"k-services/data-key";
+
+fn data_key(data_key: &[u8]) -> Result<&[u8; 32], Error> {
+    data_key.try_into().map_err(|_| crate::error::wrong_length("data_key", 32, data_key.len()))
+}
+
+/// What protects a data key: a local master key (the key is sealed in an envelope
+/// token) or an external `KeyProvider`.
+pub(crate) enum KeyWrapper<'a> {
+    MasterKey(&'a [u8]),
+    Provider(Arc<dyn Provider>),
+}
+
+impl<'a> KeyWrapper<'a> {
+    /// Exactly one of `master_key` / `provider` must be given.
+    fn new(master_key: Option<&'a [u8]>, provider: Option<&Bound<'_, KeyProvider>>, role: &str) -> PyResult<Self> {
+        match (master_key, provider) {
+            (Some(master_key), None) => Ok(KeyWrapper::MasterKey(master_key)),
+            (None, Some(provider)) => Ok(KeyWrapper::Provider(provider.get().inner.clone())),
+            _ => Err(crate::error::to_pyerr(
+                Error::config(format!("Exactly one of {role}master_key or {role}provider is required"))
+            )),
+        }
+    }
+
+    pub(crate) fn wrap(&self, plaintext_key: &[u8], key_id: Option<&str>, kdf_params: KdfParams) -> Result<String, Error> {
+        match self {
+            KeyWrapper::MasterKey(master_key) => {
+                let envelope = Envelope::seal(
+                    Algorithm::AES_256_GCM, kdf_params, default_extensions(key_id), plaintext_key, master_key, DATA_KEY_CONTEXT
+                )?;
+                encode_token(&envelope)
+            }
+            KeyWrapper::Provider(provider) => provider::wrap_key(provider.as_ref(), plaintext_key, DATA_KEY_CONTEXT),
+        }
+    }
+
+    /// Returns the plaintext key and, for master-key wrapping, the KDF parameters it used.
+    pub(crate) fn unwrap(&self, wrapped_key: &str) -> Result<(Zeroizing<Vec<u8>>, Option<KdfParams>), Error> {
+        match self {
+            KeyWrapper::MasterKey(master_key) => {
+                let envelope = Envelope::from_bytes(&Encoding::Base64Url.decode_secret_text(wrapped_key, "wrapped key")?)?;
+                Ok((envelope.open(master_key, DATA_KEY_CONTEXT)?, Some(envelope.kdf_params)))
+            }
+            KeyWrapper::Provider(provider) => {
+                Ok((Zeroizing::new(provider::unwrap_key(provider.as_ref(), wrapped_key, DATA_KEY_CONTEXT)?), None))
+            }
+        }
+    }
+}
+
+/// KMS-style data key: returns `{plaintext_key, wrapped_key}` where `plaintext_key`
+/// is 32 random bytes for `encrypt_with_data_key` and `wrapped_key` is that key
+/// sealed under `master_key` (an envelope token) or wrapped by `provider` (e.g. an
+/// `AwsKmsProvider`, whose key ARN is recorded in the token). Store only the wrapped
+/// key next to the data; the KDF (or KMS call) runs once per unwrap, not per record.
+/// `key_id` labels the master key and does not apply to providers.
+#[pyfunction]
+#[pyo3(signature = (master_key=None, key_id=None, kdf_profile=None, provider=None))]
+pub fn generate_data_key(
+    py: Python<'_>,
+    master_key: Option<MasterKeyArg>,
+    key_id: Option<&str>,
+    kdf_profile: Option<&str>,
+    provider: Option<Bound<'_, KeyProvider>>
+) -> PyResult<Py<PyDict>> {
+    let wrapper = KeyWrapper::new(master_key.as_deref(), provider.as_ref(), "")?;
+    let (kdf_params, _) = crate::resolve_kdf_params(None, kdf_profile, None, None, None)?;
+    let mut plaintext_key = [0u8; 32];
+    OsRng.fill_bytes(&mut plaintext_key);
+
+    let wrapped_key = py.detach(|| wrapper.wrap(&plaintext_key, key_id, kdf_params))
+        .map_err(crate::error::to_pyerr)?;
+
+    let result = PyDict::new(py);
+    result.set_item("plaintext_key", plaintext_key.to_vec())?;
+    result.set_item("wrapped_key", wrapped_key)?;
+    Ok(result.unbind())
+}
+
+/// Recovers the plaintext data key from a `wrapped_key`, with the same kind of
+/// protection (`master_key` or `provider`) it was generated with.
+#[pyfunction]
+#[pyo3(signature = (wrapped_key, master_key=None, provider=None))]
+pub fn decrypt_data_key<'py>(
+    py: Python<'py>,
+    wrapped_key: &str,
+    master_key: Option<MasterKeyArg>,
+    provider: Option<Bound<'_, KeyProvider>>
+) -> PyResult<Bound<'py, PyBytes>> {
+    let wrapper = KeyWrapper::new(master_key.as_deref(), provider.as_ref(), "")?;
+    py.detach(|| wrapper.unwrap(wrapped_key).map(|(key, _)| key))
+        .map(|key| PyBytes::new(py, &key))
+        .map_err(crate::error::to_pyerr)
+}
+
+/// Re-wraps a data key under a new master key or provider (e.g. to move from a local
+/// master key to KMS). Data encrypted with the key is untouched. Between master keys
+/// the original KDF parameters are kept.
+#[pyfunction]
+#[pyo3(signature = (
+    wrapped_key, old_master_key=None, new_master_key=None, new_key_id=None,
+    old_provider=None, new_provider=None
+))]
+pub fn rewrap_data_key(
+    py: Python<'_>,
+    wrapped_key: &str,
+    old_master_key: Option<MasterKeyArg>,
+    new_master_key: Option<MasterKeyArg>,
+    new_key_id: Option<&str>,
+    old_provider: Option<Bound<'_, KeyProvider>>,
+    new_provider: Option<Bound<'_, KeyProvider>>
+) -> PyResult<String> {
+    let old = KeyWrapper::new(old_master_key.as_deref(), old_provider.as_ref(), "old_")?;
+    let new = KeyWrapper::new(new_master_key.as_deref(), new_provider.as_ref(), "new_")?;
+
+    py.detach(|| {
+        let (plaintext_key, kdf_params) = old.unwrap(wrapped_key)?;
+        new.wrap(&plaintext_key, new_key_id, kdf_params.unwrap_or_default())
+    }).map_err(crate::error::to_pyerr)
+}
+
+/// Encrypts with a plaintext data key directly (no KDF) and returns `nonce || ciphertext`
+/// in `encoding` (`"raw"` for `bytes`).
+#[pyfunction]
+#[pyo3(signature = (
+    plaintext, data_key, aad_context, algorithm=Algorithm::DEFAULT_NAME, encoding=Encoding::DEFAULT_NAME
+))]
+pub fn encrypt_with_data_key(
+    py: Python<'_>,
+    plaintext: BytesLike,
+    data_key: &[u8],
+    aad_context: AadArg,
+    algorithm: &str,
+    encoding: &str
+) -> PyResult<Py<PyAny>> {
+    let algorithm = Algorithm::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+    let encoding = Encoding::from_name(encoding).map_err(crate::error::to_pyerr)?;
+    let key = self::data_key(data_key).map_err(crate::error::to_pyerr)?;
+
+    let blob = py.detach(|| -> Result<Vec<u8>, Error> {
+        let mut blob = vec![0u8; algorithm.nonce_len()];
+        OsRng.fill_bytes(&mut blob);
+        let operation = Operation::new("encrypt", algorithm.name()).with_aad(&aad_context);
+        let ciphertext = operation.run(|| algorithm.encrypt(key, &blob, &plaintext, &aad_context))?;
+        blob.extend_from_slice(&ciphertext);
+        Ok(blob)
+    }).map_err(crate::error::to_pyerr)?;
+    Ok(encoding.encode(py, &blob).unbind())
+}
+
+/// Decrypts a blob from `encrypt_with_data_key`; `algorithm` and `encoding` must match.
+#[pyfunction]
+#[pyo3(signature = (blob, data_key, aad_context, algorithm=Algorithm::DEFAULT_NAME, encoding=Encoding::DEFAULT_NAME))]
+pub fn decrypt_with_data_key(
+    py: Python<'_>,
+    blob: Encoded,
+    data_key: &[u8],
+    aad_context: AadArg,
+    algorithm: &str,
+    encoding: &str
+) -> PyResult<Vec<u8>> {
+    let algorithm = Algorithm::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+    let encoding = Encoding::from_name(encoding).map_err(crate::error::to_pyerr)?;
+    let key = self::data_key(data_key).map_err(crate::error::to_pyerr)?;
+
+    py.detach(|| {
+        let blob = encoding.decode(&blob, "blob")?;
+        let nonce_len = algorithm.nonce_len();
+        if blob.len() < nonce_len {
+            return Err(Error::invalid_format("Ciphertext too short").with_code("truncated"));
+        }
+        let (nonce, ciphertext) = blob.split_at(nonce_len);
+        let operation = Operation::new("decrypt", algorithm.name()).with_aad(&aad_context);
+        operation.run(|| algorithm.decrypt(key, nonce, ciphertext, &aad_context))
+    }).map_err(crate::error::to_pyerr)
+}
@@ -0,0 +1,189 @@
+use pyo3::prelude::*;
+use hkdf::Hkdf;
+use hmac::{digest::KeyInit, Hmac, Mac};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::aad::AadArg;
+use crate::audit::Operation;
+use crate::buffer::BytesLike;
+use crate::cipher::Algorithm;
+use crate::encoding::Encoding;
+use crate::envelope::{Envelope, EXT_KEY_ID};
+use crate::error::Error;
+use crate::kdf::KdfParams;
+use crate::master_key::MasterKeyArg;
+
+const SALT_LABEL: &[u8] = b"k-services/deterministic";
+const BLIND_INDEX_LABEL: &[u8] = b"k-services/blind-index";
+const NONCE_INFO: &[u8] = b"k-services/synthetic-nonce";
+const RECORD_SALT_LABEL: &[u8] = b"k-services/record";
+const RECORD_NONCE_INFO: &[u8] = b"k-services/record-nonce";
+/// Misuse-resistant, so the repeated nonce of equal plaintexts reveals only their equality.
+const ALGORITHM: Algorithm = Algorithm::AES_256_GCM_SIV;
+
+/// Same context, same salt: one key per (purpose, context), derived once when the
+/// key cache is on.
+pub(crate) fn context_salt(label: &[u8], aad_context: &[u8]) -> Vec<u8> {
+    Sha256::new_with_prefix(label).chain_update(aad_context).finalize()[..16].to_vec()
+}
+
+/// HMAC keyed with a nonce key split off the content key under `info`.
+fn nonce_mac(key: &[u8; 32], info: &[u8]) -> Hmac<Sha256> {
+    let mut nonce_key = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(None, key).expand(info, nonce_key.as_mut()).expect("32 bytes is a valid HKDF length");
+    <Hmac<Sha256> as KeyInit>::new_from_slice(nonce_key.as_slice()).expect("HMAC accepts any key length")
+}
+
+/// Nonce = HMAC(nonce key, plaintext).
+fn synthetic_nonce(key: &[u8; 32], plaintext: &[u8]) -> Vec<u8> {
+    let mut mac = nonce_mac(key, NONCE_INFO);
+    mac.update(plaintext);
+    mac.finalize().into_bytes()[..ALGORITHM.nonce_len()].to_vec()
+}
+
+/// Nonce = HMAC(nonce key, len u32 | record_id | version u64), one per (record, version).
+fn record_nonce(key: &[u8; 32], record_id: &[u8], version: u64) -> Vec<u8> {
+    let mut mac = nonce_mac(key, RECORD_NONCE_INFO);
+    mac.update(&(record_id.len() as u32).to_be_bytes());
+    mac.update(record_id);
+    mac.update(&version.to_be_bytes());
+    mac.finalize().into_bytes()[..ALGORITHM.nonce_len()].to_vec()
+}
+
+/// Seals with a salt fixed by `salt_label` and `aad_context`, and the nonce `nonce`
+/// computes from the derived key, so the same inputs always give the same token.
+fn seal(
+    plaintext: &[u8],
+    master_key: &[u8],
+    aad_context: &[u8],
+    kdf_params: KdfParams,
+    key_id: Option<&str>,
+    salt_label: &[u8],
+    nonce: impl FnOnce(&[u8; 32]) -> Vec<u8>
+) -> Result<Vec<u8>, Error> {
+    let salt = context_salt(salt_label, aad_context);
+    let key = kdf_params.derive(master_key, &salt)?;
+    // No created-at extension: a timestamp would make equal plaintexts differ
+    let extensions = key_id.map(|id| vec![(EXT_KEY_ID, id.as_bytes().to_vec())]).unwrap_or_default();
+    let mut envelope = Envelope {
+        algorithm: ALGORITHM,
+        kdf_params,
+        salt,
+        extensions,
+        nonce: nonce(&key),
+        ciphertext: Vec::new(),
+    };
+    let aad = Envelope::aad(&envelope.header()?, aad_context);
+    let operation = Operation::new("encrypt", ALGORITHM.name()).with_key_id(key_id).with_aad(aad_context);
+    envelope.ciphertext = operation.run(|| ALGORITHM.encrypt(&key, &envelope.nonce, plaintext, &aad))?;
+    envelope.to_bytes()
+}
+
+/// DETERMINISTIC encryption: the same plaintext, master key and `aad_context`
+/// always give the same token, so an encrypted column can be matched with `=`.
+/// That is also what it leaks: anyone who sees the tokens learns which rows are
+/// equal (and how often each value occurs). Use it only for fields that must be
+/// searchable, and give each column its own `aad_context`.
+///
+/// The token is a regular envelope (AES-256-GCM-SIV with a synthetic nonce) and
+/// opens with `decrypt_token`, given the same `encoding`.
+#[pyfunction]
+#[pyo3(signature = (
+    plaintext, master_key, aad_context, kdf_profile=None, key_id=None, encoding=Encoding::TOKEN_DEFAULT_NAME
+))]
+pub fn encrypt_deterministic_leaks_equality(
+    py: Python<'_>,
+    plaintext: BytesLike,
+    master_key: MasterKeyArg,
+    aad_context: AadArg,
+    kdf_profile: Option<&str>,
+    key_id: Option<&str>,
+    encoding: &str
+) -> PyResult<Py<PyAny>> {
+    let kdf_params = match kdf_profile {
+        Some(name) => KdfParams::from_profile(name).map(|(_, params)| params)
+            .map_err(crate::error::to_pyerr)?,
+        None => KdfParams::default(),
+    };
+    let encoding = Encoding::from_name(encoding).map_err(crate::error::to_pyerr)?;
+    let envelope = py.detach(|| {
+        seal(&plaintext, &master_key, &aad_context, kdf_params, key_id, SALT_LABEL, |key| synthetic_nonce(key, &plaintext))
+    }).map_err(crate::error::to_pyerr)?;
+    Ok(encoding.encode(py, &envelope).unbind())
+}
+
+/// Encrypts a record so that re-encrypting the same `record_id` and `version` is
+/// idempotent: same plaintext, master key and `aad_context` give the same token,
+/// as content-addressed storage needs. The nonce is a PRF of the derived key,
+/// `record_id` and `version`, so distinct records (or versions) never share one.
+///
+/// Bump `version` whenever a record's content changes. Encrypting different content
+/// under the same record and version reuses the nonce, which AES-256-GCM-SIV
+/// tolerates: it only reveals that the two plaintexts differ. Equal tokens always
+/// mean equal records.
+///
+/// The token is a regular envelope and opens with `decrypt_token`, given the same
+/// `encoding`; `record_id` isn't checked on decryption, so put it in `aad_context`
+/// too if a token must not be accepted in place of another record's.
+#[pyfunction]
+#[pyo3(signature = (
+    plaintext, master_key, aad_context, record_id, version=0, kdf_profile=None, key_id=None,
+    encoding=Encoding::TOKEN_DEFAULT_NAME
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn encrypt_for_record(
+    py: Python<'_>,
+    plaintext: BytesLike,
+    master_key: MasterKeyArg,
+    aad_context: AadArg,
+    record_id: &str,
+    version: u64,
+    kdf_profile: Option<&str>,
+    key_id: Option<&str>,
+    encoding: &str
+) -> PyResult<Py<PyAny>> {
+    let kdf_params = match kdf_profile {
+        Some(name) => KdfParams::from_profile(name).map(|(_, params)| params)
+            .map_err(crate::error::to_pyerr)?,
+        None => KdfParams::default(),
+    };
+    let encoding = Encoding::from_name(encoding).map_err(crate::error::to_pyerr)?;
+    let envelope = py.detach(|| {
+        seal(&plaintext, &master_key, &aad_context, kdf_params, key_id, RECORD_SALT_LABEL, |key| {
+            record_nonce(key, record_id.as_bytes(), version)
+        })
+    }).map_err(crate::error::to_pyerr)?;
+    Ok(encoding.encode(py, &envelope).unbind())
+}
+
+fn blind(value: &[u8], master_key: &[u8], context: &[u8], truncate_bits: usize) -> Result<String, Error> {
+    let key = KdfParams::default().derive(master_key, &context_salt(BLIND_INDEX_LABEL, context))?;
+    let mut mac = <Hmac<Sha256> as KeyInit>::new_from_slice(key.as_slice()).expect("HMAC accepts any key length");
+    mac.update(value);
+    let mut index = mac.finalize().into_bytes()[..truncate_bits.div_ceil(8)].to_vec();
+    // Clear the bits past truncate_bits in the last byte
+    if !truncate_bits.is_multiple_of(8) {
+        *index.last_mut().unwrap() &= 0xff << (8 - truncate_bits % 8);
+    }
+    Ok(crate::hash::to_hex(&index))
+}
+
+/// Blind index for searching an encrypted column: HMAC-SHA256 of `value` under a
+/// key derived from the master key and `context` (use one context per column),
+/// truncated to `truncate_bits` and returned as hex. Store it next to the
+/// ciphertext and look rows up by the index of the search term.
+///
+/// Fewer bits mean more false positives (filter them after decrypting) but less
+/// leakage: with 16-32 bits many values share an index, so equal indexes don't
+/// prove equal values. The full 256 bits leak equality like deterministic encryption.
+#[pyfunction]
+pub fn blind_index(py: Python<'_>, value: &[u8], master_key: MasterKeyArg, context: &str, truncate_bits: usize) -> PyResult<String> {
+    if truncate_bits == 0 || truncate_bits > 256 {
+        return Err(crate::error::to_pyerr(
+            Error::config("truncate_bits must be between 1 and 256").with_input("truncate_bits")
+        ));
+    }
+    py.detach(|| blind(value, &master_key, context.as_bytes(), truncate_bits))
+        .map_err(crate::error::to_pyerr)
+}
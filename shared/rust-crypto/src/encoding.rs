@@ -0,0 +1,408 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyString};
+use base64::{Engine as _, engine::general_purpose::{STANDARD as BASE64, URL_SAFE_NO_PAD as BASE64_URL}};
+use base64ct::Encoding as _;
+use subtle::{Choice, ConstantTimeEq, ConditionallySelectable};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::buffer::BytesLike;
+use crate::error::Error;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const BASE32_ALPHABET_LOWER: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+/// RFC 1924 alphabet, as used by Python's `base64.b85encode`.
+const BASE85_ALPHABET: &[u8; 85] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz!#$%&()*+-;<=>?@^_`{|}~";
+
+/// How binary values (ciphertext, salt, nonce, tag, tokens) cross the Python boundary.
+/// Text encodings decode leniently where it's unambiguous: surrounding whitespace is
+/// ignored, hex and base32 are case-insensitive, and padding is optional.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Encoding {
+    /// Standard padded base64 `str`.
+    Base64,
+    /// URL- and filename-safe base64 without padding.
+    Base64Url,
+    /// RFC 4648 base32: upper-case letters and digits, safe for case-insensitive
+    /// systems and DNS labels.
+    Base32,
+    /// Lower-case hexadecimal.
+    Hex,
+    /// RFC 1924 base85, matching `base64.b85encode`: the most compact text form.
+    Base85,
+    /// Plain `bytes`, for BYTEA/BLOB columns: no size overhead and no encode/decode.
+    Raw,
+}
+
+impl Encoding {
+    pub(crate) const DEFAULT_NAME: &'static str = "base64";
+    /// Default for envelope tokens, which have always been URL-safe.
+    pub(crate) const TOKEN_DEFAULT_NAME: &'static str = "base64url";
+
+    pub(crate) fn from_name(name: &str) -> Result<Self, Error> {
+        match name {
+            "base64" => Ok(Encoding::Base64),
+            "base64url" => Ok(Encoding::Base64Url),
+            "base32" => Ok(Encoding::Base32),
+            "hex" => Ok(Encoding::Hex),
+            "base85" => Ok(Encoding::Base85),
+            "raw" => Ok(Encoding::Raw),
+            other => Err(Error::config(format!(
+                "Unsupported encoding: {} (expected base64, base64url, base32, hex, base85 or raw)", other
+            )).with_code("unsupported_encoding")),
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Encoding::Base64 => "base64",
+            Encoding::Base64Url => "base64url",
+            Encoding::Base32 => "base32",
+            Encoding::Hex => "hex",
+            Encoding::Base85 => "base85",
+            Encoding::Raw => "raw",
+        }
+    }
+
+    pub(crate) fn encode<'py>(self, py: Python<'py>, data: &[u8]) -> Bound<'py, PyAny> {
+        let text = match self {
+            Encoding::Base64 => BASE64.encode(data),
+            Encoding::Base64Url => BASE64_URL.encode(data),
+            Encoding::Base32 => base32_encode(data),
+            Encoding::Hex => crate::hash::to_hex(data),
+            Encoding::Base85 => base85_encode(data),
+            Encoding::Raw => return PyBytes::new(py, data).into_any(),
+        };
+        PyString::new(py, &text).into_any()
+    }
+
+    /// Decodes `value`, naming it `what` in errors.
+    pub(crate) fn decode(self, value: &Encoded, what: &str) -> Result<Vec<u8>, Error> {
+        let text = match (self, value) {
+            (Encoding::Raw, Encoded::Bytes(bytes)) => return Ok(bytes.to_vec()),
+            (Encoding::Raw, Encoded::Text(_)) => return Err(
+                Error::config(format!("{} must be bytes for encoding='raw'", what))
+                    .with_code("wrong_input_type").with_input(what)
+            ),
+            (encoding, Encoded::Bytes(_)) => {
+                return Err(
+                    Error::config(format!("{} must be a str for encoding='{}'", what, encoding.name()))
+                        .with_code("wrong_input_type").with_input(what)
+                );
+            }
+            (_, Encoded::Text(text)) => text.trim(),
+        };
+        let decoded = match self {
+            Encoding::Base64 => BASE64.decode(text).map_err(|e| Error::invalid_format(e.to_string())),
+            Encoding::Base64Url => BASE64_URL.decode(text.trim_end_matches('='))
+                .map_err(|e| Error::invalid_format(e.to_string())),
+            Encoding::Base32 => base32_decode(text),
+            Encoding::Hex => hex_decode(text),
+            Encoding::Base85 => base85_decode(text),
+            Encoding::Raw => unreachable!(),
+        };
+        decoded.map_err(|e| e.context(format!("Invalid {} {}", self.name(), what))
+            .with_code("invalid_encoding").with_input(what))
+    }
+
+    /// `decode` for secrets (key material, tags, key shares): the time taken depends
+    /// only on the length, not on the characters, and errors don't quote the input.
+    pub(crate) fn decode_secret(self, value: &Encoded, what: &str) -> Result<Zeroizing<Vec<u8>>, Error> {
+        match (self, value) {
+            (Encoding::Raw, _) | (_, Encoded::Bytes(_)) => self.decode(value, what).map(Zeroizing::new),
+            (_, Encoded::Text(text)) => self.decode_secret_text(text, what),
+        }
+    }
+
+    /// `decode_secret` for a text encoding.
+    pub(crate) fn decode_secret_text(self, text: &str, what: &str) -> Result<Zeroizing<Vec<u8>>, Error> {
+        let text = text.trim();
+        let decoded = match self {
+            Encoding::Base64 => base64ct::Base64::decode_vec(text).ok(),
+            Encoding::Base64Url => base64ct::Base64UrlUnpadded::decode_vec(text.trim_end_matches('=')).ok(),
+            Encoding::Base32 => ct_base32_decode(text),
+            Encoding::Hex => base16ct::mixed::decode_vec(text).ok(),
+            Encoding::Base85 => ct_base85_decode(text),
+            Encoding::Raw => return Err(
+                Error::config(format!("{} must be bytes for encoding='raw'", what))
+                    .with_code("wrong_input_type").with_input(what)
+            ),
+        };
+        decoded.map(Zeroizing::new)
+            .ok_or_else(|| {
+                Error::invalid_format(format!("Invalid {} {}: bad character, padding or length", self.name(), what))
+                    .with_code("invalid_encoding").with_input(what)
+            })
+    }
+}
+
+/// Value of `c` in `alphabet`, found by comparing against every entry rather than
+/// stopping at the match; the value is 0 when it isn't there.
+fn ct_lookup(c: u8, alphabet: &[u8]) -> (u32, Choice) {
+    let (mut value, mut found) = (0u32, Choice::from(0));
+    for (index, symbol) in alphabet.iter().enumerate() {
+        let hit = c.ct_eq(symbol);
+        value.conditional_assign(&(index as u32), hit);
+        found |= hit;
+    }
+    (value, found)
+}
+
+/// Zeroes `out` and fails unless every character was `valid`.
+fn ct_finish(mut out: Vec<u8>, valid: Choice) -> Option<Vec<u8>> {
+    if bool::from(valid) {
+        return Some(out);
+    }
+    out.zeroize();
+    None
+}
+
+fn ct_base32_decode(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim_end_matches('=');
+    if matches!(text.len() % 8, 1 | 3 | 6) {
+        return None;
+    }
+    let mut out = Vec::with_capacity(text.len() * 5 / 8);
+    let (mut bits, mut count, mut valid) = (0u32, 0, Choice::from(1));
+    for &c in text.as_bytes() {
+        let (upper, in_upper) = ct_lookup(c, BASE32_ALPHABET);
+        let (lower, in_lower) = ct_lookup(c, BASE32_ALPHABET_LOWER);
+        valid &= in_upper | in_lower;
+        bits = bits << 5 | upper | lower;
+        count += 5;
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+            bits &= (1 << count) - 1;
+        }
+    }
+    ct_finish(out, valid)
+}
+
+fn ct_base85_decode(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 5 == 1 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(text.len() * 4 / 5);
+    let mut valid = Choice::from(1);
+    for chunk in text.as_bytes().chunks(5) {
+        let mut value = 0u64;
+        for i in 0..5 {
+            let digit = match chunk.get(i) {
+                Some(&c) => {
+                    let (digit, found) = ct_lookup(c, BASE85_ALPHABET);
+                    valid &= found;
+                    digit
+                }
+                None => 84,
+            };
+            value = value * 85 + digit as u64;
+        }
+        valid &= (value >> 32).ct_eq(&0);
+        out.extend_from_slice(&(value as u32).to_be_bytes()[..chunk.len() - 1]);
+    }
+    ct_finish(out, valid)
+}
+
+pub(crate) fn base32_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(5) * 8);
+    for chunk in data.chunks(5) {
+        let mut group = [0u8; 5];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let bits = group.iter().fold(0u64, |acc, &byte| acc << 8 | byte as u64);
+        let symbols = (chunk.len() * 8).div_ceil(5);
+        for i in 0..8 {
+            if i < symbols {
+                out.push(BASE32_ALPHABET[(bits >> (35 - i * 5)) as usize & 31] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn base32_decode(text: &str) -> Result<Vec<u8>, Error> {
+    let text = text.trim_end_matches('=');
+    if matches!(text.len() % 8, 1 | 3 | 6) {
+        return Err(Error::invalid_format("invalid length"));
+    }
+    let mut out = Vec::with_capacity(text.len() * 5 / 8);
+    let (mut bits, mut count) = (0u64, 0);
+    for c in text.bytes() {
+        let value = BASE32_ALPHABET.iter().position(|&a| a == c.to_ascii_uppercase())
+            .ok_or_else(|| Error::invalid_format(format!("invalid character {:?}", c as char)))?;
+        bits = bits << 5 | value as u64;
+        count += 5;
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+        }
+    }
+    Ok(out)
+}
+
+pub(crate) fn hex_decode(text: &str) -> Result<Vec<u8>, Error> {
+    if !text.len().is_multiple_of(2) {
+        return Err(Error::invalid_format("odd number of digits"));
+    }
+    let digit = |c: u8| (c as char).to_digit(16)
+        .ok_or_else(|| Error::invalid_format(format!("invalid character {:?}", c as char)));
+    text.as_bytes().chunks(2).map(|pair| Ok((digit(pair[0])? << 4 | digit(pair[1])?) as u8)).collect()
+}
+
+fn base85_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(4) * 5);
+    for chunk in data.chunks(4) {
+        let mut group = [0u8; 4];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let mut value = u32::from_be_bytes(group);
+        let mut digits = [0u8; 5];
+        for digit in digits.iter_mut().rev() {
+            *digit = BASE85_ALPHABET[(value % 85) as usize];
+            value /= 85;
+        }
+        // A partial final group keeps only as many digits as it needs
+        out.extend(digits[..chunk.len() + 1].iter().map(|&d| d as char));
+    }
+    out
+}
+
+fn base85_decode(text: &str) -> Result<Vec<u8>, Error> {
+    if text.len() % 5 == 1 {
+        return Err(Error::invalid_format("invalid length"));
+    }
+    let mut out = Vec::with_capacity(text.len() * 4 / 5);
+    for chunk in text.as_bytes().chunks(5) {
+        // Padding with the highest digit rounds up, so truncating recovers the bytes
+        let mut value = 0u64;
+        for i in 0..5 {
+            let digit = match chunk.get(i) {
+                Some(&c) => BASE85_ALPHABET.iter().position(|&a| a == c)
+                    .ok_or_else(|| Error::invalid_format(format!("invalid character {:?}", c as char)))?,
+                None => 84,
+            };
+            value = value * 85 + digit as u64;
+        }
+        let value = u32::try_from(value).map_err(|_| Error::invalid_format("group out of range"))?;
+        out.extend_from_slice(&value.to_be_bytes()[..chunk.len() - 1]);
+    }
+    Ok(out)
+}
+
+/// Encoded binary argument: a `str` for the text encodings, or a bytes-like object
+/// for `encoding="raw"`.
+pub(crate) enum Encoded {
+    Text(String),
+    Bytes(BytesLike),
+}
+
+impl Encoded {
+    /// Size as given: bytes, or UTF-8 bytes of the text.
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            Encoded::Text(text) => text.len(),
+            Encoded::Bytes(bytes) => bytes.len(),
+        }
+    }
+}
+
+impl<'a, 'py> FromPyObject<'a, 'py> for Encoded {
+    type Error = PyErr;
+
+    fn extract(obj: Borrowed<'a, 'py, PyAny>) -> PyResult<Self> {
+        if let Ok(text) = obj.cast::<PyString>() {
+            return Ok(Encoded::Text(text.to_str()?.to_owned()));
+        }
+        obj.extract().map(Encoded::Bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Encoding; 6] =
+        [Encoding::Base64, Encoding::Base64Url, Encoding::Base32, Encoding::Hex, Encoding::Base85, Encoding::Raw];
+
+    fn text(text: &str) -> Encoded {
+        Encoded::Text(text.to_string())
+    }
+
+    #[test]
+    fn every_encoding_round_trips() {
+        Python::initialize();
+        Python::attach(|py| {
+            for encoding in ALL {
+                assert_eq!(Encoding::from_name(encoding.name()).unwrap(), encoding);
+                for len in 0..70 {
+                    let data: Vec<u8> = (0..len).map(|i| (i * 37 + len) as u8).collect();
+                    let encoded: Encoded = encoding.encode(py, &data).extract().unwrap();
+                    assert_eq!(encoding.decode(&encoded, "value").unwrap(), data, "{:?}, length {}", encoding, len);
+                    assert_eq!(
+                        encoding.decode_secret(&encoded, "value").unwrap().as_slice(), data,
+                        "{:?}, length {}", encoding, len
+                    );
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn matches_pythons_base64_module() {
+        for (encoding, encoded) in [
+            (Encoding::Base64, "aGVsbG8/Pg=="),
+            (Encoding::Base64Url, "aGVsbG8_Pg"),
+            (Encoding::Base32, "NBSWY3DPH47A===="),
+            (Encoding::Hex, "68656c6c6f3f3e"),
+            (Encoding::Base85, "Xk~0{Z$CZ"),
+        ] {
+            assert_eq!(encoding.decode(&text(encoded), "value").unwrap(), b"hello?>", "{:?}", encoding);
+            assert_eq!(encoding.decode_secret_text(encoded, "value").unwrap().as_slice(), b"hello?>", "{:?}", encoding);
+        }
+        assert_eq!(base32_encode(b"hi"), "NBUQ====");
+        assert_eq!(base85_encode(b"hi"), "XlV");
+    }
+
+    #[test]
+    fn decodes_leniently() {
+        for (encoding, encoded) in [
+            (Encoding::Base64Url, " aGk= \n"),
+            (Encoding::Base32, "nbuq"),
+            (Encoding::Hex, "6869"),
+            (Encoding::Hex, "\t6869 "),
+        ] {
+            assert_eq!(encoding.decode(&text(encoded), "value").unwrap(), b"hi", "{:?} {:?}", encoding, encoded);
+            assert_eq!(encoding.decode_secret_text(encoded, "value").unwrap().as_slice(), b"hi", "{:?}", encoding);
+        }
+        assert_eq!(Encoding::Hex.decode(&text("4A4b"), "value").unwrap(), b"JK");
+    }
+
+    #[test]
+    fn rejects_malformed_text() {
+        for (encoding, encoded) in [
+            (Encoding::Base64, "aGk*"),
+            (Encoding::Base64Url, "aGk+"),
+            (Encoding::Base32, "NBUQ1"),
+            (Encoding::Base32, "N"),
+            (Encoding::Hex, "686"),
+            (Encoding::Hex, "6g"),
+            (Encoding::Base85, "X"),
+            (Encoding::Base85, "Xl\""),
+            (Encoding::Base85, "~~~~~"),
+        ] {
+            let error = encoding.decode(&text(encoded), "tag").unwrap_err();
+            assert_eq!(error.code(), "invalid_encoding", "{:?} {:?}", encoding, encoded);
+            assert!(error.message().starts_with(&format!("Invalid {} tag", encoding.name())), "{}", error.message());
+            let error = encoding.decode_secret_text(encoded, "tag").unwrap_err();
+            assert_eq!(error.code(), "invalid_encoding", "{:?} {:?}", encoding, encoded);
+            assert!(!error.message().contains(encoded), "secret input quoted: {}", error.message());
+        }
+    }
+
+    #[test]
+    fn raw_takes_only_bytes() {
+        assert_eq!(Encoding::Raw.decode(&text("abc"), "token").unwrap_err().code(), "wrong_input_type");
+        assert_eq!(Encoding::Raw.decode_secret_text("abc", "token").unwrap_err().code(), "wrong_input_type");
+        assert_eq!(Encoding::from_name("base58").unwrap_err().code(), "unsupported_encoding");
+    }
+}
@@ -0,0 +1,766 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use argon2::password_hash::rand_core::OsRng;
+use rand::RngCore;
+use base64::{Engine as _, engine::general_purpose::{STANDARD as BASE64, URL_SAFE_NO_PAD as BASE64_URL}};
+use hkdf::Hkdf;
+use sha2::Sha512;
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::aad::AadArg;
+use crate::audit::Operation;
+use crate::buffer::BytesLike;
+use crate::cipher::Algorithm;
+use crate::compression::{self, DEFAULT_COMPRESSION_LEVEL};
+use crate::error::Error;
+use crate::padding::{self, Padding, DEFAULT_BLOCK_SIZE};
+use crate::encoding::{Encoded, Encoding};
+use crate::kdf::{DerivedKey, KdfParams};
+use crate::master_key::MasterKeyArg;
+
+/// First bytes of every envelope, so tokens can be recognised without a key.
+pub(crate) const MAGIC: &[u8; 3] = b"KSC";
+pub(crate) const VERSION: u8 = 1;
+
+/// Extension records. Unknown types are preserved and still authenticated.
+const EXT_CREATED_AT: u8 = 1; // u64 unix seconds
+pub(crate) const EXT_KEY_ID: u8 = 2; // UTF-8 label of the master key generation
+const EXT_COMPRESSION: u8 = 3; // see `compression::compress`
+const EXT_PADDING: u8 = 4; // see `Padding::record`
+const EXT_KEY_COMMITMENT: u8 = 5; // 32-byte commitment to the key; see `commit_key`
+pub(crate) const EXT_EXPIRES_AT: u8 = 6; // u64 unix seconds; see `Envelope::check_expiry`
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// Self-describing ciphertext container.
+///
+/// Binary layout (all integers big-endian):
+///
+/// ```text
+/// magic "KSC" | version u8 | algorithm u8 | kdf u8 | kdf params
+/// | salt_len u8 | salt | ext_len u16 | extensions | nonce | ciphertext || tag
+/// ```
+///
+/// The algorithm and KDF bytes are the `envelope_id`s of their registry entries, and
+/// the KDF params are laid out as in `KdfParams::write`. Extensions are
+/// `type u8 | len u16 | value` records reserved for optional header fields.
+/// Everything before the ciphertext is the header and is authenticated as
+/// part of the AAD, so no field can be altered without failing decryption.
+pub(crate) struct Envelope {
+    pub(crate) algorithm: Algorithm,
+    pub(crate) kdf_params: KdfParams,
+    pub(crate) salt: Vec<u8>,
+    pub(crate) extensions: Vec<(u8, Vec<u8>)>,
+    pub(crate) nonce: Vec<u8>,
+    pub(crate) ciphertext: Vec<u8>,
+}
+
+/// Minimal cursor over the envelope bytes; every read reports truncation as an error.
+pub(crate) struct Reader<'a> {
+    pub(crate) data: &'a [u8],
+    pub(crate) pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        if self.data.len() - self.pos < n {
+            return Err(Error::invalid_format("Invalid envelope: truncated").with_code("truncated"));
+        }
+        let slice = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(slice)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+impl Envelope {
+    fn extension(&self, kind: u8) -> Option<&[u8]> {
+        self.extensions.iter().find(|(k, _)| *k == kind).map(|(_, v)| v.as_slice())
+    }
+
+    pub(crate) fn created_at(&self) -> Option<u64> {
+        self.extension(EXT_CREATED_AT)
+            .and_then(|v| v.try_into().ok())
+            .map(u64::from_be_bytes)
+    }
+
+    pub(crate) fn expires_at(&self) -> Option<u64> {
+        self.extension(EXT_EXPIRES_AT)
+            .and_then(|v| v.try_into().ok())
+            .map(u64::from_be_bytes)
+    }
+
+    /// Fails once the recorded expiry has passed. Only meaningful after decryption,
+    /// which is what authenticates the expiry.
+    pub(crate) fn check_expiry(&self) -> Result<(), Error> {
+        match self.expires_at() {
+            Some(expires_at) if now() >= expires_at => Err(
+                Error::expired(format!("Ciphertext has expired (at {})", expires_at))
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn key_id(&self) -> Option<String> {
+        self.extension(EXT_KEY_ID).map(|v| String::from_utf8_lossy(v).into_owned())
+    }
+
+    pub(crate) fn is_compressed(&self) -> bool {
+        self.extension(EXT_COMPRESSION).is_some()
+    }
+
+    fn compression(&self) -> Option<&'static str> {
+        self.extension(EXT_COMPRESSION).map(|record| compression::codec_name(record).unwrap_or("unknown"))
+    }
+
+    pub(crate) fn padding(&self) -> Result<Option<Padding>, Error> {
+        self.extension(EXT_PADDING).map(Padding::from_record).transpose()
+    }
+
+    pub(crate) fn is_key_committed(&self) -> bool {
+        self.extension(EXT_KEY_COMMITMENT).is_some()
+    }
+
+    /// Algorithm, KDF and options, for log records.
+    fn summary(&self) -> String {
+        let mut summary = format!("{}, {}", self.algorithm.name(), self.kdf_params.name());
+        for (present, option) in [
+            (self.key_id().is_some(), "key ID"),
+            (self.expires_at().is_some(), "expiring"),
+            (self.is_compressed(), "compressed"),
+            (self.extension(EXT_PADDING).is_some(), "padded"),
+            (self.is_key_committed(), "key-committing"),
+        ] {
+            if present {
+                summary.push_str(", ");
+                summary.push_str(option);
+            }
+        }
+        summary
+    }
+
+    /// Key the cipher actually ran under. For key-committing envelopes that is
+    /// derived from `key_bytes`, after checking the stored commitment.
+    pub(crate) fn cipher_key(&self, key_bytes: &[u8; 32]) -> Result<DerivedKey, Error> {
+        let Some(stored) = self.extension(EXT_KEY_COMMITMENT) else {
+            return Ok(Zeroizing::new(*key_bytes));
+        };
+        let (cipher_key, commitment) = commit_key(key_bytes, &self.nonce);
+        if !bool::from(commitment.ct_eq(stored)) {
+            return Err(
+                Error::decryption("Decryption failed: key commitment mismatch").with_code("key_commitment_mismatch")
+            );
+        }
+        Ok(cipher_key)
+    }
+
+    /// Serializes everything up to (and including) the nonce.
+    pub(crate) fn header(&self) -> Result<Vec<u8>, Error> {
+        let mut out = Vec::with_capacity(64);
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.push(self.algorithm.envelope_id());
+        self.kdf_params.write(&mut out);
+
+        let salt_len = u8::try_from(self.salt.len()).map_err(|_| Error::config("Salt too long"))?;
+        out.push(salt_len);
+        out.extend_from_slice(&self.salt);
+
+        let mut extensions = Vec::new();
+        for (kind, value) in &self.extensions {
+            let len = u16::try_from(value.len()).map_err(|_| Error::config("Envelope extension too long"))?;
+            extensions.push(*kind);
+            extensions.extend_from_slice(&len.to_be_bytes());
+            extensions.extend_from_slice(value);
+        }
+        let ext_len = u16::try_from(extensions.len()).map_err(|_| Error::config("Envelope extensions too long"))?;
+        out.extend_from_slice(&ext_len.to_be_bytes());
+        out.extend_from_slice(&extensions);
+
+        out.extend_from_slice(&self.nonce);
+        Ok(out)
+    }
+
+    /// AAD actually given to the cipher: the serialized header followed by the caller's context.
+    pub(crate) fn aad(header: &[u8], aad_context: &[u8]) -> Vec<u8> {
+        let mut aad = Vec::with_capacity(header.len() + aad_context.len());
+        aad.extend_from_slice(header);
+        aad.extend_from_slice(aad_context);
+        aad
+    }
+
+    pub(crate) fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut out = self.header()?;
+        out.extend_from_slice(&self.ciphertext);
+        Ok(out)
+    }
+
+    pub(crate) fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        let (mut envelope, body) = Self::parse_header(data)?;
+        envelope.ciphertext = data[body..].to_vec();
+        Ok(envelope)
+    }
+
+    /// Parses everything up to the nonce and returns the envelope (with an empty
+    /// `ciphertext`) and the offset where `ciphertext || tag` starts in `data`.
+    pub(crate) fn parse_header(data: &[u8]) -> Result<(Self, usize), Error> {
+        let mut reader = Reader { data, pos: 0 };
+
+        if reader.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+            return Err(Error::invalid_format("Invalid envelope: bad magic").with_code("unrecognized_format"));
+        }
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(
+                Error::invalid_format(format!("Unsupported envelope version: {}", version))
+                    .with_code("unsupported_version")
+            );
+        }
+        let algorithm = Algorithm::from_envelope_id(reader.u8()?)?;
+
+        let kdf_params = KdfParams::read(&mut reader)?;
+
+        let salt_len = reader.u8()? as usize;
+        let salt = reader.take(salt_len)?.to_vec();
+
+        let ext_len = reader.u16()? as usize;
+        let mut ext = Reader { data: reader.take(ext_len)?, pos: 0 };
+        let mut extensions = Vec::new();
+        while ext.pos < ext.data.len() {
+            let kind = ext.u8()?;
+            let len = ext.u16()? as usize;
+            extensions.push((kind, ext.take(len)?.to_vec()));
+        }
+
+        let nonce = reader.take(algorithm.nonce_len())?.to_vec();
+
+        Ok((Envelope { algorithm, kdf_params, salt, extensions, nonce, ciphertext: Vec::new() }, reader.pos))
+    }
+
+    /// Encrypts `plaintext` into a new envelope with a fresh salt and nonce.
+    pub(crate) fn seal(
+        algorithm: Algorithm,
+        kdf_params: KdfParams,
+        extensions: Vec<(u8, Vec<u8>)>,
+        plaintext: &[u8],
+        master_key: &[u8],
+        aad_context: &[u8]
+    ) -> Result<Self, Error> {
+        let mut salt = vec![0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let key_bytes = kdf_params.derive(master_key, &salt)?;
+        Self::seal_with_key(algorithm, kdf_params, salt, extensions, &key_bytes, plaintext, aad_context)
+    }
+
+    /// Like `seal`, for callers that already derived `key_bytes` from the master key
+    /// and `salt` (e.g. to encrypt many values under one derivation). The nonce is
+    /// still fresh per envelope. A record from `request_key_commitment` in
+    /// `extensions` is filled in here, once the nonce is known.
+    pub(crate) fn seal_with_key(
+        algorithm: Algorithm,
+        kdf_params: KdfParams,
+        salt: Vec<u8>,
+        mut extensions: Vec<(u8, Vec<u8>)>,
+        key_bytes: &[u8; 32],
+        plaintext: &[u8],
+        aad_context: &[u8]
+    ) -> Result<Self, Error> {
+        let mut nonce = vec![0u8; algorithm.nonce_len()];
+        OsRng.fill_bytes(&mut nonce);
+
+        let mut cipher_key = Zeroizing::new(*key_bytes);
+        if let Some((_, value)) = extensions.iter_mut().find(|(kind, _)| *kind == EXT_KEY_COMMITMENT) {
+            let (key, commitment) = commit_key(key_bytes, &nonce);
+            (cipher_key, *value) = (key, commitment.to_vec());
+        }
+
+        let mut envelope = Envelope { algorithm, kdf_params, salt, extensions, nonce, ciphertext: Vec::new() };
+        crate::logging::debug(|| format!("Sealing envelope: {}", envelope.summary()));
+        let header = envelope.header()?;
+        let key_id = envelope.key_id();
+        let operation = Operation::new("encrypt", algorithm.name())
+            .with_key_id(key_id.as_deref())
+            .with_aad(aad_context);
+        envelope.ciphertext = operation.run(|| {
+            algorithm.encrypt(&cipher_key, &envelope.nonce, plaintext, &Self::aad(&header, aad_context))
+        })?;
+        Ok(envelope)
+    }
+
+    /// Derives the key from the recorded parameters and decrypts, authenticating the header too.
+    pub(crate) fn open(&self, master_key: &[u8], aad_context: &[u8]) -> Result<Zeroizing<Vec<u8>>, Error> {
+        let key_bytes = self.kdf_params.derive(master_key, &self.salt)?;
+        self.open_with_key(&key_bytes, aad_context)
+    }
+
+    /// `open`, which with `allow_expired` also accepts an envelope past its expiry.
+    pub(crate) fn open_allowing(&self, master_key: &[u8], aad_context: &[u8], allow_expired: bool) -> Result<Zeroizing<Vec<u8>>, Error> {
+        if !allow_expired {
+            return self.open(master_key, aad_context);
+        }
+        let key_bytes = self.kdf_params.derive(master_key, &self.salt)?;
+        self.decrypt_with_key(&key_bytes, aad_context)
+    }
+
+    /// Decrypts with a key the caller derived from this envelope's salt and KDF parameters,
+    /// undoing any padding and compression recorded in the header, and fails if the
+    /// envelope has expired. The plaintext is wiped on drop; only the copy handed to
+    /// Python outlives it.
+    pub(crate) fn open_with_key(&self, key_bytes: &[u8; 32], aad_context: &[u8]) -> Result<Zeroizing<Vec<u8>>, Error> {
+        let plaintext = self.decrypt_with_key(key_bytes, aad_context)?;
+        self.check_expiry()?;
+        Ok(plaintext)
+    }
+
+    fn decrypt_with_key(&self, key_bytes: &[u8; 32], aad_context: &[u8]) -> Result<Zeroizing<Vec<u8>>, Error> {
+        crate::logging::debug(|| format!("Opening envelope: {}", self.summary()));
+        let key_id = self.key_id();
+        let operation = Operation::new("decrypt", self.algorithm.name())
+            .with_key_id(key_id.as_deref())
+            .with_aad(aad_context);
+        operation.run(|| {
+            let header = self.header()?;
+            let key = self.cipher_key(key_bytes)?;
+            let mut plaintext = Zeroizing::new(
+                self.algorithm.decrypt(&key, &self.nonce, &self.ciphertext, &Self::aad(&header, aad_context))?
+            );
+            if self.padding()?.is_some() {
+                // Truncating keeps the pad bytes in the allocation, where they are wiped with the rest
+                let len = padding::unpadded_len(&plaintext)?;
+                plaintext.truncate(len);
+            }
+            match self.extension(EXT_COMPRESSION) {
+                Some(record) => compression::decompress(&plaintext, record).map(Zeroizing::new),
+                None => Ok(plaintext),
+            }
+        })
+    }
+}
+
+/// Splits `key_bytes` into the key the cipher uses and a commitment to it, as the
+/// AWS Encryption SDK's committing suites do: HKDF-SHA512 salted with the nonce.
+/// AES-GCM and ChaCha20-Poly1305 alone let one ciphertext be crafted to decrypt
+/// under two different keys; checking the commitment first rules that out.
+fn commit_key(key_bytes: &[u8; 32], nonce: &[u8]) -> (DerivedKey, [u8; 32]) {
+    let hkdf = Hkdf::<Sha512>::new(Some(nonce), key_bytes);
+    let (mut cipher_key, mut commitment) = (Zeroizing::new([0u8; 32]), [0u8; 32]);
+    hkdf.expand(b"KSC-DERIVEKEY", cipher_key.as_mut()).expect("32 bytes is a valid HKDF length");
+    hkdf.expand(b"KSC-COMMITKEY", &mut commitment).expect("32 bytes is a valid HKDF length");
+    (cipher_key, commitment)
+}
+
+/// Makes `Envelope::seal_with_key` commit to its key; see `commit_key`.
+pub(crate) fn request_key_commitment(extensions: &mut Vec<(u8, Vec<u8>)>) {
+    extensions.push((EXT_KEY_COMMITMENT, Vec::new()));
+}
+
+/// Compresses and then pads `plaintext` as requested, recording each step in
+/// `extensions` so `open_with_key` can undo them.
+fn encode_plaintext(
+    plaintext: &[u8],
+    compression_level: Option<i32>,
+    padding: Option<Padding>,
+    extensions: &mut Vec<(u8, Vec<u8>)>
+) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let mut plaintext = Zeroizing::new(match compression_level {
+        Some(level) => {
+            let (compressed, record) = compression::compress(plaintext, level)?;
+            extensions.push((EXT_COMPRESSION, record));
+            compressed
+        }
+        None => plaintext.to_vec(),
+    });
+    if let Some(padding) = padding {
+        padding.pad(&mut plaintext);
+        extensions.push((EXT_PADDING, padding.record()));
+    }
+    Ok(plaintext)
+}
+
+/// Header fields every new envelope carries, plus the key ID when one is known.
+pub(crate) fn default_extensions(key_id: Option<&str>) -> Vec<(u8, Vec<u8>)> {
+    let mut extensions = vec![(EXT_CREATED_AT, now().to_be_bytes().to_vec())];
+    if let Some(key_id) = key_id {
+        extensions.push((EXT_KEY_ID, key_id.as_bytes().to_vec()));
+    }
+    extensions
+}
+
+/// The expiry from `expires_at` (unix seconds) or `ttl` (seconds from now), if either is given.
+fn resolve_expiry(expires_at: Option<u64>, ttl: Option<u64>) -> Result<Option<u64>, Error> {
+    match (expires_at, ttl) {
+        (Some(_), Some(_)) => Err(Error::config("Pass either expires_at or ttl, not both")),
+        (expires_at, ttl) => Ok(expires_at.or_else(|| ttl.map(|ttl| now().saturating_add(ttl)))),
+    }
+}
+
+pub(crate) fn encode_token(envelope: &Envelope) -> Result<String, Error> {
+    Ok(BASE64_URL.encode(envelope.to_bytes()?))
+}
+
+pub(crate) fn decode_token(token: &str) -> Result<Envelope, Error> {
+    let data = BASE64_URL.decode(token.trim())
+        .map_err(|e| Error::invalid_format(format!("Invalid token encoding: {}", e)))?;
+    Envelope::from_bytes(&data)
+}
+
+/// `decode_token` for a token given to Python in `encoding`.
+fn decode_token_as(token: &Encoded, encoding: Encoding) -> Result<Envelope, Error> {
+    Envelope::from_bytes(&encoding.decode(token, "token")?)
+}
+
+fn parse_encoding(encoding: &str) -> PyResult<Encoding> {
+    Encoding::from_name(encoding).map_err(crate::error::to_pyerr)
+}
+
+/// Encrypts `plaintext` into a single URL-safe token carrying the algorithm,
+/// KDF parameters, salt and nonce, so storage is one column. `key_id` labels the
+/// master key generation used; it is authenticated along with the rest of the header.
+///
+/// `encoding` picks the token's text form (default URL-safe base64), or `"raw"`
+/// for the binary envelope as `bytes`; decrypt with the same `encoding`.
+///
+/// `compress=True` runs zstd at `compression_level` before encrypting; it is
+/// recorded in the header and undone by decryption. Don't compress data that mixes
+/// secrets with attacker-controlled input: the token length then reveals how well
+/// the two compress together (CRIME/BREACH), which can leak the secret.
+///
+/// `padding` hides the exact plaintext length: `"padme"` pads to one of a sparse
+/// set of lengths (at most ~12% larger), `"block"` to a multiple of
+/// `padding_block_size`. Padding is applied after compression and stripped on decrypt.
+///
+/// `commit_key=True` makes the token key-committing: it can only be decrypted
+/// with the master key it was made under. Use it when a token may be tried against
+/// several keys (a `Keyring`, per-recipient keys), where a crafted token could
+/// otherwise open under more than one.
+///
+/// `expires_at` (unix seconds) or `ttl` (seconds from now) is authenticated in the
+/// header; decrypting after that time raises `ExpiredCiphertextError` unless
+/// `allow_expired=True`. Useful for password-reset and invite blobs.
+#[pyfunction]
+#[pyo3(signature = (
+    plaintext, master_key, aad_context, algorithm=Algorithm::DEFAULT_NAME,
+    memory_cost=None, time_cost=None, parallelism=None, kdf_profile=None, key_id=None,
+    kdf_params=None, encoding=Encoding::TOKEN_DEFAULT_NAME, compress=false, compression_level=DEFAULT_COMPRESSION_LEVEL,
+    padding=None, padding_block_size=DEFAULT_BLOCK_SIZE, commit_key=false, expires_at=None, ttl=None
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn encrypt_to_token(
+    py: Python<'_>,
+    plaintext: BytesLike,
+    master_key: MasterKeyArg,
+    aad_context: AadArg,
+    algorithm: &str,
+    memory_cost: Option<u32>,
+    time_cost: Option<u32>,
+    parallelism: Option<u32>,
+    kdf_profile: Option<&str>,
+    key_id: Option<&str>,
+    kdf_params: Option<&str>,
+    encoding: &str,
+    compress: bool,
+    compression_level: i32,
+    padding: Option<&str>,
+    padding_block_size: u32,
+    commit_key: bool,
+    expires_at: Option<u64>,
+    ttl: Option<u64>
+) -> PyResult<Py<PyAny>> {
+    let algorithm = Algorithm::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+    let (kdf_params, _) = crate::resolve_kdf_params(kdf_params, kdf_profile, memory_cost, time_cost, parallelism)?;
+    let encoding = parse_encoding(encoding)?;
+    let padding = padding.map(|name| Padding::from_name(name, padding_block_size)).transpose()
+        .map_err(crate::error::to_pyerr)?;
+    let expires_at = resolve_expiry(expires_at, ttl).map_err(crate::error::to_pyerr)?;
+
+    let envelope = py.detach(|| {
+        let mut extensions = default_extensions(key_id);
+        if let Some(expires_at) = expires_at {
+            extensions.push((EXT_EXPIRES_AT, expires_at.to_be_bytes().to_vec()));
+        }
+        if commit_key {
+            request_key_commitment(&mut extensions);
+        }
+        let plaintext = encode_plaintext(&plaintext, compress.then_some(compression_level), padding, &mut extensions)?;
+        Envelope::seal(algorithm, kdf_params, extensions, &plaintext, &master_key, &aad_context)?.to_bytes()
+    }).map_err(crate::error::to_pyerr)?;
+    Ok(encoding.encode(py, &envelope).unbind())
+}
+
+/// Decrypts a token produced by `encrypt_to_token`. Everything needed besides the
+/// master key and AAD is read from the token itself. Intermediate buffers are wiped,
+/// but the returned `bytes` is immutable and can't be; use `decrypt_into` with a
+/// `bytearray` to clear the plaintext after use. Expired tokens raise
+/// `ExpiredCiphertextError` (after authenticating) unless `allow_expired=True`.
+#[pyfunction]
+#[pyo3(signature = (token, master_key, aad_context, encoding=Encoding::TOKEN_DEFAULT_NAME, allow_expired=false))]
+pub fn decrypt_token<'py>(
+    py: Python<'py>,
+    token: Encoded,
+    master_key: MasterKeyArg,
+    aad_context: AadArg,
+    encoding: &str,
+    allow_expired: bool
+) -> PyResult<Bound<'py, PyBytes>> {
+    let encoding = parse_encoding(encoding)?;
+    py.detach(|| decode_token_as(&token, encoding)?.open_allowing(&master_key, &aad_context, allow_expired))
+        .map(|plaintext| PyBytes::new(py, &plaintext))
+        .map_err(crate::error::to_pyerr)
+}
+
+/// Decrypts `token` with `old_master_key` and re-encrypts it under `new_master_key`
+/// in one call, so the plaintext never crosses into Python. The new token gets a
+/// fresh salt and nonce and keeps the original KDF parameters; the cipher is kept
+/// too unless `algorithm` is given. `new_key_id` labels the new master key. Both
+/// tokens use `encoding`; compression, padding, key commitment and expiry carry over
+/// to the new token, and an expired token is refused.
+#[pyfunction]
+#[pyo3(signature = (
+    token, old_master_key, new_master_key, aad_context, algorithm=None, new_key_id=None,
+    encoding=Encoding::TOKEN_DEFAULT_NAME
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn re_encrypt(
+    py: Python<'_>,
+    token: Encoded,
+    old_master_key: MasterKeyArg,
+    new_master_key: MasterKeyArg,
+    aad_context: AadArg,
+    algorithm: Option<&str>,
+    new_key_id: Option<&str>,
+    encoding: &str
+) -> PyResult<Py<PyAny>> {
+    let algorithm = algorithm.map(Algorithm::from_name).transpose()
+        .map_err(crate::error::to_pyerr)?;
+    let encoding = parse_encoding(encoding)?;
+
+    let envelope = py.detach(|| {
+        let old = decode_token_as(&token, encoding)?;
+        let plaintext = old.open(&old_master_key, &aad_context)?;
+        let mut extensions = default_extensions(new_key_id);
+        if let Some(expires_at) = old.expires_at() {
+            extensions.push((EXT_EXPIRES_AT, expires_at.to_be_bytes().to_vec()));
+        }
+        if old.is_key_committed() {
+            request_key_commitment(&mut extensions);
+        }
+        let compression_level = old.is_compressed().then_some(DEFAULT_COMPRESSION_LEVEL);
+        let plaintext = encode_plaintext(&plaintext, compression_level, old.padding()?, &mut extensions)?;
+        let envelope = Envelope::seal(
+            algorithm.unwrap_or(old.algorithm),
+            old.kdf_params,
+            extensions,
+            &plaintext,
+            &new_master_key,
+            &aad_context
+        )?;
+        envelope.to_bytes()
+    }).map_err(crate::error::to_pyerr)?;
+    Ok(encoding.encode(py, &envelope).unbind())
+}
+
+/// Reads an envelope's header without the master key: `{version, algorithm, kdf,
+/// kdf_params, salt, nonce, key_id, created_at, expires_at, compression, padding,
+/// key_committed, ciphertext_len}`.
+///
+/// Nothing here is verified until the token is actually decrypted; treat the
+/// values as informational (audits, migration planning), not as trusted input.
+#[pyfunction]
+#[pyo3(signature = (token, encoding=Encoding::TOKEN_DEFAULT_NAME))]
+pub fn inspect(py: Python<'_>, token: Encoded, encoding: &str) -> PyResult<Py<PyDict>> {
+    let envelope = decode_token_as(&token, parse_encoding(encoding)?).map_err(crate::error::to_pyerr)?;
+    Ok(metadata_dict(py, &envelope)?.unbind())
+}
+
+/// Like `decrypt_token`, but returns `{plaintext, ...}` together with the same
+/// header fields as `inspect()`, which at this point have been authenticated.
+#[pyfunction]
+#[pyo3(signature = (token, master_key, aad_context, encoding=Encoding::TOKEN_DEFAULT_NAME, allow_expired=false))]
+pub fn decrypt_token_with_metadata(
+    py: Python<'_>,
+    token: Encoded,
+    master_key: MasterKeyArg,
+    aad_context: AadArg,
+    encoding: &str,
+    allow_expired: bool
+) -> PyResult<Py<PyDict>> {
+    let encoding = parse_encoding(encoding)?;
+    let (envelope, plaintext) = py.detach(|| {
+        let envelope = decode_token_as(&token, encoding)?;
+        let plaintext = envelope.open_allowing(&master_key, &aad_context, allow_expired)?;
+        Ok::<_, Error>((envelope, plaintext))
+    }).map_err(crate::error::to_pyerr)?;
+
+    let result = metadata_dict(py, &envelope)?;
+    result.set_item("plaintext", PyBytes::new(py, &plaintext))?;
+    Ok(result.unbind())
+}
+
+fn metadata_dict<'py>(py: Python<'py>, envelope: &Envelope) -> PyResult<Bound<'py, PyDict>> {
+    let result = PyDict::new(py);
+    result.set_item("version", VERSION)?;
+    result.set_item("algorithm", envelope.algorithm.name())?;
+    result.set_item("kdf", envelope.kdf_params.name())?;
+    result.set_item("kdf_params", envelope.kdf_params.encode())?;
+    result.set_item("salt", BASE64.encode(&envelope.salt))?;
+    result.set_item("nonce", BASE64.encode(&envelope.nonce))?;
+    result.set_item("key_id", envelope.key_id())?;
+    result.set_item("created_at", envelope.created_at())?;
+    result.set_item("expires_at", envelope.expires_at())?;
+    result.set_item("compression", envelope.compression())?;
+    result.set_item("padding", envelope.padding().ok().flatten().map(Padding::name))?;
+    result.set_item("key_committed", envelope.is_key_committed())?;
+    result.set_item("ciphertext_len", envelope.ciphertext.len())?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Kind;
+
+    const KEY: [u8; 32] = [7; 32];
+
+    fn kdf_params() -> KdfParams {
+        KdfParams::new(Some(8), Some(1), Some(1)).unwrap()
+    }
+
+    fn sealed(plaintext: &[u8], aad_context: &[u8]) -> Envelope {
+        let extensions = default_extensions(Some("k1"));
+        let salt = vec![1; 16];
+        Envelope::seal_with_key(Algorithm::AES_256_GCM, kdf_params(), salt, extensions, &KEY, plaintext, aad_context).unwrap()
+    }
+
+    #[test]
+    fn round_trips_through_bytes() {
+        let data = sealed(b"attack at dawn", b"ctx").to_bytes().unwrap();
+        let envelope = Envelope::from_bytes(&data).unwrap();
+        assert_eq!(envelope.algorithm.name(), "aes-256-gcm");
+        assert_eq!(envelope.kdf_params, kdf_params());
+        assert_eq!(envelope.salt, vec![1; 16]);
+        assert_eq!(envelope.key_id().as_deref(), Some("k1"));
+        assert!(envelope.created_at().is_some());
+        assert_eq!(envelope.to_bytes().unwrap(), data);
+        assert_eq!(envelope.open_with_key(&KEY, b"ctx").unwrap().as_slice(), b"attack at dawn");
+    }
+
+    #[test]
+    fn opens_with_the_master_key() {
+        let envelope = Envelope::seal(Algorithm::XCHACHA20_POLY1305, kdf_params(), Vec::new(), b"", b"master", b"")
+            .unwrap();
+        let envelope = Envelope::from_bytes(&envelope.to_bytes().unwrap()).unwrap();
+        assert!(envelope.open(b"master", b"").unwrap().is_empty());
+        assert_eq!(envelope.open(b"other", b"").unwrap_err().kind(), Kind::Decryption);
+    }
+
+    #[test]
+    fn rejects_a_truncated_header() {
+        let envelope = sealed(b"x", b"");
+        let header = envelope.header().unwrap();
+        for len in 0..header.len() {
+            let error = Envelope::from_bytes(&header[..len]).err().expect("truncated header parsed");
+            assert_eq!(error.kind(), Kind::InvalidFormat, "length {}", len);
+            if len >= MAGIC.len() {
+                assert_eq!(error.code(), "truncated", "length {}", len);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_a_truncated_body() {
+        let data = sealed(b"x", b"").to_bytes().unwrap();
+        let (_, body) = Envelope::parse_header(&data).unwrap();
+        for len in body..data.len() {
+            let envelope = Envelope::from_bytes(&data[..len]).unwrap();
+            assert_eq!(envelope.open_with_key(&KEY, b"").unwrap_err().kind(), Kind::Decryption);
+        }
+    }
+
+    #[test]
+    fn rejects_every_flipped_byte() {
+        let data = sealed(b"attack at dawn", b"ctx").to_bytes().unwrap();
+        for index in 0..data.len() {
+            let mut tampered = data.clone();
+            tampered[index] ^= 0x01;
+            let result = Envelope::from_bytes(&tampered).and_then(|envelope| envelope.open_with_key(&KEY, b"ctx"));
+            assert!(result.is_err(), "byte {} changed without being detected", index);
+        }
+    }
+
+    #[test]
+    fn rejects_the_wrong_aad_context_or_key() {
+        let envelope = sealed(b"x", b"ctx");
+        assert_eq!(envelope.open_with_key(&KEY, b"other").unwrap_err().kind(), Kind::Decryption);
+        assert_eq!(envelope.open_with_key(&[8; 32], b"ctx").unwrap_err().kind(), Kind::Decryption);
+    }
+
+    #[test]
+    fn rejects_bad_magic_and_unknown_versions() {
+        let data = sealed(b"x", b"").to_bytes().unwrap();
+        let mut bad_magic = data.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(Envelope::from_bytes(&bad_magic).err().unwrap().code(), "unrecognized_format");
+        let mut bad_version = data;
+        bad_version[MAGIC.len()] = VERSION + 1;
+        assert_eq!(Envelope::from_bytes(&bad_version).err().unwrap().code(), "unsupported_version");
+    }
+
+    fn expiring(expires_at: u64) -> Envelope {
+        let extensions = vec![(EXT_EXPIRES_AT, expires_at.to_be_bytes().to_vec())];
+        let envelope = Envelope::seal(Algorithm::AES_256_GCM, kdf_params(), extensions, b"invite", b"master", b"ctx")
+            .unwrap();
+        Envelope::from_bytes(&envelope.to_bytes().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn rejects_expired_envelopes_unless_allowed() {
+        let envelope = expiring(now() - 1);
+        assert_eq!(envelope.expires_at(), Some(now() - 1));
+        assert_eq!(envelope.open(b"master", b"ctx").unwrap_err().kind(), Kind::Expired);
+        assert_eq!(envelope.open_allowing(b"master", b"ctx", false).unwrap_err().kind(), Kind::Expired);
+        assert_eq!(envelope.open_allowing(b"master", b"ctx", true).unwrap().as_slice(), b"invite");
+        // Authenticated first: a wrong key is a decryption failure, expired or not
+        assert_eq!(envelope.open_allowing(b"other", b"ctx", false).unwrap_err().kind(), Kind::Decryption);
+        assert_eq!(envelope.open_allowing(b"other", b"ctx", true).unwrap_err().kind(), Kind::Decryption);
+    }
+
+    #[test]
+    fn opens_envelopes_before_their_expiry() {
+        let envelope = expiring(now() + 3600);
+        assert_eq!(envelope.open(b"master", b"ctx").unwrap().as_slice(), b"invite");
+        assert!(expiring(now()).check_expiry().is_err());
+        assert!(sealed(b"x", b"").check_expiry().is_ok());
+    }
+
+    #[test]
+    fn resolves_expiry() {
+        assert_eq!(resolve_expiry(None, None).unwrap(), None);
+        assert_eq!(resolve_expiry(Some(5), None).unwrap(), Some(5));
+        let expires_at = resolve_expiry(None, Some(60)).unwrap().unwrap();
+        assert!((now() + 59..=now() + 60).contains(&expires_at));
+        assert_eq!(resolve_expiry(Some(5), Some(60)).unwrap_err().kind(), Kind::Config);
+    }
+
+    #[test]
+    fn keeps_unknown_extensions() {
+        let mut envelope = sealed(b"x", b"");
+        envelope.extensions.push((200, b"future".to_vec()));
+        let parsed = Envelope::from_bytes(&envelope.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed.extension(200), Some(b"future".as_slice()));
+    }
+}
@@ -0,0 +1,316 @@
+use pyo3::prelude::*;
+use pyo3::create_exception;
+
+create_exception!(k_services_crypto, CryptoError, pyo3::exceptions::PyValueError,
+    "Base class for this module's errors. Subclasses `ValueError`, which everything used to raise, so existing handlers keep working.
+
+Every instance carries a stable `code` (e.g. `\"authentication_failed\"`, `\"invalid_encoding\"`,
+`\"buffer_too_small\"`) and structured context, each `None` when it doesn't apply: `input` names
+the argument or part that failed, `item` is the index in a batch, `field` the dict field, and
+`expected` / `actual` are byte lengths.");
+create_exception!(k_services_crypto, DecryptionError, CryptoError,
+    "The data didn't authenticate: wrong key, wrong AAD context, or tampered ciphertext.");
+create_exception!(k_services_crypto, InvalidFormatError, CryptoError,
+    "Input isn't a well-formed token, envelope, key or encoding (bad base64, wrong magic, truncated, unknown version).");
+create_exception!(k_services_crypto, KdfError, CryptoError,
+    "Key derivation failed or its parameters are invalid.");
+create_exception!(k_services_crypto, ExpiredCiphertextError, CryptoError,
+    "The data authenticated, but the expiry it was encrypted with has passed.");
+create_exception!(k_services_crypto, ConfigError, CryptoError,
+    "Invalid argument or option: an unknown algorithm, encoding or profile, or a conflicting combination.");
+
+/// Exception class an error raises as.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Kind {
+    Decryption,
+    Expired,
+    Config,
+    Kdf,
+    InvalidFormat,
+    Crypto,
+}
+
+impl Kind {
+    /// The `code` of errors of this kind that don't set one.
+    fn default_code(self) -> &'static str {
+        match self {
+            Kind::Decryption => "authentication_failed",
+            Kind::Expired => "expired",
+            Kind::Config => "invalid_argument",
+            Kind::Kdf => "kdf_failed",
+            Kind::InvalidFormat => "invalid_format",
+            Kind::Crypto => "crypto_error",
+        }
+    }
+}
+
+/// Structured context, each `None` when it doesn't apply; see `CryptoError`.
+#[derive(Debug, Default)]
+struct Details {
+    input: Option<String>,
+    item: Option<usize>,
+    field: Option<String>,
+    expected: Option<usize>,
+    actual: Option<usize>,
+}
+
+/// An internal failure, with the exception class, `code` and context chosen where it
+/// happens; `to_pyerr` turns it into the exception at the boundary.
+#[derive(Debug)]
+pub(crate) struct Error {
+    kind: Kind,
+    code: &'static str,
+    message: String,
+    // Boxed so results stay small: most errors carry no details
+    details: Option<Box<Details>>,
+}
+
+impl Error {
+    fn new(kind: Kind, message: impl Into<String>) -> Self {
+        Error { kind, code: kind.default_code(), message: message.into(), details: None }
+    }
+
+    fn details(&mut self) -> &mut Details {
+        self.details.get_or_insert_with(Box::default)
+    }
+
+    /// Wrong key, wrong AAD context or tampered data.
+    pub(crate) fn decryption(message: impl Into<String>) -> Self {
+        Error::new(Kind::Decryption, message)
+    }
+
+    pub(crate) fn expired(message: impl Into<String>) -> Self {
+        Error::new(Kind::Expired, message)
+    }
+
+    /// An invalid argument or option.
+    pub(crate) fn config(message: impl Into<String>) -> Self {
+        Error::new(Kind::Config, message)
+    }
+
+    pub(crate) fn kdf(message: impl Into<String>) -> Self {
+        Error::new(Kind::Kdf, message)
+    }
+
+    /// Input that isn't a well-formed token, envelope, key or encoding.
+    pub(crate) fn invalid_format(message: impl Into<String>) -> Self {
+        Error::new(Kind::InvalidFormat, message)
+    }
+
+    /// Anything else: provider, I/O and token-claim failures.
+    pub(crate) fn crypto(message: impl Into<String>) -> Self {
+        Error::new(Kind::Crypto, message)
+    }
+
+    pub(crate) fn with_code(mut self, code: &'static str) -> Self {
+        self.code = code;
+        self
+    }
+
+    /// Names the argument or part that failed.
+    pub(crate) fn with_input(mut self, input: impl Into<String>) -> Self {
+        self.details().input = Some(input.into());
+        self
+    }
+
+    /// Byte lengths: the one required and the one given.
+    pub(crate) fn with_lengths(mut self, expected: usize, actual: usize) -> Self {
+        let details = self.details();
+        (details.expected, details.actual) = (Some(expected), Some(actual));
+        self
+    }
+
+    pub(crate) fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    pub(crate) fn code(&self) -> &'static str {
+        self.code
+    }
+
+    pub(crate) fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The same failure, with the message prefixed by `prefix: `.
+    pub(crate) fn context(mut self, prefix: impl std::fmt::Display) -> Self {
+        self.message = format!("{}: {}", prefix, self.message);
+        self
+    }
+
+    /// The failure of item `index` of a batch.
+    pub(crate) fn in_item(mut self, index: usize) -> Self {
+        self.details().item = Some(index);
+        self.context(format_args!("Item {}", index))
+    }
+
+    /// The failure of dict field `name`.
+    pub(crate) fn in_field(mut self, name: &str) -> Self {
+        self.details().field = Some(name.to_string());
+        self.context(format_args!("Field {:?}", name))
+    }
+
+    fn annotate(&self, py: Python<'_>, err: &PyErr) -> PyResult<()> {
+        let value = err.value(py);
+        let details = self.details.as_deref();
+        value.setattr("code", self.code)?;
+        value.setattr("input", details.and_then(|d| d.input.as_deref()))?;
+        value.setattr("item", details.and_then(|d| d.item))?;
+        value.setattr("field", details.and_then(|d| d.field.as_deref()))?;
+        value.setattr("expected", details.and_then(|d| d.expected))?;
+        value.setattr("actual", details.and_then(|d| d.actual))?;
+        Ok(())
+    }
+}
+
+/// Length mismatch of `input`.
+pub(crate) fn wrong_length(input: &str, expected: usize, actual: usize) -> Error {
+    Error::invalid_format(format!("Invalid {} length: expected {} bytes, got {}", input, expected, actual))
+        .with_code("invalid_length")
+        .with_input(input)
+        .with_lengths(expected, actual)
+}
+
+/// Raises `error` as the exception class of its kind, with its `code` and context as
+/// attributes. Authentication failures go through the `set_decrypt_failure_hook`
+/// hook first.
+pub(crate) fn to_pyerr(error: Error) -> PyErr {
+    let message = error.message();
+    let err = match error.kind() {
+        Kind::Decryption => DecryptionError::new_err(message.to_string()),
+        Kind::Expired => ExpiredCiphertextError::new_err(message.to_string()),
+        Kind::Config => ConfigError::new_err(message.to_string()),
+        Kind::Kdf => KdfError::new_err(message.to_string()),
+        Kind::InvalidFormat => InvalidFormatError::new_err(message.to_string()),
+        Kind::Crypto => CryptoError::new_err(message.to_string()),
+    };
+    crate::metrics::record_failure(error.code());
+    crate::logging::info(|| format!("Failed [{}]: {}", error.code(), message));
+    // Setting attributes on a fresh exception instance can't fail in practice; if it
+    // somehow did, the exception is still worth raising without them
+    let _ = Python::attach(|py| error.annotate(py, &err));
+    if error.kind() == Kind::Decryption {
+        return crate::throttle::on_decryption_failure(err);
+    }
+    err
+}
+
+pub(crate) fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add("CryptoError", py.get_type::<CryptoError>())?;
+    m.add("DecryptionError", py.get_type::<DecryptionError>())?;
+    m.add("InvalidFormatError", py.get_type::<InvalidFormatError>())?;
+    m.add("KdfError", py.get_type::<KdfError>())?;
+    m.add("ConfigError", py.get_type::<ConfigError>())?;
+    m.add("ExpiredCiphertextError", py.get_type::<ExpiredCiphertextError>())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+    use crate::cipher::Algorithm;
+    use crate::envelope::Envelope;
+    use crate::jwt::{self, JwtAlgorithm, Validation};
+    use crate::kdf::KdfParams;
+    use crate::{branca, fernet, paseto};
+
+    #[test]
+    fn kinds_raise_their_exception_class() {
+        Python::initialize();
+        Python::attach(|py| {
+            let raised = |error: Error| to_pyerr(error).get_type(py).name().unwrap().to_string();
+            assert_eq!(raised(Error::decryption("x")), "DecryptionError");
+            assert_eq!(raised(Error::expired("x")), "ExpiredCiphertextError");
+            assert_eq!(raised(Error::config("x")), "ConfigError");
+            assert_eq!(raised(Error::kdf("x")), "KdfError");
+            assert_eq!(raised(Error::invalid_format("x")), "InvalidFormatError");
+            assert_eq!(raised(Error::crypto("x")), "CryptoError");
+            for error in [Error::decryption("x"), Error::config("x"), Error::crypto("x")] {
+                let err = to_pyerr(error);
+                assert!(err.is_instance_of::<CryptoError>(py));
+                assert!(err.is_instance_of::<pyo3::exceptions::PyValueError>(py));
+            }
+            assert_eq!(to_pyerr(Error::kdf("Bad parameters")).value(py).to_string(), "Bad parameters");
+        });
+    }
+
+    #[test]
+    fn failures_are_classified_where_they_happen() {
+        let key = [0u8; 32];
+        let nonce = [0u8; 12];
+        let ciphertext = Algorithm::AES_256_GCM.encrypt(&key, &nonce, b"x", b"").unwrap();
+        let error = Algorithm::AES_256_GCM.decrypt(&key, &nonce, &ciphertext, b"other").unwrap_err();
+        assert_eq!(error.kind(), Kind::Decryption);
+        assert_eq!(Algorithm::from_name("rot13").unwrap_err().kind(), Kind::Config);
+        assert_eq!(KdfParams::parse("argon2id:m=1").unwrap_err().kind(), Kind::Kdf);
+        assert_eq!(Envelope::from_bytes(b"not an envelope").err().unwrap().kind(), Kind::InvalidFormat);
+    }
+
+    #[test]
+    fn token_formats_classify_a_wrong_key_as_decryption() {
+        let fernet = fernet::FernetKey::parse(&fernet::fernet_generate_key()).unwrap();
+        let other = fernet::FernetKey::parse(&fernet::fernet_generate_key()).unwrap();
+        let token = fernet::seal(&fernet, b"x", 0);
+        assert_eq!(fernet::open(&other, &token, None).unwrap_err().kind(), Kind::Decryption, "Fernet");
+
+        let token = branca::seal(&[1; 32], b"x", 0).unwrap();
+        assert_eq!(branca::open(&[2; 32], &token, None).unwrap_err().kind(), Kind::Decryption, "Branca");
+
+        let token = paseto::local_encrypt(&[1; 32], b"x", b"", b"").unwrap();
+        let error = paseto::local_decrypt(&token, &[2; 32], None, b"").unwrap_err();
+        assert_eq!(error.kind(), Kind::Decryption, "PASETO");
+
+        let input = "eyJhbGciOiJIUzI1NiJ9.e30";
+        let signature = JwtAlgorithm::Hs256.sign(b"key", input.as_bytes()).unwrap();
+        let token = format!("{}.{}", input, URL_SAFE_NO_PAD.encode(signature));
+        let validation = Validation {
+            algorithms: vec![JwtAlgorithm::Hs256], audience: None, issuer: None, leeway: 0.0, require: Vec::new(),
+        };
+        assert!(jwt::verify(&token, b"key", &validation).is_ok());
+        assert_eq!(jwt::verify(&token, b"other", &validation).unwrap_err().kind(), Kind::Decryption, "JWT");
+    }
+
+    #[test]
+    fn codes_default_to_the_kind() {
+        assert_eq!(Error::decryption("x").code(), "authentication_failed");
+        assert_eq!(Error::expired("x").code(), "expired");
+        assert_eq!(Error::config("x").code(), "invalid_argument");
+        assert_eq!(Error::kdf("x").code(), "kdf_failed");
+        assert_eq!(Error::invalid_format("x").code(), "invalid_format");
+        assert_eq!(Error::crypto("x").code(), "crypto_error");
+        let error = Error::invalid_format("x").with_code("truncated");
+        assert_eq!((error.kind(), error.code()), (Kind::InvalidFormat, "truncated"));
+    }
+
+    #[test]
+    fn context_prefixes_the_message() {
+        assert_eq!(Error::decryption("Decryption failed").in_item(3).message(), "Item 3: Decryption failed");
+        assert_eq!(Error::config("bad").in_field("ssn").message(), "Field \"ssn\": bad");
+        assert_eq!(Error::kdf("bad").context("Old key").message(), "Old key: bad");
+    }
+
+    #[test]
+    fn exceptions_carry_code_and_context() {
+        Python::initialize();
+        Python::attach(|py| {
+            let attribute = |err: &PyErr, name: &str| err.value(py).getattr(name).unwrap().to_string();
+            let err = to_pyerr(wrong_length("nonce", 12, 8).in_item(2));
+            assert_eq!(attribute(&err, "code"), "invalid_length");
+            assert_eq!(attribute(&err, "input"), "nonce");
+            assert_eq!(attribute(&err, "item"), "2");
+            assert_eq!(attribute(&err, "field"), "None");
+            assert_eq!(attribute(&err, "expected"), "12");
+            assert_eq!(attribute(&err, "actual"), "8");
+            assert_eq!(err.value(py).to_string(), "Item 2: Invalid nonce length: expected 12 bytes, got 8");
+
+            let err = to_pyerr(Error::decryption("Decryption failed").in_field("email"));
+            assert_eq!(attribute(&err, "code"), "authentication_failed");
+            assert_eq!(attribute(&err, "field"), "email");
+            for name in ["input", "item", "expected", "actual"] {
+                assert_eq!(attribute(&err, name), "None", "{}", name);
+            }
+        });
+    }
+}
@@ -0,0 +1,599 @@
+use pyo3::prelude::*;
+use aes_gcm::{aead::{consts::U12, Aead, KeyInit, Payload}, aes::Aes192, Aes128Gcm, Aes256Gcm, AesGcm};
+use argon2::password_hash::rand_core::OsRng;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use hkdf::Hkdf;
+use p384::ecdsa::signature::{DigestSigner, DigestVerifier};
+use p384::ecdsa::{Signature, SigningKey, VerifyingKey};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+
+use crate::audit::Operation;
+use crate::cipher::Algorithm;
+use crate::envelope::Reader;
+use crate::error::Error;
+use crate::provider::{KeyProvider, Provider};
+use crate::random::random_bytes;
+
+/// Encryption context entry holding the signing public key of signed suites.
+const PUBLIC_KEY_CONTEXT_KEY: &str = "aws-crypto-public-key";
+/// Context keys with this prefix are reserved for the SDK itself.
+const RESERVED_CONTEXT_PREFIX: &str = "aws-crypto-";
+
+const VERSION_1: u8 = 0x01;
+const VERSION_2: u8 = 0x02;
+/// v1 message type: customer authenticated encrypted data.
+const TYPE_CUSTOMER_AED: u8 = 0x80;
+const CONTENT_NON_FRAMED: u8 = 0x01;
+const CONTENT_FRAMED: u8 = 0x02;
+const IV_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const DATA_KEY_LEN: usize = 32;
+const COMMITMENT_LEN: usize = 32;
+const FINAL_FRAME_MARKER: u32 = 0xFFFF_FFFF;
+const DEFAULT_FRAME_LENGTH: u32 = 4096;
+const RAW_AES_TAG_BITS: u32 = 128;
+
+const SINGLE_BLOCK: &[u8] = b"AWSKMSEncryptionClient Single Block";
+const FRAME: &[u8] = b"AWSKMSEncryptionClient Frame";
+const FINAL_FRAME: &[u8] = b"AWSKMSEncryptionClient Final Frame";
+
+/// The AES-256-GCM algorithm suites with a KDF. The unsigned/non-committing ones
+/// are what older SDKs default to; the SDK's current default is `CommitKeyEcdsaP384`.
+#[derive(Clone, Copy, PartialEq)]
+enum Suite {
+    HkdfSha256,
+    HkdfSha384EcdsaP384,
+    CommitKey,
+    CommitKeyEcdsaP384,
+}
+
+impl Suite {
+    const DEFAULT_NAME: &'static str = "AES_256_GCM_HKDF_SHA512_COMMIT_KEY_ECDSA_P384";
+
+    fn from_id(id: u16) -> Result<Self, Error> {
+        match id {
+            0x0178 => Ok(Suite::HkdfSha256),
+            0x0378 => Ok(Suite::HkdfSha384EcdsaP384),
+            0x0478 => Ok(Suite::CommitKey),
+            0x0578 => Ok(Suite::CommitKeyEcdsaP384),
+            _ => Err(Error::config(format!("Unsupported AWS Encryption SDK algorithm suite: 0x{:04x}", id))),
+        }
+    }
+
+    fn from_name(name: &str) -> Result<Self, Error> {
+        match name.to_ascii_uppercase().as_str() {
+            "AES_256_GCM_IV12_TAG16_HKDF_SHA256" => Ok(Suite::HkdfSha256),
+            "AES_256_GCM_IV12_TAG16_HKDF_SHA384_ECDSA_P384" => Ok(Suite::HkdfSha384EcdsaP384),
+            "AES_256_GCM_HKDF_SHA512_COMMIT_KEY" => Ok(Suite::CommitKey),
+            "AES_256_GCM_HKDF_SHA512_COMMIT_KEY_ECDSA_P384" => Ok(Suite::CommitKeyEcdsaP384),
+            _ => Err(Error::config(format!("Unsupported AWS Encryption SDK algorithm suite: {}", name))),
+        }
+    }
+
+    fn id(self) -> u16 {
+        match self {
+            Suite::HkdfSha256 => 0x0178,
+            Suite::HkdfSha384EcdsaP384 => 0x0378,
+            Suite::CommitKey => 0x0478,
+            Suite::CommitKeyEcdsaP384 => 0x0578,
+        }
+    }
+
+    /// Committing suites use the v2 message format.
+    fn committing(self) -> bool {
+        matches!(self, Suite::CommitKey | Suite::CommitKeyEcdsaP384)
+    }
+
+    fn signed(self) -> bool {
+        matches!(self, Suite::HkdfSha384EcdsaP384 | Suite::CommitKeyEcdsaP384)
+    }
+
+    fn message_id_len(self) -> usize {
+        if self.committing() { 32 } else { 16 }
+    }
+
+    /// Content key and, for committing suites, the key commitment stored in the header.
+    fn derive(self, data_key: &[u8], message_id: &[u8]) -> ([u8; 32], Option<Vec<u8>>) {
+        let mut key = [0u8; 32];
+        let id = self.id().to_be_bytes();
+        match self {
+            Suite::HkdfSha256 => Hkdf::<Sha256>::new(None, data_key)
+                .expand(&[&id[..], message_id].concat(), &mut key).unwrap(),
+            Suite::HkdfSha384EcdsaP384 => Hkdf::<Sha384>::new(None, data_key)
+                .expand(&[&id[..], message_id].concat(), &mut key).unwrap(),
+            Suite::CommitKey | Suite::CommitKeyEcdsaP384 => {
+                let hkdf = Hkdf::<Sha512>::new(Some(message_id), data_key);
+                hkdf.expand(&[&id[..], b"DERIVEKEY"].concat(), &mut key).unwrap();
+                let mut commitment = vec![0u8; COMMITMENT_LEN];
+                hkdf.expand(b"COMMITKEY", &mut commitment).unwrap();
+                return (key, Some(commitment));
+            }
+        }
+        (key, None)
+    }
+}
+
+/// Key/value pairs sorted by key, as in the header AAD; empty context is empty bytes.
+fn serialize_context(context: &BTreeMap<String, String>) -> Result<Vec<u8>, Error> {
+    if context.is_empty() {
+        return Ok(Vec::new());
+    }
+    let too_long = || Error::config("Encryption context is too large");
+    let mut out = Vec::new();
+    out.extend_from_slice(&u16::try_from(context.len()).map_err(|_| too_long())?.to_be_bytes());
+    for (key, value) in context {
+        for field in [key, value] {
+            out.extend_from_slice(&u16::try_from(field.len()).map_err(|_| too_long())?.to_be_bytes());
+            out.extend_from_slice(field.as_bytes());
+        }
+    }
+    u16::try_from(out.len()).map_err(|_| too_long())?;
+    Ok(out)
+}
+
+fn parse_context(data: &[u8]) -> Result<BTreeMap<String, String>, Error> {
+    let mut context = BTreeMap::new();
+    if data.is_empty() {
+        return Ok(context);
+    }
+    let mut reader = Reader { data, pos: 0 };
+    let field = |reader: &mut Reader| -> Result<String, Error> {
+        let len = reader.u16()? as usize;
+        String::from_utf8(reader.take(len)?.to_vec())
+            .map_err(|_| Error::invalid_format("Invalid encryption context: not UTF-8"))
+    };
+    for _ in 0..reader.u16()? {
+        let key = field(&mut reader)?;
+        let value = field(&mut reader)?;
+        if context.insert(key, value).is_some() {
+            return Err(Error::invalid_format("Invalid encryption context: duplicate key"));
+        }
+    }
+    if reader.pos != data.len() {
+        return Err(Error::invalid_format("Invalid encryption context: trailing bytes"));
+    }
+    Ok(context)
+}
+
+struct EncryptedDataKey {
+    provider_id: String,
+    provider_info: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+fn wrapping_gcm(key: &[u8], iv: &[u8], payload: Payload, encrypt: bool) -> Option<Vec<u8>> {
+    macro_rules! run {
+        ($cipher:ty) => {{
+            let cipher = <$cipher>::new(key.into());
+            if encrypt { cipher.encrypt(iv.into(), payload) } else { cipher.decrypt(iv.into(), payload) }
+        }};
+    }
+    match key.len() {
+        16 => run!(Aes128Gcm),
+        24 => run!(AesGcm<Aes192, U12>),
+        _ => run!(Aes256Gcm),
+    }.ok()
+}
+
+/// Where the data key comes from: an SDK raw AES keyring, or a `KeyProvider` that
+/// has an SDK keyring counterpart (AWS KMS).
+enum Keyring {
+    RawAes { namespace: String, name: String, key: Vec<u8> },
+    Provider(Arc<dyn Provider>),
+}
+
+impl Keyring {
+    fn wrap(&self, data_key: &[u8], context: &BTreeMap<String, String>) -> Result<EncryptedDataKey, Error> {
+        match self {
+            Keyring::RawAes { namespace, name, key } => {
+                // provider info: key name | tag length (bits) u32 | IV length u32 | IV
+                let iv = random_bytes(IV_LEN);
+                let aad = serialize_context(context)?;
+                let ciphertext = wrapping_gcm(key, &iv, Payload { msg: data_key, aad: &aad }, true)
+                    .ok_or_else(|| Error::crypto("Data key wrapping failed"))?;
+                let mut provider_info = name.as_bytes().to_vec();
+                provider_info.extend_from_slice(&RAW_AES_TAG_BITS.to_be_bytes());
+                provider_info.extend_from_slice(&(IV_LEN as u32).to_be_bytes());
+                provider_info.extend_from_slice(&iv);
+                Ok(EncryptedDataKey { provider_id: namespace.clone(), provider_info, ciphertext })
+            }
+            Keyring::Provider(provider) => {
+                let (key_id, ciphertext) = provider.wrap_with_encryption_context(data_key, context)?;
+                Ok(EncryptedDataKey {
+                    provider_id: provider.name().to_string(),
+                    provider_info: key_id.into_bytes(),
+                    ciphertext,
+                })
+            }
+        }
+    }
+
+    /// Tries every encrypted data key that belongs to this keyring.
+    fn unwrap(&self, keys: &[EncryptedDataKey], context: &BTreeMap<String, String>) -> Result<Vec<u8>, Error> {
+        let mut last_error = None;
+        match self {
+            Keyring::RawAes { namespace, name, key } => {
+                let aad = serialize_context(context)?;
+                for edk in keys.iter().filter(|k| &k.provider_id == namespace) {
+                    let Some(rest) = edk.provider_info.strip_prefix(name.as_bytes()) else { continue };
+                    let mut reader = Reader { data: rest, pos: 0 };
+                    if reader.u32().ok() != Some(RAW_AES_TAG_BITS) || reader.u32().ok() != Some(IV_LEN as u32) || rest.len() != 8 + IV_LEN {
+                        continue;
+                    }
+                    let payload = Payload { msg: &edk.ciphertext, aad: &aad };
+                    match wrapping_gcm(key, &rest[8..], payload, false) {
+                        Some(data_key) if data_key.len() == DATA_KEY_LEN => return Ok(data_key),
+                        _ => {
+                            last_error = Some(Error::decryption("Data key unwrapping failed: wrong wrapping key or context"));
+                        }
+                    }
+                }
+            }
+            Keyring::Provider(provider) => {
+                for edk in keys.iter().filter(|k| k.provider_id == provider.name()) {
+                    let key_id = String::from_utf8_lossy(&edk.provider_info);
+                    match provider.unwrap_with_encryption_context(&key_id, &edk.ciphertext, context) {
+                        Ok(data_key) if data_key.len() == DATA_KEY_LEN => return Ok(data_key),
+                        Ok(_) => last_error = Some(Error::invalid_format("Unwrapped data key has the wrong length")
+                            .with_code("invalid_length")),
+                        Err(e) => last_error = Some(e),
+                    }
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            Error::decryption("No encrypted data key in the message belongs to this keyring").with_code("no_matching_key")
+        }))
+    }
+}
+
+fn frame_iv(sequence: u32) -> [u8; IV_LEN] {
+    let mut iv = [0u8; IV_LEN];
+    iv[IV_LEN - 4..].copy_from_slice(&sequence.to_be_bytes());
+    iv
+}
+
+/// message ID | content string | sequence number u32 | content length u64
+fn body_aad(message_id: &[u8], content: &[u8], sequence: u32, len: usize) -> Vec<u8> {
+    [message_id, content, &sequence.to_be_bytes(), &(len as u64).to_be_bytes()].concat()
+}
+
+fn signing_digest(data: &[u8]) -> Sha384 {
+    Sha384::new_with_prefix(data)
+}
+
+/// Writes a framed message (see `decrypt` for the layout).
+fn encrypt(
+    plaintext: &[u8],
+    keyring: &Keyring,
+    mut context: BTreeMap<String, String>,
+    suite: Suite,
+    frame_length: u32
+) -> Result<Vec<u8>, Error> {
+    if frame_length == 0 {
+        return Err(Error::config("frame_length must be positive").with_input("frame_length"));
+    }
+    if context.keys().any(|k| k.starts_with(RESERVED_CONTEXT_PREFIX)) {
+        return Err(
+            Error::crypto(format!("Encryption context keys starting with {:?} are reserved", RESERVED_CONTEXT_PREFIX))
+        );
+    }
+    let signing_key = suite.signed().then(|| SigningKey::random(&mut OsRng));
+    if let Some(signing_key) = &signing_key {
+        let public_key = VerifyingKey::from(signing_key).to_encoded_point(true);
+        context.insert(PUBLIC_KEY_CONTEXT_KEY.to_string(), BASE64.encode(public_key.as_bytes()));
+    }
+
+    // 1. Data key, wrapped by the keyring, and the per-message content key
+    let data_key = random_bytes(DATA_KEY_LEN);
+    let message_id = random_bytes(suite.message_id_len());
+    let edk = keyring.wrap(&data_key, &context)?;
+    let (key, commitment) = suite.derive(&data_key, &message_id);
+
+    // 2. Header
+    let context_bytes = serialize_context(&context)?;
+    let u16_len = |len: usize, what: &str| u16::try_from(len).map(u16::to_be_bytes)
+        .map_err(|_| Error::config(format!("{} is too long", what)));
+    let mut out = Vec::with_capacity(plaintext.len() + 512);
+    if suite.committing() {
+        out.push(VERSION_2);
+    } else {
+        out.extend_from_slice(&[VERSION_1, TYPE_CUSTOMER_AED]);
+    }
+    out.extend_from_slice(&suite.id().to_be_bytes());
+    out.extend_from_slice(&message_id);
+    out.extend_from_slice(&(context_bytes.len() as u16).to_be_bytes());
+    out.extend_from_slice(&context_bytes);
+    out.extend_from_slice(&1u16.to_be_bytes());
+    for (field, what) in [(edk.provider_id.as_bytes(), "Provider ID"), (&edk.provider_info, "Provider info"), (&edk.ciphertext, "Encrypted data key")] {
+        out.extend_from_slice(&u16_len(field.len(), what)?);
+        out.extend_from_slice(field);
+    }
+    out.push(CONTENT_FRAMED);
+    if !suite.committing() {
+        out.extend_from_slice(&[0, 0, 0, 0]);
+        out.push(IV_LEN as u8);
+    }
+    out.extend_from_slice(&frame_length.to_be_bytes());
+    if let Some(commitment) = &commitment {
+        out.extend_from_slice(commitment);
+    }
+
+    // 3. Header authentication: a tag over the header, under an all-zero IV
+    let zero_iv = [0u8; IV_LEN];
+    let tag = Algorithm::AES_256_GCM.encrypt(&key, &zero_iv, b"", &out)?;
+    if !suite.committing() {
+        out.extend_from_slice(&zero_iv);
+    }
+    out.extend_from_slice(&tag);
+
+    // 4. Frames, the last one always marked final (and possibly empty)
+    let frame_length = frame_length as usize;
+    let regular_frames = plaintext.len().saturating_sub(1) / frame_length;
+    if regular_frames >= FINAL_FRAME_MARKER as usize - 1 {
+        return Err(Error::crypto("Plaintext needs too many frames; use a larger frame_length"));
+    }
+    let (body, last) = plaintext.split_at(regular_frames * frame_length);
+    for (index, chunk) in body.chunks(frame_length).enumerate() {
+        let sequence = index as u32 + 1;
+        let iv = frame_iv(sequence);
+        out.extend_from_slice(&sequence.to_be_bytes());
+        out.extend_from_slice(&iv);
+        out.extend_from_slice(&Algorithm::AES_256_GCM.encrypt(&key, &iv, chunk, &body_aad(&message_id, FRAME, sequence, chunk.len()))?);
+    }
+    let sequence = regular_frames as u32 + 1;
+    let iv = frame_iv(sequence);
+    out.extend_from_slice(&FINAL_FRAME_MARKER.to_be_bytes());
+    out.extend_from_slice(&sequence.to_be_bytes());
+    out.extend_from_slice(&iv);
+    out.extend_from_slice(&(last.len() as u32).to_be_bytes());
+    out.extend_from_slice(&Algorithm::AES_256_GCM.encrypt(&key, &iv, last, &body_aad(&message_id, FINAL_FRAME, sequence, last.len()))?);
+
+    // 5. Footer: ECDSA P-384 signature over everything before it
+    if let Some(signing_key) = signing_key {
+        let signature: Signature = signing_key.sign_digest(signing_digest(&out));
+        let signature = signature.to_der();
+        out.extend_from_slice(&(signature.as_bytes().len() as u16).to_be_bytes());
+        out.extend_from_slice(signature.as_bytes());
+    }
+    Ok(out)
+}
+
+/// Reads a message in the AWS Encryption SDK format
+/// (https://docs.aws.amazon.com/encryption-sdk/latest/developer-guide/message-format.html):
+///
+/// ```text
+/// header:  version | [type 0x80, v1] | suite u16 | message ID (16 or 32)
+///          | context_len u16 | context | edk_count u16 | edks | content type
+///          | [reserved (4) | iv_len u8, v1] | frame_length u32 | [commitment (32), v2]
+/// auth:    [iv (12), v1] | tag (16)
+/// body:    frames (seq u32 | iv | ciphertext | tag), then a final frame
+///          (0xFFFFFFFF | seq u32 | iv | len u32 | ciphertext | tag); or, non-framed,
+///          iv | len u64 | ciphertext | tag
+/// footer:  sig_len u16 | DER ECDSA P-384 signature   (signed suites only)
+/// ```
+fn decrypt(data: &[u8], keyring: &Keyring) -> Result<(Vec<u8>, BTreeMap<String, String>), Error> {
+    let mut reader = Reader { data, pos: 0 };
+
+    // 1. Header
+    let version = reader.u8()?;
+    match version {
+        VERSION_1 => {
+            if reader.u8()? != TYPE_CUSTOMER_AED {
+                return Err(
+                    Error::invalid_format("Not an AWS Encryption SDK message: bad message type")
+                        .with_code("unrecognized_format")
+                );
+            }
+        }
+        VERSION_2 => {}
+        _ => return Err(
+            Error::invalid_format("Not an AWS Encryption SDK message: unknown version").with_code("unrecognized_format")
+        ),
+    }
+    let suite = Suite::from_id(reader.u16()?)?;
+    if suite.committing() != (version == VERSION_2) {
+        return Err(
+            Error::invalid_format("Invalid AWS Encryption SDK message: algorithm suite does not match the version")
+        );
+    }
+    let message_id = reader.take(suite.message_id_len())?;
+    let context_len = reader.u16()? as usize;
+    let context = parse_context(reader.take(context_len)?)?;
+
+    let edk_count = reader.u16()?;
+    if edk_count == 0 {
+        return Err(Error::invalid_format("Invalid AWS Encryption SDK message: no encrypted data keys"));
+    }
+    let mut edks = Vec::with_capacity(edk_count as usize);
+    for _ in 0..edk_count {
+        let mut field = || -> Result<Vec<u8>, Error> {
+            let len = reader.u16()? as usize;
+            Ok(reader.take(len)?.to_vec())
+        };
+        let provider_id = String::from_utf8_lossy(&field()?).into_owned();
+        edks.push(EncryptedDataKey { provider_id, provider_info: field()?, ciphertext: field()? });
+    }
+
+    let content_type = reader.u8()?;
+    if content_type != CONTENT_FRAMED && content_type != CONTENT_NON_FRAMED {
+        return Err(Error::invalid_format("Invalid AWS Encryption SDK message: unknown content type"));
+    }
+    if version == VERSION_1 && (reader.take(4)? != [0, 0, 0, 0] || reader.u8()? as usize != IV_LEN) {
+        return Err(Error::invalid_format("Invalid AWS Encryption SDK message: bad header"));
+    }
+    let frame_length = reader.u32()? as usize;
+    if content_type == CONTENT_FRAMED && frame_length == 0 {
+        return Err(
+            Error::invalid_format("Invalid AWS Encryption SDK message: zero frame length").with_code("invalid_length")
+        );
+    }
+    let stored_commitment = if suite.committing() { Some(reader.take(COMMITMENT_LEN)?) } else { None };
+    let header = &data[..reader.pos];
+    let header_iv = if version == VERSION_1 { reader.take(IV_LEN)? } else { &[0u8; IV_LEN] };
+    let header_tag = reader.take(TAG_LEN)?;
+
+    // 2. Data key; the commitment must match before the key is used for anything
+    let data_key = keyring.unwrap(&edks, &context)?;
+    let (key, commitment) = suite.derive(&data_key, message_id);
+    if let (Some(stored), Some(commitment)) = (stored_commitment, commitment) {
+        if !bool::from(commitment.ct_eq(stored)) {
+            return Err(
+                Error::decryption("Key commitment mismatch: the message was not encrypted for this data key")
+                    .with_code("key_commitment_mismatch")
+            );
+        }
+    }
+    Algorithm::AES_256_GCM.decrypt(&key, header_iv, header_tag, header)
+        .map_err(|_| Error::decryption("Header authentication failed"))?;
+
+    // 3. Body
+    let open = |sealed: &[u8], iv: &[u8], content: &[u8], sequence: u32| {
+        let len = sealed.len() - TAG_LEN;
+        Algorithm::AES_256_GCM.decrypt(&key, iv, sealed, &body_aad(message_id, content, sequence, len))
+    };
+    let mut plaintext = Vec::new();
+    if content_type == CONTENT_FRAMED {
+        let mut expected = 1u32;
+        loop {
+            let marker = reader.u32()?;
+            let is_final = marker == FINAL_FRAME_MARKER;
+            let sequence = if is_final { reader.u32()? } else { marker };
+            if sequence != expected {
+                return Err(Error::invalid_format("Invalid AWS Encryption SDK message: frames out of order"));
+            }
+            let iv = reader.take(IV_LEN)?;
+            if iv != frame_iv(sequence) {
+                return Err(Error::invalid_format("Invalid AWS Encryption SDK message: unexpected frame IV"));
+            }
+            let len = if is_final { reader.u32()? as usize } else { frame_length };
+            if len > frame_length {
+                return Err(Error::invalid_format("Invalid AWS Encryption SDK message: final frame too long"));
+            }
+            let content = if is_final { FINAL_FRAME } else { FRAME };
+            plaintext.extend_from_slice(&open(reader.take(len + TAG_LEN)?, iv, content, sequence)?);
+            if is_final {
+                break;
+            }
+            expected = expected.checked_add(1)
+                .ok_or_else(|| Error::invalid_format("Invalid AWS Encryption SDK message: too many frames"))?;
+        }
+    } else {
+        let iv = reader.take(IV_LEN)?;
+        let len = u64::from_be_bytes(reader.take(8)?.try_into().unwrap());
+        let len = usize::try_from(len).ok().and_then(|len| len.checked_add(TAG_LEN))
+            .ok_or_else(|| Error::invalid_format("Invalid AWS Encryption SDK message: body too long"))?;
+        plaintext = open(reader.take(len)?, iv, SINGLE_BLOCK, 1)?;
+    }
+
+    // 4. Footer
+    if suite.signed() {
+        let signed = &data[..reader.pos];
+        let sig_len = reader.u16()? as usize;
+        let signature = Signature::from_der(reader.take(sig_len)?)
+            .map_err(|_| Error::invalid_format("Invalid AWS Encryption SDK message: bad signature encoding")
+                .with_code("invalid_signature"))?;
+        let public_key = context.get(PUBLIC_KEY_CONTEXT_KEY)
+            .and_then(|key| BASE64.decode(key).ok())
+            .and_then(|key| VerifyingKey::from_sec1_bytes(&key).ok())
+            .ok_or_else(|| {
+                Error::invalid_format("Invalid AWS Encryption SDK message: missing or bad signing public key")
+            })?;
+        public_key.verify_digest(signing_digest(signed), &signature)
+            .map_err(|_| Error::decryption("AWS Encryption SDK message signature verification failed")
+                .with_code("invalid_signature"))?;
+    }
+    if reader.pos != data.len() {
+        return Err(Error::invalid_format("Invalid AWS Encryption SDK message: trailing bytes"));
+    }
+    Ok((plaintext, context))
+}
+
+/// Raw AES keyring of the AWS Encryption SDK (`RawAESKeyring` / `RawMasterKey`
+/// with `AES_*_GCM_IV12_TAG16_NO_PADDING`). `key_namespace` and `key_name` must be
+/// the ones the other side configured; `wrapping_key` is 16, 24 or 32 bytes.
+#[pyclass(frozen)]
+pub struct EsdkRawAesKeyring {
+    key_namespace: String,
+    key_name: String,
+    wrapping_key: Vec<u8>,
+}
+
+#[pymethods]
+impl EsdkRawAesKeyring {
+    #[new]
+    fn new(key_namespace: String, key_name: String, wrapping_key: Vec<u8>) -> PyResult<Self> {
+        if ![16, 24, 32].contains(&wrapping_key.len()) {
+            return Err(crate::error::to_pyerr(
+                Error::config("wrapping_key must be 16, 24 or 32 bytes").with_input("wrapping_key")
+            ));
+        }
+        Ok(EsdkRawAesKeyring { key_namespace, key_name, wrapping_key })
+    }
+
+    fn __repr__(&self) -> String {
+        format!("EsdkRawAesKeyring(key_namespace={:?}, key_name={:?})", self.key_namespace, self.key_name)
+    }
+}
+
+#[derive(FromPyObject)]
+pub(crate) enum KeyringArg<'py> {
+    RawAes(Bound<'py, EsdkRawAesKeyring>),
+    Provider(Bound<'py, KeyProvider>),
+}
+
+impl KeyringArg<'_> {
+    fn keyring(&self) -> Keyring {
+        match self {
+            KeyringArg::RawAes(raw) => {
+                let raw = raw.get();
+                Keyring::RawAes {
+                    namespace: raw.key_namespace.clone(),
+                    name: raw.key_name.clone(),
+                    key: raw.wrapping_key.clone(),
+                }
+            }
+            KeyringArg::Provider(provider) => Keyring::Provider(provider.get().inner.clone()),
+        }
+    }
+}
+
+/// Encrypts into an AWS Encryption SDK message (framed) that the SDK's `decrypt`
+/// accepts with the matching keyring: an `EsdkRawAesKeyring` or an `AwsKmsProvider`
+/// (the SDK's KMS keyring). `encryption_context` is authenticated and stored in
+/// the clear, like `aad_context`. The default suite is the SDK's own default
+/// (key-committing, ECDSA P-384 signed).
+#[pyfunction]
+#[pyo3(signature = (plaintext, keyring, encryption_context=None, algorithm=Suite::DEFAULT_NAME, frame_length=DEFAULT_FRAME_LENGTH))]
+pub fn esdk_encrypt(
+    py: Python<'_>,
+    plaintext: &[u8],
+    keyring: KeyringArg<'_>,
+    encryption_context: Option<BTreeMap<String, String>>,
+    algorithm: &str,
+    frame_length: u32
+) -> PyResult<Vec<u8>> {
+    let suite = Suite::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+    let keyring = keyring.keyring();
+    let context = encryption_context.unwrap_or_default();
+    let operation = Operation::new("encrypt", "aws-esdk");
+    py.detach(|| operation.run(|| encrypt(plaintext, &keyring, context, suite, frame_length)))
+        .map_err(crate::error::to_pyerr)
+}
+
+/// Decrypts an AWS Encryption SDK message (framed or not, any of the AES-256-GCM
+/// HKDF suites) and returns `(plaintext, encryption_context)`. Check the returned
+/// context against what you expect: the message chooses it.
+#[pyfunction]
+pub fn esdk_decrypt(
+    py: Python<'_>,
+    message: &[u8],
+    keyring: KeyringArg<'_>
+) -> PyResult<(Vec<u8>, BTreeMap<String, String>)> {
+    let keyring = keyring.keyring();
+    py.detach(|| Operation::new("decrypt", "aws-esdk").run(|| decrypt(message, &keyring)))
+        .map_err(crate::error::to_pyerr)
+}
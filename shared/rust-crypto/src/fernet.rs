@@ -0,0 +1,155 @@
+use pyo3::prelude::*;
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE as BASE64_URL_PADDED};
+use hmac::{digest::KeyInit, Hmac, Mac};
+use sha2::Sha256;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::audit::Operation;
+use crate::encoding::Encoding;
+use crate::error::Error;
+use crate::random::random_bytes;
+
+type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
+type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+
+const VERSION: u8 = 0x80;
+/// Tokens stamped further than this in the future are rejected, as in `cryptography`.
+const MAX_CLOCK_SKEW: u64 = 60;
+
+/// Error for tokens that aren't Fernet at all (bad base64, length or version).
+const INVALID_TOKEN: &str = "Invalid Fernet token";
+
+const ENCRYPT: Operation<'static> = Operation::new("encrypt", "fernet");
+const DECRYPT: Operation<'static> = Operation::new("decrypt", "fernet");
+
+pub(crate) struct FernetKey {
+    signing: [u8; 16],
+    encryption: [u8; 16],
+}
+
+impl FernetKey {
+    pub(crate) fn parse(key: &str) -> Result<FernetKey, Error> {
+        let raw = Encoding::Base64Url.decode_secret_text(key, "Fernet key")
+            .map_err(|_| Error::config("Fernet key must be 32 url-safe base64-encoded bytes"))?;
+        if raw.len() != 32 {
+            return Err(Error::config("Fernet key must be 32 url-safe base64-encoded bytes"));
+        }
+        Ok(FernetKey { signing: raw[..16].try_into().unwrap(), encryption: raw[16..].try_into().unwrap() })
+    }
+
+    fn mac(&self) -> Hmac<Sha256> {
+        <Hmac<Sha256> as KeyInit>::new_from_slice(&self.signing).expect("HMAC accepts any key length")
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// Token layout (https://github.com/fernet/spec):
+///
+/// ```text
+/// version 0x80 | timestamp u64 | iv (16) | AES-128-CBC(PKCS#7) ciphertext | HMAC-SHA256 (32)
+/// ```
+pub(crate) fn seal(key: &FernetKey, plaintext: &[u8], timestamp: u64) -> String {
+    let iv = random_bytes(16);
+    let mut token = vec![VERSION];
+    token.extend_from_slice(&timestamp.to_be_bytes());
+    token.extend_from_slice(&iv);
+    token.extend_from_slice(&Aes128CbcEnc::new(&key.encryption.into(), iv.as_slice().into())
+        .encrypt_padded_vec_mut::<Pkcs7>(plaintext));
+
+    let mut mac = key.mac();
+    mac.update(&token);
+    token.extend_from_slice(&mac.finalize().into_bytes());
+    BASE64_URL_PADDED.encode(token)
+}
+
+pub(crate) fn open(key: &FernetKey, token: &str, ttl: Option<u64>) -> Result<Vec<u8>, Error> {
+    let data = BASE64_URL_PADDED.decode(token.trim()).map_err(|_| Error::invalid_format(INVALID_TOKEN))?;
+    // version + timestamp + iv + one block + hmac
+    if data.len() < 1 + 8 + 16 + 16 + 32 || data[0] != VERSION || (data.len() - 57) % 16 != 0 {
+        return Err(Error::invalid_format(INVALID_TOKEN));
+    }
+    let (signed, tag) = data.split_at(data.len() - 32);
+    let mut mac = key.mac();
+    mac.update(signed);
+    mac.verify_slice(tag).map_err(|_| Error::decryption("Fernet token authentication failed"))?;
+
+    let timestamp = u64::from_be_bytes(signed[1..9].try_into().unwrap());
+    if let Some(ttl) = ttl {
+        let now = now();
+        if timestamp.saturating_add(ttl) < now {
+            return Err(Error::expired("Fernet token has expired"));
+        }
+        if now + MAX_CLOCK_SKEW < timestamp {
+            return Err(Error::crypto("Fernet token is not yet valid").with_code("not_yet_valid"));
+        }
+    }
+
+    Aes128CbcDec::new(&key.encryption.into(), signed[9..25].into())
+        .decrypt_padded_vec_mut::<Pkcs7>(&signed[25..])
+        .map_err(|_| Error::decryption("Fernet token authentication failed"))
+}
+
+/// New random Fernet key, in the same format as `Fernet.generate_key()`.
+#[pyfunction]
+pub fn fernet_generate_key() -> String {
+    BASE64_URL_PADDED.encode(random_bytes(32))
+}
+
+/// Encrypts `plaintext` into a token that `cryptography.fernet.Fernet(key).decrypt()`
+/// accepts.
+#[pyfunction]
+pub fn fernet_encrypt(py: Python<'_>, key: &str, plaintext: &[u8]) -> PyResult<String> {
+    let key = FernetKey::parse(key).map_err(crate::error::to_pyerr)?;
+    py.detach(|| ENCRYPT.run(|| Ok(seal(&key, plaintext, now())))).map_err(crate::error::to_pyerr)
+}
+
+/// Decrypts a Fernet token. With `ttl` (seconds), tokens older than that are
+/// rejected, matching `Fernet.decrypt(token, ttl)`.
+#[pyfunction]
+#[pyo3(signature = (key, token, ttl=None))]
+pub fn fernet_decrypt(py: Python<'_>, key: &str, token: &str, ttl: Option<u64>) -> PyResult<Vec<u8>> {
+    let key = FernetKey::parse(key).map_err(crate::error::to_pyerr)?;
+    py.detach(|| DECRYPT.run(|| open(&key, token, ttl))).map_err(crate::error::to_pyerr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Kind;
+
+    /// `generate.json` from the Fernet spec.
+    const SPEC_KEY: &str = "cw_0x689RpI-jtRR7oE8h_eQsKImvJapLeSbXpwF4e4=";
+    const SPEC_TOKEN: &str =
+        "gAAAAAAdwJ6wAAECAwQFBgcICQoLDA0ODy021cpGVWKZ_eEwCGM4BLLF_5CV9dOPmrhuVUPgJobwOz7JcbmrR64jVmpU4IwqDA==";
+
+    #[test]
+    fn opens_the_spec_vector() {
+        let key = FernetKey::parse(SPEC_KEY).unwrap();
+        assert_eq!(open(&key, SPEC_TOKEN, None).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn rejects_tokens_past_their_ttl() {
+        let key = FernetKey::parse(SPEC_KEY).unwrap();
+        assert_eq!(open(&key, SPEC_TOKEN, Some(60)).unwrap_err().kind(), Kind::Expired);
+
+        let fresh = seal(&key, b"hello", now() - 30);
+        assert_eq!(open(&key, &fresh, Some(60)).unwrap(), b"hello");
+        assert_eq!(open(&key, &fresh, Some(10)).unwrap_err().kind(), Kind::Expired);
+        let future = seal(&key, b"hello", now() + 2 * MAX_CLOCK_SKEW);
+        assert_eq!(open(&key, &future, Some(60)).unwrap_err().code(), "not_yet_valid");
+    }
+
+    #[test]
+    fn classifies_bad_tokens() {
+        let key = FernetKey::parse(SPEC_KEY).unwrap();
+        let other = FernetKey::parse(&fernet_generate_key()).unwrap();
+        assert_eq!(open(&other, SPEC_TOKEN, None).unwrap_err().kind(), Kind::Decryption);
+        assert_eq!(open(&key, "not base64!", None).unwrap_err().kind(), Kind::InvalidFormat);
+        assert_eq!(open(&key, &SPEC_TOKEN[..40], None).unwrap_err().kind(), Kind::InvalidFormat);
+    }
+}
@@ -0,0 +1,120 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use argon2::password_hash::rand_core::OsRng;
+use rand::RngCore;
+use std::collections::hash_map::{Entry, HashMap};
+use zeroize::Zeroizing;
+
+use crate::aad::AadArg;
+use crate::cipher::Algorithm;
+use crate::envelope::{decode_token, default_extensions, encode_token, Envelope};
+use crate::error::Error;
+use crate::json::{to_json, to_python};
+use crate::kdf::DerivedKey;
+use crate::master_key::MasterKeyArg;
+
+/// Each field's name is part of its AAD, so encrypted values can't be moved
+/// between fields (or records with another `aad_context`).
+fn field_aad(aad_context: &[u8], field: &str) -> Vec<u8> {
+    [aad_context, b"\0", field.as_bytes()].concat()
+}
+
+/// Returns a copy of `d` with each of `fields` replaced by an envelope token of its
+/// JSON-encoded value, so `decrypt_fields` restores ints, lists, etc. as well as
+/// strings. Fields that are missing or `None` are left alone. All fields share one
+/// salt, so the KDF runs once per call instead of once per field; every field still
+/// gets its own nonce.
+#[pyfunction]
+#[pyo3(signature = (d, fields, master_key, aad_context, algorithm=Algorithm::DEFAULT_NAME, kdf_profile=None))]
+pub fn encrypt_fields<'py>(
+    py: Python<'py>,
+    d: &Bound<'py, PyDict>,
+    fields: Vec<String>,
+    master_key: MasterKeyArg,
+    aad_context: AadArg,
+    algorithm: &str,
+    kdf_profile: Option<&str>
+) -> PyResult<Bound<'py, PyDict>> {
+    let algorithm = Algorithm::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+    let (kdf_params, _) = crate::resolve_kdf_params(None, kdf_profile, None, None, None)?;
+
+    let mut values = Vec::with_capacity(fields.len());
+    for field in &fields {
+        if let Some(value) = d.get_item(field)?.filter(|value| !value.is_none()) {
+            values.push((field.as_str(), to_json(&value)?.to_string()));
+        }
+    }
+
+    let tokens = py.detach(|| -> Result<Vec<(&str, String)>, Error> {
+        let mut salt = vec![0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let key = kdf_params.derive(&master_key, &salt)?;
+        values.iter().map(|(field, json)| {
+            let envelope = Envelope::seal_with_key(
+                algorithm, kdf_params, salt.clone(), default_extensions(None),
+                &key, json.as_bytes(), &field_aad(&aad_context, field)
+            )?;
+            Ok((*field, encode_token(&envelope)?))
+        }).collect()
+    }).map_err(crate::error::to_pyerr)?;
+
+    let out = d.copy()?;
+    for (field, token) in tokens {
+        out.set_item(field, token)?;
+    }
+    Ok(out)
+}
+
+/// Keys derived while opening a batch of tokens, one per distinct (salt, KDF
+/// parameters), so tokens sealed together cost a single KDF run.
+#[derive(Default)]
+pub(crate) struct DerivedKeys {
+    keys: HashMap<(Vec<u8>, String), DerivedKey>,
+}
+
+impl DerivedKeys {
+    pub(crate) fn open(&mut self, envelope: &Envelope, master_key: &[u8], aad: &[u8]) -> Result<Zeroizing<Vec<u8>>, Error> {
+        let id = (envelope.salt.clone(), envelope.kdf_params.encode());
+        let key = match self.keys.entry(id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(envelope.kdf_params.derive(master_key, &envelope.salt)?),
+        };
+        envelope.open_with_key(key, aad)
+    }
+}
+
+/// Reverses `encrypt_fields`: returns a copy of `d` with each of `fields` decrypted
+/// back to its original value. The KDF runs once per distinct salt.
+#[pyfunction]
+pub fn decrypt_fields<'py>(
+    py: Python<'py>,
+    d: &Bound<'py, PyDict>,
+    fields: Vec<String>,
+    master_key: MasterKeyArg,
+    aad_context: AadArg
+) -> PyResult<Bound<'py, PyDict>> {
+    let mut tokens = Vec::with_capacity(fields.len());
+    for field in &fields {
+        if let Some(value) = d.get_item(field)?.filter(|value| !value.is_none()) {
+            tokens.push((field.as_str(), value.extract::<String>()?));
+        }
+    }
+
+    let values = py.detach(|| -> Result<Vec<(&str, serde_json::Value)>, Error> {
+        let mut keys = DerivedKeys::default();
+        tokens.iter().map(|(field, token)| {
+            let envelope = decode_token(token).map_err(|e| e.in_field(field))?;
+            let plaintext = keys.open(&envelope, &master_key, &field_aad(&aad_context, field))
+                .map_err(|e| e.in_field(field))?;
+            let value = serde_json::from_slice(&plaintext)
+                .map_err(|_| Error::invalid_format(format!("Field {:?} was not encrypted by encrypt_fields", field)))?;
+            Ok((*field, value))
+        }).collect()
+    }).map_err(crate::error::to_pyerr)?;
+
+    let out = d.copy()?;
+    for (field, value) in values {
+        out.set_item(field, to_python(py, &value)?)?;
+    }
+    Ok(out)
+}
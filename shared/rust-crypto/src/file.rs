@@ -0,0 +1,217 @@
+use pyo3::prelude::*;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::aad::AadArg;
+use crate::cipher::Algorithm;
+use crate::error::Error;
+use crate::master_key::MasterKeyArg;
+use crate::progress::{CancellationToken, Progress, DEFAULT_PROGRESS_INTERVAL};
+use crate::stream::{self, StreamDecryptor, StreamEncryptor, DEFAULT_CHUNK_SIZE, MAX_HEADER_LEN};
+
+/// Size of each `read()` from the source file; independent of the stream chunk size.
+const READ_BUF_SIZE: usize = 64 * 1024;
+
+/// Output is written next to `dst` and only renamed into place once the whole
+/// file was processed, so a failed run never leaves a half-written `dst`.
+fn partial_path(dst: &Path) -> PathBuf {
+    let mut name = dst.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    dst.with_file_name(name)
+}
+
+/// Incremental transform driven by `pump`: both stream directions fit this shape.
+trait Transform {
+    fn push(&mut self, data: &[u8]) -> Result<Vec<u8>, Error>;
+    fn finish(&mut self) -> Result<Vec<u8>, Error>;
+}
+
+impl Transform for StreamEncryptor {
+    fn push(&mut self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        StreamEncryptor::push(self, data)
+    }
+    fn finish(&mut self) -> Result<Vec<u8>, Error> {
+        StreamEncryptor::finish(self)
+    }
+}
+
+impl Transform for StreamDecryptor {
+    fn push(&mut self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        StreamDecryptor::push(self, data)
+    }
+    fn finish(&mut self) -> Result<Vec<u8>, Error> {
+        StreamDecryptor::finish(self)
+    }
+}
+
+/// Pumps `src` through `transform` in fixed-size reads and writes the result to `dst`,
+/// reporting the bytes read from `src` to `progress`.
+fn pump<T: Transform>(src: &Path, dst: &Path, transform: &mut T, progress: &Progress) -> Result<(), Error> {
+    let input = File::open(src).map_err(|e| Error::crypto(format!("Cannot open {}: {}", src.display(), e)))?;
+    let mut reader = BufReader::new(input);
+
+    let tmp = partial_path(dst);
+    let result = (|| {
+        let output = File::create(&tmp).map_err(|e| Error::crypto(format!("Cannot create {}: {}", tmp.display(), e)))?;
+        let mut writer = BufWriter::new(output);
+
+        let mut buf = vec![0u8; READ_BUF_SIZE];
+        loop {
+            let n = reader.read(&mut buf).map_err(|e| Error::crypto(format!("Read failed: {}", e)))?;
+            if n == 0 {
+                break;
+            }
+            writer.write_all(&transform.push(&buf[..n])?).map_err(|e| Error::crypto(format!("Write failed: {}", e)))?;
+            progress.advance(n)?;
+        }
+        writer.write_all(&transform.finish()?).map_err(|e| Error::crypto(format!("Write failed: {}", e)))?;
+        writer.flush().map_err(|e| Error::crypto(format!("Write failed: {}", e)))?;
+        progress.finish()
+    })();
+
+    match result {
+        Ok(()) => fs::rename(&tmp, dst).map_err(|e| Error::crypto(format!("Cannot move output into place: {}", e))),
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            Err(e)
+        }
+    }
+}
+
+/// The first bytes of `src`, enough for its stream header; empty if it can't be read
+/// (`pump` then reports why).
+fn read_head(src: &Path) -> Vec<u8> {
+    let mut head = Vec::with_capacity(MAX_HEADER_LEN);
+    let _ = File::open(src).and_then(|file| file.take(MAX_HEADER_LEN as u64).read_to_end(&mut head));
+    head
+}
+
+/// Progress over the whole of `src`; its size is the `total`.
+fn file_progress(src: &Path, callback: Option<Py<PyAny>>, interval: u64, cancel: Option<&CancellationToken>) -> Progress {
+    let total = fs::metadata(src).ok().map(|metadata| metadata.len());
+    Progress::new(callback, total, interval).with_cancel(cancel)
+}
+
+/// Encrypts the file at `src` into `dst` using the `StreamEncryptor` format, with
+/// `algorithm`, `kdf_profile` and `kdf_params` as for `StreamEncryptor`.
+/// `progress(bytes_processed, total)` is called every `progress_interval` bytes of
+/// `src`, and once at the end; raising from it aborts the encryption, as does
+/// cancelling `cancel`.
+#[pyfunction]
+#[pyo3(signature = (
+    src, dst, master_key, aad_context, chunk_size=DEFAULT_CHUNK_SIZE,
+    progress=None, progress_interval=DEFAULT_PROGRESS_INTERVAL, cancel=None,
+    algorithm=Algorithm::DEFAULT_NAME, kdf_profile=None, kdf_params=None
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn encrypt_file(
+    py: Python<'_>,
+    src: PathBuf,
+    dst: PathBuf,
+    master_key: MasterKeyArg,
+    aad_context: AadArg,
+    chunk_size: usize,
+    progress: Option<Py<PyAny>>,
+    progress_interval: u64,
+    cancel: Option<PyRef<'_, CancellationToken>>,
+    algorithm: &str,
+    kdf_profile: Option<&str>,
+    kdf_params: Option<&str>
+) -> PyResult<()> {
+    let algorithm = Algorithm::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+    let (kdf_params, _) = crate::resolve_kdf_params(kdf_params, kdf_profile, None, None, None)?;
+    let progress = file_progress(&src, progress, progress_interval, cancel.as_deref());
+    py.detach(|| {
+        let mut encryptor = StreamEncryptor::create(&master_key, &aad_context, chunk_size, algorithm, kdf_params)?;
+        stream::operation("encrypt", algorithm, &aad_context).run(|| pump(&src, &dst, &mut encryptor, &progress))
+    }).map_err(|e| progress.to_pyerr(e))
+}
+
+/// Decrypts a file produced by `encrypt_file` (or a `StreamEncryptor`) from `src` into `dst`.
+/// `dst` is only created if the whole file authenticates. `progress` and `cancel`
+/// work as in `encrypt_file`, counting bytes of the encrypted `src`.
+#[pyfunction]
+#[pyo3(signature = (
+    src, dst, master_key, aad_context, progress=None, progress_interval=DEFAULT_PROGRESS_INTERVAL, cancel=None
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn decrypt_file(
+    py: Python<'_>,
+    src: PathBuf,
+    dst: PathBuf,
+    master_key: MasterKeyArg,
+    aad_context: AadArg,
+    progress: Option<Py<PyAny>>,
+    progress_interval: u64,
+    cancel: Option<PyRef<'_, CancellationToken>>
+) -> PyResult<()> {
+    let progress = file_progress(&src, progress, progress_interval, cancel.as_deref());
+    py.detach(|| {
+        let mut decryptor = StreamDecryptor::create(&master_key, &aad_context);
+        let algorithm = stream::recorded_algorithm(&read_head(&src));
+        stream::operation("decrypt", algorithm, &aad_context).run(|| pump(&src, &dst, &mut decryptor, &progress))
+    }).map_err(|e| progress.to_pyerr(e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kdf::KdfParams;
+
+    const KEY: &[u8] = b"file test master key";
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("k_services_crypto-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn progress() -> Progress {
+        Progress::new(None, None, DEFAULT_PROGRESS_INTERVAL)
+    }
+
+    #[test]
+    fn failed_runs_leave_no_output() {
+        let dir = scratch_dir("cleanup");
+        let (plain, encrypted, decrypted) = (dir.join("plain"), dir.join("encrypted"), dir.join("decrypted"));
+        fs::write(&plain, vec![7u8; 3 * READ_BUF_SIZE]).unwrap();
+        let kdf_params = KdfParams::new(Some(8), Some(1), Some(1)).unwrap();
+        let mut encryptor = StreamEncryptor::create(KEY, b"ctx", 1000, Algorithm::AES_256_GCM, kdf_params).unwrap();
+        pump(&plain, &encrypted, &mut encryptor, &progress()).unwrap();
+
+        // Corrupt the last chunk: every chunk before it decrypts and is written out first
+        let mut data = fs::read(&encrypted).unwrap();
+        *data.last_mut().unwrap() ^= 1;
+        fs::write(&encrypted, data).unwrap();
+        let error = pump(&encrypted, &decrypted, &mut StreamDecryptor::create(KEY, b"ctx"), &progress()).unwrap_err();
+        assert_eq!(error.code(), "stream_truncated");
+        assert!(!decrypted.exists());
+        assert!(!partial_path(&decrypted).exists());
+
+        pump(&plain, &decrypted, &mut StreamDecryptor::create(KEY, b"ctx"), &progress()).unwrap_err();
+        assert!(!partial_path(&decrypted).exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn round_trips_a_file() {
+        let dir = scratch_dir("round-trip");
+        let (plain, encrypted, decrypted) = (dir.join("plain"), dir.join("encrypted"), dir.join("decrypted"));
+        let data: Vec<u8> = (0..2 * READ_BUF_SIZE + 123).map(|i| i as u8).collect();
+        fs::write(&plain, &data).unwrap();
+        let kdf_params = KdfParams::pbkdf2_sha256(1000).unwrap();
+        let mut encryptor =
+            StreamEncryptor::create(KEY, b"ctx", 4096, Algorithm::XCHACHA20_POLY1305, kdf_params).unwrap();
+        pump(&plain, &encrypted, &mut encryptor, &progress()).unwrap();
+        assert_eq!(stream::recorded_algorithm(&read_head(&encrypted)).name(), Algorithm::XCHACHA20_POLY1305.name());
+
+        pump(&encrypted, &decrypted, &mut StreamDecryptor::create(KEY, b"ctx"), &progress()).unwrap();
+        assert_eq!(fs::read(&decrypted).unwrap(), data);
+        assert!(!partial_path(&decrypted).exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,52 @@
+use pyo3::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::Error;
+
+/// Builds with the `fips` feature start (and stay) in FIPS mode.
+static ENABLED: AtomicBool = AtomicBool::new(cfg!(feature = "fips"));
+
+pub(crate) fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Fails when FIPS mode is on; call before using anything other than AES-256-GCM,
+/// PBKDF2-HMAC-SHA256 or a SHA-2 function.
+pub(crate) fn reject(primitive: &str) -> Result<(), Error> {
+    if enabled() {
+        return Err(Error::config(format!(
+            "{} is not allowed in FIPS mode (only AES-256-GCM, PBKDF2-SHA256 and SHA-2 are)", primitive
+        )).with_code("fips_disallowed"));
+    }
+    Ok(())
+}
+
+/// Restricts the module to AES-256-GCM, PBKDF2-SHA256 and SHA-2 (including HMAC and
+/// HKDF over them): XChaCha20-Poly1305, AES-256-GCM-SIV, Argon2id, scrypt, bcrypt,
+/// BLAKE3, HMAC-SHA1 (the HOTP/TOTP default), and the formats built on them (Branca, PASETO v4.local, secretbox,
+/// secretstream, sealed boxes, age) raise `ConfigError` with code `"fips_disallowed"`,
+/// also when decrypting data written before the switch. The default KDF is Argon2id,
+/// so pass e.g. `kdf_params="pbkdf2-sha256:i=600000"`. This restricts algorithm choice
+/// only; it does not make the build a validated module.
+#[pyfunction]
+pub fn enable_fips_mode() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Fails on builds with the `fips` feature, where the mode can't be turned off.
+#[pyfunction]
+pub fn disable_fips_mode() -> PyResult<()> {
+    if cfg!(feature = "fips") {
+        return Err(crate::error::to_pyerr(
+            Error::crypto("FIPS mode can't be disabled: this build has the fips feature")
+        ));
+    }
+    ENABLED.store(false, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Whether FIPS mode is on, via `enable_fips_mode()` or the `fips` build feature.
+#[pyfunction]
+pub fn fips_mode() -> bool {
+    enabled()
+}
@@ -0,0 +1,185 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyString};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL};
+
+use crate::aad::AadArg;
+use crate::buffer::BytesLike;
+use crate::cipher::Algorithm;
+use crate::encoding::{Encoded, Encoding};
+use crate::envelope::{self, Envelope, MAGIC};
+use crate::error::Error;
+use crate::master_key::MasterKeyArg;
+
+/// Recorded as `version` in the dicts `encrypt_with_context` and `encrypt_bytes` return,
+/// along with the `algorithm` and `encoding` needed to decrypt them.
+pub(crate) const DICT_VERSION: u8 = 2;
+/// Dicts without a `version`: `{ciphertext, salt, nonce}`, optionally with the
+/// `kdf_params`, `kdf_profile` and `tag` entries added later. The algorithm was
+/// whatever the caller passed, and the fields are base64 (or `bytes` for `"raw"`).
+const LEGACY_DICT_VERSION: u8 = 1;
+
+enum Format {
+    Envelope(u8),
+    Dict(u8),
+    Unknown,
+}
+
+/// Raw envelope bytes, or a token in any text encoding decoded to them.
+fn envelope_bytes(blob: &Bound<'_, PyAny>) -> Result<Vec<u8>, Error> {
+    let Ok(text) = blob.cast::<PyString>() else {
+        return blob.extract::<BytesLike>().map(|bytes| bytes.to_vec()).map_err(|e| Error::crypto(e.to_string()));
+    };
+    let text = Encoded::Text(text.to_str().map_err(|e| Error::crypto(e.to_string()))?.to_owned());
+    [Encoding::Base64Url, Encoding::Base64, Encoding::Hex].iter()
+        .find_map(|encoding| encoding.decode(&text, "token").ok().filter(|data| data.starts_with(MAGIC)))
+        .ok_or_else(|| Error::invalid_format("Invalid token encoding: expected base64url, base64 or hex"))
+}
+
+/// Magic and version of what looks like an envelope, decoding only the first few
+/// characters of a token: raw bytes, base64 (either alphabet) or hex.
+fn envelope_version(blob: &Bound<'_, PyAny>) -> Option<u8> {
+    let head: Vec<u8> = if let Ok(text) = blob.cast::<PyString>() {
+        let text = text.to_str().ok()?.trim_start();
+        // Eight characters are four bytes of hex, or six of base64
+        let prefix = text.get(..8)?;
+        match crate::encoding::hex_decode(prefix) {
+            Ok(head) if head.starts_with(MAGIC) => head,
+            _ => BASE64_URL.decode(prefix.replace('+', "-").replace('/', "_")).ok()?,
+        }
+    } else {
+        let bytes = blob.extract::<BytesLike>().ok()?;
+        bytes.get(..MAGIC.len() + 1)?.to_vec()
+    };
+    head.starts_with(MAGIC).then(|| head[MAGIC.len()])
+}
+
+fn detect(blob: &Bound<'_, PyAny>) -> PyResult<Format> {
+    if let Ok(dict) = blob.cast::<PyDict>() {
+        if let Some(version) = dict.get_item("version")? {
+            return Ok(version.extract().map_or(Format::Unknown, Format::Dict));
+        }
+        for field in ["ciphertext", "salt", "nonce"] {
+            if !dict.contains(field)? {
+                return Ok(Format::Unknown);
+            }
+        }
+        return Ok(Format::Dict(LEGACY_DICT_VERSION));
+    }
+    Ok(envelope_version(blob).map_or(Format::Unknown, Format::Envelope))
+}
+
+/// Reports what `blob` is without decrypting it, for migration tooling counting
+/// legacy data: `{"format": "envelope" | "dict" | "unknown", "version", "legacy",
+/// "supported"}`. Envelopes are tokens (a base64url, base64 or hex `str`) or raw `bytes`;
+/// dicts are `encrypt_with_context` / `encrypt_bytes` output, where untagged ones
+/// (the original `{ciphertext, salt, nonce}` shape) are version 1 and `legacy`.
+/// `supported` says whether `decrypt_any` can read it.
+#[pyfunction]
+pub fn detect_format<'py>(py: Python<'py>, blob: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyDict>> {
+    let (format, version, supported) = match detect(blob)? {
+        Format::Envelope(version) => ("envelope", Some(version), version == envelope::VERSION),
+        Format::Dict(version) => ("dict", Some(version), version == LEGACY_DICT_VERSION || version == DICT_VERSION),
+        Format::Unknown => ("unknown", None, false),
+    };
+    let result = PyDict::new(py);
+    result.set_item("format", format)?;
+    result.set_item("version", version)?;
+    result.set_item("legacy", format == "dict" && version == Some(LEGACY_DICT_VERSION))?;
+    result.set_item("supported", supported)?;
+    Ok(result)
+}
+
+/// Cheap check for values this module encrypted, so pipelines can skip them instead
+/// of encrypting twice: envelopes (tokens or raw bytes) of a supported version, found
+/// from their first bytes without decoding the rest, and versioned dicts. With
+/// `include_legacy=True`, dicts with the untagged `{ciphertext, salt, nonce}` shape
+/// count too. Nothing is authenticated, so this can't tell a real envelope from a
+/// value crafted to start like one.
+#[pyfunction]
+#[pyo3(signature = (value, include_legacy=false))]
+pub fn is_encrypted(value: &Bound<'_, PyAny>, include_legacy: bool) -> PyResult<bool> {
+    Ok(match detect(value)? {
+        Format::Envelope(version) => version == envelope::VERSION,
+        Format::Dict(DICT_VERSION) => true,
+        Format::Dict(LEGACY_DICT_VERSION) => include_legacy,
+        Format::Dict(_) | Format::Unknown => false,
+    })
+}
+
+fn field<'py, T: FromPyObjectOwned<'py>>(dict: &Bound<'py, PyDict>, name: &str) -> PyResult<Option<T>> {
+    dict.get_item(name)?
+        .filter(|value| !value.is_none())
+        .map(|value| value.extract().map_err(Into::into))
+        .transpose()
+}
+
+fn required<'py, T: FromPyObjectOwned<'py>>(dict: &Bound<'py, PyDict>, name: &str) -> PyResult<T> {
+    field(dict, name)?
+        .ok_or_else(|| crate::error::to_pyerr(
+            Error::invalid_format(format!("Invalid encrypted dict: missing \"{}\"", name))
+        ))
+}
+
+/// Decrypts any format this module has produced, routed by `detect_format`: envelope
+/// tokens (base64url, base64 or hex) and raw envelopes, versioned dicts, and legacy untagged dicts, which are
+/// read forever. `algorithm` is only needed for legacy dicts made with something other
+/// than the default; versioned dicts and envelopes record their own. `allow_expired`
+/// is as for `decrypt_token`.
+#[pyfunction]
+#[pyo3(signature = (data, master_key, aad_context, algorithm=None, allow_expired=false))]
+pub fn decrypt_any<'py>(
+    py: Python<'py>,
+    data: &Bound<'py, PyAny>,
+    master_key: MasterKeyArg,
+    aad_context: AadArg,
+    algorithm: Option<&str>,
+    allow_expired: bool
+) -> PyResult<Bound<'py, PyBytes>> {
+    let dict = match detect(data)? {
+        Format::Envelope(_) => {
+            let bytes = envelope_bytes(data).map_err(crate::error::to_pyerr)?;
+            return py.detach(|| Envelope::from_bytes(&bytes)?.open_allowing(&master_key, &aad_context, allow_expired))
+                .map(|plaintext| PyBytes::new(py, &plaintext))
+                .map_err(crate::error::to_pyerr);
+        }
+        Format::Dict(LEGACY_DICT_VERSION | DICT_VERSION) => data.cast::<PyDict>()?,
+        Format::Dict(version) => {
+            return Err(crate::error::to_pyerr(
+                Error::invalid_format(format!("Unsupported encrypted dict version: {}", version))
+                    .with_code("unsupported_version")
+            ));
+        }
+        Format::Unknown => {
+            return Err(crate::error::to_pyerr(
+                Error::invalid_format("Not a recognized ciphertext: expected an envelope token or an encrypt_with_context dict")
+                    .with_code("unrecognized_format")
+            ));
+        }
+    };
+
+    let ciphertext: Encoded = required(dict, "ciphertext")?;
+    let (salt, nonce): (Encoded, Encoded) = (required(dict, "salt")?, required(dict, "nonce")?);
+    let tag: Option<Encoded> = field(dict, "tag")?;
+    let kdf_params: Option<String> = field(dict, "kdf_params")?;
+    let (algorithm, encoding) = if dict.contains("version")? {
+        let recorded: String = required(dict, "algorithm")?;
+        if algorithm.is_some_and(|given| !given.eq_ignore_ascii_case(&recorded)) {
+            return Err(crate::error::to_pyerr(Error::config(format!(
+                "algorithm {} given, but the dict records {}", algorithm.unwrap_or_default(), recorded
+            ))));
+        }
+        (recorded, required::<String>(dict, "encoding")?)
+    } else {
+        let encoding = if matches!(ciphertext, Encoded::Bytes(_)) { "raw" } else { Encoding::DEFAULT_NAME };
+        (algorithm.unwrap_or(Algorithm::DEFAULT_NAME).to_string(), encoding.to_string())
+    };
+    let algorithm = Algorithm::from_name(&algorithm).map_err(crate::error::to_pyerr)?;
+    let encoding = Encoding::from_name(&encoding).map_err(crate::error::to_pyerr)?;
+    let kdf_params = crate::parse_kdf_params(kdf_params.as_deref())?;
+
+    py.detach(|| {
+        crate::open(algorithm, kdf_params, encoding, &ciphertext, &salt, &nonce, tag.as_ref(), &master_key, &aad_context)
+    })
+        .map(|plaintext| PyBytes::new(py, &plaintext))
+        .map_err(crate::error::to_pyerr)
+}
@@ -0,0 +1,211 @@
+use pyo3::prelude::*;
+use ::blake3::Hasher as Blake3;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use sha3::{Sha3_256, Sha3_384, Sha3_512};
+
+use crate::error::Error;
+
+/// Hash functions accepted by `digest` / `hexdigest`.
+const DIGEST_ALGORITHMS: &[&str] = &["sha256", "sha384", "sha512", "sha3-256", "sha3-384", "sha3-512", "blake3"];
+
+/// Below this size the rayon fan-out costs more than it saves (per the blake3 docs).
+const BLAKE3_PARALLEL_THRESHOLD: usize = 128 * 1024;
+
+/// Hash functions selectable through the `algorithm` argument of the HKDF and HMAC helpers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum HashAlgorithm {
+    Sha256,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    pub(crate) const DEFAULT_NAME: &'static str = "sha256";
+
+    pub(crate) fn from_name(name: &str) -> Result<Self, Error> {
+        match name.to_ascii_lowercase().as_str() {
+            "sha256" | "sha-256" => Ok(HashAlgorithm::Sha256),
+            "sha512" | "sha-512" => Ok(HashAlgorithm::Sha512),
+            _ => Err(Error::config(format!("Unsupported hash algorithm: {}", name)).with_code("unsupported_hash")),
+        }
+    }
+
+    /// Digest size in bytes.
+    pub(crate) fn output_len(self) -> usize {
+        match self {
+            HashAlgorithm::Sha256 => 32,
+            HashAlgorithm::Sha512 => 64,
+        }
+    }
+}
+
+/// Running state of one of the `DIGEST_ALGORITHMS`.
+#[derive(Clone)]
+pub(crate) enum DigestState {
+    Sha256(Sha256),
+    Sha384(Sha384),
+    Sha512(Sha512),
+    Sha3_256(Sha3_256),
+    Sha3_384(Sha3_384),
+    Sha3_512(Sha3_512),
+    Blake3(Box<Blake3>),
+}
+
+impl DigestState {
+    pub(crate) fn new(algorithm: &str) -> Result<Self, Error> {
+        let state = match algorithm.to_ascii_lowercase().replace('_', "-").as_str() {
+            "sha256" | "sha-256" => DigestState::Sha256(Sha256::new()),
+            "sha384" | "sha-384" => DigestState::Sha384(Sha384::new()),
+            "sha512" | "sha-512" => DigestState::Sha512(Sha512::new()),
+            "sha3-256" => DigestState::Sha3_256(Sha3_256::new()),
+            "sha3-384" => DigestState::Sha3_384(Sha3_384::new()),
+            "sha3-512" => DigestState::Sha3_512(Sha3_512::new()),
+            "blake3" => {
+                crate::fips::reject("BLAKE3")?;
+                DigestState::Blake3(Box::new(Blake3::new()))
+            }
+            _ => return Err(Error::config(format!(
+                "Unsupported hash algorithm: {} (expected one of {})", algorithm, DIGEST_ALGORITHMS.join(", ")
+            )).with_code("unsupported_hash")),
+        };
+        Ok(state)
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            DigestState::Sha256(_) => "sha256",
+            DigestState::Sha384(_) => "sha384",
+            DigestState::Sha512(_) => "sha512",
+            DigestState::Sha3_256(_) => "sha3-256",
+            DigestState::Sha3_384(_) => "sha3-384",
+            DigestState::Sha3_512(_) => "sha3-512",
+            DigestState::Blake3(_) => "blake3",
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            DigestState::Sha256(h) => h.update(data),
+            DigestState::Sha384(h) => h.update(data),
+            DigestState::Sha512(h) => h.update(data),
+            DigestState::Sha3_256(h) => h.update(data),
+            DigestState::Sha3_384(h) => h.update(data),
+            DigestState::Sha3_512(h) => h.update(data),
+            DigestState::Blake3(h) if data.len() >= BLAKE3_PARALLEL_THRESHOLD => { h.update_rayon(data); }
+            DigestState::Blake3(h) => { h.update(data); }
+        }
+    }
+
+    pub(crate) fn finalize(self) -> Vec<u8> {
+        match self {
+            DigestState::Sha256(h) => h.finalize().to_vec(),
+            DigestState::Sha384(h) => h.finalize().to_vec(),
+            DigestState::Sha512(h) => h.finalize().to_vec(),
+            DigestState::Sha3_256(h) => h.finalize().to_vec(),
+            DigestState::Sha3_384(h) => h.finalize().to_vec(),
+            DigestState::Sha3_512(h) => h.finalize().to_vec(),
+            DigestState::Blake3(h) => h.finalize().as_bytes().to_vec(),
+        }
+    }
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn digest_bytes(py: Python<'_>, data: &[u8], algorithm: &str) -> PyResult<Vec<u8>> {
+    let mut state = DigestState::new(algorithm).map_err(crate::error::to_pyerr)?;
+    Ok(py.detach(|| {
+        state.update(data);
+        state.finalize()
+    }))
+}
+
+/// One-shot digest of `data`. `algorithm` is one of sha256, sha384, sha512,
+/// sha3-256, sha3-384, sha3-512 or blake3.
+#[pyfunction]
+#[pyo3(signature = (data, algorithm=HashAlgorithm::DEFAULT_NAME))]
+pub fn digest(py: Python<'_>, data: &[u8], algorithm: &str) -> PyResult<Vec<u8>> {
+    digest_bytes(py, data, algorithm)
+}
+
+/// Same as `digest`, as a lowercase hex string.
+#[pyfunction]
+#[pyo3(signature = (data, algorithm=HashAlgorithm::DEFAULT_NAME))]
+pub fn hexdigest(py: Python<'_>, data: &[u8], algorithm: &str) -> PyResult<String> {
+    digest_bytes(py, data, algorithm).map(|d| to_hex(&d))
+}
+
+/// Incremental hash over any of the `digest` algorithms, for inputs too large to
+/// buffer. Works like a `hashlib` object: `update()` can be called any number of
+/// times (each call runs without the GIL) and `digest()` does not end the hash.
+#[pyclass]
+pub struct Hasher {
+    state: DigestState,
+}
+
+#[pymethods]
+impl Hasher {
+    #[new]
+    #[pyo3(signature = (algorithm=HashAlgorithm::DEFAULT_NAME))]
+    fn new(algorithm: &str) -> PyResult<Self> {
+        let state = DigestState::new(algorithm).map_err(crate::error::to_pyerr)?;
+        Ok(Hasher { state })
+    }
+
+    #[getter]
+    fn name(&self) -> &'static str {
+        self.state.name()
+    }
+
+    fn update(&mut self, py: Python<'_>, data: &[u8]) {
+        let state = &mut self.state;
+        py.detach(|| state.update(data));
+    }
+
+    /// Digest of everything fed so far.
+    fn digest(&self) -> Vec<u8> {
+        self.state.clone().finalize()
+    }
+
+    fn hexdigest(&self) -> String {
+        to_hex(&self.digest())
+    }
+
+    /// Independent copy of the current state, e.g. to hash a common prefix once.
+    fn copy(&self) -> Self {
+        Hasher { state: self.state.clone() }
+    }
+}
+
+fn blake3_hash(mut hasher: Blake3, data: &[u8]) -> Vec<u8> {
+    if data.len() >= BLAKE3_PARALLEL_THRESHOLD {
+        hasher.update_rayon(data);
+    } else {
+        hasher.update(data);
+    }
+    hasher.finalize().as_bytes().to_vec()
+}
+
+/// 32-byte BLAKE3 digest of `data`. Large inputs are hashed on all cores without the GIL.
+#[pyfunction]
+pub fn blake3(py: Python<'_>, data: &[u8]) -> PyResult<Vec<u8>> {
+    crate::fips::reject("BLAKE3").map_err(crate::error::to_pyerr)?;
+    Ok(py.detach(|| blake3_hash(Blake3::new(), data)))
+}
+
+/// BLAKE3 in keyed mode (a MAC / keyed fingerprint); `key` must be 32 bytes.
+#[pyfunction]
+pub fn blake3_keyed(py: Python<'_>, key: &[u8], data: &[u8]) -> PyResult<Vec<u8>> {
+    crate::fips::reject("BLAKE3").map_err(crate::error::to_pyerr)?;
+    let key: &[u8; 32] = key.try_into()
+        .map_err(|_| crate::error::to_pyerr(Error::config("BLAKE3 key must be 32 bytes")))?;
+    Ok(py.detach(|| blake3_hash(Blake3::new_keyed(key), data)))
+}
+
+/// BLAKE3 in key derivation mode: a 32-byte key for `context` (a hardcoded,
+/// globally unique string such as `"k-services 2024 dedup fingerprint"`) from `material`.
+#[pyfunction]
+pub fn blake3_derive_key(py: Python<'_>, context: &str, material: &[u8]) -> PyResult<Vec<u8>> {
+    crate::fips::reject("BLAKE3").map_err(crate::error::to_pyerr)?;
+    Ok(py.detach(|| blake3_hash(Blake3::new_derive_key(context), material)))
+}
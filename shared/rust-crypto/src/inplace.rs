@@ -0,0 +1,131 @@
+use pyo3::prelude::*;
+use argon2::password_hash::rand_core::OsRng;
+use rand::RngCore;
+
+use crate::aad::AadArg;
+use crate::audit::Operation;
+use crate::buffer::{BytesLike, WritableBuffer};
+use crate::cipher::{Algorithm, TAG_LEN};
+use crate::envelope::{default_extensions, Envelope};
+use crate::error::Error;
+use crate::master_key::MasterKeyArg;
+
+fn check_output(out_buffer: &WritableBuffer, input: &[u8], needed: usize) -> Result<(), Error> {
+    if out_buffer.overlaps(input) {
+        return Err(
+            Error::config("out_buffer must not overlap the input").with_code("buffer_overlap").with_input("out_buffer")
+        );
+    }
+    if out_buffer.len() < needed {
+        return Err(Error::config(format!("out_buffer too small: {} bytes needed, {} available", needed, out_buffer.len()))
+            .with_code("buffer_too_small").with_input("out_buffer").with_lengths(needed, out_buffer.len()));
+    }
+    Ok(())
+}
+
+/// Encrypts `plaintext` straight into `out_buffer` and returns the number of bytes
+/// written. The output is an envelope in binary form (an `encrypt_to_token` token
+/// before base64url), so a pre-allocated buffer can be reused across messages with
+/// no per-call output allocation. The key derivation runs without the GIL, the
+/// writes to `out_buffer` with it. It needs `len(plaintext)` plus the envelope
+/// overhead (about 80 bytes); a short buffer is rejected with the exact size needed.
+#[pyfunction]
+#[pyo3(signature = (
+    plaintext, out_buffer, master_key, aad_context, algorithm=Algorithm::DEFAULT_NAME, kdf_profile=None, key_id=None,
+    kdf_params=None
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn encrypt_into(
+    py: Python<'_>,
+    plaintext: BytesLike,
+    mut out_buffer: WritableBuffer,
+    master_key: MasterKeyArg,
+    aad_context: AadArg,
+    algorithm: &str,
+    kdf_profile: Option<&str>,
+    key_id: Option<&str>,
+    kdf_params: Option<&str>
+) -> PyResult<usize> {
+    let algorithm = Algorithm::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+    let (kdf_params, _) = crate::resolve_kdf_params(kdf_params, kdf_profile, None, None, None)?;
+
+    py.detach(|| -> Result<usize, Error> {
+        let mut salt = vec![0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let mut nonce = vec![0u8; algorithm.nonce_len()];
+        OsRng.fill_bytes(&mut nonce);
+        let envelope = Envelope {
+            algorithm, kdf_params, salt, extensions: default_extensions(key_id), nonce, ciphertext: Vec::new()
+        };
+        let header = envelope.header()?;
+        let body_end = header.len() + plaintext.len();
+        check_output(&out_buffer, &plaintext, body_end + TAG_LEN)?;
+
+        let key = kdf_params.derive(&master_key, &envelope.salt)?;
+        // Back under the GIL, so no Python thread writes to out_buffer meanwhile
+        Python::attach(|py| {
+            let out = out_buffer.as_mut_slice(py);
+            out[..header.len()].copy_from_slice(&header);
+            out[header.len()..body_end].copy_from_slice(&plaintext);
+            let operation = Operation::new("encrypt", algorithm.name()).with_key_id(key_id).with_aad(&aad_context);
+            let tag = operation.run(|| algorithm.encrypt_detached(
+                &key, &envelope.nonce, &mut out[header.len()..body_end], &Envelope::aad(&header, &aad_context)
+            ))?;
+            out[body_end..body_end + TAG_LEN].copy_from_slice(&tag);
+            Ok(body_end + TAG_LEN)
+        })
+    }).map_err(crate::error::to_pyerr)
+}
+
+/// Decrypts a binary envelope (as written by `encrypt_into`) straight into
+/// `out_buffer` and returns the plaintext length. The buffer needs room for the
+/// ciphertext, which is at most `len(envelope)` bytes; if the envelope doesn't
+/// authenticate, the bytes it was given are zeroed. Padding is stripped (the pad
+/// bytes past the returned length are left in the buffer); compressed envelopes
+/// are rejected. An expired envelope raises `ExpiredCiphertextError`, and the
+/// buffer is zeroed, unless `allow_expired` is set.
+#[pyfunction]
+#[pyo3(signature = (envelope, out_buffer, master_key, aad_context, allow_expired=false))]
+pub fn decrypt_into(
+    py: Python<'_>,
+    envelope: BytesLike,
+    mut out_buffer: WritableBuffer,
+    master_key: MasterKeyArg,
+    aad_context: AadArg,
+    allow_expired: bool
+) -> PyResult<usize> {
+    py.detach(|| -> Result<usize, Error> {
+        let (parsed, body) = Envelope::parse_header(&envelope)?;
+        if parsed.is_compressed() {
+            return Err(
+                Error::crypto("decrypt_into can't decompress in place; use decrypt_token for compressed envelopes")
+            );
+        }
+        let Some(len) = envelope.len().checked_sub(body + TAG_LEN) else {
+            return Err(Error::invalid_format("Invalid envelope: truncated").with_code("truncated"));
+        };
+        check_output(&out_buffer, &envelope, len)?;
+
+        let key = parsed.cipher_key(&*parsed.kdf_params.derive(&master_key, &parsed.salt)?)?;
+        // Back under the GIL, so no Python thread reads or writes out_buffer meanwhile
+        Python::attach(|py| {
+            let out = &mut out_buffer.as_mut_slice(py)[..len];
+            out.copy_from_slice(&envelope[body..body + len]);
+            let aad = Envelope::aad(&envelope[..body], &aad_context);
+            let key_id = parsed.key_id();
+            let operation = Operation::new("decrypt", parsed.algorithm.name())
+                .with_key_id(key_id.as_deref())
+                .with_aad(&aad_context);
+            operation.run(|| parsed.algorithm.decrypt_detached(&key, &parsed.nonce, out, &aad, &envelope[body + len..]))
+                .inspect_err(|_| out.fill(0))?;
+            if !allow_expired {
+                parsed.check_expiry().inspect_err(|_| out.fill(0))?;
+            }
+            let unpadded = parsed.padding().and_then(|padding| match padding {
+                Some(_) => crate::padding::unpadded_len(out),
+                None => Ok(len),
+            });
+            unpadded.inspect_err(|_| out.fill(0))
+        })
+    }).map_err(crate::error::to_pyerr)
+}
@@ -0,0 +1,75 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyInt, PyList, PyString, PyTuple};
+use serde_json::{Map, Number, Value};
+
+use crate::error::Error;
+
+/// Converts JSON-compatible Python values (dict with str keys, list/tuple, str, int,
+/// float, bool, None) into a `serde_json::Value`.
+pub(crate) fn to_json(obj: &Bound<'_, PyAny>) -> PyResult<Value> {
+    if obj.is_none() {
+        return Ok(Value::Null);
+    }
+    // bool before int: Python's bool is an int subclass
+    if let Ok(value) = obj.cast::<PyBool>() {
+        return Ok(Value::Bool(value.is_true()));
+    }
+    if obj.is_instance_of::<PyInt>() {
+        return match obj.extract::<i64>() {
+            Ok(value) => Ok(Value::from(value)),
+            Err(_) => Ok(Value::from(obj.extract::<u64>().map_err(|_| {
+                crate::error::to_pyerr(Error::crypto("Integer out of range for JSON"))
+            })?)),
+        };
+    }
+    if let Ok(value) = obj.cast::<PyFloat>() {
+        return Number::from_f64(value.value()).map(Value::Number)
+            .ok_or_else(|| crate::error::to_pyerr(Error::crypto("NaN and infinity are not valid JSON")));
+    }
+    if let Ok(value) = obj.cast::<PyString>() {
+        return Ok(Value::String(value.to_str()?.to_string()));
+    }
+    if let Ok(dict) = obj.cast::<PyDict>() {
+        let mut map = Map::new();
+        for (key, value) in dict.iter() {
+            let key = key.cast::<PyString>()
+                .map_err(|_| pyo3::exceptions::PyTypeError::new_err("JSON object keys must be str"))?;
+            map.insert(key.to_str()?.to_string(), to_json(&value)?);
+        }
+        return Ok(Value::Object(map));
+    }
+    if obj.is_instance_of::<PyList>() || obj.is_instance_of::<PyTuple>() {
+        return obj.try_iter()?.map(|item| to_json(&item?)).collect::<PyResult<Vec<_>>>().map(Value::Array);
+    }
+    Err(pyo3::exceptions::PyTypeError::new_err(format!(
+        "Object of type {} is not JSON serializable", obj.get_type().name()?
+    )))
+}
+
+/// Inverse of `to_json`: objects become dicts, arrays lists.
+pub(crate) fn to_python<'py>(py: Python<'py>, value: &Value) -> PyResult<Bound<'py, PyAny>> {
+    Ok(match value {
+        Value::Null => py.None().into_bound(py),
+        Value::Bool(value) => PyBool::new(py, *value).to_owned().into_any(),
+        Value::Number(number) => match (number.as_i64(), number.as_u64()) {
+            (Some(value), _) => value.into_pyobject(py)?.into_any(),
+            (None, Some(value)) => value.into_pyobject(py)?.into_any(),
+            _ => number.as_f64().unwrap_or(f64::NAN).into_pyobject(py)?.into_any(),
+        },
+        Value::String(value) => PyString::new(py, value).into_any(),
+        Value::Array(items) => {
+            let list = PyList::empty(py);
+            for item in items {
+                list.append(to_python(py, item)?)?;
+            }
+            list.into_any()
+        }
+        Value::Object(map) => {
+            let dict = PyDict::new(py);
+            for (key, value) in map {
+                dict.set_item(key, to_python(py, value)?)?;
+            }
+            dict.into_any()
+        }
+    })
+}
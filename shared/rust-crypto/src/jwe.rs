@@ -0,0 +1,372 @@
+use pyo3::prelude::*;
+use argon2::password_hash::rand_core::OsRng;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL};
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use p256::elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use crate::audit::Operation;
+use crate::cipher::Algorithm;
+use crate::error::Error;
+use crate::keywrap::Kek;
+use crate::random::random_bytes;
+
+const ENC_A256GCM: &str = "A256GCM";
+const CEK_LEN: usize = 32;
+const DEFAULT_P2C: u32 = 600_000;
+/// `p2c` comes from the (unauthenticated until decrypted) header; bound the work an
+/// attacker-supplied token can make us do.
+const MAX_P2C: u32 = 1_000_000;
+const P2S_LEN: usize = 16;
+
+/// `key` argument: raw bytes (`dir`, PBES2) or text (PBES2 passphrase, JWK JSON).
+#[derive(FromPyObject)]
+pub(crate) enum KeyInput {
+    Bytes(Vec<u8>),
+    Text(String),
+}
+
+impl KeyInput {
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        match self {
+            KeyInput::Bytes(bytes) => bytes,
+            KeyInput::Text(text) => text.as_bytes(),
+        }
+    }
+
+    fn jwk(&self) -> Result<Map<String, Value>, Error> {
+        match self {
+            KeyInput::Text(text) => match serde_json::from_str(text) {
+                Ok(Value::Object(jwk)) => Ok(jwk),
+                _ => Err(Error::config("key must be a JWK JSON object").with_input("key")),
+            },
+            KeyInput::Bytes(_) => Err(Error::crypto("ECDH-ES needs the key as a JWK JSON string")),
+        }
+    }
+}
+
+fn b64_field(object: &Map<String, Value>, name: &str) -> Result<Vec<u8>, Error> {
+    let value = object.get(name).and_then(Value::as_str)
+        .ok_or_else(|| Error::crypto(format!("Missing \"{}\"", name)))?;
+    BASE64_URL.decode(value).map_err(|_| Error::invalid_format(format!("Invalid base64url in \"{}\"", name)))
+}
+
+fn str_field<'a>(object: &'a Map<String, Value>, name: &str) -> Result<&'a str, Error> {
+    object.get(name).and_then(Value::as_str).ok_or_else(|| Error::crypto(format!("Missing \"{}\"", name)))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum KeyManagement {
+    Direct,
+    EcdhEs,
+    Pbes2(Pbes2),
+}
+
+/// PBES2 with HMAC-SHA-256/384/512 and A128KW/A192KW/A256KW respectively.
+#[derive(Clone, Copy, PartialEq)]
+enum Pbes2 {
+    Hs256,
+    Hs384,
+    Hs512,
+}
+
+impl KeyManagement {
+    fn from_name(name: &str) -> Result<Self, Error> {
+        match name {
+            "dir" => Ok(KeyManagement::Direct),
+            "ECDH-ES" => Ok(KeyManagement::EcdhEs),
+            "PBES2-HS256+A128KW" => Ok(KeyManagement::Pbes2(Pbes2::Hs256)),
+            "PBES2-HS384+A192KW" => Ok(KeyManagement::Pbes2(Pbes2::Hs384)),
+            "PBES2-HS512+A256KW" => Ok(KeyManagement::Pbes2(Pbes2::Hs512)),
+            _ => Err(Error::config(format!(
+                "Unsupported JWE alg: {} (expected dir, ECDH-ES or PBES2-HS256+A128KW/HS384+A192KW/HS512+A256KW)", name
+            ))),
+        }
+    }
+}
+
+impl Pbes2 {
+    fn name(self) -> &'static str {
+        match self {
+            Pbes2::Hs256 => "PBES2-HS256+A128KW",
+            Pbes2::Hs384 => "PBES2-HS384+A192KW",
+            Pbes2::Hs512 => "PBES2-HS512+A256KW",
+        }
+    }
+
+    /// RFC 7518 §4.8.1.1: salt = UTF8(alg) || 0x00 || p2s.
+    fn kek(self, passphrase: &[u8], p2s: &[u8], p2c: u32) -> Result<Kek, Error> {
+        let mut salt = self.name().as_bytes().to_vec();
+        salt.push(0);
+        salt.extend_from_slice(p2s);
+        let mut kek = vec![0u8; match self { Pbes2::Hs256 => 16, Pbes2::Hs384 => 24, Pbes2::Hs512 => 32 }];
+        match self {
+            Pbes2::Hs256 => pbkdf2::pbkdf2_hmac::<Sha256>(passphrase, &salt, p2c, &mut kek),
+            Pbes2::Hs384 => pbkdf2::pbkdf2_hmac::<Sha384>(passphrase, &salt, p2c, &mut kek),
+            Pbes2::Hs512 => pbkdf2::pbkdf2_hmac::<Sha512>(passphrase, &salt, p2c, &mut kek),
+        }
+        Kek::new(&kek)
+    }
+}
+
+/// Concat KDF (NIST SP 800-56A, RFC 7518 §4.6.2) for a single SHA-256 round.
+fn concat_kdf(shared_secret: &[u8], algorithm_id: &str, apu: &[u8], apv: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(1u32.to_be_bytes());
+    hasher.update(shared_secret);
+    for part in [algorithm_id.as_bytes(), apu, apv] {
+        hasher.update((part.len() as u32).to_be_bytes());
+        hasher.update(part);
+    }
+    hasher.update(((CEK_LEN * 8) as u32).to_be_bytes());
+    hasher.finalize().into()
+}
+
+/// Public key from a P-256 (`kty: EC`) or X25519 (`kty: OKP`) JWK.
+enum EcPublic {
+    P256(p256::PublicKey),
+    X25519([u8; 32]),
+}
+
+impl EcPublic {
+    fn from_jwk(jwk: &Map<String, Value>) -> Result<Self, Error> {
+        match (str_field(jwk, "kty")?, str_field(jwk, "crv")?) {
+            ("EC", "P-256") => {
+                let (x, y) = (b64_field(jwk, "x")?, b64_field(jwk, "y")?);
+                if x.len() != 32 || y.len() != 32 {
+                    return Err(Error::invalid_format("Invalid P-256 JWK"));
+                }
+                let point = p256::EncodedPoint::from_affine_coordinates(x.as_slice().into(), y.as_slice().into(), false);
+                Option::from(p256::PublicKey::from_encoded_point(&point))
+                    .map(EcPublic::P256)
+                    .ok_or_else(|| Error::invalid_format("Invalid P-256 public key"))
+            }
+            ("OKP", "X25519") => b64_field(jwk, "x")?.try_into()
+                .map(EcPublic::X25519)
+                .map_err(|_| Error::invalid_format("Invalid X25519 JWK")),
+            (kty, crv) => Err(Error::config(format!("Unsupported JWK for ECDH-ES: kty={} crv={}", kty, crv))),
+        }
+    }
+
+    fn to_jwk(&self) -> Value {
+        match self {
+            EcPublic::P256(public) => {
+                let point = public.to_encoded_point(false);
+                json!({
+                    "kty": "EC", "crv": "P-256",
+                    "x": BASE64_URL.encode(point.x().unwrap()),
+                    "y": BASE64_URL.encode(point.y().unwrap()),
+                })
+            }
+            EcPublic::X25519(public) => json!({ "kty": "OKP", "crv": "X25519", "x": BASE64_URL.encode(public) }),
+        }
+    }
+
+    /// Fresh ephemeral key agreement with this key: `(ephemeral public, shared secret)`.
+    fn agree_ephemeral(&self) -> Result<(EcPublic, Vec<u8>), Error> {
+        match self {
+            EcPublic::P256(public) => {
+                let ephemeral = p256::ecdh::EphemeralSecret::random(&mut OsRng);
+                let shared = ephemeral.diffie_hellman(public);
+                Ok((EcPublic::P256(ephemeral.public_key()), shared.raw_secret_bytes().to_vec()))
+            }
+            EcPublic::X25519(public) => {
+                let secret: [u8; 32] = random_bytes(32).try_into().unwrap();
+                let shared = x25519_shared(&secret, public)?;
+                Ok((EcPublic::X25519(MontgomeryPoint::mul_base_clamped(secret).to_bytes()), shared))
+            }
+        }
+    }
+}
+
+fn x25519_shared(secret: &[u8; 32], public: &[u8; 32]) -> Result<Vec<u8>, Error> {
+    let shared = MontgomeryPoint(*public).mul_clamped(*secret).to_bytes();
+    // Low-order public keys give an all-zero secret (RFC 7748 §6.1)
+    if shared == [0u8; 32] {
+        return Err(Error::invalid_format("Invalid X25519 public key"));
+    }
+    Ok(shared.to_vec())
+}
+
+/// Key agreement with the private JWK `jwk` and the sender's ephemeral key `epk`.
+fn agree_static(jwk: &Map<String, Value>, epk: &EcPublic) -> Result<Vec<u8>, Error> {
+    let d = b64_field(jwk, "d").map_err(|_| Error::crypto("ECDH-ES decryption needs a private JWK (with \"d\")"))?;
+    match (EcPublic::from_jwk(jwk)?, epk) {
+        (EcPublic::P256(_), EcPublic::P256(epk)) => {
+            let secret = p256::SecretKey::from_slice(&d)
+                .map_err(|_| Error::invalid_format("Invalid P-256 private key"))?;
+            let shared = p256::ecdh::diffie_hellman(secret.to_nonzero_scalar(), epk.as_affine());
+            Ok(shared.raw_secret_bytes().to_vec())
+        }
+        (EcPublic::X25519(_), EcPublic::X25519(epk)) => {
+            let d: [u8; 32] = d.try_into().map_err(|_| Error::invalid_format("Invalid X25519 private key"))?;
+            x25519_shared(&d, epk)
+        }
+        _ => Err(Error::crypto("epk curve does not match the key")),
+    }
+}
+
+fn encrypt(
+    plaintext: &[u8],
+    key: &KeyInput,
+    alg: KeyManagement,
+    kid: Option<&str>,
+    p2c: u32
+) -> Result<String, Error> {
+    let mut header = Map::new();
+    header.insert("enc".to_string(), json!(ENC_A256GCM));
+    if let Some(kid) = kid {
+        header.insert("kid".to_string(), json!(kid));
+    }
+
+    let (alg_name, cek, encrypted_key) = match alg {
+        KeyManagement::Direct => {
+            let cek: [u8; CEK_LEN] = key.as_bytes().try_into()
+                .map_err(|_| Error::crypto(format!("dir needs a {}-byte key for A256GCM", CEK_LEN)))?;
+            ("dir", cek, Vec::new())
+        }
+        KeyManagement::EcdhEs => {
+            let (epk, shared) = EcPublic::from_jwk(&key.jwk()?)?.agree_ephemeral()?;
+            header.insert("epk".to_string(), epk.to_jwk());
+            ("ECDH-ES", concat_kdf(&shared, ENC_A256GCM, b"", b""), Vec::new())
+        }
+        KeyManagement::Pbes2(pbes2) => {
+            let p2s = random_bytes(P2S_LEN);
+            header.insert("p2s".to_string(), json!(BASE64_URL.encode(&p2s)));
+            header.insert("p2c".to_string(), json!(p2c));
+            let cek: [u8; CEK_LEN] = random_bytes(CEK_LEN).try_into().unwrap();
+            let wrapped = pbes2.kek(key.as_bytes(), &p2s, p2c)?.wrap(&cek, false)
+                .map_err(|e| Error::crypto(format!("Key wrap failed: {}", e)))?;
+            (pbes2.name(), cek, wrapped)
+        }
+    };
+    header.insert("alg".to_string(), json!(alg_name));
+
+    let protected = BASE64_URL.encode(Value::Object(header).to_string());
+    let iv = random_bytes(Algorithm::AES_256_GCM.nonce_len());
+    let mut ciphertext = Algorithm::AES_256_GCM.encrypt(&cek, &iv, plaintext, protected.as_bytes())?;
+    let tag = ciphertext.split_off(ciphertext.len() - 16);
+
+    Ok([
+        protected,
+        BASE64_URL.encode(encrypted_key),
+        BASE64_URL.encode(iv),
+        BASE64_URL.encode(ciphertext),
+        BASE64_URL.encode(tag),
+    ].join("."))
+}
+
+fn decrypt(token: &str, key: &KeyInput, expected_alg: Option<KeyManagement>) -> Result<Vec<u8>, Error> {
+    let parts: Vec<&str> = token.trim().split('.').collect();
+    let [protected, encrypted_key, iv, ciphertext, tag] = parts.as_slice() else {
+        return Err(Error::invalid_format("Invalid JWE: expected 5 compact-serialization parts"));
+    };
+    let decode = |part: &str| BASE64_URL.decode(part).map_err(|_| Error::invalid_format("Invalid JWE: bad base64url"));
+
+    let header: Map<String, Value> = serde_json::from_slice(&decode(protected)?)
+        .map_err(|_| Error::invalid_format("Invalid JWE: protected header is not a JSON object"))?;
+    if header.contains_key("crit") || header.contains_key("zip") {
+        return Err(Error::config("Unsupported JWE header parameter (crit/zip)"));
+    }
+    if str_field(&header, "enc")? != ENC_A256GCM {
+        return Err(
+            Error::config(format!("Unsupported JWE enc: {} (expected {})", str_field(&header, "enc")?, ENC_A256GCM))
+        );
+    }
+    let alg = KeyManagement::from_name(str_field(&header, "alg")?)?;
+    if expected_alg.is_some_and(|expected| expected != alg) {
+        return Err(Error::crypto(format!("Unexpected JWE alg: {}", str_field(&header, "alg")?)));
+    }
+    let encrypted_key = decode(encrypted_key)?;
+
+    let cek: Vec<u8> = match alg {
+        KeyManagement::Direct | KeyManagement::EcdhEs if !encrypted_key.is_empty() => {
+            return Err(Error::invalid_format("Invalid JWE: encrypted key must be empty for dir and ECDH-ES"));
+        }
+        KeyManagement::Direct => key.as_bytes().to_vec(),
+        KeyManagement::EcdhEs => {
+            let epk = match header.get("epk") {
+                Some(Value::Object(epk)) => EcPublic::from_jwk(epk)?,
+                _ => return Err(Error::invalid_format("Invalid JWE: missing epk")),
+            };
+            let apu = header.get("apu").map(|_| b64_field(&header, "apu")).transpose()?.unwrap_or_default();
+            let apv = header.get("apv").map(|_| b64_field(&header, "apv")).transpose()?.unwrap_or_default();
+            concat_kdf(&agree_static(&key.jwk()?, &epk)?, ENC_A256GCM, &apu, &apv).to_vec()
+        }
+        KeyManagement::Pbes2(pbes2) => {
+            let p2s = b64_field(&header, "p2s")?;
+            let p2c = header.get("p2c").and_then(Value::as_u64)
+                .filter(|&p2c| (1..=MAX_P2C as u64).contains(&p2c))
+                .ok_or_else(|| Error::invalid_format(format!("Invalid JWE: p2c must be between 1 and {}", MAX_P2C))
+                    .with_input("p2c"))?;
+            pbes2.kek(key.as_bytes(), &p2s, p2c as u32)?.unwrap(&encrypted_key, false)
+                .map_err(|_| Error::decryption("Decryption failed: wrong passphrase").with_code("wrong_passphrase"))?
+        }
+    };
+    let cek: [u8; CEK_LEN] = cek.try_into()
+        .map_err(|_| Error::crypto(format!("A256GCM needs a {}-byte key", CEK_LEN)))?;
+
+    let mut sealed = decode(ciphertext)?;
+    sealed.extend_from_slice(&decode(tag)?);
+    Algorithm::AES_256_GCM.decrypt(&cek, &decode(iv)?, &sealed, protected.as_bytes())
+}
+
+/// New key pair for ECDH-ES as `(private JWK, public JWK)` JSON strings. `crv` is
+/// `P-256` or `X25519`.
+#[pyfunction]
+#[pyo3(signature = (crv="P-256"))]
+pub fn jwe_generate_keypair(crv: &str) -> PyResult<(String, String)> {
+    let (private, public) = match crv {
+        "P-256" => {
+            let secret = p256::SecretKey::random(&mut OsRng);
+            let public = EcPublic::P256(secret.public_key()).to_jwk();
+            let mut private = public.clone();
+            private["d"] = json!(BASE64_URL.encode(secret.to_bytes()));
+            (private, public)
+        }
+        "X25519" => {
+            let secret: [u8; 32] = random_bytes(32).try_into().unwrap();
+            let public = EcPublic::X25519(MontgomeryPoint::mul_base_clamped(secret).to_bytes()).to_jwk();
+            let mut private = public.clone();
+            private["d"] = json!(BASE64_URL.encode(secret));
+            (private, public)
+        }
+        _ => return Err(crate::error::to_pyerr(Error::config("crv must be P-256 or X25519").with_input("crv"))),
+    };
+    Ok((private.to_string(), public.to_string()))
+}
+
+/// JWE compact serialization with A256GCM content encryption. `alg` picks key
+/// management: `dir` (`key` = 32 raw bytes), `ECDH-ES` (`key` = recipient's public
+/// JWK JSON, P-256 or X25519) or `PBES2-HS*+A*KW` (`key` = passphrase, `p2c` iterations).
+#[pyfunction]
+#[pyo3(signature = (plaintext, key, alg="dir", kid=None, p2c=DEFAULT_P2C))]
+pub fn jwe_encrypt(
+    py: Python<'_>,
+    plaintext: &[u8],
+    key: KeyInput,
+    alg: &str,
+    kid: Option<&str>,
+    p2c: u32
+) -> PyResult<String> {
+    let alg = KeyManagement::from_name(alg).map_err(crate::error::to_pyerr)?;
+    if !(1_000..=MAX_P2C).contains(&p2c) {
+        return Err(crate::error::to_pyerr(
+            Error::config(format!("p2c must be between 1000 and {}", MAX_P2C)).with_input("p2c")
+        ));
+    }
+    let operation = Operation::new("encrypt", "jwe").with_key_id(kid);
+    py.detach(|| operation.run(|| encrypt(plaintext, &key, alg, kid, p2c))).map_err(crate::error::to_pyerr)
+}
+
+/// Decrypts a compact JWE. `key` is interpreted according to the token's `alg` (see
+/// `jwe_encrypt`; ECDH-ES needs the private JWK). Pass `alg` to reject tokens that
+/// use any other key management.
+#[pyfunction]
+#[pyo3(signature = (token, key, alg=None))]
+pub fn jwe_decrypt(py: Python<'_>, token: &str, key: KeyInput, alg: Option<&str>) -> PyResult<Vec<u8>> {
+    let alg = alg.map(KeyManagement::from_name).transpose().map_err(crate::error::to_pyerr)?;
+    let operation = Operation::new("decrypt", "jwe");
+    py.detach(|| operation.run(|| decrypt(token, &key, alg))).map_err(crate::error::to_pyerr)
+}
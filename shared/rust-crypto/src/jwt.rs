@@ -0,0 +1,213 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL};
+use ed25519_dalek::{Signature, Signer, VerifyingKey};
+use hmac::{digest::KeyInit, Hmac, Mac};
+use serde_json::{json, Map, Value};
+use sha2::{Sha256, Sha384, Sha512};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::audit::Operation;
+use crate::error::Error;
+use crate::json::{to_json, to_python};
+use crate::jwe::KeyInput;
+use crate::sign::signing_key;
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum JwtAlgorithm {
+    Hs256,
+    Hs384,
+    Hs512,
+    EdDsa,
+}
+
+impl JwtAlgorithm {
+    fn from_name(name: &str) -> Result<Self, Error> {
+        match name {
+            "HS256" => Ok(JwtAlgorithm::Hs256),
+            "HS384" => Ok(JwtAlgorithm::Hs384),
+            "HS512" => Ok(JwtAlgorithm::Hs512),
+            "EdDSA" => Ok(JwtAlgorithm::EdDsa),
+            _ => Err(Error::config(format!("Unsupported JWT alg: {} (expected HS256, HS384, HS512 or EdDSA)", name))),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            JwtAlgorithm::Hs256 => "HS256",
+            JwtAlgorithm::Hs384 => "HS384",
+            JwtAlgorithm::Hs512 => "HS512",
+            JwtAlgorithm::EdDsa => "EdDSA",
+        }
+    }
+
+    /// HS*: `key` is the shared secret. EdDSA: `key` is the 32-byte Ed25519 private key.
+    pub(crate) fn sign(self, key: &[u8], input: &[u8]) -> Result<Vec<u8>, Error> {
+        fn hmac<M: Mac + KeyInit>(key: &[u8], input: &[u8]) -> Vec<u8> {
+            let mut mac = <M as KeyInit>::new_from_slice(key).expect("HMAC accepts any key length");
+            mac.update(input);
+            mac.finalize().into_bytes().to_vec()
+        }
+        Ok(match self {
+            JwtAlgorithm::Hs256 => hmac::<Hmac<Sha256>>(key, input),
+            JwtAlgorithm::Hs384 => hmac::<Hmac<Sha384>>(key, input),
+            JwtAlgorithm::Hs512 => hmac::<Hmac<Sha512>>(key, input),
+            JwtAlgorithm::EdDsa => signing_key(key)?.sign(input).to_bytes().to_vec(),
+        })
+    }
+
+    /// HS*: `key` is the shared secret. EdDSA: `key` is the 32-byte Ed25519 public key.
+    fn verify(self, key: &[u8], input: &[u8], signature: &[u8]) -> Result<bool, Error> {
+        fn hmac<M: Mac + KeyInit>(key: &[u8], input: &[u8], signature: &[u8]) -> bool {
+            let mut mac = <M as KeyInit>::new_from_slice(key).expect("HMAC accepts any key length");
+            mac.update(input);
+            mac.verify_slice(signature).is_ok()
+        }
+        Ok(match self {
+            JwtAlgorithm::Hs256 => hmac::<Hmac<Sha256>>(key, input, signature),
+            JwtAlgorithm::Hs384 => hmac::<Hmac<Sha384>>(key, input, signature),
+            JwtAlgorithm::Hs512 => hmac::<Hmac<Sha512>>(key, input, signature),
+            JwtAlgorithm::EdDsa => {
+                let key: &[u8; 32] = key.try_into()
+                    .map_err(|_| Error::config("Ed25519 public key must be 32 bytes"))?;
+                let key = VerifyingKey::from_bytes(key)
+                    .map_err(|_| Error::invalid_format("Invalid Ed25519 public key"))?;
+                Signature::from_slice(signature).is_ok_and(|signature| key.verify_strict(input, &signature).is_ok())
+            }
+        })
+    }
+}
+
+/// Registered-claim checks applied after the signature verifies.
+pub(crate) struct Validation {
+    pub(crate) algorithms: Vec<JwtAlgorithm>,
+    pub(crate) audience: Option<String>,
+    pub(crate) issuer: Option<String>,
+    pub(crate) leeway: f64,
+    pub(crate) require: Vec<String>,
+}
+
+fn now() -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |d| d.as_secs_f64())
+}
+
+fn numeric_claim(claims: &Map<String, Value>, name: &str) -> Result<Option<f64>, Error> {
+    match claims.get(name) {
+        None => Ok(None),
+        Some(value) => value.as_f64().map(Some)
+            .ok_or_else(|| Error::config(format!("The \"{}\" claim must be a number", name))),
+    }
+}
+
+pub(crate) fn verify(token: &str, key: &[u8], validation: &Validation) -> Result<Map<String, Value>, Error> {
+    let token = token.trim();
+    let parts: Vec<&str> = token.split('.').collect();
+    let [header, payload, signature] = parts.as_slice() else {
+        return Err(Error::invalid_format("Invalid JWT: expected 3 parts"));
+    };
+    let decode = |part: &str| BASE64_URL.decode(part).map_err(|_| Error::invalid_format("Invalid JWT: bad base64url"));
+    let object = |bytes: Vec<u8>, what: &str| match serde_json::from_slice(&bytes) {
+        Ok(Value::Object(object)) => Ok(object),
+        _ => Err(Error::invalid_format(format!("Invalid JWT: {} is not a JSON object", what))),
+    };
+
+    let signing_input = &token[..header.len() + 1 + payload.len()];
+    let header = object(decode(header)?, "header")?;
+    if header.contains_key("crit") {
+        return Err(Error::config("Unsupported JWT header parameter: crit"));
+    }
+    let alg = header.get("alg").and_then(Value::as_str)
+        .ok_or_else(|| Error::invalid_format("Invalid JWT: missing alg"))?;
+    // The allow-list is what stops an HS256 token "signed" with a public key from passing
+    let alg = JwtAlgorithm::from_name(alg).ok().filter(|alg| validation.algorithms.contains(alg))
+        .ok_or_else(|| Error::config(format!("JWT alg {} is not allowed", alg)))?;
+    if !alg.verify(key, signing_input.as_bytes(), &decode(signature)?)? {
+        return Err(Error::decryption("Invalid JWT signature").with_code("invalid_signature"));
+    }
+
+    let claims = object(decode(payload)?, "payload")?;
+    for name in &validation.require {
+        if !claims.contains_key(name) {
+            return Err(Error::crypto(format!("Token is missing the \"{}\" claim", name)));
+        }
+    }
+    let now = now();
+    if numeric_claim(&claims, "exp")?.is_some_and(|exp| exp <= now - validation.leeway) {
+        return Err(Error::expired("Token has expired"));
+    }
+    if numeric_claim(&claims, "nbf")?.is_some_and(|nbf| nbf > now + validation.leeway) {
+        return Err(Error::crypto("Token is not yet valid (nbf)").with_code("not_yet_valid"));
+    }
+    match (&validation.audience, claims.get("aud")) {
+        (None, None) => {}
+        (None, Some(_)) => return Err(Error::config("Token has an \"aud\" claim but no audience was given")),
+        (Some(_), None) => return Err(Error::crypto("Token is missing the \"aud\" claim")),
+        (Some(audience), Some(aud)) => {
+            let matches = match aud {
+                Value::String(aud) => aud == audience,
+                Value::Array(auds) => auds.iter().any(|aud| aud.as_str() == Some(audience)),
+                _ => false,
+            };
+            if !matches {
+                return Err(Error::crypto("Invalid audience"));
+            }
+        }
+    }
+    if let Some(issuer) = &validation.issuer {
+        if claims.get("iss").and_then(Value::as_str) != Some(issuer.as_str()) {
+            return Err(Error::crypto("Invalid issuer"));
+        }
+    }
+    Ok(claims)
+}
+
+/// Signs `claims` (a JSON-serializable dict) into a compact JWT. HS256/384/512 take the
+/// shared secret as `key`; EdDSA takes the 32-byte Ed25519 private key.
+#[pyfunction]
+#[pyo3(signature = (claims, key, alg="HS256", kid=None))]
+pub fn jwt_sign(claims: &Bound<'_, PyDict>, key: KeyInput, alg: &str, kid: Option<&str>) -> PyResult<String> {
+    let alg = JwtAlgorithm::from_name(alg).map_err(crate::error::to_pyerr)?;
+    let mut header = json!({ "alg": alg.name(), "typ": "JWT" });
+    if let Some(kid) = kid {
+        header["kid"] = json!(kid);
+    }
+    let mut token = BASE64_URL.encode(header.to_string());
+    token.push('.');
+    token.push_str(&BASE64_URL.encode(to_json(claims.as_any())?.to_string()));
+
+    let operation = Operation::new("sign", alg.name()).with_key_id(kid);
+    let signature = operation.run(|| alg.sign(key.as_bytes(), token.as_bytes())).map_err(crate::error::to_pyerr)?;
+    token.push('.');
+    token.push_str(&BASE64_URL.encode(signature));
+    Ok(token)
+}
+
+/// Verifies a JWT and returns its claims. `algorithms` lists the accepted `alg`
+/// values and is required. `exp` and `nbf` are checked when present (with `leeway`
+/// seconds of clock skew); `aud` must contain `audience`, and `iss` equal `issuer`,
+/// when those are given. `require` names claims that must be present.
+#[pyfunction]
+#[pyo3(signature = (token, key, algorithms, audience=None, issuer=None, leeway=0.0, require=Vec::new()))]
+#[allow(clippy::too_many_arguments)]
+pub fn jwt_verify<'py>(
+    py: Python<'py>,
+    token: &str,
+    key: KeyInput,
+    algorithms: Vec<String>,
+    audience: Option<String>,
+    issuer: Option<String>,
+    leeway: f64,
+    require: Vec<String>
+) -> PyResult<Bound<'py, PyAny>> {
+    let algorithms = algorithms.iter().map(|name| JwtAlgorithm::from_name(name))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(crate::error::to_pyerr)?;
+    if algorithms.is_empty() {
+        return Err(crate::error::to_pyerr(Error::config("algorithms must not be empty").with_input("algorithms")));
+    }
+    let validation = Validation { algorithms, audience, issuer, leeway, require };
+
+    let claims = py.detach(|| Operation::new("verify", "jwt").run(|| verify(token, key.as_bytes(), &validation)))
+        .map_err(crate::error::to_pyerr)?;
+    to_python(py, &Value::Object(claims))
+}
@@ -0,0 +1,446 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use argon2::{Algorithm as Argon2Algorithm, Argon2, Params, Version};
+use hkdf::Hkdf;
+use sha2::{Sha256, Sha512};
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+use crate::envelope::Reader;
+use crate::error::Error;
+use crate::hash::HashAlgorithm;
+
+/// A derived key, wiped from memory when dropped.
+pub(crate) type DerivedKey = Zeroizing<[u8; 32]>;
+
+/// Upper bounds accepted when parsing recorded parameters. Decrypt takes these
+/// values from stored metadata, so they must not be able to request a
+/// multi-minute or multi-gigabyte derivation: the memory cap is the "sensitive"
+/// profile's.
+const MAX_MEMORY_COST: u32 = 1024 * 1024; // KiB (1 GiB)
+const MAX_TIME_COST: u32 = 16;
+const MAX_PARALLELISM: u32 = 16;
+/// scrypt uses 128 * r * N bytes; ln=20, r=8 is 1 GiB.
+const MAX_SCRYPT_LOG_N: u8 = 20;
+const MAX_SCRYPT_R: u32 = 32;
+const MAX_SCRYPT_P: u32 = 16;
+/// SP 800-132 asks for at least 1,000; OWASP currently recommends 600,000 for SHA-256.
+const MIN_PBKDF2_ITERATIONS: u32 = 1_000;
+const MAX_PBKDF2_ITERATIONS: u32 = 10_000_000;
+
+/// Key derivation function plus its cost parameters. `Default` is Argon2id with the
+/// parameters of `Argon2::default()`, i.e. what every ciphertext produced before the
+/// parameters became configurable was derived with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum KdfParams {
+    Argon2id { memory_cost: u32, time_cost: u32, parallelism: u32 },
+    /// Kept for data written by services that derived keys with scrypt (e.g. Node's
+    /// `crypto.scrypt`). `log_n` is log2(N).
+    Scrypt { log_n: u8, r: u32, p: u32 },
+    /// PBKDF2-HMAC-SHA256, for deployments restricted to FIPS-approved primitives.
+    Pbkdf2Sha256 { iterations: u32 },
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams::Argon2id {
+            memory_cost: Params::DEFAULT_M_COST,
+            time_cost: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+/// Vetted named parameter sets, mirroring libsodium's `crypto_pwhash` limits,
+/// so application teams pick a profile instead of inventing their own numbers.
+pub(crate) const PROFILES: &[(&str, KdfParams)] = &[
+    // Online operations where latency matters (~tens of ms, 64 MiB).
+    ("interactive", KdfParams::Argon2id { memory_cost: 64 * 1024, time_cost: 2, parallelism: 1 }),
+    // Default choice when a request can afford a few hundred ms (256 MiB).
+    ("moderate", KdfParams::Argon2id { memory_cost: 256 * 1024, time_cost: 3, parallelism: 1 }),
+    // Offline / high-value secrets, roughly a second per derivation (1 GiB).
+    ("sensitive", KdfParams::Argon2id { memory_cost: 1024 * 1024, time_cost: 4, parallelism: 1 }),
+];
+
+/// Entry in the KDF registry.
+pub(crate) struct KdfSpec {
+    /// Name used in `kdf_params` strings and reported by `inspect()`.
+    pub(crate) id: &'static str,
+    /// Byte recorded in envelope headers ahead of the parameters. Never reuse one.
+    pub(crate) envelope_id: u8,
+    pub(crate) fips_approved: bool,
+    /// Reads the parameters `KdfParams::write` recorded after `envelope_id`.
+    read: fn(&mut Reader<'_>) -> Result<KdfParams, Error>,
+}
+
+const ARGON2ID: KdfSpec = KdfSpec {
+    id: "argon2id",
+    envelope_id: 1,
+    fips_approved: false,
+    read: |reader| KdfParams::new(Some(reader.u32()?), Some(reader.u32()?), Some(reader.u32()?)),
+};
+const SCRYPT: KdfSpec = KdfSpec {
+    id: "scrypt",
+    envelope_id: 2,
+    fips_approved: false,
+    read: |reader| KdfParams::scrypt(reader.u8()?, reader.u32()?, reader.u32()?),
+};
+const PBKDF2_SHA256: KdfSpec = KdfSpec {
+    id: "pbkdf2-sha256",
+    envelope_id: 3,
+    fips_approved: true,
+    read: |reader| KdfParams::pbkdf2_sha256(reader.u32()?),
+};
+
+/// Every KDF the module knows, in the order `list_algorithms()` reports them. Like
+/// the cipher registry, ids 128-255 are left for organization-specific entries.
+pub(crate) const KDFS: &[KdfSpec] = &[ARGON2ID, SCRYPT, PBKDF2_SHA256];
+
+impl KdfSpec {
+    fn from_envelope_id(id: u8) -> Result<&'static KdfSpec, Error> {
+        KDFS.iter()
+            .find(|spec| spec.envelope_id == id)
+            .ok_or_else(|| Error::kdf(format!("Unknown KDF id in envelope: {}", id)).with_code("unsupported_kdf"))
+    }
+}
+
+fn invalid(encoded: &str) -> Error {
+    Error::kdf(format!("Invalid KDF parameters: {}", encoded)).with_code("invalid_kdf_params")
+}
+
+/// Splits `a=1,b=2` into name/value pairs.
+fn parse_fields(encoded: &str, fields: &str) -> Result<Vec<(String, u32)>, Error> {
+    fields.split(',')
+        .map(|part| {
+            let (name, value) = part.trim().split_once('=').ok_or_else(|| invalid(encoded))?;
+            let value = value.parse().map_err(|_| invalid(encoded))?;
+            Ok((name.to_string(), value))
+        })
+        .collect()
+}
+
+impl KdfParams {
+    /// Looks up a named profile from `PROFILES`, returning its canonical name as well.
+    pub(crate) fn from_profile(name: &str) -> Result<(&'static str, Self), Error> {
+        PROFILES.iter()
+            .find(|(profile, _)| profile.eq_ignore_ascii_case(name))
+            .map(|(profile, params)| (*profile, *params))
+            .ok_or_else(|| Error::kdf(format!("Unknown KDF profile: {}", name)).with_code("unknown_kdf_profile"))
+    }
+
+    /// Builds Argon2id parameters from optional overrides, falling back to the defaults.
+    pub(crate) fn new(memory_cost: Option<u32>, time_cost: Option<u32>, parallelism: Option<u32>) -> Result<Self, Error> {
+        let params = KdfParams::Argon2id {
+            memory_cost: memory_cost.unwrap_or(Params::DEFAULT_M_COST),
+            time_cost: time_cost.unwrap_or(Params::DEFAULT_T_COST),
+            parallelism: parallelism.unwrap_or(Params::DEFAULT_P_COST),
+        };
+        params.validate()?;
+        Ok(params)
+    }
+
+    pub(crate) fn scrypt(log_n: u8, r: u32, p: u32) -> Result<Self, Error> {
+        let params = KdfParams::Scrypt { log_n, r, p };
+        params.validate()?;
+        Ok(params)
+    }
+
+    pub(crate) fn pbkdf2_sha256(iterations: u32) -> Result<Self, Error> {
+        let params = KdfParams::Pbkdf2Sha256 { iterations };
+        params.validate()?;
+        Ok(params)
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        match *self {
+            KdfParams::Argon2id { memory_cost, time_cost, parallelism } => {
+                if memory_cost > MAX_MEMORY_COST || time_cost > MAX_TIME_COST || parallelism > MAX_PARALLELISM {
+                    return Err(
+                        Error::kdf("KDF parameters exceed the supported maximum").with_code("invalid_kdf_params")
+                    );
+                }
+                self.to_argon2_params().map(|_| ())
+            }
+            KdfParams::Scrypt { log_n, r, p } => {
+                if log_n > MAX_SCRYPT_LOG_N || r > MAX_SCRYPT_R || p > MAX_SCRYPT_P {
+                    return Err(
+                        Error::kdf("KDF parameters exceed the supported maximum").with_code("invalid_kdf_params")
+                    );
+                }
+                scrypt::Params::new(log_n, r, p, 32)
+                    .map(|_| ())
+                    .map_err(|e| Error::kdf(format!("Invalid KDF parameters: {}", e)).with_code("invalid_kdf_params"))
+            }
+            KdfParams::Pbkdf2Sha256 { iterations } => {
+                if iterations > MAX_PBKDF2_ITERATIONS {
+                    return Err(
+                        Error::kdf("KDF parameters exceed the supported maximum").with_code("invalid_kdf_params")
+                    );
+                }
+                if iterations < MIN_PBKDF2_ITERATIONS {
+                    return Err(
+                        Error::kdf(format!("PBKDF2 requires at least {} iterations", MIN_PBKDF2_ITERATIONS))
+                            .with_code("invalid_kdf_params")
+                    );
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn to_argon2_params(self) -> Result<Params, Error> {
+        match self {
+            KdfParams::Argon2id { memory_cost, time_cost, parallelism } => {
+                Params::new(memory_cost, time_cost, parallelism, Some(32))
+                    .map_err(|e| Error::kdf(format!("Invalid KDF parameters: {}", e)).with_code("invalid_kdf_params"))
+            }
+            _ => Err(Error::kdf("Argon2id parameters required")),
+        }
+    }
+
+    /// This KDF's registry entry.
+    pub(crate) fn spec(&self) -> &'static KdfSpec {
+        match self {
+            KdfParams::Argon2id { .. } => &ARGON2ID,
+            KdfParams::Scrypt { .. } => &SCRYPT,
+            KdfParams::Pbkdf2Sha256 { .. } => &PBKDF2_SHA256,
+        }
+    }
+
+    fn check_fips(&self) -> Result<(), Error> {
+        match self.spec() {
+            spec if spec.fips_approved => Ok(()),
+            spec => crate::fips::reject(spec.id),
+        }
+    }
+
+    /// Argon2id instance configured with these parameters.
+    pub(crate) fn argon2(&self) -> Result<Argon2<'static>, Error> {
+        self.check_fips()?;
+        Ok(Argon2::new(Argon2Algorithm::Argon2id, Version::V0x13, self.to_argon2_params()?))
+    }
+
+    /// Short KDF name, as reported by `inspect()`.
+    pub(crate) fn name(&self) -> &'static str {
+        self.spec().id
+    }
+
+    /// Memory one derivation takes, in bytes.
+    pub(crate) fn memory_bytes(&self) -> u64 {
+        match *self {
+            KdfParams::Argon2id { memory_cost, .. } => u64::from(memory_cost) * 1024,
+            KdfParams::Scrypt { log_n, r, .. } => (128 * u64::from(r)) << log_n,
+            KdfParams::Pbkdf2Sha256 { .. } => 0,
+        }
+    }
+
+    /// Writes the envelope id and parameters: `m u32 | t u32 | p u32` for Argon2id,
+    /// `log_n u8 | r u32 | p u32` for scrypt and `iterations u32` for PBKDF2.
+    pub(crate) fn write(&self, out: &mut Vec<u8>) {
+        out.push(self.spec().envelope_id);
+        match *self {
+            KdfParams::Argon2id { memory_cost, time_cost, parallelism } => {
+                out.extend_from_slice(&memory_cost.to_be_bytes());
+                out.extend_from_slice(&time_cost.to_be_bytes());
+                out.extend_from_slice(&parallelism.to_be_bytes());
+            }
+            KdfParams::Scrypt { log_n, r, p } => {
+                out.push(log_n);
+                out.extend_from_slice(&r.to_be_bytes());
+                out.extend_from_slice(&p.to_be_bytes());
+            }
+            KdfParams::Pbkdf2Sha256 { iterations } => out.extend_from_slice(&iterations.to_be_bytes()),
+        }
+    }
+
+    /// Reads what `write` wrote.
+    pub(crate) fn read(reader: &mut Reader<'_>) -> Result<Self, Error> {
+        let spec = KdfSpec::from_envelope_id(reader.u8()?)?;
+        (spec.read)(reader)
+    }
+
+    /// Serializes the parameters. Argon2id keeps the bare `m=<KiB>,t=<passes>,p=<lanes>`
+    /// form (what has always been recorded); other KDFs are prefixed with their name,
+    /// e.g. `scrypt:ln=15,r=8,p=1`.
+    pub(crate) fn encode(&self) -> String {
+        match self {
+            KdfParams::Argon2id { memory_cost, time_cost, parallelism } => {
+                format!("m={},t={},p={}", memory_cost, time_cost, parallelism)
+            }
+            KdfParams::Scrypt { log_n, r, p } => format!("scrypt:ln={},r={},p={}", log_n, r, p),
+            KdfParams::Pbkdf2Sha256 { iterations } => format!("pbkdf2-sha256:i={}", iterations),
+        }
+    }
+
+    /// Parses the output of `encode`. Every field is required.
+    pub(crate) fn parse(encoded: &str) -> Result<Self, Error> {
+        let (kdf, fields) = encoded.trim().split_once(':').unwrap_or(("argon2id", encoded));
+        let fields = parse_fields(encoded, fields)?;
+        let get = |name: &str| fields.iter().find(|(n, _)| n == name).map(|(_, v)| *v);
+        let known = |names: &[&str]| fields.len() == names.len() && names.iter().all(|n| get(n).is_some());
+
+        match kdf {
+            "argon2id" if known(&["m", "t", "p"]) => KdfParams::new(get("m"), get("t"), get("p")),
+            "scrypt" if known(&["ln", "r", "p"]) => {
+                let log_n = u8::try_from(get("ln").unwrap()).map_err(|_| invalid(encoded))?;
+                KdfParams::scrypt(log_n, get("r").unwrap(), get("p").unwrap())
+            }
+            "pbkdf2-sha256" if known(&["i"]) => KdfParams::pbkdf2_sha256(get("i").unwrap()),
+            _ => Err(invalid(encoded)),
+        }
+    }
+
+    /// Derives a 32-byte key from the master key and a salt,
+    /// going through the derived-key cache when it is enabled.
+    pub(crate) fn derive(&self, master_key: &[u8], salt: &[u8]) -> Result<DerivedKey, Error> {
+        self.check_fips()?;
+        crate::cache::get_or_derive(master_key, salt, self, || self.derive_uncached(master_key, salt))
+    }
+
+    /// Always runs the KDF, bypassing the cache.
+    fn derive_uncached(&self, master_key: &[u8], salt: &[u8]) -> Result<DerivedKey, Error> {
+        let start = Instant::now();
+        let mut output_key_material = Zeroizing::new([0u8; 32]);
+
+        match *self {
+            KdfParams::Argon2id { .. } => {
+                self.argon2()?.hash_password_into(
+                    master_key,
+                    salt,
+                    output_key_material.as_mut()
+                ).map_err(|e| Error::kdf(format!("Key derivation failed: {}", e)))?;
+            }
+            KdfParams::Scrypt { log_n, r, p } => {
+                let params = scrypt::Params::new(log_n, r, p, 32)
+                    .map_err(|e| Error::kdf(format!("Invalid KDF parameters: {}", e)).with_code("invalid_kdf_params"))?;
+                scrypt::scrypt(master_key, salt, &params, output_key_material.as_mut())
+                    .map_err(|e| Error::kdf(format!("Key derivation failed: {}", e)))?;
+            }
+            KdfParams::Pbkdf2Sha256 { iterations } => {
+                pbkdf2::pbkdf2_hmac::<Sha256>(master_key, salt, iterations, output_key_material.as_mut());
+            }
+        }
+
+        let elapsed = start.elapsed();
+        crate::metrics::KDF.record(elapsed);
+        crate::logging::debug(|| format!(
+            "Derived key with {} ({}) in {:.1} ms", self.name(), self.encode(), elapsed.as_secs_f64() * 1000.0
+        ));
+        Ok(output_key_material)
+    }
+}
+
+/// Time a single derivation with `params` (fixed dummy key and salt).
+fn measure(params: &KdfParams) -> Result<Duration, Error> {
+    let start = Instant::now();
+    params.derive_uncached(b"calibration-master-key", &[0u8; 16])?;
+    Ok(start.elapsed())
+}
+
+/// Benchmarks Argon2id on this host and returns parameters whose derivation takes
+/// roughly `target_ms`. Memory is kept at `memory_cost` (KiB) and passes are added
+/// until the target is met; if a single pass is already too slow, memory is halved.
+///
+/// Returns `{memory_cost, time_cost, parallelism, kdf_params, elapsed_ms}`.
+#[pyfunction]
+#[pyo3(signature = (target_ms, memory_cost=None, parallelism=None))]
+pub fn calibrate_kdf(
+    py: Python<'_>,
+    target_ms: u64,
+    memory_cost: Option<u32>,
+    parallelism: Option<u32>
+) -> PyResult<Py<PyDict>> {
+    if target_ms == 0 {
+        return Err(crate::error::to_pyerr(Error::config("target_ms must be positive").with_input("target_ms")));
+    }
+    let target = Duration::from_millis(target_ms);
+    let memory_cost = memory_cost.unwrap_or(Params::DEFAULT_M_COST);
+    let parallelism = parallelism.unwrap_or(Params::DEFAULT_P_COST);
+    KdfParams::new(Some(memory_cost), Some(1), Some(parallelism))
+        .map_err(crate::error::to_pyerr)?;
+
+    let (time_cost, memory_cost, elapsed) = py.detach(|| calibrate(memory_cost, parallelism, target))
+        .map_err(crate::error::to_pyerr)?;
+    let params = KdfParams::Argon2id { memory_cost, time_cost, parallelism };
+
+    let result = PyDict::new(py);
+    result.set_item("memory_cost", memory_cost)?;
+    result.set_item("time_cost", time_cost)?;
+    result.set_item("parallelism", parallelism)?;
+    result.set_item("kdf_params", params.encode())?;
+    result.set_item("elapsed_ms", elapsed.as_secs_f64() * 1000.0)?;
+    Ok(result.unbind())
+}
+
+/// Calibration loop behind `calibrate_kdf`, starting from a single pass at the requested
+/// memory. Returns `(time_cost, memory_cost, elapsed)`.
+fn calibrate(mut memory_cost: u32, parallelism: u32, target: Duration) -> Result<(u32, u32, Duration), Error> {
+    let run = |memory_cost, time_cost| measure(&KdfParams::Argon2id { memory_cost, time_cost, parallelism });
+    let mut time_cost = 1;
+
+    // 1. Shrink memory until a single pass fits in the budget (Argon2 floor: 8 KiB per lane)
+    let mut elapsed = run(memory_cost, time_cost)?;
+    while elapsed > target && memory_cost / 2 >= 8 * parallelism {
+        memory_cost /= 2;
+        elapsed = run(memory_cost, time_cost)?;
+    }
+
+    // 2. Add passes: cost scales linearly with time_cost, so extrapolate then verify
+    if elapsed < target {
+        let per_pass = elapsed.as_secs_f64().max(1e-6);
+        let passes = (target.as_secs_f64() / per_pass).floor() as u32;
+        time_cost = passes.clamp(1, MAX_TIME_COST);
+        elapsed = run(memory_cost, time_cost)?;
+        while elapsed > target && time_cost > 1 {
+            time_cost -= 1;
+            elapsed = run(memory_cost, time_cost)?;
+        }
+    }
+
+    Ok((time_cost, memory_cost, elapsed))
+}
+
+/// HKDF-Extract (RFC 5869): condenses input keying material into a pseudorandom key.
+///
+/// Argon2/scrypt/PBKDF2 exist to slow down guessing of low-entropy passwords; when the
+/// input is already a high-entropy key, HKDF is the right (and much cheaper) tool.
+/// A missing `salt` is treated as a string of zeros, as the RFC specifies.
+#[pyfunction]
+#[pyo3(signature = (ikm, salt=None, algorithm=HashAlgorithm::DEFAULT_NAME))]
+pub fn hkdf_extract(ikm: &[u8], salt: Option<&[u8]>, algorithm: &str) -> PyResult<Vec<u8>> {
+    let algorithm = HashAlgorithm::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+    let prk = match algorithm {
+        HashAlgorithm::Sha256 => Hkdf::<Sha256>::extract(salt, ikm).0.to_vec(),
+        HashAlgorithm::Sha512 => Hkdf::<Sha512>::extract(salt, ikm).0.to_vec(),
+    };
+    Ok(prk)
+}
+
+/// HKDF-Expand (RFC 5869): derives `length` bytes of purpose-specific key material
+/// from a pseudorandom key (e.g. the output of `hkdf_extract`). Different `info`
+/// values yield independent subkeys.
+#[pyfunction]
+#[pyo3(signature = (prk, info=b"".as_slice(), length=32, algorithm=HashAlgorithm::DEFAULT_NAME))]
+pub fn hkdf_expand(prk: &[u8], info: &[u8], length: usize, algorithm: &str) -> PyResult<Vec<u8>> {
+    let algorithm = HashAlgorithm::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+    if prk.len() < algorithm.output_len() {
+        return Err(crate::error::to_pyerr(
+            Error::config(format!("prk must be at least {} bytes", algorithm.output_len())).with_input("prk")
+        ));
+    }
+    if length == 0 || length > 255 * algorithm.output_len() {
+        return Err(crate::error::to_pyerr(
+            Error::config(format!("length must be between 1 and {}", 255 * algorithm.output_len())).with_input("length")
+        ));
+    }
+
+    let mut okm = vec![0u8; length];
+    let expanded = match algorithm {
+        HashAlgorithm::Sha256 => Hkdf::<Sha256>::from_prk(prk).map(|h| h.expand(info, &mut okm)),
+        HashAlgorithm::Sha512 => Hkdf::<Sha512>::from_prk(prk).map(|h| h.expand(info, &mut okm)),
+    };
+    match expanded {
+        Ok(Ok(())) => Ok(okm),
+        _ => Err(crate::error::to_pyerr(Error::kdf("HKDF expansion failed"))),
+    }
+}
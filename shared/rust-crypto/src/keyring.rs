@@ -0,0 +1,202 @@
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use zeroize::Zeroizing;
+
+use crate::aad::AadArg;
+use crate::buffer::BytesLike;
+use crate::cipher::Algorithm;
+use crate::encoding::{Encoded, Encoding};
+use crate::envelope::{default_extensions, request_key_commitment, Envelope};
+use crate::error::{Error, Kind};
+use crate::master_key::MasterKeyArg;
+
+struct KeyEntry {
+    key_id: Option<String>,
+    master_key: MasterKeyArg,
+}
+
+/// Set of master keys that are live at the same time, e.g. during a rotation window.
+///
+/// `encrypt()` always uses the primary key (the first one added unless changed) and
+/// records its key ID in the token. `decrypt()` goes straight to the key whose ID
+/// matches the token; tokens without a known key ID are tried against every key,
+/// primary first.
+#[pyclass]
+pub struct Keyring {
+    keys: Vec<KeyEntry>,
+    primary: usize,
+}
+
+impl Keyring {
+    fn open(&self, envelope: &Envelope, aad_context: &[u8], allow_expired: bool) -> Result<Zeroizing<Vec<u8>>, Error> {
+
+        if let Some(key_id) = envelope.key_id() {
+            if let Some(entry) = self.keys.iter().find(|k| k.key_id.as_deref() == Some(key_id.as_str())) {
+                return envelope.open_allowing(&entry.master_key, aad_context, allow_expired);
+            }
+        }
+
+        // No usable key ID: primary first, then the rest in insertion order. Only a
+        // failed authentication means "wrong key"; anything else (an expired envelope,
+        // bad KDF parameters) is the answer.
+        let order = std::iter::once(self.primary).chain((0..self.keys.len()).filter(|&i| i != self.primary));
+        for index in order {
+            match envelope.open_allowing(&self.keys[index].master_key, aad_context, allow_expired) {
+                Err(e) if e.kind() == Kind::Decryption => continue,
+                result => return result,
+            }
+        }
+        Err(Error::decryption("Decryption failed: no key in the keyring matches").with_code("no_matching_key"))
+    }
+}
+
+#[pymethods]
+impl Keyring {
+    #[new]
+    fn new() -> Self {
+        Keyring { keys: Vec::new(), primary: 0 }
+    }
+
+    /// Adds a master key. `key_id` labels it in the tokens it encrypts and must be unique.
+    #[pyo3(signature = (master_key, key_id=None, primary=false))]
+    fn add(&mut self, master_key: MasterKeyArg, key_id: Option<String>, primary: bool) -> PyResult<()> {
+        if key_id.is_some() && self.keys.iter().any(|k| k.key_id == key_id) {
+            return Err(crate::error::to_pyerr(Error::config("Duplicate key_id in keyring")));
+        }
+        self.keys.push(KeyEntry { key_id, master_key });
+        if primary {
+            self.primary = self.keys.len() - 1;
+        }
+        Ok(())
+    }
+
+    /// Makes the key labeled `key_id` the one used for new encryptions.
+    fn set_primary(&mut self, key_id: &str) -> PyResult<()> {
+        self.primary = self.keys.iter()
+            .position(|k| k.key_id.as_deref() == Some(key_id))
+            .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err(key_id.to_string()))?;
+        Ok(())
+    }
+
+    /// Key IDs in insertion order (`None` for unlabeled keys).
+    #[getter]
+    fn key_ids(&self) -> Vec<Option<String>> {
+        self.keys.iter().map(|k| k.key_id.clone()).collect()
+    }
+
+    #[getter]
+    fn primary_key_id(&self) -> Option<String> {
+        self.keys.get(self.primary).and_then(|k| k.key_id.clone())
+    }
+
+    fn __len__(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Encrypts into an envelope token under the primary key. `commit_key=True`
+    /// makes the token openable by that key only, as in `encrypt_to_token`; worth
+    /// it here, since tokens without a key ID are tried against the whole ring.
+    /// `encoding` is as for `encrypt_to_token`.
+    #[pyo3(signature = (
+        plaintext, aad_context, algorithm=Algorithm::DEFAULT_NAME,
+        memory_cost=None, time_cost=None, parallelism=None, kdf_profile=None, kdf_params=None, commit_key=false,
+        encoding=Encoding::TOKEN_DEFAULT_NAME
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn encrypt(
+        &self,
+        py: Python<'_>,
+        plaintext: BytesLike,
+        aad_context: AadArg,
+        algorithm: &str,
+        memory_cost: Option<u32>,
+        time_cost: Option<u32>,
+        parallelism: Option<u32>,
+        kdf_profile: Option<&str>,
+        kdf_params: Option<&str>,
+        commit_key: bool,
+        encoding: &str
+    ) -> PyResult<Py<PyAny>> {
+        let entry = self.keys.get(self.primary)
+            .ok_or_else(|| crate::error::to_pyerr(Error::config("Keyring is empty")))?;
+        let algorithm = Algorithm::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+        let (kdf_params, _) = crate::resolve_kdf_params(kdf_params, kdf_profile, memory_cost, time_cost, parallelism)?;
+        let encoding = Encoding::from_name(encoding).map_err(crate::error::to_pyerr)?;
+
+        let envelope = py.detach(|| {
+            let mut extensions = default_extensions(entry.key_id.as_deref());
+            if commit_key {
+                request_key_commitment(&mut extensions);
+            }
+            Envelope::seal(algorithm, kdf_params, extensions, &plaintext, &entry.master_key, &aad_context)?.to_bytes()
+        }).map_err(crate::error::to_pyerr)?;
+        Ok(encoding.encode(py, &envelope).unbind())
+    }
+
+    /// Decrypts an envelope token with whichever key in the ring matches. Raises
+    /// `ExpiredCiphertextError` (after authenticating) unless `allow_expired=True`.
+    /// `encoding` must be the one the token was encrypted with.
+    #[pyo3(signature = (token, aad_context, allow_expired=false, encoding=Encoding::TOKEN_DEFAULT_NAME))]
+    fn decrypt<'py>(
+        &self,
+        py: Python<'py>,
+        token: Encoded,
+        aad_context: AadArg,
+        allow_expired: bool,
+        encoding: &str
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        let encoding = Encoding::from_name(encoding).map_err(crate::error::to_pyerr)?;
+        py.detach(|| {
+            let envelope = Envelope::from_bytes(&encoding.decode(&token, "token")?)?;
+            self.open(&envelope, &aad_context, allow_expired)
+        })
+            .map(|plaintext| PyBytes::new(py, &plaintext))
+            .map_err(crate::error::to_pyerr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::EXT_EXPIRES_AT;
+    use crate::kdf::KdfParams;
+
+    fn keyring(keys: &[(Option<&str>, &[u8])]) -> Keyring {
+        let keys = keys.iter()
+            .map(|(key_id, key)| KeyEntry {
+                key_id: key_id.map(str::to_string),
+                master_key: MasterKeyArg::Owned(Zeroizing::new(key.to_vec())),
+            })
+            .collect();
+        Keyring { keys, primary: 0 }
+    }
+
+    fn envelope(master_key: &[u8], key_id: Option<&str>, expires_at: Option<u64>) -> Envelope {
+        let mut extensions = default_extensions(key_id);
+        extensions.extend(expires_at.map(|expires_at| (EXT_EXPIRES_AT, expires_at.to_be_bytes().to_vec())));
+        let kdf_params = KdfParams::new(Some(8), Some(1), Some(1)).unwrap();
+        Envelope::seal(Algorithm::AES_256_GCM, kdf_params, extensions, b"secret", master_key, b"ctx").unwrap()
+    }
+
+    #[test]
+    fn opens_with_the_labelled_or_any_matching_key() {
+        let ring = keyring(&[(Some("new"), b"new key"), (Some("old"), b"old key"), (None, b"unlabelled")]);
+        assert_eq!(ring.open(&envelope(b"old key", Some("old"), None), b"ctx", false).unwrap().as_slice(), b"secret");
+        assert_eq!(ring.open(&envelope(b"unlabelled", None, None), b"ctx", false).unwrap().as_slice(), b"secret");
+        assert_eq!(ring.open(&envelope(b"old key", Some("gone"), None), b"ctx", false).unwrap().as_slice(), b"secret");
+        let error = ring.open(&envelope(b"other", None, None), b"ctx", false).unwrap_err();
+        assert_eq!(error.code(), "no_matching_key");
+    }
+
+    #[test]
+    fn expiry_stops_the_search() {
+        let ring = keyring(&[(None, b"first"), (None, b"second")]);
+        let expired = envelope(b"second", None, Some(1));
+        assert_eq!(ring.open(&expired, b"ctx", false).unwrap_err().kind(), Kind::Expired);
+        assert_eq!(ring.open(&expired, b"ctx", true).unwrap().as_slice(), b"secret");
+        let labelled = envelope(b"second", Some("b"), Some(1));
+        let ring = keyring(&[(Some("a"), b"first"), (Some("b"), b"second")]);
+        assert_eq!(ring.open(&labelled, b"ctx", false).unwrap_err().kind(), Kind::Expired);
+        assert_eq!(ring.open(&labelled, b"ctx", true).unwrap().as_slice(), b"secret");
+    }
+}
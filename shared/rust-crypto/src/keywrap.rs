@@ -0,0 +1,76 @@
+use pyo3::prelude::*;
+use aes_kw::{KekAes128, KekAes192, KekAes256};
+
+use crate::audit::Operation;
+use crate::error::Error;
+
+/// Key-encryption key of any AES size.
+pub(crate) enum Kek {
+    Aes128(KekAes128),
+    Aes192(KekAes192),
+    Aes256(KekAes256),
+}
+
+impl Kek {
+    pub(crate) fn new(kek: &[u8]) -> Result<Self, Error> {
+        let invalid = |_| Error::config("KEK must be 16, 24 or 32 bytes");
+        match kek.len() {
+            16 => KekAes128::try_from(kek).map(Kek::Aes128).map_err(invalid),
+            24 => KekAes192::try_from(kek).map(Kek::Aes192).map_err(invalid),
+            _ => KekAes256::try_from(kek).map(Kek::Aes256).map_err(invalid),
+        }
+    }
+
+    pub(crate) fn wrap(&self, key: &[u8], padding: bool) -> Result<Vec<u8>, aes_kw::Error> {
+        match (self, padding) {
+            (Kek::Aes128(kek), false) => kek.wrap_vec(key),
+            (Kek::Aes192(kek), false) => kek.wrap_vec(key),
+            (Kek::Aes256(kek), false) => kek.wrap_vec(key),
+            (Kek::Aes128(kek), true) => kek.wrap_with_padding_vec(key),
+            (Kek::Aes192(kek), true) => kek.wrap_with_padding_vec(key),
+            (Kek::Aes256(kek), true) => kek.wrap_with_padding_vec(key),
+        }
+    }
+
+    pub(crate) fn unwrap(&self, wrapped: &[u8], padding: bool) -> Result<Vec<u8>, aes_kw::Error> {
+        match (self, padding) {
+            (Kek::Aes128(kek), false) => kek.unwrap_vec(wrapped),
+            (Kek::Aes192(kek), false) => kek.unwrap_vec(wrapped),
+            (Kek::Aes256(kek), false) => kek.unwrap_vec(wrapped),
+            (Kek::Aes128(kek), true) => kek.unwrap_with_padding_vec(wrapped),
+            (Kek::Aes192(kek), true) => kek.unwrap_with_padding_vec(wrapped),
+            (Kek::Aes256(kek), true) => kek.unwrap_with_padding_vec(wrapped),
+        }
+    }
+}
+
+fn algorithm_name(padding: bool) -> &'static str {
+    if padding { "aes-kwp" } else { "aes-kw" }
+}
+
+/// Wraps `key` under `kek` with AES Key Wrap (RFC 3394). Keys that are not a
+/// multiple of 8 bytes (or shorter than 16) need `padding=True`, i.e. AES-KWP (RFC 5649).
+/// The output is 8 bytes longer than the (padded) key.
+#[pyfunction]
+#[pyo3(signature = (kek, key, padding=false))]
+pub fn wrap_key(kek: &[u8], key: &[u8], padding: bool) -> PyResult<Vec<u8>> {
+    let kek = Kek::new(kek).map_err(crate::error::to_pyerr)?;
+    let operation = Operation::new("encrypt", algorithm_name(padding));
+    operation.run(|| kek.wrap(key, padding).map_err(|e| match e {
+        aes_kw::Error::InvalidDataSize => Error::config("Key length must be a multiple of 8 bytes and at least 16 bytes; use padding=True")
+            .with_code("invalid_length"),
+        e => Error::crypto(format!("Key wrap failed: {}", e)),
+    })).map_err(crate::error::to_pyerr)
+}
+
+/// Reverses `wrap_key`; `padding` must match. Fails if the integrity check does not pass.
+#[pyfunction]
+#[pyo3(signature = (kek, wrapped_key, padding=false))]
+pub fn unwrap_key(kek: &[u8], wrapped_key: &[u8], padding: bool) -> PyResult<Vec<u8>> {
+    let kek = Kek::new(kek).map_err(crate::error::to_pyerr)?;
+    let operation = Operation::new("decrypt", algorithm_name(padding));
+    operation.run(|| {
+        kek.unwrap(wrapped_key, padding)
+            .map_err(|_| Error::decryption("Key unwrap failed: Integrity check failed or wrong KEK"))
+    }).map_err(crate::error::to_pyerr)
+}
@@ -1,124 +1,515 @@
 use pyo3::prelude::*;
 use pyo3::types::PyDict;
 // HAPUS import pyo3::PyObject yang bikin error
-use aes_gcm::{
-    aead::{Aead, KeyInit, Payload},
-    Aes256Gcm, Nonce, Key
-};
-use argon2::{
-    password_hash::{
-        rand_core::OsRng,
-        PasswordHasher // Trait ini WAJIB ada biar hash_password_into jalan
-    },
-    Argon2
-};
+use argon2::password_hash::rand_core::OsRng;
 use rand::RngCore;
-use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
-
-/// Derives a 32-byte key from the master key and a salt using Argon2id.
-fn derive_key(master_key: &str, salt: &[u8]) -> Result<[u8; 32], String> {
-    let argon2 = Argon2::default();
-    let mut output_key_material = [0u8; 32];
-    
-    // Method ini butuh trait PasswordHasher di-import
-    argon2.hash_password_into(
-        master_key.as_bytes(),
-        salt,
-        &mut output_key_material
-    ).map_err(|e| format!("Key derivation failed: {}", e))?;
-
-    Ok(output_key_material)
+
+mod aad;
+mod age;
+mod apikey;
+mod asyncio;
+mod audit;
+mod batch;
+mod branca;
+mod buffer;
+mod cache;
+mod cipher;
+mod compression;
+mod context;
+mod datakey;
+mod deterministic;
+mod document;
+mod encoding;
+mod envelope;
+mod error;
+mod esdk;
+mod fernet;
+mod fields;
+mod file;
+mod fips;
+mod format;
+mod fpe;
+mod hash;
+mod inplace;
+mod json;
+mod jwe;
+mod jwt;
+mod kdf;
+mod keyring;
+mod keywrap;
+mod locked;
+mod logging;
+mod mac;
+mod mask;
+mod master_key;
+mod metrics;
+mod multi;
+mod nonce;
+mod openssl_enc;
+mod otp;
+mod padding;
+mod paseto;
+mod password;
+mod progress;
+mod provider;
+mod pubkey;
+mod random;
+mod selftest;
+mod session;
+mod shamir;
+mod shred;
+mod sign;
+mod sodium;
+mod stream;
+mod strength;
+mod threshold;
+mod throttle;
+mod tink;
+mod tokenize;
+
+use aad::AadArg;
+use buffer::BytesLike;
+use cipher::{Algorithm, TAG_LEN};
+use encoding::{Encoded, Encoding};
+use kdf::KdfParams;
+use master_key::MasterKeyArg;
+
+use crate::error::Error;
+
+/// Output of a single AEAD encryption: the ciphertext plus the salt and nonce needed to open it.
+struct Sealed {
+    algorithm: Algorithm,
+    ciphertext: Vec<u8>,
+    salt: [u8; 16],
+    nonce: Vec<u8>,
+    kdf_params: KdfParams,
 }
 
-#[pyfunction]
-fn encrypt_with_context(
-    py: Python<'_>,
-    plaintext: &str,
-    master_key: &str,
-    aad_context: &str 
-) -> PyResult<Py<PyDict>> { // FIX: Return type spesifik Py<PyDict>, bukan PyObject
-    
+/// Encrypts `plaintext` under a fresh salt/nonce, binding `aad` as associated data.
+fn seal(
+    algorithm: Algorithm,
+    kdf_params: KdfParams,
+    plaintext: &[u8],
+    master_key: &[u8],
+    aad: &[u8]
+) -> Result<Sealed, Error> {
     // 1. Generate Random Salt
     let mut salt = [0u8; 16];
     OsRng.fill_bytes(&mut salt);
 
-    // 2. Generate Random Nonce
-    let mut nonce_bytes = [0u8; 12];
-    OsRng.fill_bytes(&mut nonce_bytes);
-    let nonce = Nonce::from_slice(&nonce_bytes);
+    // 2. Generate Random Nonce (length depends on the cipher)
+    let mut nonce = vec![0u8; algorithm.nonce_len()];
+    OsRng.fill_bytes(&mut nonce);
 
     // 3. Derive Session Key
-    let key_bytes = derive_key(master_key, &salt).map_err(pyo3::exceptions::PyValueError::new_err)?;
-    let key = Key::<Aes256Gcm>::from_slice(&key_bytes);
+    crate::logging::debug(|| format!("Encrypting with {} and {}", algorithm.name(), kdf_params.name()));
+    let key_bytes = kdf_params.derive(master_key, &salt)?;
 
-    // 4. Initialize Cipher
-    let cipher = Aes256Gcm::new(key);
+    // 4. Encrypt with AAD
+    let operation = audit::Operation::new("encrypt", algorithm.name()).with_aad(aad);
+    let ciphertext = operation.run(|| algorithm.encrypt(&key_bytes, &nonce, plaintext, aad))?;
 
-    // 5. Encrypt with AAD
-    let payload = Payload {
-        msg: plaintext.as_bytes(),
-        aad: aad_context.as_bytes(),
-    };
+    Ok(Sealed { algorithm, ciphertext, salt, nonce, kdf_params })
+}
 
-    let ciphertext = cipher.encrypt(nonce, payload)
-        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Encryption failed: {}", e)))?;
+/// Decodes the base64 fields produced by `seal` and decrypts them, verifying `aad`.
+/// `tag` is the tag when it was stored apart from the ciphertext.
+#[allow(clippy::too_many_arguments)]
+fn open(
+    algorithm: Algorithm,
+    kdf_params: KdfParams,
+    encoding: Encoding,
+    ciphertext: &Encoded,
+    salt: &Encoded,
+    nonce: &Encoded,
+    tag: Option<&Encoded>,
+    master_key: &[u8],
+    aad: &[u8]
+) -> Result<Vec<u8>, Error> {
+    // 1. Decode
+    let mut ciphertext = encoding.decode(ciphertext, "ciphertext")?;
+    if let Some(tag) = tag {
+        let tag = encoding.decode_secret(tag, "tag")?;
+        if tag.len() != TAG_LEN {
+            return Err(crate::error::wrong_length("tag", TAG_LEN, tag.len()));
+        }
+        ciphertext.extend_from_slice(&tag);
+    }
+    let salt = encoding.decode(salt, "salt")?;
+    let nonce = encoding.decode(nonce, "nonce")?;
 
-    // 6. Return Dictionary
+    if nonce.len() != algorithm.nonce_len() {
+        return Err(crate::error::wrong_length("nonce", algorithm.nonce_len(), nonce.len()));
+    }
+
+    // 2. Derive Key
+    crate::logging::debug(|| format!("Decrypting with {} and {}", algorithm.name(), kdf_params.name()));
+    let key_bytes = kdf_params.derive(master_key, &salt)?;
+
+    // 3. Decrypt
+    let operation = audit::Operation::new("decrypt", algorithm.name()).with_aad(aad);
+    operation.run(|| algorithm.decrypt(&key_bytes, &nonce, &ciphertext, aad))
+}
+
+/// Packs a `Sealed` result into the `{ciphertext, salt, nonce, kdf_params, kdf_profile,
+/// version, algorithm, encoding}` dict returned to Python.
+/// With `detached_tag` the tag is split off the ciphertext into its own `tag` entry.
+fn sealed_to_dict(
+    py: Python<'_>,
+    sealed: &Sealed,
+    kdf_profile: Option<&str>,
+    detached_tag: bool,
+    encoding: Encoding
+) -> PyResult<Py<PyDict>> {
     // Di PyO3 0.28, PyDict::new(py) returnnya Bound<'py, PyDict>
     let result = PyDict::new(py);
-    result.set_item("ciphertext", BASE64.encode(ciphertext))?;
-    result.set_item("salt", BASE64.encode(salt))?;
-    result.set_item("nonce", BASE64.encode(nonce_bytes))?;
+    if detached_tag {
+        let (ciphertext, tag) = sealed.ciphertext.split_at(sealed.ciphertext.len() - TAG_LEN);
+        result.set_item("ciphertext", encoding.encode(py, ciphertext))?;
+        result.set_item("tag", encoding.encode(py, tag))?;
+    } else {
+        result.set_item("ciphertext", encoding.encode(py, &sealed.ciphertext))?;
+    }
+    result.set_item("salt", encoding.encode(py, &sealed.salt))?;
+    result.set_item("nonce", encoding.encode(py, &sealed.nonce))?;
+    result.set_item("kdf_params", sealed.kdf_params.encode())?;
+    result.set_item("kdf_profile", kdf_profile)?;
+    result.set_item("version", format::DICT_VERSION)?;
+    result.set_item("algorithm", sealed.algorithm.name())?;
+    result.set_item("encoding", encoding.name())?;
 
     // .unbind() mengubah Bound<'_, PyDict> menjadi Py<PyDict> (detached object)
     // Ini solusi paling bersih untuk return value di versi baru
     Ok(result.unbind())
 }
 
-#[pyfunction]
-fn decrypt_with_context(
-    ciphertext_b64: &str,
-    salt_b64: &str,
-    nonce_b64: &str,
-    master_key: &str,
-    aad_context: &str
-) -> PyResult<String> {
-    // 1. Decode Base64
-    let ciphertext = BASE64.decode(ciphertext_b64)
-        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid base64 ciphertext: {}", e)))?;
-    let salt = BASE64.decode(salt_b64)
-        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid base64 salt: {}", e)))?;
-    let nonce_bytes = BASE64.decode(nonce_b64)
-        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid base64 nonce: {}", e)))?;
-
-    if nonce_bytes.len() != 12 {
-        return Err(pyo3::exceptions::PyValueError::new_err("Invalid nonce length"));
+/// Resolves the encrypt-side KDF arguments: an encoded `kdf_params` string (which can
+/// select a KDF other than Argon2id, e.g. `pbkdf2-sha256:i=600000`), a named profile, or
+/// explicit Argon2id costs. Only one of the three may be given.
+fn resolve_kdf_params(
+    kdf_params: Option<&str>,
+    kdf_profile: Option<&str>,
+    memory_cost: Option<u32>,
+    time_cost: Option<u32>,
+    parallelism: Option<u32>
+) -> PyResult<(KdfParams, Option<&'static str>)> {
+    let costs_given = memory_cost.is_some() || time_cost.is_some() || parallelism.is_some();
+    if kdf_params.is_some() && (kdf_profile.is_some() || costs_given) {
+        return Err(crate::error::to_pyerr(
+            Error::crypto("kdf_params cannot be combined with kdf_profile or memory_cost/time_cost/parallelism")
+        ));
+    }
+    if let Some(encoded) = kdf_params {
+        return parse_kdf_params(Some(encoded)).map(|params| (params, None));
     }
-    let nonce = Nonce::from_slice(&nonce_bytes);
-
-    // 2. Derive Key
-    let key_bytes = derive_key(master_key, &salt).map_err(pyo3::exceptions::PyValueError::new_err)?;
-    let key = Key::<Aes256Gcm>::from_slice(&key_bytes);
 
-    // 3. Decrypt
-    let cipher = Aes256Gcm::new(key);
-    let payload = Payload {
-        msg: &ciphertext,
-        aad: aad_context.as_bytes(),
+    let resolved = match kdf_profile {
+        Some(_) if costs_given => {
+            Err(Error::crypto("kdf_profile cannot be combined with memory_cost/time_cost/parallelism"))
+        }
+        Some(name) => KdfParams::from_profile(name).map(|(profile, params)| (params, Some(profile))),
+        None => KdfParams::new(memory_cost, time_cost, parallelism).map(|params| (params, None)),
     };
+    resolved.map_err(crate::error::to_pyerr)
+}
 
-    let plaintext_bytes = cipher.decrypt(nonce, payload)
-        .map_err(|_| pyo3::exceptions::PyValueError::new_err("Decryption failed: Integrity check failed or context mismatch"))?;
+/// Parses the `kdf_params` string recorded at encrypt time; `None` means the defaults,
+/// which is what outputs created before the parameters were recorded used.
+fn parse_kdf_params(kdf_params: Option<&str>) -> PyResult<KdfParams> {
+    kdf_params.map_or(Ok(KdfParams::default()), KdfParams::parse)
+        .map_err(crate::error::to_pyerr)
+}
+
+/// Encrypts `plaintext` and returns the base64 fields needed to decrypt it. With
+/// `detached_tag=True` the 16-byte authentication tag is returned as `tag` instead
+/// of being appended to `ciphertext`, for record layouts that store it on its own;
+/// pass it back as `tag_b64` to decrypt. `encoding="raw"` returns the binary fields
+/// as `bytes` instead; decrypt then takes them as bytes with the same `encoding`.
+/// The dict also records its format `version`, `algorithm` and `encoding`, so
+/// `decrypt_any` can decrypt it as a whole.
+#[pyfunction]
+#[pyo3(signature = (
+    plaintext, master_key, aad_context, algorithm=Algorithm::DEFAULT_NAME,
+    memory_cost=None, time_cost=None, parallelism=None, kdf_profile=None, kdf_params=None,
+    detached_tag=false, encoding=Encoding::DEFAULT_NAME
+))]
+#[allow(clippy::too_many_arguments)]
+fn encrypt_with_context(
+    py: Python<'_>,
+    plaintext: &str,
+    master_key: MasterKeyArg,
+    aad_context: AadArg,
+    algorithm: &str,
+    memory_cost: Option<u32>,
+    time_cost: Option<u32>,
+    parallelism: Option<u32>,
+    kdf_profile: Option<&str>,
+    kdf_params: Option<&str>,
+    detached_tag: bool,
+    encoding: &str
+) -> PyResult<Py<PyDict>> { // FIX: Return type spesifik Py<PyDict>, bukan PyObject
+    let algorithm = Algorithm::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+    let encoding = Encoding::from_name(encoding).map_err(crate::error::to_pyerr)?;
+    let (kdf_params, kdf_profile) = resolve_kdf_params(kdf_params, kdf_profile, memory_cost, time_cost, parallelism)?;
+    // Argon2 + AES run without the GIL; only the dict building below needs it
+    let sealed = py.detach(|| seal(algorithm, kdf_params, plaintext.as_bytes(), &master_key, &aad_context))
+        .map_err(crate::error::to_pyerr)?;
+    sealed_to_dict(py, &sealed, kdf_profile, detached_tag, encoding)
+}
+
+#[pyfunction]
+#[pyo3(signature = (
+    ciphertext_b64, salt_b64, nonce_b64, master_key, aad_context, algorithm=Algorithm::DEFAULT_NAME,
+    kdf_params=None, tag_b64=None,
+    encoding=Encoding::DEFAULT_NAME
+))]
+#[allow(clippy::too_many_arguments)]
+fn decrypt_with_context(
+    py: Python<'_>,
+    ciphertext_b64: Encoded,
+    salt_b64: Encoded,
+    nonce_b64: Encoded,
+    master_key: MasterKeyArg,
+    aad_context: AadArg,
+    algorithm: &str,
+    kdf_params: Option<&str>,
+    tag_b64: Option<Encoded>,
+    encoding: &str
+) -> PyResult<String> {
+    let algorithm = Algorithm::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+    let encoding = Encoding::from_name(encoding).map_err(crate::error::to_pyerr)?;
+    let kdf_params = parse_kdf_params(kdf_params)?;
+    let plaintext_bytes = py.detach(|| {
+        open(
+            algorithm, kdf_params, encoding, &ciphertext_b64, &salt_b64, &nonce_b64, tag_b64.as_ref(), &master_key, &aad_context
+        )
+    }).map_err(crate::error::to_pyerr)?;
 
     let plaintext = String::from_utf8(plaintext_bytes)
-        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid UTF-8: {}", e)))?;
+        .map_err(|e| crate::error::to_pyerr(Error::invalid_format(format!("Invalid UTF-8: {}", e))))?;
 
     Ok(plaintext)
 }
 
+/// Same as `encrypt_with_context`, but takes arbitrary binary plaintext (`bytes`).
+#[pyfunction]
+#[pyo3(signature = (
+    plaintext, master_key, aad_context, algorithm=Algorithm::DEFAULT_NAME,
+    memory_cost=None, time_cost=None, parallelism=None, kdf_profile=None, kdf_params=None,
+    detached_tag=false, encoding=Encoding::DEFAULT_NAME
+))]
+#[allow(clippy::too_many_arguments)]
+fn encrypt_bytes(
+    py: Python<'_>,
+    plaintext: BytesLike,
+    master_key: MasterKeyArg,
+    aad_context: AadArg,
+    algorithm: &str,
+    memory_cost: Option<u32>,
+    time_cost: Option<u32>,
+    parallelism: Option<u32>,
+    kdf_profile: Option<&str>,
+    kdf_params: Option<&str>,
+    detached_tag: bool,
+    encoding: &str
+) -> PyResult<Py<PyDict>> {
+    let algorithm = Algorithm::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+    let encoding = Encoding::from_name(encoding).map_err(crate::error::to_pyerr)?;
+    let (kdf_params, kdf_profile) = resolve_kdf_params(kdf_params, kdf_profile, memory_cost, time_cost, parallelism)?;
+    let sealed = py.detach(|| seal(algorithm, kdf_params, &plaintext, &master_key, &aad_context))
+        .map_err(crate::error::to_pyerr)?;
+    sealed_to_dict(py, &sealed, kdf_profile, detached_tag, encoding)
+}
+
+/// Same as `decrypt_with_context`, but returns the raw plaintext as `bytes` (no UTF-8 check).
+#[pyfunction]
+#[pyo3(signature = (
+    ciphertext_b64, salt_b64, nonce_b64, master_key, aad_context, algorithm=Algorithm::DEFAULT_NAME,
+    kdf_params=None, tag_b64=None,
+    encoding=Encoding::DEFAULT_NAME
+))]
+#[allow(clippy::too_many_arguments)]
+fn decrypt_bytes(
+    py: Python<'_>,
+    ciphertext_b64: Encoded,
+    salt_b64: Encoded,
+    nonce_b64: Encoded,
+    master_key: MasterKeyArg,
+    aad_context: AadArg,
+    algorithm: &str,
+    kdf_params: Option<&str>,
+    tag_b64: Option<Encoded>,
+    encoding: &str
+) -> PyResult<Vec<u8>> {
+    let algorithm = Algorithm::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+    let encoding = Encoding::from_name(encoding).map_err(crate::error::to_pyerr)?;
+    let kdf_params = parse_kdf_params(kdf_params)?;
+    py.detach(|| {
+        open(
+            algorithm, kdf_params, encoding, &ciphertext_b64, &salt_b64, &nonce_b64, tag_b64.as_ref(), &master_key, &aad_context
+        )
+    }).map_err(crate::error::to_pyerr)
+}
+
 #[pymodule]
 fn k_services_crypto(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    error::register(m)?;
     m.add_function(wrap_pyfunction!(encrypt_with_context, m)?)?;
     m.add_function(wrap_pyfunction!(decrypt_with_context, m)?)?;
-    Ok(())
+    m.add_function(wrap_pyfunction!(encrypt_bytes, m)?)?;
+    m.add_function(wrap_pyfunction!(decrypt_bytes, m)?)?;
+    m.add_function(wrap_pyfunction!(asyncio::encrypt_with_context_async, m)?)?;
+    m.add_function(wrap_pyfunction!(asyncio::decrypt_with_context_async, m)?)?;
+    m.add_class::<stream::StreamEncryptor>()?;
+    m.add_class::<stream::StreamDecryptor>()?;
+    m.add_function(wrap_pyfunction!(envelope::encrypt_to_token, m)?)?;
+    m.add_function(wrap_pyfunction!(envelope::decrypt_token, m)?)?;
+    m.add_function(wrap_pyfunction!(inplace::encrypt_into, m)?)?;
+    m.add_function(wrap_pyfunction!(inplace::decrypt_into, m)?)?;
+    m.add_function(wrap_pyfunction!(envelope::decrypt_token_with_metadata, m)?)?;
+    m.add_function(wrap_pyfunction!(envelope::inspect, m)?)?;
+    m.add_function(wrap_pyfunction!(envelope::re_encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(cipher::list_algorithms, m)?)?;
+    m.add_function(wrap_pyfunction!(format::detect_format, m)?)?;
+    m.add_function(wrap_pyfunction!(format::decrypt_any, m)?)?;
+    m.add_function(wrap_pyfunction!(format::is_encrypted, m)?)?;
+    m.add_class::<keyring::Keyring>()?;
+    m.add_class::<shred::SubjectKeyRegistry>()?;
+    m.add_class::<context::CryptoContext>()?;
+    m.add_class::<nonce::NonceSequence>()?;
+    m.add_class::<session::TokenIssuer>()?;
+    m.add_class::<master_key::MasterKey>()?;
+    m.add_function(wrap_pyfunction!(file::encrypt_file, m)?)?;
+    m.add_function(wrap_pyfunction!(file::decrypt_file, m)?)?;
+    m.add_function(wrap_pyfunction!(kdf::calibrate_kdf, m)?)?;
+    m.add_function(wrap_pyfunction!(kdf::hkdf_extract, m)?)?;
+    m.add_function(wrap_pyfunction!(kdf::hkdf_expand, m)?)?;
+    m.add_function(wrap_pyfunction!(mac::hmac_sign, m)?)?;
+    m.add_function(wrap_pyfunction!(mac::hmac_verify, m)?)?;
+    m.add_function(wrap_pyfunction!(mac::constant_time_compare, m)?)?;
+    m.add_function(wrap_pyfunction!(otp::generate_otp_secret, m)?)?;
+    m.add_function(wrap_pyfunction!(otp::hotp, m)?)?;
+    m.add_function(wrap_pyfunction!(otp::totp, m)?)?;
+    m.add_function(wrap_pyfunction!(otp::verify_hotp, m)?)?;
+    m.add_function(wrap_pyfunction!(otp::verify_totp, m)?)?;
+    m.add_function(wrap_pyfunction!(otp::otp_provisioning_uri, m)?)?;
+    m.add_function(wrap_pyfunction!(hash::digest, m)?)?;
+    m.add_function(wrap_pyfunction!(hash::hexdigest, m)?)?;
+    m.add_class::<hash::Hasher>()?;
+    m.add_function(wrap_pyfunction!(hash::blake3, m)?)?;
+    m.add_function(wrap_pyfunction!(hash::blake3_keyed, m)?)?;
+    m.add_function(wrap_pyfunction!(hash::blake3_derive_key, m)?)?;
+    m.add_function(wrap_pyfunction!(password::hash_password, m)?)?;
+    m.add_function(wrap_pyfunction!(password::verify_password, m)?)?;
+    m.add_function(wrap_pyfunction!(password::verify_legacy_bcrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(strength::estimate_password_strength, m)?)?;
+    m.add_function(wrap_pyfunction!(sign::ed25519_generate_keypair, m)?)?;
+    m.add_function(wrap_pyfunction!(sign::ed25519_public_key, m)?)?;
+    m.add_function(wrap_pyfunction!(sign::ed25519_sign, m)?)?;
+    m.add_function(wrap_pyfunction!(sign::ed25519_verify, m)?)?;
+    m.add_function(wrap_pyfunction!(pubkey::x25519_generate_keypair, m)?)?;
+    m.add_function(wrap_pyfunction!(pubkey::x25519_public_key, m)?)?;
+    m.add_function(wrap_pyfunction!(pubkey::sealed_box_encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(pubkey::sealed_box_decrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(pubkey::rsa_generate_keypair, m)?)?;
+    m.add_function(wrap_pyfunction!(pubkey::rsa_oaep_encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(pubkey::rsa_oaep_decrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(keywrap::wrap_key, m)?)?;
+    m.add_function(wrap_pyfunction!(keywrap::unwrap_key, m)?)?;
+    m.add_class::<provider::KeyProvider>()?;
+    #[cfg(feature = "aws-kms")]
+    m.add_class::<provider::aws::AwsKmsProvider>()?;
+    #[cfg(feature = "gcp-kms")]
+    m.add_class::<provider::gcp::GcpKmsProvider>()?;
+    #[cfg(feature = "azure-keyvault")]
+    m.add_class::<provider::azure::AzureKeyVaultProvider>()?;
+    #[cfg(feature = "vault")]
+    m.add_class::<provider::vault::VaultTransitProvider>()?;
+    #[cfg(feature = "pkcs11")]
+    m.add_class::<provider::pkcs11::Pkcs11Provider>()?;
+    m.add_function(wrap_pyfunction!(datakey::generate_data_key, m)?)?;
+    m.add_function(wrap_pyfunction!(datakey::decrypt_data_key, m)?)?;
+    m.add_function(wrap_pyfunction!(datakey::rewrap_data_key, m)?)?;
+    m.add_function(wrap_pyfunction!(datakey::encrypt_with_data_key, m)?)?;
+    m.add_function(wrap_pyfunction!(datakey::decrypt_with_data_key, m)?)?;
+    m.add_function(wrap_pyfunction!(multi::encrypt_for_recipients, m)?)?;
+    m.add_function(wrap_pyfunction!(multi::decrypt_for_recipient, m)?)?;
+    m.add_function(wrap_pyfunction!(shamir::split_secret, m)?)?;
+    m.add_function(wrap_pyfunction!(shamir::combine_shares, m)?)?;
+    m.add_function(wrap_pyfunction!(threshold::threshold_generate_key, m)?)?;
+    m.add_function(wrap_pyfunction!(threshold::threshold_encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(threshold::threshold_partial_decrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(threshold::threshold_combine, m)?)?;
+    m.add_function(wrap_pyfunction!(fernet::fernet_generate_key, m)?)?;
+    m.add_function(wrap_pyfunction!(fernet::fernet_encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(fernet::fernet_decrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(branca::branca_encode, m)?)?;
+    m.add_function(wrap_pyfunction!(branca::branca_decode, m)?)?;
+    m.add_function(wrap_pyfunction!(sodium::secretbox_encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(sodium::secretbox_decrypt, m)?)?;
+    m.add_class::<sodium::SecretStreamPush>()?;
+    m.add_class::<sodium::SecretStreamPull>()?;
+    m.add_function(wrap_pyfunction!(age::age_generate_identity, m)?)?;
+    m.add_function(wrap_pyfunction!(age::age_encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(age::age_decrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(openssl_enc::openssl_enc_encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(openssl_enc::openssl_enc_decrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(jwe::jwe_generate_keypair, m)?)?;
+    m.add_function(wrap_pyfunction!(jwe::jwe_encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(jwe::jwe_decrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(jwt::jwt_sign, m)?)?;
+    m.add_function(wrap_pyfunction!(jwt::jwt_verify, m)?)?;
+    m.add_function(wrap_pyfunction!(paseto::paseto_v4_local_encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(paseto::paseto_v4_local_decrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(paseto::paseto_v4_public_sign, m)?)?;
+    m.add_function(wrap_pyfunction!(paseto::paseto_v4_public_verify, m)?)?;
+    m.add_function(wrap_pyfunction!(paseto::paseto_footer, m)?)?;
+    m.add_class::<tink::TinkKeyset>()?;
+    m.add_class::<esdk::EsdkRawAesKeyring>()?;
+    m.add_function(wrap_pyfunction!(esdk::esdk_encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(esdk::esdk_decrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(fpe::ff1_encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(fpe::ff1_decrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(deterministic::encrypt_deterministic_leaks_equality, m)?)?;
+    m.add_function(wrap_pyfunction!(deterministic::encrypt_for_record, m)?)?;
+    m.add_function(wrap_pyfunction!(deterministic::blind_index, m)?)?;
+    m.add_function(wrap_pyfunction!(tokenize::tokenize, m)?)?;
+    m.add_function(wrap_pyfunction!(tokenize::detokenize, m)?)?;
+    m.add_function(wrap_pyfunction!(mask::decrypt_masked, m)?)?;
+    m.add_function(wrap_pyfunction!(fields::encrypt_fields, m)?)?;
+    m.add_function(wrap_pyfunction!(fields::decrypt_fields, m)?)?;
+    m.add_class::<document::EncryptionPolicy>()?;
+    m.add_function(wrap_pyfunction!(document::encrypt_document, m)?)?;
+    m.add_function(wrap_pyfunction!(document::decrypt_document, m)?)?;
+    m.add_function(wrap_pyfunction!(batch::encrypt_many, m)?)?;
+    m.add_function(wrap_pyfunction!(batch::decrypt_many, m)?)?;
+    m.add_class::<progress::CancellationToken>()?;
+    m.add("OperationCancelled", m.py().get_type::<progress::OperationCancelled>())?;
+    m.add_function(wrap_pyfunction!(random::token_bytes, m)?)?;
+    m.add_function(wrap_pyfunction!(random::token_hex, m)?)?;
+    m.add_function(wrap_pyfunction!(random::token_urlsafe, m)?)?;
+    m.add_function(wrap_pyfunction!(random::generate_passphrase, m)?)?;
+    m.add_function(wrap_pyfunction!(random::generate_password, m)?)?;
+    m.add_function(wrap_pyfunction!(random::uuid4, m)?)?;
+    m.add_function(wrap_pyfunction!(random::uuid7, m)?)?;
+    m.add_function(wrap_pyfunction!(random::uuid_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(apikey::generate_api_key, m)?)?;
+    m.add_function(wrap_pyfunction!(apikey::verify_api_key, m)?)?;
+    m.add_function(wrap_pyfunction!(apikey::is_valid_api_key, m)?)?;
+    m.add_function(wrap_pyfunction!(logging::enable_logging, m)?)?;
+    m.add_function(wrap_pyfunction!(logging::disable_logging, m)?)?;
+    m.add_function(wrap_pyfunction!(throttle::set_decrypt_failure_hook, m)?)?;
+    m.add_function(wrap_pyfunction!(audit::set_audit_callback, m)?)?;
+    m.add_function(wrap_pyfunction!(audit::verify_audit_chain, m)?)?;
+    m.add_function(wrap_pyfunction!(metrics::get_metrics, m)?)?;
+    m.add_function(wrap_pyfunction!(metrics::reset_metrics, m)?)?;
+    m.add_function(wrap_pyfunction!(fips::enable_fips_mode, m)?)?;
+    m.add_function(wrap_pyfunction!(fips::disable_fips_mode, m)?)?;
+    m.add_function(wrap_pyfunction!(fips::fips_mode, m)?)?;
+    m.add_function(wrap_pyfunction!(cache::enable_key_cache, m)?)?;
+    m.add_function(wrap_pyfunction!(cache::disable_key_cache, m)?)?;
+    m.add_function(wrap_pyfunction!(cache::clear_key_cache, m)?)?;
+    m.add_function(wrap_pyfunction!(cache::key_cache_info, m)?)?;
+    m.add_function(wrap_pyfunction!(selftest::self_test, m)?)?;
+    selftest::run_on_import()
 }
\ No newline at end of file
@@ -0,0 +1,66 @@
+use std::alloc::{self, Layout};
+use std::ops::Deref;
+use std::ptr::NonNull;
+use zeroize::Zeroize;
+
+/// Secret bytes in their own page-aligned allocation, locked into RAM (`mlock` /
+/// `VirtualLock`) so they can't be written to swap, and wiped before being freed.
+/// Each secret gets whole pages to itself: unlocking works per page, so sharing
+/// one with another secret would unpin it when either is dropped.
+///
+/// Locking is best effort. When the OS refuses (e.g. `RLIMIT_MEMLOCK` is
+/// exhausted, or the Windows working set is too small) the secret is still usable
+/// and still wiped, just not pinned; `is_locked` reports which.
+pub(crate) struct LockedBytes {
+    ptr: NonNull<u8>,
+    len: usize,
+    layout: Layout,
+    guard: Option<region::LockGuard>,
+}
+
+// SAFETY: the allocation is owned exclusively and never mutated after `new`.
+unsafe impl Send for LockedBytes {}
+unsafe impl Sync for LockedBytes {}
+
+impl LockedBytes {
+    /// Copies `secret` into a fresh locked allocation. The pages are locked before
+    /// the copy, so the secret never sits in them unpinned.
+    pub(crate) fn new(secret: &[u8]) -> Self {
+        let page = region::page::size();
+        let layout = Layout::from_size_align(secret.len().max(1).next_multiple_of(page), page)
+            .expect("page size is a power of two");
+        // SAFETY: the layout has a non-zero size
+        let ptr = NonNull::new(unsafe { alloc::alloc_zeroed(layout) })
+            .unwrap_or_else(|| alloc::handle_alloc_error(layout));
+        let guard = region::lock(ptr.as_ptr(), layout.size()).ok();
+        // SAFETY: the allocation holds at least `secret.len()` bytes and is fresh, so
+        // it can't overlap `secret`
+        unsafe { std::ptr::copy_nonoverlapping(secret.as_ptr(), ptr.as_ptr(), secret.len()) };
+        LockedBytes { ptr, len: secret.len(), layout, guard }
+    }
+
+    /// Whether the OS agreed to keep the secret out of swap.
+    pub(crate) fn is_locked(&self) -> bool {
+        self.guard.is_some()
+    }
+}
+
+impl Deref for LockedBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: `ptr` points to `layout.size() >= len` initialized bytes owned by self
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for LockedBytes {
+    fn drop(&mut self) {
+        // SAFETY: as for `deref`, and `&mut self` makes this the only reference
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.layout.size()) }.zeroize();
+        // Unlock only once wiped, and before the memory goes back to the allocator
+        self.guard.take();
+        // SAFETY: allocated in `new` with this layout
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) };
+    }
+}
@@ -0,0 +1,50 @@
+use pyo3::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const LOGGER_NAME: &str = "k_services_crypto";
+const DEBUG: u8 = 10;
+const INFO: u8 = 20;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Logs at DEBUG when `enable_logging()` is on. The message is built lazily, so a
+/// disabled hook costs one atomic load. Only metadata may be logged: never keys,
+/// passphrases, plaintext or AAD.
+pub(crate) fn debug(message: impl FnOnce() -> String) {
+    log(DEBUG, message)
+}
+
+/// Logs at INFO; same rules as `debug`.
+pub(crate) fn info(message: impl FnOnce() -> String) {
+    log(INFO, message)
+}
+
+/// Must not be called with a lock held that a GIL-holding thread might wait on.
+fn log(level: u8, message: impl FnOnce() -> String) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let message = message();
+    Python::attach(|py| {
+        // `logging` reports its own handler errors; a broken logging setup must not
+        // turn into a crypto failure, so anything else is dropped
+        let _ = py.import("logging")
+            .and_then(|logging| logging.call_method1("getLogger", (LOGGER_NAME,)))
+            .and_then(|logger| logger.call_method1("log", (level, message)));
+    });
+}
+
+/// Sends records to the `k_services_crypto` logger: key derivations (KDF and time
+/// taken) and key cache hits/misses at DEBUG, the algorithm and KDF of every envelope
+/// sealed or opened at DEBUG, and failures with their error code at INFO. Records
+/// carry metadata only; keys, passphrases, plaintext and AAD are never logged. Off by
+/// default, since each record briefly takes the GIL, also from worker threads.
+#[pyfunction]
+pub fn enable_logging() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+#[pyfunction]
+pub fn disable_logging() {
+    ENABLED.store(false, Ordering::Relaxed);
+}
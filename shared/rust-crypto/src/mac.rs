@@ -0,0 +1,56 @@
+use pyo3::prelude::*;
+use hmac::{digest::KeyInit, Hmac, Mac};
+use sha2::{Sha256, Sha512};
+use subtle::ConstantTimeEq;
+
+use crate::audit::Operation;
+use crate::hash::HashAlgorithm;
+
+fn hmac<M: Mac + KeyInit>(key: &[u8], message: &[u8]) -> M {
+    let mut mac = <M as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac
+}
+
+/// How `hmac_sign` and `hmac_verify` report themselves to the audit callback.
+fn operation(name: &'static str, algorithm: HashAlgorithm) -> Operation<'static> {
+    let algorithm = match algorithm {
+        HashAlgorithm::Sha256 => "hmac-sha256",
+        HashAlgorithm::Sha512 => "hmac-sha512",
+    };
+    Operation::new(name, algorithm)
+}
+
+/// Computes an HMAC tag (`algorithm` is `sha256` or `sha512`) over `message`.
+#[pyfunction]
+#[pyo3(signature = (key, message, algorithm=HashAlgorithm::DEFAULT_NAME))]
+pub fn hmac_sign(key: &[u8], message: &[u8], algorithm: &str) -> PyResult<Vec<u8>> {
+    let algorithm = HashAlgorithm::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+    operation("sign", algorithm).run(|| Ok(match algorithm {
+        HashAlgorithm::Sha256 => hmac::<Hmac<Sha256>>(key, message).finalize().into_bytes().to_vec(),
+        HashAlgorithm::Sha512 => hmac::<Hmac<Sha512>>(key, message).finalize().into_bytes().to_vec(),
+    })).map_err(crate::error::to_pyerr)
+}
+
+/// Checks `tag` against the HMAC of `message` in constant time. Returns `False`
+/// for a wrong or truncated tag rather than raising, e.g. for webhook signatures.
+#[pyfunction]
+#[pyo3(signature = (key, message, tag, algorithm=HashAlgorithm::DEFAULT_NAME))]
+pub fn hmac_verify(key: &[u8], message: &[u8], tag: &[u8], algorithm: &str) -> PyResult<bool> {
+    let algorithm = HashAlgorithm::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+    let valid = operation("verify", algorithm).run_checked(
+        || Ok(match algorithm {
+            HashAlgorithm::Sha256 => hmac::<Hmac<Sha256>>(key, message).verify_slice(tag).is_ok(),
+            HashAlgorithm::Sha512 => hmac::<Hmac<Sha512>>(key, message).verify_slice(tag).is_ok(),
+        }),
+        |valid| (!valid).then_some("invalid_signature"),
+    );
+    valid.map_err(crate::error::to_pyerr)
+}
+
+/// Compares two byte strings without short-circuiting on the first difference,
+/// for tokens, MACs and API keys. Only the lengths are compared in variable time.
+#[pyfunction]
+pub fn constant_time_compare(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
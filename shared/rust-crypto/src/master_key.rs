@@ -0,0 +1,153 @@
+use pyo3::prelude::*;
+use std::ops::Deref;
+use std::sync::Arc;
+use zeroize::Zeroizing;
+
+use crate::buffer::BytesLike;
+use crate::encoding::{Encoded, Encoding};
+use crate::locked::LockedBytes;
+
+/// Master key held in Rust memory. Can be passed as `master_key` to every API that
+/// takes one, instead of the raw value, and is never handed back to Python:
+/// there is no accessor and `repr()` is redacted, so it doesn't end up in logs.
+/// Built from a `str` (used as its UTF-8 bytes) or any bytes-like object, so
+/// binary keys need no text encoding. The key lives in locked memory (see
+/// `locked`), which APIs given a `MasterKey` read in place rather than copying.
+/// Every Rust-side copy is wiped when dropped; the object it was built from stays
+/// the caller's to clear (a `bytearray` can be zeroed after use, a `str` or
+/// `bytes` lingers until Python frees it).
+#[pyclass(frozen)]
+pub struct MasterKey {
+    secret: Arc<LockedBytes>,
+}
+
+impl MasterKey {
+    pub(crate) fn from_secret(secret: Zeroizing<Vec<u8>>) -> Self {
+        MasterKey { secret: Arc::new(LockedBytes::new(&secret)) }
+    }
+}
+
+#[pymethods]
+impl MasterKey {
+    #[new]
+    fn new(master_key: MasterKeyArg) -> Self {
+        match master_key {
+            MasterKeyArg::Owned(secret) => MasterKey { secret: Arc::new(LockedBytes::new(&secret)) },
+            MasterKeyArg::Shared(secret) => MasterKey { secret },
+        }
+    }
+
+    /// Builds the key from the bytes `encoded` spells in `encoding`, e.g. a base64 or
+    /// hex key from a secrets manager, decoded in constant time and without a Python
+    /// copy of the bytes. (`MasterKey(text)` uses the text itself as the key.)
+    #[staticmethod]
+    #[pyo3(signature = (encoded, encoding=Encoding::DEFAULT_NAME))]
+    fn from_encoded(encoded: Encoded, encoding: &str) -> PyResult<Self> {
+        let encoding = Encoding::from_name(encoding).map_err(crate::error::to_pyerr)?;
+        let secret = encoding.decode_secret(&encoded, "master key").map_err(crate::error::to_pyerr)?;
+        Ok(MasterKey { secret: Arc::new(LockedBytes::new(&secret)) })
+    }
+
+    /// Whether the key is pinned in RAM. False when the OS refused to lock it (e.g.
+    /// `RLIMIT_MEMLOCK` is too low); the key still works and is still wiped.
+    #[getter]
+    fn locked(&self) -> bool {
+        self.secret.is_locked()
+    }
+
+    /// Loads the master key from the OS credential store (macOS Keychain, Windows
+    /// Credential Manager, or the Secret Service on Linux) without it ever becoming
+    /// a Python object.
+    #[cfg(feature = "os-keyring")]
+    #[staticmethod]
+    fn from_os_keyring(py: Python<'_>, service: &str, account: &str) -> PyResult<Self> {
+        let secret = py.detach(|| {
+            keyring::Entry::new(service, account).and_then(|entry| entry.get_password())
+        }).map_err(|e| match e {
+            keyring::Error::NoEntry => pyo3::exceptions::PyKeyError::new_err(
+                format!("No master key in the OS keyring for service {:?}, account {:?}", service, account)
+            ),
+            e => crate::error::to_pyerr(crate::error::Error::crypto(format!("OS keyring error: {}", e))),
+        })?;
+        Ok(MasterKey::from_secret(Zeroizing::new(secret.into_bytes())))
+    }
+
+    /// Derives the master key from a YubiKey's HMAC-SHA1 challenge-response slot, so
+    /// decrypting needs the token plugged in. The key is the lowercase hex response,
+    /// i.e. the same string `ykchalresp -<slot> -x <challenge hex>` prints. `serial`
+    /// picks the device when more than one is attached.
+    #[cfg(feature = "yubikey")]
+    #[staticmethod]
+    #[pyo3(signature = (challenge, slot=2, serial=None))]
+    fn from_yubikey(py: Python<'_>, challenge: &[u8], slot: usize, serial: Option<u32>) -> PyResult<Self> {
+        use challenge_response::config::{Config, Mode, Slot};
+        use challenge_response::error::ChallengeResponseError;
+        use challenge_response::ChallengeResponse;
+
+        use crate::error::Error;
+
+        if challenge.is_empty() || challenge.len() > 64 {
+            return Err(crate::error::to_pyerr(Error::config("challenge must be 1 to 64 bytes")));
+        }
+        let slot = Slot::from_int(slot)
+            .ok_or_else(|| crate::error::to_pyerr(Error::config("slot must be 1 or 2")))?;
+
+        let response = py.detach(|| -> Result<_, ChallengeResponseError> {
+            let mut client = ChallengeResponse::new()?;
+            let device = match serial {
+                Some(serial) => client.find_device_from_serial(serial)?,
+                None => client.find_device()?,
+            };
+            let config = Config::new_from(device)
+                .set_variable_size(true)
+                .set_mode(Mode::Sha1)
+                .set_slot(slot);
+            client.challenge_response_hmac(challenge, config)
+        }).map_err(|e| match e {
+            ChallengeResponseError::DeviceNotFound => crate::error::to_pyerr(Error::crypto("No YubiKey found")),
+            e => crate::error::to_pyerr(Error::crypto(format!("YubiKey challenge-response failed: {}", e))),
+        })?;
+        Ok(MasterKey::from_secret(Zeroizing::new(crate::hash::to_hex(&response).into_bytes())))
+    }
+
+    fn __repr__(&self) -> &'static str {
+        "MasterKey(<redacted>)"
+    }
+}
+
+/// `master_key` argument: a `str`, a bytes-like object or a `MasterKey`. A `str`
+/// key is its UTF-8 bytes, so it derives the same keys as before bytes were
+/// accepted.
+pub(crate) enum MasterKeyArg {
+    /// Copy of a `str` or bytes-like argument, wiped on drop.
+    Owned(Zeroizing<Vec<u8>>),
+    /// A `MasterKey`'s locked allocation, shared rather than copied.
+    Shared(Arc<LockedBytes>),
+}
+
+impl Deref for MasterKeyArg {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            MasterKeyArg::Owned(secret) => secret,
+            MasterKeyArg::Shared(secret) => secret,
+        }
+    }
+}
+
+impl<'a, 'py> FromPyObject<'a, 'py> for MasterKeyArg {
+    type Error = PyErr;
+
+    fn extract(obj: Borrowed<'a, 'py, PyAny>) -> PyResult<Self> {
+        if let Ok(key) = obj.cast::<MasterKey>() {
+            return Ok(MasterKeyArg::Shared(key.get().secret.clone()));
+        }
+        if let Ok(secret) = obj.extract::<String>() {
+            return Ok(MasterKeyArg::Owned(Zeroizing::new(secret.into_bytes())));
+        }
+        obj.extract::<BytesLike>()
+            .map(|secret| MasterKeyArg::Owned(Zeroizing::new(secret.to_vec())))
+            .map_err(|_| pyo3::exceptions::PyTypeError::new_err("master_key must be a str, bytes or MasterKey"))
+    }
+}
@@ -0,0 +1,109 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Bucket `i` counts durations under 2^i microseconds; the last one catches the rest
+/// (2^26 us is about 67 s).
+const BUCKETS: usize = 28;
+
+/// Latency histogram updated with relaxed atomics: a few uncontended increments per
+/// recorded call, no lock.
+pub(crate) struct Histogram {
+    count: AtomicU64,
+    total_nanos: AtomicU64,
+    buckets: [AtomicU64; BUCKETS],
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        Histogram { count: AtomicU64::new(0), total_nanos: AtomicU64::new(0), buckets: [const { AtomicU64::new(0) }; BUCKETS] }
+    }
+
+    pub(crate) fn record(&self, elapsed: Duration) {
+        let micros = elapsed.as_micros() as u64;
+        let bucket = (u64::BITS - micros.leading_zeros()) as usize;
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.total_nanos.fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+        self.buckets[bucket.min(BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn reset(&self) {
+        self.count.store(0, Ordering::Relaxed);
+        self.total_nanos.store(0, Ordering::Relaxed);
+        for bucket in &self.buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// `{count, total_seconds, mean_seconds, buckets}`, with `buckets` cumulative
+    /// `(le, count)` pairs as Prometheus expects them; the last `le` is infinity.
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let count = self.count.load(Ordering::Relaxed);
+        let total = self.total_nanos.load(Ordering::Relaxed) as f64 / 1e9;
+        let result = PyDict::new(py);
+        result.set_item("count", count)?;
+        result.set_item("total_seconds", total)?;
+        result.set_item("mean_seconds", if count == 0 { 0.0 } else { total / count as f64 })?;
+        let buckets = PyList::empty(py);
+        let mut cumulative = 0;
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let le = if i == BUCKETS - 1 { f64::INFINITY } else { (1u64 << i) as f64 / 1e6 };
+            buckets.append((le, cumulative))?;
+        }
+        result.set_item("buckets", buckets)?;
+        Ok(result)
+    }
+}
+
+pub(crate) static KDF: Histogram = Histogram::new();
+pub(crate) static ENCRYPT: Histogram = Histogram::new();
+pub(crate) static DECRYPT: Histogram = Histogram::new();
+pub(crate) static CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+pub(crate) static CACHE_MISSES: AtomicU64 = AtomicU64::new(0);
+/// Failures by error `code`; only touched on the error path.
+static FAILURES: Mutex<Option<HashMap<&'static str, u64>>> = Mutex::new(None);
+
+pub(crate) fn record_failure(code: &'static str) {
+    *FAILURES.lock().unwrap().get_or_insert_with(HashMap::new).entry(code).or_insert(0) += 1;
+}
+
+/// Counters and latency histograms collected since import (or `reset_metrics()`):
+/// `kdf` (every key derivation that ran, not cache hits), `encrypt` and `decrypt`
+/// (the AEAD call alone, for the `algorithm=` ciphers), `key_cache` (`hits`,
+/// `misses`, `hit_rate`) and `failures` (raised errors by `code`). Comparing `kdf`
+/// and cipher totals with wall-clock time measured in Python shows how much goes to
+/// Argon2, to encryption, and to everything else (argument conversion, encoding,
+/// the GIL).
+#[pyfunction]
+pub fn get_metrics(py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+    let result = PyDict::new(py);
+    result.set_item("kdf", KDF.to_dict(py)?)?;
+    result.set_item("encrypt", ENCRYPT.to_dict(py)?)?;
+    result.set_item("decrypt", DECRYPT.to_dict(py)?)?;
+    let (hits, misses) = (CACHE_HITS.load(Ordering::Relaxed), CACHE_MISSES.load(Ordering::Relaxed));
+    let cache = PyDict::new(py);
+    cache.set_item("hits", hits)?;
+    cache.set_item("misses", misses)?;
+    cache.set_item("hit_rate", if hits + misses == 0 { 0.0 } else { hits as f64 / (hits + misses) as f64 })?;
+    result.set_item("key_cache", cache)?;
+    let failures = PyDict::new(py);
+    for (code, count) in FAILURES.lock().unwrap().iter().flatten() {
+        failures.set_item(code, count)?;
+    }
+    result.set_item("failures", failures)?;
+    Ok(result)
+}
+
+#[pyfunction]
+pub fn reset_metrics() {
+    for histogram in [&KDF, &ENCRYPT, &DECRYPT] {
+        histogram.reset();
+    }
+    CACHE_HITS.store(0, Ordering::Relaxed);
+    CACHE_MISSES.store(0, Ordering::Relaxed);
+    *FAILURES.lock().unwrap() = None;
+}
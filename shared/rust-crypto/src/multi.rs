@@ -0,0 +1,241 @@
+use pyo3::prelude::*;
+use argon2::password_hash::rand_core::OsRng;
+use rand::RngCore;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL};
+
+use crate::aad::AadArg;
+use crate::audit::Operation;
+use crate::cipher::Algorithm;
+use crate::envelope::Reader;
+use crate::error::Error;
+use crate::kdf::KdfParams;
+use crate::pubkey::{public_key, secret_key};
+
+pub(crate) const MAGIC: &[u8; 3] = b"KSM";
+const VERSION: u8 = 1;
+
+const RECIPIENT_X25519: u8 = 1;
+const RECIPIENT_PASSPHRASE: u8 = 2;
+
+/// Content keys for passphrase recipients are always wrapped with AES-256-GCM.
+const WRAP_ALGORITHM: Algorithm = Algorithm::AES_256_GCM;
+
+/// One wrapped copy of the content key.
+///
+/// ```text
+/// x25519:     sealed box of the content key (80 bytes)
+/// passphrase: kdf u8 | kdf params | salt_len u8 | salt | nonce (12) | AES-256-GCM(content key)
+/// ```
+struct Stanza {
+    kind: u8,
+    body: Vec<u8>,
+}
+
+/// Ciphertext that any one of several recipients can open. A random content key
+/// encrypts the payload once; each recipient gets its own wrapped copy of that key.
+///
+/// Binary layout (all integers big-endian):
+///
+/// ```text
+/// magic "KSM" | version u8 | algorithm u8 | count u16
+/// | count * (type u8 | len u16 | stanza) | nonce | ciphertext || tag
+/// ```
+///
+/// Everything before the ciphertext is authenticated as AAD of the payload, so a
+/// recipient cannot be added or removed without invalidating the ciphertext.
+struct MultiEnvelope {
+    algorithm: Algorithm,
+    stanzas: Vec<Stanza>,
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+fn wrap_for_passphrase(content_key: &[u8; 32], passphrase: &str, kdf_params: KdfParams) -> Result<Vec<u8>, Error> {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let mut nonce = vec![0u8; WRAP_ALGORITHM.nonce_len()];
+    OsRng.fill_bytes(&mut nonce);
+    let kek = kdf_params.derive(passphrase.as_bytes(), &salt)?;
+
+    let mut body = Vec::new();
+    kdf_params.write(&mut body);
+    body.push(salt.len() as u8);
+    body.extend_from_slice(&salt);
+    body.extend_from_slice(&nonce);
+    body.extend_from_slice(&WRAP_ALGORITHM.encrypt(&kek, &nonce, content_key, &[])?);
+    Ok(body)
+}
+
+fn unwrap_with_passphrase(body: &[u8], passphrase: &str) -> Result<Vec<u8>, Error> {
+    let mut reader = Reader { data: body, pos: 0 };
+    let kdf_params = KdfParams::read(&mut reader)?;
+    let salt_len = reader.u8()? as usize;
+    let salt = reader.take(salt_len)?;
+    let nonce = reader.take(WRAP_ALGORITHM.nonce_len())?;
+    let kek = kdf_params.derive(passphrase.as_bytes(), salt)?;
+    WRAP_ALGORITHM.decrypt(&kek, nonce, &body[reader.pos..], &[])
+}
+
+impl MultiEnvelope {
+    fn header(&self) -> Result<Vec<u8>, Error> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.push(self.algorithm.envelope_id());
+        let count = u16::try_from(self.stanzas.len()).map_err(|_| Error::config("Too many recipients"))?;
+        out.extend_from_slice(&count.to_be_bytes());
+        for stanza in &self.stanzas {
+            let len = u16::try_from(stanza.body.len()).map_err(|_| Error::config("Recipient stanza too long"))?;
+            out.push(stanza.kind);
+            out.extend_from_slice(&len.to_be_bytes());
+            out.extend_from_slice(&stanza.body);
+        }
+        out.extend_from_slice(&self.nonce);
+        Ok(out)
+    }
+
+    fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader { data, pos: 0 };
+        if reader.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+            return Err(
+                Error::invalid_format("Invalid multi-recipient envelope: bad magic").with_code("unrecognized_format")
+            );
+        }
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(
+                Error::invalid_format(format!("Unsupported multi-recipient envelope version: {}", version))
+                    .with_code("unsupported_version")
+            );
+        }
+        let algorithm = Algorithm::from_envelope_id(reader.u8()?)?;
+
+        let count = reader.u16()?;
+        let mut stanzas = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let kind = reader.u8()?;
+            let len = reader.u16()? as usize;
+            stanzas.push(Stanza { kind, body: reader.take(len)?.to_vec() });
+        }
+
+        let nonce = reader.take(algorithm.nonce_len())?.to_vec();
+        let ciphertext = data[reader.pos..].to_vec();
+        Ok(MultiEnvelope { algorithm, stanzas, nonce, ciphertext })
+    }
+
+    fn seal(
+        algorithm: Algorithm,
+        public_keys: &[Vec<u8>],
+        passphrases: &[String],
+        kdf_params: KdfParams,
+        plaintext: &[u8],
+        aad_context: &[u8]
+    ) -> Result<Self, Error> {
+        if public_keys.is_empty() && passphrases.is_empty() {
+            return Err(Error::config("At least one recipient public key or passphrase is required"));
+        }
+
+        // 1. Random content key, wrapped once per recipient
+        let mut content_key = [0u8; 32];
+        OsRng.fill_bytes(&mut content_key);
+
+        let mut stanzas = Vec::with_capacity(public_keys.len() + passphrases.len());
+        for key in public_keys {
+            let body = public_key(key)?.seal(&mut OsRng, &content_key)
+                .map_err(|_| Error::crypto("Encryption failed"))?;
+            stanzas.push(Stanza { kind: RECIPIENT_X25519, body });
+        }
+        for passphrase in passphrases {
+            let body = wrap_for_passphrase(&content_key, passphrase, kdf_params)?;
+            stanzas.push(Stanza { kind: RECIPIENT_PASSPHRASE, body });
+        }
+
+        // 2. Encrypt the payload once, authenticating the full recipient list
+        let mut nonce = vec![0u8; algorithm.nonce_len()];
+        OsRng.fill_bytes(&mut nonce);
+        let mut envelope = MultiEnvelope { algorithm, stanzas, nonce, ciphertext: Vec::new() };
+        let mut aad = envelope.header()?;
+        aad.extend_from_slice(aad_context);
+        envelope.ciphertext = algorithm.encrypt(&content_key, &envelope.nonce, plaintext, &aad)?;
+        Ok(envelope)
+    }
+
+    /// Tries every stanza of the matching type until one yields a content key
+    /// that decrypts the payload.
+    fn open(&self, private_key: Option<&[u8]>, passphrase: Option<&str>, aad_context: &[u8]) -> Result<Vec<u8>, Error> {
+        let secret = private_key.map(secret_key).transpose()?;
+        let mut aad = self.header()?;
+        aad.extend_from_slice(aad_context);
+
+        for stanza in &self.stanzas {
+            let content_key = match (stanza.kind, &secret, passphrase) {
+                (RECIPIENT_X25519, Some(secret), _) => secret.unseal(&stanza.body).ok(),
+                (RECIPIENT_PASSPHRASE, _, Some(passphrase)) => unwrap_with_passphrase(&stanza.body, passphrase).ok(),
+                _ => None,
+            };
+            let Some(content_key) = content_key.and_then(|k| <[u8; 32]>::try_from(k).ok()) else {
+                continue;
+            };
+            if let Ok(plaintext) = self.algorithm.decrypt(&content_key, &self.nonce, &self.ciphertext, &aad) {
+                return Ok(plaintext);
+            }
+        }
+        Err(
+            Error::decryption("Decryption failed: no recipient entry matches the given key")
+                .with_code("no_matching_key")
+        )
+    }
+}
+
+/// Encrypts `plaintext` once for several recipients, returning a URL-safe token.
+/// Each entry of `public_keys` (X25519, see `x25519_generate_keypair`) and of
+/// `passphrases` can open it on its own. Passphrases are stretched with the
+/// `kdf_profile` / default Argon2id parameters.
+#[pyfunction]
+#[pyo3(signature = (
+    plaintext, aad_context, public_keys=Vec::new(), passphrases=Vec::new(),
+    algorithm=Algorithm::DEFAULT_NAME, kdf_profile=None
+))]
+pub fn encrypt_for_recipients(
+    py: Python<'_>,
+    plaintext: &[u8],
+    aad_context: AadArg,
+    public_keys: Vec<Vec<u8>>,
+    passphrases: Vec<String>,
+    algorithm: &str,
+    kdf_profile: Option<&str>
+) -> PyResult<String> {
+    let algorithm = Algorithm::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+    let (kdf_params, _) = crate::resolve_kdf_params(None, kdf_profile, None, None, None)?;
+
+    let operation = Operation::new("encrypt", "multi-recipient").with_aad(&aad_context);
+    py.detach(|| operation.run(|| {
+        let envelope = MultiEnvelope::seal(algorithm, &public_keys, &passphrases, kdf_params, plaintext, &aad_context)?;
+        let mut data = envelope.header()?;
+        data.extend_from_slice(&envelope.ciphertext);
+        Ok(BASE64_URL.encode(data))
+    })).map_err(crate::error::to_pyerr)
+}
+
+/// Opens a token from `encrypt_for_recipients` with one recipient's X25519
+/// `private_key` or one of the `passphrase`s.
+#[pyfunction]
+#[pyo3(signature = (token, aad_context, private_key=None, passphrase=None))]
+pub fn decrypt_for_recipient(
+    py: Python<'_>,
+    token: &str,
+    aad_context: AadArg,
+    private_key: Option<Vec<u8>>,
+    passphrase: Option<&str>
+) -> PyResult<Vec<u8>> {
+    if private_key.is_none() && passphrase.is_none() {
+        return Err(crate::error::to_pyerr(Error::config("private_key or passphrase is required")));
+    }
+
+    let operation = Operation::new("decrypt", "multi-recipient").with_aad(&aad_context);
+    py.detach(|| operation.run(|| {
+        let data = BASE64_URL.decode(token.trim())
+            .map_err(|e| Error::invalid_format(format!("Invalid token encoding: {}", e)))?;
+        MultiEnvelope::from_bytes(&data)?.open(private_key.as_deref(), passphrase, &aad_context)
+    })).map_err(crate::error::to_pyerr)
+}
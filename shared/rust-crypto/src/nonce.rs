@@ -0,0 +1,103 @@
+use pyo3::prelude::*;
+use pyo3::sync::MutexExt;
+use pyo3::types::PyBytes;
+use argon2::password_hash::rand_core::OsRng;
+use rand::RngCore;
+use std::sync::Mutex;
+
+use crate::error::Error;
+
+/// Nonces are the SP 800-38D deterministic construction: a 4-byte fixed field
+/// followed by a 64-bit invocation counter.
+pub(crate) const NONCE_LEN: usize = 12;
+const PREFIX_LEN: usize = 4;
+const DEFAULT_RESERVE: u64 = 1024;
+
+struct State {
+    /// Counter the next nonce gets.
+    counter: u64,
+    /// First counter not yet covered by a `persist` call.
+    reserved_until: u64,
+}
+
+/// Monotonically increasing 96-bit nonces, `prefix || counter`, for services that
+/// encrypt more under one key than random nonces allow (see `CryptoContext`).
+/// Counter nonces never repeat as long as the counter is never reused, so every
+/// sequence sharing a key needs its own `prefix` (random by default; random 4-byte
+/// prefixes are only safe for a few thousand sequences per key) and must resume
+/// from where it stopped.
+///
+/// `persist` makes that crash-safe: before handing out counters beyond what was
+/// last saved, the sequence calls `persist(counter)` with the value to resume from,
+/// reserving `reserve` nonces at a time. If the process dies, restart with
+/// `NonceSequence(prefix, counter)`; up to `reserve` unused nonces are skipped,
+/// none are repeated. A `persist` that raises stops the sequence from issuing.
+#[pyclass(frozen)]
+pub struct NonceSequence {
+    prefix: [u8; PREFIX_LEN],
+    persist: Option<Py<PyAny>>,
+    reserve: u64,
+    state: Mutex<State>,
+}
+
+impl NonceSequence {
+    pub(crate) fn next_nonce(&self, py: Python<'_>) -> PyResult<[u8; NONCE_LEN]> {
+        // Waits without the GIL: the holder may need it to call `persist`
+        let mut state = self.state.lock_py_attached(py).unwrap();
+        if state.counter == u64::MAX {
+            return Err(crate::error::to_pyerr(
+                Error::crypto("Nonce sequence exhausted: use a new key or prefix").with_code("key_usage_exceeded")
+            ));
+        }
+        if let Some(persist) = &self.persist {
+            if state.counter >= state.reserved_until {
+                let reserved_until = state.counter.saturating_add(self.reserve);
+                persist.call1(py, (reserved_until,))?;
+                state.reserved_until = reserved_until;
+            }
+        }
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce[..PREFIX_LEN].copy_from_slice(&self.prefix);
+        nonce[PREFIX_LEN..].copy_from_slice(&state.counter.to_be_bytes());
+        state.counter += 1;
+        Ok(nonce)
+    }
+}
+
+#[pymethods]
+impl NonceSequence {
+    /// `counter` is where to start, e.g. the last value given to `persist`.
+    #[new]
+    #[pyo3(signature = (prefix=None, counter=0, persist=None, reserve=DEFAULT_RESERVE))]
+    fn new(prefix: Option<&[u8]>, counter: u64, persist: Option<Py<PyAny>>, reserve: u64) -> PyResult<Self> {
+        let prefix = match prefix {
+            Some(prefix) => prefix.try_into()
+                .map_err(|_| crate::error::to_pyerr(crate::error::wrong_length("prefix", PREFIX_LEN, prefix.len())))?,
+            None => {
+                let mut prefix = [0u8; PREFIX_LEN];
+                OsRng.fill_bytes(&mut prefix);
+                prefix
+            }
+        };
+        if reserve == 0 {
+            return Err(crate::error::to_pyerr(Error::config("reserve must be at least 1").with_input("reserve")));
+        }
+        Ok(NonceSequence { prefix, persist, reserve, state: Mutex::new(State { counter, reserved_until: counter }) })
+    }
+
+    /// The next nonce: 12 bytes, never one this sequence issued before.
+    fn next<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        Ok(PyBytes::new(py, &self.next_nonce(py)?))
+    }
+
+    #[getter]
+    fn prefix<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.prefix)
+    }
+
+    /// Counter of the next nonce; with `prefix`, enough to resume after a clean shutdown.
+    #[getter]
+    fn counter(&self, py: Python<'_>) -> u64 {
+        self.state.lock_py_attached(py).unwrap().counter
+    }
+}
@@ -0,0 +1,237 @@
+use pyo3::prelude::*;
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::audit::Operation;
+use crate::error::Error;
+use crate::random::random_bytes;
+
+const MAGIC: &[u8; 8] = b"Salted__";
+const SALT_LEN: usize = 8;
+const IV_LEN: usize = 16;
+/// `openssl enc -pbkdf2` default when `-iter` is not given.
+const DEFAULT_ITERATIONS: u32 = 10_000;
+/// `openssl enc -a` wraps base64 at 64 columns.
+const ARMOR_LINE_LEN: usize = 64;
+
+#[derive(Clone, Copy)]
+enum CbcCipher {
+    Aes128,
+    Aes192,
+    Aes256,
+}
+
+impl CbcCipher {
+    fn from_name(name: &str) -> Result<Self, Error> {
+        match name.to_ascii_lowercase().as_str() {
+            "aes-128-cbc" => Ok(CbcCipher::Aes128),
+            "aes-192-cbc" => Ok(CbcCipher::Aes192),
+            "aes-256-cbc" => Ok(CbcCipher::Aes256),
+            _ => Err(
+                Error::config(format!("Unsupported cipher: {} (expected aes-128-cbc, aes-192-cbc or aes-256-cbc)", name))
+            ),
+        }
+    }
+
+    fn key_len(self) -> usize {
+        match self {
+            CbcCipher::Aes128 => 16,
+            CbcCipher::Aes192 => 24,
+            CbcCipher::Aes256 => 32,
+        }
+    }
+
+    fn encrypt(self, key: &[u8], iv: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        match self {
+            CbcCipher::Aes128 => cbc::Encryptor::<aes::Aes128>::new(key.into(), iv.into())
+                .encrypt_padded_vec_mut::<Pkcs7>(plaintext),
+            CbcCipher::Aes192 => cbc::Encryptor::<aes::Aes192>::new(key.into(), iv.into())
+                .encrypt_padded_vec_mut::<Pkcs7>(plaintext),
+            CbcCipher::Aes256 => cbc::Encryptor::<aes::Aes256>::new(key.into(), iv.into())
+                .encrypt_padded_vec_mut::<Pkcs7>(plaintext),
+        }
+    }
+
+    fn decrypt(self, key: &[u8], iv: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, Error> {
+        match self {
+            CbcCipher::Aes128 => cbc::Decryptor::<aes::Aes128>::new(key.into(), iv.into())
+                .decrypt_padded_vec_mut::<Pkcs7>(ciphertext),
+            CbcCipher::Aes192 => cbc::Decryptor::<aes::Aes192>::new(key.into(), iv.into())
+                .decrypt_padded_vec_mut::<Pkcs7>(ciphertext),
+            CbcCipher::Aes256 => cbc::Decryptor::<aes::Aes256>::new(key.into(), iv.into())
+                .decrypt_padded_vec_mut::<Pkcs7>(ciphertext),
+        }.map_err(|_| Error::decryption("Decryption failed: bad passphrase or corrupted data")
+            .with_code("wrong_passphrase"))
+    }
+}
+
+/// `-md` digest, used by PBKDF2 or by the legacy `EVP_BytesToKey` derivation.
+#[derive(Clone, Copy)]
+enum EncDigest {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl EncDigest {
+    fn from_name(name: &str) -> Result<Self, Error> {
+        match name.to_ascii_lowercase().as_str() {
+            "sha1" => Ok(EncDigest::Sha1),
+            "sha256" => Ok(EncDigest::Sha256),
+            "sha512" => Ok(EncDigest::Sha512),
+            _ => Err(Error::config(format!("Unsupported digest: {} (expected sha1, sha256 or sha512)", name))),
+        }
+    }
+
+    fn hash(self, parts: &[&[u8]]) -> Vec<u8> {
+        fn run<D: Digest>(parts: &[&[u8]]) -> Vec<u8> {
+            let mut hasher = D::new();
+            for part in parts {
+                hasher.update(part);
+            }
+            hasher.finalize().to_vec()
+        }
+        match self {
+            EncDigest::Sha1 => run::<Sha1>(parts),
+            EncDigest::Sha256 => run::<Sha256>(parts),
+            EncDigest::Sha512 => run::<Sha512>(parts),
+        }
+    }
+}
+
+struct EncParams {
+    cipher: CbcCipher,
+    digest: EncDigest,
+    /// `None` is the legacy `EVP_BytesToKey` derivation (no `-pbkdf2`).
+    iterations: Option<u32>,
+}
+
+impl EncParams {
+    /// Key and IV, derived together the way `openssl enc` does.
+    fn key_iv(&self, passphrase: &str, salt: &[u8]) -> Vec<u8> {
+        let len = self.cipher.key_len() + IV_LEN;
+        let passphrase = passphrase.as_bytes();
+        match self.iterations {
+            Some(iterations) => {
+                let mut out = vec![0u8; len];
+                match self.digest {
+                    EncDigest::Sha1 => pbkdf2::pbkdf2_hmac::<Sha1>(passphrase, salt, iterations, &mut out),
+                    EncDigest::Sha256 => pbkdf2::pbkdf2_hmac::<Sha256>(passphrase, salt, iterations, &mut out),
+                    EncDigest::Sha512 => pbkdf2::pbkdf2_hmac::<Sha512>(passphrase, salt, iterations, &mut out),
+                }
+                out
+            }
+            None => {
+                // EVP_BytesToKey with count = 1: D_i = H(D_{i-1} || passphrase || salt)
+                let mut out = Vec::with_capacity(len + 64);
+                let mut block = Vec::new();
+                while out.len() < len {
+                    block = self.digest.hash(&[&block, passphrase, salt]);
+                    out.extend_from_slice(&block);
+                }
+                out.truncate(len);
+                out
+            }
+        }
+    }
+
+    fn parse(cipher: &str, md: &str, pbkdf2: bool, iterations: u32) -> Result<EncParams, Error> {
+        if pbkdf2 && iterations == 0 {
+            return Err(Error::config("iterations must be positive").with_input("iterations"));
+        }
+        Ok(EncParams {
+            cipher: CbcCipher::from_name(cipher)?,
+            digest: EncDigest::from_name(md)?,
+            iterations: pbkdf2.then_some(iterations),
+        })
+    }
+}
+
+fn armor(data: &[u8]) -> Vec<u8> {
+    let encoded = BASE64.encode(data);
+    let mut out = Vec::with_capacity(encoded.len() + encoded.len() / ARMOR_LINE_LEN + 1);
+    for line in encoded.as_bytes().chunks(ARMOR_LINE_LEN) {
+        out.extend_from_slice(line);
+        out.push(b'\n');
+    }
+    out
+}
+
+/// Binary input starts with `Salted__`; anything else is tried as `-a` base64.
+fn dearmor(data: &[u8]) -> Result<Vec<u8>, Error> {
+    if data.starts_with(MAGIC) {
+        return Ok(data.to_vec());
+    }
+    let compact: Vec<u8> = data.iter().copied().filter(|b| !b.is_ascii_whitespace()).collect();
+    BASE64.decode(compact)
+        .map_err(|_| Error::invalid_format("Not an openssl enc file: missing Salted__ header")
+            .with_code("unrecognized_format"))
+}
+
+/// Writes the format of `openssl enc -<cipher> -pbkdf2 -iter <iterations> -md <md>`:
+/// `Salted__ | salt (8) | CBC ciphertext`. Pass `pbkdf2=False` for the legacy
+/// derivation (no `-pbkdf2`), and `armor=True` for base64 output like `-a`.
+///
+/// The format has no integrity protection (`openssl enc` rejects AEAD ciphers such
+/// as GCM); use it for interchange only, not for data at rest.
+#[pyfunction]
+#[pyo3(signature = (
+    plaintext, passphrase, cipher="aes-256-cbc", iterations=DEFAULT_ITERATIONS, md="sha256", pbkdf2=true, armor=false
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn openssl_enc_encrypt(
+    py: Python<'_>,
+    plaintext: &[u8],
+    passphrase: &str,
+    cipher: &str,
+    iterations: u32,
+    md: &str,
+    pbkdf2: bool,
+    armor: bool
+) -> PyResult<Vec<u8>> {
+    let params = EncParams::parse(cipher, md, pbkdf2, iterations).map_err(crate::error::to_pyerr)?;
+    let operation = Operation::new("encrypt", "openssl-enc");
+    py.detach(|| operation.run(|| {
+        let salt = random_bytes(SALT_LEN);
+        let key_iv = params.key_iv(passphrase, &salt);
+        let (key, iv) = key_iv.split_at(params.cipher.key_len());
+
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&salt);
+        out.extend_from_slice(&params.cipher.encrypt(key, iv, plaintext));
+        Ok(if armor { self::armor(&out) } else { out })
+    })).map_err(crate::error::to_pyerr)
+}
+
+/// Reads `openssl enc` output, binary or base64 (`-a`). `cipher`, `iterations`, `md`
+/// and `pbkdf2` must match the options the file was written with: the format
+/// doesn't record them.
+#[pyfunction]
+#[pyo3(signature = (ciphertext, passphrase, cipher="aes-256-cbc", iterations=DEFAULT_ITERATIONS, md="sha256", pbkdf2=true))]
+pub fn openssl_enc_decrypt(
+    py: Python<'_>,
+    ciphertext: &[u8],
+    passphrase: &str,
+    cipher: &str,
+    iterations: u32,
+    md: &str,
+    pbkdf2: bool
+) -> PyResult<Vec<u8>> {
+    let params = EncParams::parse(cipher, md, pbkdf2, iterations).map_err(crate::error::to_pyerr)?;
+    let operation = Operation::new("decrypt", "openssl-enc");
+    py.detach(|| operation.run(|| {
+        let data = dearmor(ciphertext)?;
+        if data.len() < MAGIC.len() + SALT_LEN || !data.starts_with(MAGIC) {
+            return Err(
+                Error::invalid_format("Not an openssl enc file: missing Salted__ header")
+                    .with_code("unrecognized_format")
+            );
+        }
+        let (salt, body) = data[MAGIC.len()..].split_at(SALT_LEN);
+        let key_iv = params.key_iv(passphrase, salt);
+        let (key, iv) = key_iv.split_at(params.cipher.key_len());
+        params.cipher.decrypt(key, iv, body)
+    })).map_err(crate::error::to_pyerr)
+}
@@ -0,0 +1,317 @@
+use pyo3::prelude::*;
+use argon2::password_hash::rand_core::OsRng;
+use hmac::{digest::KeyInit, Hmac, Mac};
+use rand::RngCore;
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+use std::time::{SystemTime, UNIX_EPOCH};
+use subtle::{ConstantTimeEq, ConditionallySelectable};
+
+use crate::encoding::{Encoded, Encoding};
+use crate::error::Error;
+
+const DEFAULT_SECRET_LEN: usize = 20;
+const DEFAULT_DIGITS: u32 = 6;
+const DEFAULT_PERIOD: u64 = 30;
+
+/// HMAC hashes RFC 6238 allows. Authenticator apps mostly only support SHA-1.
+#[derive(Clone, Copy)]
+enum OtpHash {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl OtpHash {
+    const DEFAULT_NAME: &'static str = "sha1";
+
+    fn from_name(name: &str) -> Result<Self, Error> {
+        match name.to_ascii_lowercase().as_str() {
+            "sha1" | "sha-1" => {
+                crate::fips::reject("HMAC-SHA1")?;
+                Ok(OtpHash::Sha1)
+            }
+            "sha256" | "sha-256" => Ok(OtpHash::Sha256),
+            "sha512" | "sha-512" => Ok(OtpHash::Sha512),
+            _ => Err(
+                Error::config(format!("Unsupported hash algorithm: {} (expected sha1, sha256 or sha512)", name))
+                    .with_code("unsupported_hash")
+            ),
+        }
+    }
+
+    /// Name as provisioning URIs spell it.
+    fn uri_name(self) -> &'static str {
+        match self {
+            OtpHash::Sha1 => "SHA1",
+            OtpHash::Sha256 => "SHA256",
+            OtpHash::Sha512 => "SHA512",
+        }
+    }
+
+    fn mac(self, key: &[u8], counter: u64) -> Vec<u8> {
+        fn run<M: Mac + KeyInit>(key: &[u8], counter: u64) -> Vec<u8> {
+            let mut mac = <M as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+            mac.update(&counter.to_be_bytes());
+            mac.finalize().into_bytes().to_vec()
+        }
+        match self {
+            OtpHash::Sha1 => run::<Hmac<Sha1>>(key, counter),
+            OtpHash::Sha256 => run::<Hmac<Sha256>>(key, counter),
+            OtpHash::Sha512 => run::<Hmac<Sha512>>(key, counter),
+        }
+    }
+}
+
+/// Parameters shared by every function here, checked once.
+struct Otp {
+    key: zeroize::Zeroizing<Vec<u8>>,
+    hash: OtpHash,
+    digits: u32,
+}
+
+impl Otp {
+    /// `secret` is base32 text as authenticator apps show it (spaces, case and padding
+    /// don't matter), or the raw key as bytes.
+    fn new(secret: &Encoded, digits: u32, algorithm: &str) -> Result<Self, Error> {
+        if !(6..=8).contains(&digits) {
+            return Err(Error::config("digits must be 6, 7 or 8").with_input("digits"));
+        }
+        let key = match secret {
+            Encoded::Text(text) => Encoding::Base32.decode_secret_text(&text.replace([' ', '-'], ""), "secret")?,
+            Encoded::Bytes(bytes) => zeroize::Zeroizing::new(bytes.to_vec()),
+        };
+        if key.is_empty() {
+            return Err(Error::config("secret must not be empty").with_input("secret"));
+        }
+        Ok(Otp { key, hash: OtpHash::from_name(algorithm)?, digits })
+    }
+
+    /// RFC 4226 5.3: dynamic truncation of the HMAC, reduced to `digits` decimal digits.
+    fn code(&self, counter: u64) -> String {
+        let mac = self.hash.mac(&self.key, counter);
+        let offset = (mac[mac.len() - 1] & 0x0f) as usize;
+        let value = u32::from_be_bytes(mac[offset..offset + 4].try_into().unwrap()) & 0x7fff_ffff;
+        format!("{:0width$}", value % 10u32.pow(self.digits), width = self.digits as usize)
+    }
+
+    /// The counter in `counters` whose code is `code`, comparing every candidate in
+    /// constant time so the position of a match isn't leaked through timing.
+    fn find(&self, code: &str, counters: impl Iterator<Item = u64>) -> Option<u64> {
+        let code = code.replace(' ', "");
+        if code.len() != self.digits as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let (mut found, mut matched) = (subtle::Choice::from(0), 0u64);
+        for counter in counters {
+            let equal = self.code(counter).as_bytes().ct_eq(code.as_bytes());
+            matched.conditional_assign(&counter, equal & !found);
+            found |= equal;
+        }
+        bool::from(found).then_some(matched)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+fn check_period(period: u64) -> Result<(), Error> {
+    if period == 0 {
+        return Err(Error::config("period must be at least 1 second").with_input("period"));
+    }
+    Ok(())
+}
+
+fn time_step(time: Option<u64>, period: u64) -> Result<u64, Error> {
+    check_period(period)?;
+    Ok(time.unwrap_or_else(now) / period)
+}
+
+/// Random OTP secret of `length` bytes (20, i.e. 160 bits, as RFC 4226 recommends),
+/// as unpadded base32 for authenticator apps.
+#[pyfunction]
+#[pyo3(signature = (length=DEFAULT_SECRET_LEN))]
+pub fn generate_otp_secret(length: usize) -> PyResult<String> {
+    if length < 16 {
+        return Err(crate::error::to_pyerr(
+            Error::config("length must be at least 16 bytes (RFC 4226 requires 128 bits)").with_input("length")
+        ));
+    }
+    let mut secret = zeroize::Zeroizing::new(vec![0u8; length]);
+    OsRng.fill_bytes(&mut secret);
+    Ok(crate::encoding::base32_encode(&secret).trim_end_matches('=').to_string())
+}
+
+/// RFC 4226 HOTP code for `counter`.
+#[pyfunction]
+#[pyo3(signature = (secret, counter, digits=DEFAULT_DIGITS, algorithm=OtpHash::DEFAULT_NAME))]
+pub fn hotp(secret: Encoded, counter: u64, digits: u32, algorithm: &str) -> PyResult<String> {
+    let otp = Otp::new(&secret, digits, algorithm).map_err(crate::error::to_pyerr)?;
+    Ok(otp.code(counter))
+}
+
+/// RFC 6238 TOTP code for `time` (unix seconds, default now).
+#[pyfunction]
+#[pyo3(signature = (secret, time=None, period=DEFAULT_PERIOD, digits=DEFAULT_DIGITS, algorithm=OtpHash::DEFAULT_NAME))]
+pub fn totp(secret: Encoded, time: Option<u64>, period: u64, digits: u32, algorithm: &str) -> PyResult<String> {
+    let otp = Otp::new(&secret, digits, algorithm).map_err(crate::error::to_pyerr)?;
+    Ok(otp.code(time_step(time, period).map_err(crate::error::to_pyerr)?))
+}
+
+/// Checks an HOTP `code` against `counter` and the next `window` counters, for
+/// tokens pressed without logging in. Returns the matching counter, or `None`; store
+/// the match plus one as the next `counter` so a code can't be used twice.
+#[pyfunction]
+#[pyo3(signature = (secret, code, counter, window=0, digits=DEFAULT_DIGITS, algorithm=OtpHash::DEFAULT_NAME))]
+pub fn verify_hotp(secret: Encoded, code: &str, counter: u64, window: u64, digits: u32, algorithm: &str) -> PyResult<Option<u64>> {
+    let otp = Otp::new(&secret, digits, algorithm).map_err(crate::error::to_pyerr)?;
+    Ok(otp.find(code, (0..=window).map_while(|offset| counter.checked_add(offset))))
+}
+
+/// Checks a TOTP `code` for `time` (default now), accepting `window` steps of clock
+/// drift either way. Returns the matching time step, or `None`. Pass the last step
+/// a user logged in with as `after_step` to reject replays of that code (RFC 6238 5.2).
+#[pyfunction]
+#[pyo3(signature = (
+    secret, code, time=None, period=DEFAULT_PERIOD, window=1, digits=DEFAULT_DIGITS,
+    algorithm=OtpHash::DEFAULT_NAME, after_step=None
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn verify_totp(
+    secret: Encoded,
+    code: &str,
+    time: Option<u64>,
+    period: u64,
+    window: u64,
+    digits: u32,
+    algorithm: &str,
+    after_step: Option<u64>
+) -> PyResult<Option<u64>> {
+    let otp = Otp::new(&secret, digits, algorithm).map_err(crate::error::to_pyerr)?;
+    let step = time_step(time, period).map_err(crate::error::to_pyerr)?;
+    let first = step.saturating_sub(window).max(after_step.map_or(0, |after| after.saturating_add(1)));
+    Ok(otp.find(code, first..=step.saturating_add(window)))
+}
+
+/// Percent-encodes everything but RFC 3986 unreserved characters.
+fn uri_escape(text: &str) -> String {
+    text.bytes().map(|b| match b {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+        _ => format!("%{:02X}", b),
+    }).collect()
+}
+
+/// `otpauth://` URI for enrolling `secret` in an authenticator app (usually shown as
+/// a QR code). `counter` makes it an HOTP URI; otherwise it is TOTP with `period`.
+#[pyfunction]
+#[pyo3(signature = (
+    secret, account_name, issuer=None, counter=None, period=DEFAULT_PERIOD, digits=DEFAULT_DIGITS,
+    algorithm=OtpHash::DEFAULT_NAME
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn otp_provisioning_uri(
+    secret: Encoded,
+    account_name: &str,
+    issuer: Option<&str>,
+    counter: Option<u64>,
+    period: u64,
+    digits: u32,
+    algorithm: &str
+) -> PyResult<String> {
+    let otp = Otp::new(&secret, digits, algorithm).map_err(crate::error::to_pyerr)?;
+    check_period(period).map_err(crate::error::to_pyerr)?;
+    let secret = crate::encoding::base32_encode(&otp.key);
+    let label = match issuer {
+        Some(issuer) => format!("{}:{}", uri_escape(issuer), uri_escape(account_name)),
+        None => uri_escape(account_name),
+    };
+    let mut uri = format!(
+        "otpauth://{}/{}?secret={}",
+        if counter.is_some() { "hotp" } else { "totp" }, label, secret.trim_end_matches('=')
+    );
+    if let Some(issuer) = issuer {
+        uri.push_str(&format!("&issuer={}", uri_escape(issuer)));
+    }
+    uri.push_str(&format!("&algorithm={}&digits={}", otp.hash.uri_name(), otp.digits));
+    match counter {
+        Some(counter) => uri.push_str(&format!("&counter={}", counter)),
+        None => uri.push_str(&format!("&period={}", period)),
+    }
+    Ok(uri)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA1_KEY: &[u8] = b"12345678901234567890";
+    const SHA256_KEY: &[u8] = b"12345678901234567890123456789012";
+    const SHA512_KEY: &[u8] = b"1234567890123456789012345678901234567890123456789012345678901234";
+
+    fn secret(key: &[u8]) -> Encoded {
+        Encoded::Text(crate::encoding::base32_encode(key))
+    }
+
+    #[test]
+    fn rfc_4226_hotp_vectors() {
+        let codes = [
+            "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583", "399871", "520489",
+        ];
+        for (counter, code) in codes.iter().enumerate() {
+            assert_eq!(hotp(secret(SHA1_KEY), counter as u64, 6, "sha1").unwrap(), *code, "counter {}", counter);
+        }
+    }
+
+    #[test]
+    fn rfc_6238_totp_vectors() {
+        let vectors = [
+            (59, "94287082", "46119246", "90693936"),
+            (1111111109, "07081804", "68084774", "25091201"),
+            (1111111111, "14050471", "67062674", "99943326"),
+            (1234567890, "89005924", "91819424", "93441116"),
+            (2000000000, "69279037", "90698825", "38618901"),
+            (20000000000, "65353130", "77737706", "47863826"),
+        ];
+        for (time, sha1, sha256, sha512) in vectors {
+            assert_eq!(totp(secret(SHA1_KEY), Some(time), 30, 8, "sha1").unwrap(), sha1, "time {}", time);
+            assert_eq!(totp(secret(SHA256_KEY), Some(time), 30, 8, "sha256").unwrap(), sha256, "time {}", time);
+            assert_eq!(totp(secret(SHA512_KEY), Some(time), 30, 8, "SHA-512").unwrap(), sha512, "time {}", time);
+        }
+    }
+
+    #[test]
+    fn secret_text_is_lenient() {
+        let otp = Otp::new(&Encoded::Text("gezd gnbv-gy3t qojq gezd gnbv gy3t qojq".to_string()), 6, "sha1").unwrap();
+        assert_eq!(otp.key.as_slice(), SHA1_KEY);
+        assert!(Otp::new(&Encoded::Text("not base32!".to_string()), 6, "sha1").is_err());
+        assert!(Otp::new(&Encoded::Text(String::new()), 6, "sha1").is_err());
+        assert!(Otp::new(&secret(SHA1_KEY), 9, "sha1").is_err());
+        assert!(Otp::new(&secret(SHA1_KEY), 6, "md5").is_err());
+    }
+
+    #[test]
+    fn hotp_window() {
+        // Counter 3 is "969429"
+        assert_eq!(verify_hotp(secret(SHA1_KEY), "969429", 0, 5, 6, "sha1").unwrap(), Some(3));
+        assert_eq!(verify_hotp(secret(SHA1_KEY), "969 429", 3, 0, 6, "sha1").unwrap(), Some(3));
+        assert_eq!(verify_hotp(secret(SHA1_KEY), "969429", 0, 2, 6, "sha1").unwrap(), None);
+        assert_eq!(verify_hotp(secret(SHA1_KEY), "969429", 4, 5, 6, "sha1").unwrap(), None);
+        assert_eq!(verify_hotp(secret(SHA1_KEY), "96942", 3, 0, 6, "sha1").unwrap(), None);
+        assert_eq!(verify_hotp(secret(SHA1_KEY), "96942x", 3, 0, 6, "sha1").unwrap(), None);
+    }
+
+    #[test]
+    fn totp_drift_and_replay() {
+        // Step 37037036 (time 1111111109) is "07081804"
+        let verify = |time, window, after_step| {
+            verify_totp(secret(SHA1_KEY), "07081804", Some(time), 30, window, 8, "sha1", after_step).unwrap()
+        };
+        assert_eq!(verify(1111111109, 0, None), Some(37037036));
+        assert_eq!(verify(1111111109 + 30, 1, None), Some(37037036));
+        assert_eq!(verify(1111111109 - 30, 1, None), Some(37037036));
+        assert_eq!(verify(1111111109 + 60, 1, None), None);
+        assert_eq!(verify(1111111109, 1, Some(37037035)), Some(37037036));
+        assert_eq!(verify(1111111109, 1, Some(37037036)), None);
+    }
+}
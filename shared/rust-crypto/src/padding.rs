@@ -0,0 +1,156 @@
+use crate::error::Error;
+
+/// Scheme ids in the padding extension.
+const SCHEME_PADME: u8 = 1;
+const SCHEME_BLOCK: u8 = 2;
+
+pub(crate) const DEFAULT_BLOCK_SIZE: u32 = 256;
+
+/// Length-hiding padding applied before encryption, so the ciphertext length no
+/// longer reveals the exact plaintext length. The pad is `0x80` followed by zeros
+/// (ISO/IEC 7816-4), so it strips without storing the original length anywhere.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Padding {
+    /// PADMÉ: pads to a length with only its top bits set, leaking O(log log n)
+    /// bits of the length at no more than ~12% overhead.
+    Padme,
+    /// Pads to a multiple of the block size; hides lengths up to the block size fully.
+    Block(u32),
+}
+
+impl Padding {
+    pub(crate) fn from_name(name: &str, block_size: u32) -> Result<Self, Error> {
+        match name {
+            "padme" => Ok(Padding::Padme),
+            "block" if block_size == 0 => Err(
+                Error::config("padding_block_size must be at least 1").with_input("padding_block_size")
+            ),
+            "block" => Ok(Padding::Block(block_size)),
+            other => Err(
+                Error::config(format!("Unsupported padding: {} (expected padme or block)", other))
+                    .with_code("unsupported_padding")
+            ),
+        }
+    }
+
+    /// Extension value: `scheme u8`, plus `block_size u32` for block padding.
+    pub(crate) fn record(self) -> Vec<u8> {
+        match self {
+            Padding::Padme => vec![SCHEME_PADME],
+            Padding::Block(size) => [&[SCHEME_BLOCK][..], &size.to_be_bytes()].concat(),
+        }
+    }
+
+    pub(crate) fn from_record(record: &[u8]) -> Result<Self, Error> {
+        match record {
+            [SCHEME_PADME] => Ok(Padding::Padme),
+            [SCHEME_BLOCK, size @ ..] => <[u8; 4]>::try_from(size).ok()
+                .map(u32::from_be_bytes)
+                .filter(|&size| size > 0)
+                .map(Padding::Block)
+                .ok_or_else(|| Error::invalid_format("Invalid padding record")),
+            _ => Err(Error::config("Unsupported padding scheme").with_code("unsupported_padding")),
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Padding::Padme => "padme",
+            Padding::Block(_) => "block",
+        }
+    }
+
+    fn padded_len(self, len: usize) -> usize {
+        match self {
+            Padding::Padme if len < 2 => len,
+            Padding::Padme => {
+                let exponent = len.ilog2();
+                let low_bits = exponent - (exponent.ilog2() + 1);
+                let mask = (1usize << low_bits) - 1;
+                (len + mask) & !mask
+            }
+            Padding::Block(size) => len.next_multiple_of(size as usize),
+        }
+    }
+
+    /// Appends the pad to `data`.
+    pub(crate) fn pad(self, data: &mut Vec<u8>) {
+        let padded = self.padded_len(data.len() + 1);
+        data.push(0x80);
+        data.resize(padded, 0);
+    }
+}
+
+/// Length of `data` without its pad. Only ever called on authenticated plaintext.
+pub(crate) fn unpadded_len(data: &[u8]) -> Result<usize, Error> {
+    match data.iter().rposition(|&byte| byte != 0) {
+        Some(end) if data[end] == 0x80 => Ok(end),
+        _ => Err(Error::invalid_format("Invalid padding")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn padme_lengths() {
+        let lengths: Vec<usize> = [0, 1, 2, 3, 4, 5, 9, 100, 1000, 1025].iter()
+            .map(|&len| Padding::Padme.padded_len(len))
+            .collect();
+        assert_eq!(lengths, [0, 1, 2, 3, 4, 5, 10, 104, 1024, 1088]);
+        for len in 2..100_000 {
+            let padded = Padding::Padme.padded_len(len);
+            assert!(padded >= len && (padded - len) * 100 <= len * 12, "length {}", len);
+        }
+    }
+
+    #[test]
+    fn block_lengths() {
+        assert_eq!(Padding::Block(16).padded_len(1), 16);
+        assert_eq!(Padding::Block(16).padded_len(16), 16);
+        assert_eq!(Padding::Block(16).padded_len(17), 32);
+        assert_eq!(Padding::Block(1).padded_len(5), 5);
+    }
+
+    #[test]
+    fn pad_then_unpad_restores_the_length() {
+        for padding in [Padding::Padme, Padding::Block(1), Padding::Block(16)] {
+            for len in 0..300 {
+                // Trailing 0x80 and zero bytes look like a pad and must survive
+                let original: Vec<u8> = (0..len).map(|i| if i % 3 == 0 { 0x80 } else { 0 }).collect();
+                let mut data = original.clone();
+                padding.pad(&mut data);
+                assert!(data.len() > len, "{:?}, length {}", padding, len);
+                assert_eq!(unpadded_len(&data).unwrap(), len, "{:?}, length {}", padding, len);
+            }
+        }
+    }
+
+    #[test]
+    fn unpad_rejects_a_missing_marker() {
+        assert!(unpadded_len(&[]).is_err());
+        assert!(unpadded_len(&[0, 0, 0]).is_err());
+        assert!(unpadded_len(&[0x80, 1, 0]).is_err());
+        assert_eq!(unpadded_len(&[1, 0x80, 0, 0]).unwrap(), 1);
+    }
+
+    #[test]
+    fn records_round_trip() {
+        for padding in [Padding::Padme, Padding::Block(1), Padding::Block(u32::MAX)] {
+            assert_eq!(Padding::from_record(&padding.record()).unwrap().record(), padding.record());
+        }
+        assert!(Padding::from_record(&[SCHEME_BLOCK, 0, 0, 0, 0]).is_err());
+        assert!(Padding::from_record(&[SCHEME_BLOCK, 0, 1]).is_err());
+        assert_eq!(Padding::from_record(&[9]).unwrap_err().code(), "unsupported_padding");
+        assert_eq!(Padding::from_record(&[]).unwrap_err().code(), "unsupported_padding");
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(Padding::from_name("padme", 0).unwrap().name(), "padme");
+        assert_eq!(Padding::from_name("block", 64).unwrap().record(), Padding::Block(64).record());
+        assert!(Padding::from_name("block", 0).is_err());
+        assert_eq!(Padding::from_name("pkcs7", 16).unwrap_err().code(), "unsupported_padding");
+    }
+}
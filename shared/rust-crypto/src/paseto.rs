@@ -0,0 +1,224 @@
+use pyo3::prelude::*;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL};
+use blake2::{Blake2bMac, digest::{consts::{U32, U56}, Mac}};
+use chacha20::XChaCha20;
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use ed25519_dalek::{Signature, Signer, VerifyingKey};
+use subtle::ConstantTimeEq;
+
+use crate::audit::Operation;
+use crate::error::Error;
+use crate::random::random_bytes;
+use crate::sign::signing_key;
+
+const LOCAL_HEADER: &str = "v4.local.";
+const PUBLIC_HEADER: &str = "v4.public.";
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 32;
+const TAG_LEN: usize = 32;
+const SIGNATURE_LEN: usize = 64;
+
+/// Pre-Authentication Encoding: the piece count, then each piece prefixed by its
+/// length, all as little-endian u64s with the top bit cleared.
+fn pae(pieces: &[&[u8]]) -> Vec<u8> {
+    let le64 = |n: usize| ((n as u64) & (u64::MAX >> 1)).to_le_bytes();
+    let mut out = Vec::with_capacity(8 + pieces.iter().map(|p| 8 + p.len()).sum::<usize>());
+    out.extend_from_slice(&le64(pieces.len()));
+    for piece in pieces {
+        out.extend_from_slice(&le64(piece.len()));
+        out.extend_from_slice(piece);
+    }
+    out
+}
+
+fn blake2b_mac<M: Mac + blake2::digest::KeyInit>(key: &[u8], parts: &[&[u8]]) -> Vec<u8> {
+    let mut mac = <M as blake2::digest::KeyInit>::new_from_slice(key).expect("BLAKE2b accepts 32-byte keys");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Encryption key + XChaCha20 nonce, and authentication key, both bound to the token nonce.
+fn split_keys(key: &[u8], nonce: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let tmp = blake2b_mac::<Blake2bMac<U56>>(key, &[b"paseto-encryption-key", nonce]);
+    let auth_key = blake2b_mac::<Blake2bMac<U32>>(key, &[b"paseto-auth-key-for-aead", nonce]);
+    (tmp, auth_key)
+}
+
+fn local_key(key: &[u8]) -> Result<&[u8], Error> {
+    crate::fips::reject("PASETO v4.local (XChaCha20, BLAKE2b)")?;
+    if key.len() != KEY_LEN {
+        return Err(Error::config(format!("PASETO v4.local key must be {} bytes, got {}", KEY_LEN, key.len()))
+            .with_code("invalid_length").with_input("key").with_lengths(KEY_LEN, key.len()));
+    }
+    Ok(key)
+}
+
+/// Splits `header.body[.footer]`, checking the header and, when `expected_footer`
+/// is given, that the footer matches it.
+fn split_token(token: &str, header: &str, expected_footer: Option<&[u8]>) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    let rest = token.trim().strip_prefix(header)
+        .ok_or_else(|| {
+            Error::invalid_format(format!("Invalid PASETO token: expected a {} token", header.trim_end_matches('.')))
+        })?;
+    let (body, footer) = match rest.split_once('.') {
+        Some((body, footer)) => {
+            let footer = BASE64_URL.decode(footer).map_err(|_| Error::invalid_format("Invalid PASETO token: bad footer"))?;
+            (body, footer)
+        }
+        None => (rest, Vec::new()),
+    };
+    if let Some(expected) = expected_footer {
+        if !bool::from(footer.ct_eq(expected)) {
+            return Err(Error::decryption("PASETO footer mismatch"));
+        }
+    }
+    let body = BASE64_URL.decode(body).map_err(|_| Error::invalid_format("Invalid PASETO token: bad base64url"))?;
+    Ok((body, footer))
+}
+
+fn join_token(header: &str, body: &[u8], footer: &[u8]) -> String {
+    let mut token = format!("{}{}", header, BASE64_URL.encode(body));
+    if !footer.is_empty() {
+        token.push('.');
+        token.push_str(&BASE64_URL.encode(footer));
+    }
+    token
+}
+
+pub(crate) fn local_encrypt(key: &[u8], message: &[u8], footer: &[u8], implicit: &[u8]) -> Result<String, Error> {
+    let key = local_key(key)?;
+    let nonce = random_bytes(NONCE_LEN);
+    let (tmp, auth_key) = split_keys(key, &nonce);
+    let (encryption_key, stream_nonce) = tmp.split_at(KEY_LEN);
+
+    let mut ciphertext = message.to_vec();
+    XChaCha20::new(encryption_key.into(), stream_nonce.into()).apply_keystream(&mut ciphertext);
+    let pre_auth = pae(&[LOCAL_HEADER.as_bytes(), &nonce, &ciphertext, footer, implicit]);
+    let tag = blake2b_mac::<Blake2bMac<U32>>(&auth_key, &[&pre_auth]);
+
+    let mut body = nonce;
+    body.extend_from_slice(&ciphertext);
+    body.extend_from_slice(&tag);
+    Ok(join_token(LOCAL_HEADER, &body, footer))
+}
+
+pub(crate) fn local_decrypt(token: &str, key: &[u8], footer: Option<&[u8]>, implicit: &[u8]) -> Result<Vec<u8>, Error> {
+    let key = local_key(key)?;
+    let (body, footer) = split_token(token, LOCAL_HEADER, footer)?;
+    if body.len() < NONCE_LEN + TAG_LEN {
+        return Err(Error::invalid_format("Invalid PASETO token: too short").with_code("truncated"));
+    }
+    let (nonce, rest) = body.split_at(NONCE_LEN);
+    let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+
+    // Authenticate before decrypting anything
+    let (tmp, auth_key) = split_keys(key, nonce);
+    let pre_auth = pae(&[LOCAL_HEADER.as_bytes(), nonce, ciphertext, &footer, implicit]);
+    let expected = blake2b_mac::<Blake2bMac<U32>>(&auth_key, &[&pre_auth]);
+    if !bool::from(expected.ct_eq(tag)) {
+        return Err(Error::decryption("Invalid PASETO token: authentication failed"));
+    }
+    let (encryption_key, stream_nonce) = tmp.split_at(KEY_LEN);
+    let mut message = ciphertext.to_vec();
+    XChaCha20::new(encryption_key.into(), stream_nonce.into()).apply_keystream(&mut message);
+    Ok(message)
+}
+
+fn public_sign(private_key: &[u8], message: &[u8], footer: &[u8], implicit: &[u8]) -> Result<String, Error> {
+    let key = signing_key(private_key)?;
+    let signature = key.sign(&pae(&[PUBLIC_HEADER.as_bytes(), message, footer, implicit]));
+    let mut body = message.to_vec();
+    body.extend_from_slice(&signature.to_bytes());
+    Ok(join_token(PUBLIC_HEADER, &body, footer))
+}
+
+fn public_verify(token: &str, public_key: &[u8], footer: Option<&[u8]>, implicit: &[u8]) -> Result<Vec<u8>, Error> {
+    let public_key: &[u8; 32] = public_key.try_into()
+        .map_err(|_| Error::config("Ed25519 public key must be 32 bytes"))?;
+    let key = VerifyingKey::from_bytes(public_key).map_err(|_| Error::invalid_format("Invalid Ed25519 public key"))?;
+    let (body, footer) = split_token(token, PUBLIC_HEADER, footer)?;
+    if body.len() < SIGNATURE_LEN {
+        return Err(Error::invalid_format("Invalid PASETO token: too short").with_code("truncated"));
+    }
+    let (message, signature) = body.split_at(body.len() - SIGNATURE_LEN);
+    let signature = Signature::from_slice(signature)
+        .map_err(|_| Error::decryption("Invalid PASETO signature").with_code("invalid_signature"))?;
+    key.verify_strict(&pae(&[PUBLIC_HEADER.as_bytes(), message, &footer, implicit]), &signature)
+        .map_err(|_| Error::decryption("Invalid PASETO signature").with_code("invalid_signature"))?;
+    Ok(message.to_vec())
+}
+
+/// Mints a `v4.local` token: XChaCha20 encryption with a keyed BLAKE2b MAC. `key` is
+/// 32 bytes. The `footer` is authenticated but stored in the clear (typically a key
+/// id); the `implicit_assertion` is authenticated but not stored, like AAD.
+#[pyfunction]
+#[pyo3(signature = (message, key, footer=b"".as_slice(), implicit_assertion=b"".as_slice()))]
+pub fn paseto_v4_local_encrypt(
+    py: Python<'_>,
+    message: &[u8],
+    key: &[u8],
+    footer: &[u8],
+    implicit_assertion: &[u8]
+) -> PyResult<String> {
+    py.detach(|| Operation::new("encrypt", "v4.local").run(|| local_encrypt(key, message, footer, implicit_assertion)))
+        .map_err(crate::error::to_pyerr)
+}
+
+/// Decrypts a `v4.local` token. When `footer` is given the token's footer must equal
+/// it; `implicit_assertion` must match the one used when minting.
+#[pyfunction]
+#[pyo3(signature = (token, key, footer=None, implicit_assertion=b"".as_slice()))]
+pub fn paseto_v4_local_decrypt(
+    py: Python<'_>,
+    token: &str,
+    key: &[u8],
+    footer: Option<&[u8]>,
+    implicit_assertion: &[u8]
+) -> PyResult<Vec<u8>> {
+    py.detach(|| Operation::new("decrypt", "v4.local").run(|| local_decrypt(token, key, footer, implicit_assertion)))
+        .map_err(crate::error::to_pyerr)
+}
+
+/// Mints a `v4.public` token signed with a 32-byte Ed25519 private key. The message
+/// is readable by anyone; only its authenticity is protected.
+#[pyfunction]
+#[pyo3(signature = (message, private_key, footer=b"".as_slice(), implicit_assertion=b"".as_slice()))]
+pub fn paseto_v4_public_sign(
+    message: &[u8],
+    private_key: &[u8],
+    footer: &[u8],
+    implicit_assertion: &[u8]
+) -> PyResult<String> {
+    Operation::new("sign", "v4.public").run(|| public_sign(private_key, message, footer, implicit_assertion))
+        .map_err(crate::error::to_pyerr)
+}
+
+/// Verifies a `v4.public` token against a 32-byte Ed25519 public key and returns
+/// the message.
+#[pyfunction]
+#[pyo3(signature = (token, public_key, footer=None, implicit_assertion=b"".as_slice()))]
+pub fn paseto_v4_public_verify(
+    py: Python<'_>,
+    token: &str,
+    public_key: &[u8],
+    footer: Option<&[u8]>,
+    implicit_assertion: &[u8]
+) -> PyResult<Vec<u8>> {
+    let operation = Operation::new("verify", "v4.public");
+    py.detach(|| operation.run(|| public_verify(token, public_key, footer, implicit_assertion)))
+        .map_err(crate::error::to_pyerr)
+}
+
+/// Reads a PASETO token's footer without verifying it, e.g. to pick the key by id.
+#[pyfunction]
+pub fn paseto_footer(token: &str) -> PyResult<Vec<u8>> {
+    let parts: Vec<&str> = token.trim().split('.').collect();
+    match parts.as_slice() {
+        [_, _, _] => Ok(Vec::new()),
+        [_, _, _, footer] => BASE64_URL.decode(footer)
+            .map_err(|_| crate::error::to_pyerr(Error::invalid_format("Invalid PASETO token: bad footer"))),
+        _ => Err(crate::error::to_pyerr(Error::invalid_format("Invalid PASETO token"))),
+    }
+}
@@ -0,0 +1,81 @@
+use pyo3::prelude::*;
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Params;
+
+use crate::error::Error;
+use crate::kdf::KdfParams;
+
+/// Hashes `password` into a PHC string (`$argon2id$v=19$m=...,t=...,p=...$salt$hash`).
+/// Cost selection works like the encrypt functions: a `kdf_profile` or explicit costs.
+/// With `min_score`, passwords `estimate_password_strength` scores lower (checked with
+/// `user_inputs`) are refused before hashing.
+#[pyfunction]
+#[pyo3(signature = (
+    password, memory_cost=None, time_cost=None, parallelism=None, kdf_profile=None, min_score=None,
+    user_inputs=Vec::new()
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn hash_password(
+    py: Python<'_>,
+    password: &str,
+    memory_cost: Option<u32>,
+    time_cost: Option<u32>,
+    parallelism: Option<u32>,
+    kdf_profile: Option<&str>,
+    min_score: Option<u8>,
+    user_inputs: Vec<String>
+) -> PyResult<String> {
+    let (kdf_params, _) = crate::resolve_kdf_params(None, kdf_profile, memory_cost, time_cost, parallelism)?;
+    if let Some(min_score) = min_score {
+        if min_score > 4 {
+            return Err(crate::error::to_pyerr(
+                Error::config("min_score must be between 0 and 4").with_input("min_score")
+            ));
+        }
+        let (score, _, warning) = py.detach(|| crate::strength::estimate(password, &user_inputs));
+        if score < min_score {
+            let reason = warning.map_or(String::new(), |warning| format!(": {}", warning));
+            return Err(crate::error::to_pyerr(Error::config(format!(
+                "Password too weak (score {}, min_score {} required){}", score, min_score, reason
+            )).with_code("weak_password")));
+        }
+    }
+
+    py.detach(|| {
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = kdf_params.argon2()?
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| Error::crypto(format!("Password hashing failed: {}", e)))?;
+        Ok::<_, Error>(hash.to_string())
+    }).map_err(crate::error::to_pyerr)
+}
+
+/// Checks `password` against a PHC string from `hash_password`. The parameters are
+/// read from the hash itself; a wrong password returns `False`, a malformed hash raises.
+#[pyfunction]
+pub fn verify_password(py: Python<'_>, password: &str, password_hash: &str) -> PyResult<bool> {
+    py.detach(|| {
+        let parsed = PasswordHash::new(password_hash)
+            .map_err(|e| Error::invalid_format(format!("Invalid password hash: {}", e)))?;
+        let params = Params::try_from(&parsed)
+            .map_err(|e| Error::invalid_format(format!("Invalid password hash: {}", e)))?;
+        // Same bounds as recorded KDF parameters, so a planted hash can't request a huge derivation
+        let kdf_params = KdfParams::new(Some(params.m_cost()), Some(params.t_cost()), Some(params.p_cost()))?;
+
+        match kdf_params.argon2()?.verify_password(password.as_bytes(), &parsed) {
+            Ok(()) => Ok(true),
+            Err(argon2::password_hash::Error::Password) => Ok(false),
+            Err(e) => Err(Error::crypto(format!("Password verification failed: {}", e))),
+        }
+    }).map_err(crate::error::to_pyerr)
+}
+
+/// Verify-only check of a legacy bcrypt hash (`$2a$`, `$2b$`, `$2y$`), for migrating old
+/// credentials: verify with this once, then store a fresh `hash_password()` result.
+/// Like classic bcrypt, only the first 72 bytes of the password are significant.
+#[pyfunction]
+pub fn verify_legacy_bcrypt(py: Python<'_>, password: &str, bcrypt_hash: &str) -> PyResult<bool> {
+    crate::fips::reject("bcrypt").map_err(crate::error::to_pyerr)?;
+    py.detach(|| bcrypt::verify(password, bcrypt_hash))
+        .map_err(|e| crate::error::to_pyerr(Error::invalid_format(format!("Invalid bcrypt hash: {}", e))))
+}
@@ -0,0 +1,135 @@
+use pyo3::prelude::*;
+use pyo3::create_exception;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::error::Error;
+
+pub(crate) const DEFAULT_PROGRESS_INTERVAL: u64 = 1024 * 1024;
+
+create_exception!(k_services_crypto, OperationCancelled, pyo3::exceptions::PyException,
+    "Raised when a `CancellationToken` stops an operation.");
+
+/// Lets another thread (a request timeout, a signal handler, an asyncio task) stop a
+/// long batch, file or stream operation that was given this token. Operations check
+/// it between chunks or items and raise `OperationCancelled`; a cancelled file
+/// operation leaves no partial `dst` behind.
+///
+/// While a token is attached, Ctrl-C is also noticed at every progress interval,
+/// even though the work runs without the GIL.
+#[pyclass(frozen)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+#[pymethods]
+impl CancellationToken {
+    #[new]
+    fn new() -> Self {
+        CancellationToken { cancelled: Arc::new(AtomicBool::new(false)) }
+    }
+
+    /// Asks every operation holding this token to stop at its next checkpoint.
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    #[getter]
+    fn cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Checkpoints of a long operation. Reports progress to an optional Python callable
+/// as `callback(bytes_processed, total)`, at most once per `interval` bytes plus once
+/// at the end (`total` is `None` when the size isn't known up front), and stops the
+/// operation once its `CancellationToken` is cancelled. Safe to advance from several
+/// threads.
+pub(crate) struct Progress {
+    callback: Option<Py<PyAny>>,
+    cancelled: Option<Arc<AtomicBool>>,
+    total: Option<u64>,
+    interval: u64,
+    done: AtomicU64,
+    /// Byte count at the last checkpoint; holding the lock also serializes the callback calls
+    checked: Mutex<Option<u64>>,
+    error: Mutex<Option<PyErr>>,
+}
+
+impl Progress {
+    pub(crate) fn new(callback: Option<Py<PyAny>>, total: Option<u64>, interval: u64) -> Self {
+        Progress {
+            callback,
+            cancelled: None,
+            total,
+            interval: interval.max(1),
+            done: AtomicU64::new(0),
+            checked: Mutex::new(None),
+            error: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn with_cancel(mut self, token: Option<&CancellationToken>) -> Self {
+        self.cancelled = token.map(|token| token.cancelled.clone());
+        self
+    }
+
+    /// Counts `bytes` more as processed. Fails once cancelled, and runs a checkpoint
+    /// (progress report, Ctrl-C check) once `interval` has passed since the last one.
+    /// If the callback raises, the operation is aborted and `to_pyerr` re-raises
+    /// that exception.
+    pub(crate) fn advance(&self, bytes: usize) -> Result<(), Error> {
+        if self.callback.is_none() && self.cancelled.is_none() {
+            return Ok(());
+        }
+        if self.cancelled.as_ref().is_some_and(|cancelled| cancelled.load(Ordering::Relaxed)) {
+            return self.fail(OperationCancelled::new_err("Operation cancelled"));
+        }
+        let done = self.done.fetch_add(bytes as u64, Ordering::Relaxed) + bytes as u64;
+        if done < self.checked.lock().unwrap().unwrap_or(0) + self.interval {
+            return Ok(());
+        }
+        self.checkpoint()
+    }
+
+    /// Makes the final report, unless the last one already covered every byte.
+    pub(crate) fn finish(&self) -> Result<(), Error> {
+        self.checkpoint()
+    }
+
+    fn checkpoint(&self) -> Result<(), Error> {
+        if self.callback.is_none() && self.cancelled.is_none() {
+            return Ok(());
+        }
+        let mut checked = self.checked.lock().unwrap();
+        // Re-read under the lock so reports from different threads never go backwards
+        let done = self.done.load(Ordering::Relaxed);
+        if *checked == Some(done) {
+            return Ok(());
+        }
+        *checked = Some(done);
+        Python::attach(|py| {
+            // A no-op except on the main thread, where Python handles signals
+            if self.cancelled.is_some() {
+                py.check_signals()?;
+            }
+            match &self.callback {
+                Some(callback) => callback.call1(py, (done, self.total)).map(drop),
+                None => Ok(()),
+            }
+        }).or_else(|e| self.fail(e))
+    }
+
+    fn fail(&self, error: PyErr) -> Result<(), Error> {
+        let message = error.to_string();
+        self.error.lock().unwrap().get_or_insert(error);
+        Err(Error::crypto(message))
+    }
+
+    /// Error for the boundary: the callback's own exception if it raised one, or
+    /// `OperationCancelled`.
+    pub(crate) fn to_pyerr(&self, error: Error) -> PyErr {
+        self.error.lock().unwrap().take()
+            .unwrap_or_else(|| crate::error::to_pyerr(error))
+    }
+}
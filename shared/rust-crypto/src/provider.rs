@@ -0,0 +1,133 @@
+use pyo3::prelude::*;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::encoding::Encoding;
+use crate::envelope::Reader;
+use crate::error::Error;
+
+#[cfg(feature = "aws-kms")]
+pub(crate) mod aws;
+#[cfg(feature = "azure-keyvault")]
+pub(crate) mod azure;
+#[cfg(feature = "gcp-kms")]
+pub(crate) mod gcp;
+#[cfg(any(feature = "gcp-kms", feature = "azure-keyvault", feature = "vault"))]
+mod http;
+#[cfg(feature = "pkcs11")]
+pub(crate) mod pkcs11;
+#[cfg(feature = "vault")]
+pub(crate) mod vault;
+
+/// First bytes of a data key wrapped by a `KeyProvider`.
+pub(crate) const MAGIC: &[u8; 3] = b"KSP";
+const VERSION: u8 = 1;
+
+/// A root key held outside this process (KMS, HSM, ...) that can wrap and unwrap
+/// data keys but never reveals itself. Calls may block on the network; they are
+/// always made without the GIL.
+pub(crate) trait Provider: Send + Sync {
+    /// Short name recorded with every wrapped key, e.g. `aws-kms`.
+    fn name(&self) -> &'static str;
+
+    /// Identifier of the configured root key (ARN, resource name, URL, ...).
+    fn key_id(&self) -> &str;
+
+    /// Wraps `plaintext_key`; `context` must be given again to unwrap. Returns the
+    /// wrapped key and the ID of the key that wrapped it (which may be more precise
+    /// than `key_id()`, e.g. a specific key version); that ID is recorded.
+    fn wrap(&self, plaintext_key: &[u8], context: &[u8]) -> Result<(String, Vec<u8>), Error>;
+
+    /// Unwraps a key previously wrapped under the root key `key_id`.
+    fn unwrap(&self, key_id: &str, wrapped_key: &[u8], context: &[u8]) -> Result<Vec<u8>, Error>;
+
+    /// Like `wrap`, but binds a key/value encryption context the way the AWS
+    /// Encryption SDK does, so the wrapped key is readable by the SDK's keyring
+    /// for this provider. Only providers with such a keyring implement it.
+    fn wrap_with_encryption_context(
+        &self,
+        _plaintext_key: &[u8],
+        _encryption_context: &BTreeMap<String, String>
+    ) -> Result<(String, Vec<u8>), Error> {
+        Err(Error::crypto(format!("The {} provider does not support AWS Encryption SDK messages", self.name())))
+    }
+
+    /// Reverses `wrap_with_encryption_context`.
+    fn unwrap_with_encryption_context(
+        &self,
+        _key_id: &str,
+        _wrapped_key: &[u8],
+        _encryption_context: &BTreeMap<String, String>
+    ) -> Result<Vec<u8>, Error> {
+        Err(Error::crypto(format!("The {} provider does not support AWS Encryption SDK messages", self.name())))
+    }
+}
+
+/// Base class of the key providers (`AwsKmsProvider`, ...); only the subclasses
+/// can be constructed. Pass an instance as `provider=` to the data key functions.
+#[pyclass(subclass, frozen)]
+pub struct KeyProvider {
+    pub(crate) inner: Arc<dyn Provider>,
+}
+
+#[pymethods]
+impl KeyProvider {
+    #[getter]
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    #[getter]
+    fn key_id(&self) -> String {
+        self.inner.key_id().to_string()
+    }
+}
+
+/// Wraps `plaintext_key` with `provider` into a URL-safe token:
+///
+/// ```text
+/// magic "KSP" | version u8 | name_len u8 | name | key_id_len u16 | key_id | wrapped key
+/// ```
+pub(crate) fn wrap_key(provider: &dyn Provider, plaintext_key: &[u8], context: &[u8]) -> Result<String, Error> {
+    let (key_id, wrapped) = provider.wrap(plaintext_key, context)?;
+    let name = provider.name().as_bytes();
+    let key_id = key_id.as_bytes();
+    let key_id_len = u16::try_from(key_id.len()).map_err(|_| Error::config("Provider key ID too long"))?;
+
+    let mut out = Vec::with_capacity(MAGIC.len() + 4 + name.len() + key_id.len() + wrapped.len());
+    out.extend_from_slice(MAGIC);
+    out.push(VERSION);
+    out.push(name.len() as u8);
+    out.extend_from_slice(name);
+    out.extend_from_slice(&key_id_len.to_be_bytes());
+    out.extend_from_slice(key_id);
+    out.extend_from_slice(&wrapped);
+    Ok(BASE64_URL.encode(out))
+}
+
+/// Reverses `wrap_key`. The token must come from a provider of the same kind;
+/// the root key recorded in it is the one asked to unwrap.
+pub(crate) fn unwrap_key(provider: &dyn Provider, token: &str, context: &[u8]) -> Result<Vec<u8>, Error> {
+    let data = Encoding::Base64Url.decode_secret_text(token, "wrapped key")?;
+    let mut reader = Reader { data: &data, pos: 0 };
+    if reader.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+        return Err(Error::invalid_format("Invalid wrapped key: not produced by a key provider"));
+    }
+    let version = reader.u8()?;
+    if version != VERSION {
+        return Err(
+            Error::invalid_format(format!("Unsupported wrapped key version: {}", version))
+                .with_code("unsupported_version")
+        );
+    }
+    let name_len = reader.u8()? as usize;
+    let name = String::from_utf8_lossy(reader.take(name_len)?).into_owned();
+    if name != provider.name() {
+        return Err(Error::crypto(format!("Wrapped key belongs to provider {}, not {}", name, provider.name())));
+    }
+    let key_id_len = reader.u16()? as usize;
+    let key_id = String::from_utf8_lossy(reader.take(key_id_len)?).into_owned();
+
+    provider.unwrap(&key_id, &data[reader.pos..], context)
+}
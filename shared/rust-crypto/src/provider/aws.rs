@@ -0,0 +1,124 @@
+use pyo3::prelude::*;
+use aws_sdk_kms::{primitives::Blob, Client};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+use super::{KeyProvider, Provider};
+
+use crate::error::Error;
+
+/// Encryption context key under which the wrapping context is passed to KMS.
+const CONTEXT_KEY: &str = "k-services-context";
+
+struct AwsKms {
+    key_arn: String,
+    client: Client,
+    runtime: Runtime,
+}
+
+fn kms_error<E: std::fmt::Display>(action: &str) -> impl FnOnce(E) -> Error + '_ {
+    move |e| Error::crypto(format!("AWS KMS {} failed: {}", action, e))
+}
+
+impl Provider for AwsKms {
+    fn name(&self) -> &'static str {
+        "aws-kms"
+    }
+
+    fn key_id(&self) -> &str {
+        &self.key_arn
+    }
+
+    fn wrap(&self, plaintext_key: &[u8], context: &[u8]) -> Result<(String, Vec<u8>), Error> {
+        let request = self.client.encrypt()
+            .key_id(&self.key_arn)
+            .plaintext(Blob::new(plaintext_key))
+            .encryption_context(CONTEXT_KEY, String::from_utf8_lossy(context));
+        let output = self.runtime.block_on(request.send()).map_err(kms_error("Encrypt"))?;
+        let wrapped_key = output.ciphertext_blob.map(Blob::into_inner)
+            .ok_or_else(|| Error::crypto("AWS KMS Encrypt returned no ciphertext"))?;
+        Ok((output.key_id.unwrap_or_else(|| self.key_arn.clone()), wrapped_key))
+    }
+
+    fn unwrap(&self, key_id: &str, wrapped_key: &[u8], context: &[u8]) -> Result<Vec<u8>, Error> {
+        let request = self.client.decrypt()
+            .key_id(key_id)
+            .ciphertext_blob(Blob::new(wrapped_key))
+            .encryption_context(CONTEXT_KEY, String::from_utf8_lossy(context));
+        let output = self.runtime.block_on(request.send()).map_err(kms_error("Decrypt"))?;
+        output.plaintext.map(Blob::into_inner)
+            .ok_or_else(|| Error::crypto("AWS KMS Decrypt returned no plaintext"))
+    }
+
+    fn wrap_with_encryption_context(
+        &self,
+        plaintext_key: &[u8],
+        encryption_context: &BTreeMap<String, String>
+    ) -> Result<(String, Vec<u8>), Error> {
+        let request = self.client.encrypt()
+            .key_id(&self.key_arn)
+            .plaintext(Blob::new(plaintext_key))
+            .set_encryption_context(Some(encryption_context.clone().into_iter().collect()));
+        let output = self.runtime.block_on(request.send()).map_err(kms_error("Encrypt"))?;
+        let wrapped_key = output.ciphertext_blob.map(Blob::into_inner)
+            .ok_or_else(|| Error::crypto("AWS KMS Encrypt returned no ciphertext"))?;
+        Ok((output.key_id.unwrap_or_else(|| self.key_arn.clone()), wrapped_key))
+    }
+
+    fn unwrap_with_encryption_context(
+        &self,
+        key_id: &str,
+        wrapped_key: &[u8],
+        encryption_context: &BTreeMap<String, String>
+    ) -> Result<Vec<u8>, Error> {
+        let request = self.client.decrypt()
+            .key_id(key_id)
+            .ciphertext_blob(Blob::new(wrapped_key))
+            .set_encryption_context(Some(encryption_context.clone().into_iter().collect()));
+        let output = self.runtime.block_on(request.send()).map_err(kms_error("Decrypt"))?;
+        output.plaintext.map(Blob::into_inner)
+            .ok_or_else(|| Error::crypto("AWS KMS Decrypt returned no plaintext"))
+    }
+}
+
+/// Data keys wrapped by an AWS KMS key (`Encrypt` / `Decrypt`). Credentials come from
+/// the standard AWS chain (environment, shared config/`profile`, SSO, instance role).
+/// The key ARN is recorded in every wrapped key; decryption asks KMS for that key.
+#[pyclass(extends = KeyProvider, frozen)]
+pub struct AwsKmsProvider;
+
+#[pymethods]
+impl AwsKmsProvider {
+    #[new]
+    #[pyo3(signature = (key_arn, region=None, profile=None, endpoint_url=None))]
+    fn new(
+        py: Python<'_>,
+        key_arn: String,
+        region: Option<String>,
+        profile: Option<String>,
+        endpoint_url: Option<String>
+    ) -> PyResult<(Self, KeyProvider)> {
+        let provider = py.detach(|| -> Result<AwsKms, Error> {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|e| Error::crypto(format!("Failed to start AWS client runtime: {}", e)))?;
+
+            let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest());
+            if let Some(region) = region {
+                loader = loader.region(aws_config::Region::new(region));
+            }
+            if let Some(profile) = profile {
+                loader = loader.profile_name(profile);
+            }
+            if let Some(endpoint_url) = endpoint_url {
+                loader = loader.endpoint_url(endpoint_url);
+            }
+            let config = runtime.block_on(loader.load());
+            Ok(AwsKms { key_arn, client: Client::new(&config), runtime })
+        }).map_err(crate::error::to_pyerr)?;
+
+        Ok((AwsKmsProvider, KeyProvider { inner: Arc::new(provider) }))
+    }
+}
@@ -0,0 +1,74 @@
+use pyo3::prelude::*;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL};
+use serde_json::json;
+use std::sync::Arc;
+
+use super::http::{field, post_json, TokenSource};
+use super::{KeyProvider, Provider};
+
+use crate::error::Error;
+
+const SERVICE: &str = "Azure Key Vault";
+const API_VERSION: &str = "7.4";
+const DEFAULT_ALGORITHM: &str = "RSA-OAEP-256";
+const IMDS_TOKEN_URL: &str =
+    "http://169.254.169.254/metadata/identity/oauth2/token?api-version=2018-02-01&resource=https%3A%2F%2Fvault.azure.net";
+
+struct AzureKeyVault {
+    key_url: String,
+    algorithm: String,
+    tokens: TokenSource,
+}
+
+impl Provider for AzureKeyVault {
+    fn name(&self) -> &'static str {
+        "azure-keyvault"
+    }
+
+    fn key_id(&self) -> &str {
+        &self.key_url
+    }
+
+    /// Key Vault's wrapkey has no associated data, so `context` is not bound here;
+    /// it still separates data keys from other envelopes on the local side.
+    fn wrap(&self, plaintext_key: &[u8], _context: &[u8]) -> Result<(String, Vec<u8>), Error> {
+        let url = format!("{}/wrapkey?api-version={}", self.key_url, API_VERSION);
+        let body = json!({ "alg": self.algorithm, "value": BASE64_URL.encode(plaintext_key) });
+        let response = post_json(SERVICE, &url, &self.tokens, body)?;
+        let wrapped = BASE64_URL.decode(field(SERVICE, &response, "value")?)
+            .map_err(|e| Error::crypto(format!("{}: invalid wrapped key: {}", SERVICE, e)))?;
+        // `kid` names the exact key version, which unwrapkey needs
+        let kid = field(SERVICE, &response, "kid").map_or_else(|_| self.key_url.clone(), str::to_string);
+        Ok((kid, wrapped))
+    }
+
+    fn unwrap(&self, key_id: &str, wrapped_key: &[u8], _context: &[u8]) -> Result<Vec<u8>, Error> {
+        let url = format!("{}/unwrapkey?api-version={}", key_id, API_VERSION);
+        let body = json!({ "alg": self.algorithm, "value": BASE64_URL.encode(wrapped_key) });
+        let response = post_json(SERVICE, &url, &self.tokens, body)?;
+        BASE64_URL.decode(field(SERVICE, &response, "value")?)
+            .map_err(|e| Error::crypto(format!("{}: invalid unwrapped key: {}", SERVICE, e)))
+    }
+}
+
+/// Data keys wrapped by an Azure Key Vault (or Managed HSM) key, given by its URL
+/// `https://<vault>.vault.azure.net/keys/<name>[/<version>]`. `algorithm` is the
+/// wrap algorithm (`RSA-OAEP-256` for RSA keys, `A256KW` for Managed HSM AES keys).
+/// Without an `access_token`, tokens come from the managed identity endpoint.
+#[pyclass(extends = KeyProvider, frozen)]
+pub struct AzureKeyVaultProvider;
+
+#[pymethods]
+impl AzureKeyVaultProvider {
+    #[new]
+    #[pyo3(signature = (key_url, access_token=None, algorithm=DEFAULT_ALGORITHM))]
+    fn new(key_url: String, access_token: Option<String>, algorithm: &str) -> (Self, KeyProvider) {
+        let tokens = match access_token {
+            Some(token) => TokenSource::Static(token),
+            None => TokenSource::metadata(IMDS_TOKEN_URL.to_string(), ("Metadata", "true")),
+        };
+        let key_url = key_url.trim_end_matches('/').to_string();
+        let provider = AzureKeyVault { key_url, algorithm: algorithm.to_string(), tokens };
+        (AzureKeyVaultProvider, KeyProvider { inner: Arc::new(provider) })
+    }
+}
@@ -0,0 +1,93 @@
+use serde_json::Value;
+#[cfg(any(feature = "gcp-kms", feature = "azure-keyvault"))]
+use std::sync::Mutex;
+#[cfg(any(feature = "gcp-kms", feature = "azure-keyvault"))]
+use std::time::{Duration, Instant};
+
+use crate::error::Error;
+
+/// Refresh cached metadata-server tokens this long before they expire.
+#[cfg(any(feature = "gcp-kms", feature = "azure-keyvault"))]
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// Where a provider gets its OAuth bearer token from.
+#[cfg(any(feature = "gcp-kms", feature = "azure-keyvault"))]
+pub(crate) enum TokenSource {
+    /// Token supplied by the caller; rebuild the provider once it expires.
+    Static(String),
+    /// Token fetched from the cloud's instance metadata endpoint and cached until shortly
+    /// before `expires_in`. `header` is the header the endpoint requires.
+    Metadata {
+        url: String,
+        header: (&'static str, &'static str),
+        cached: Mutex<Option<(String, Instant)>>,
+    },
+}
+
+#[cfg(any(feature = "gcp-kms", feature = "azure-keyvault"))]
+impl TokenSource {
+    pub(crate) fn metadata(url: String, header: (&'static str, &'static str)) -> Self {
+        TokenSource::Metadata { url, header, cached: Mutex::new(None) }
+    }
+
+    pub(crate) fn token(&self) -> Result<String, Error> {
+        let (url, header, cached) = match self {
+            TokenSource::Static(token) => return Ok(token.clone()),
+            TokenSource::Metadata { url, header, cached } => (url, header, cached),
+        };
+
+        let mut cached = cached.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((token, expires)) = cached.as_ref() {
+            if Instant::now() + TOKEN_EXPIRY_MARGIN < *expires {
+                return Ok(token.clone());
+            }
+        }
+
+        let response: Value = ureq::get(url)
+            .set(header.0, header.1)
+            .call()
+            .map_err(|e| Error::crypto(format!("Failed to fetch access token from metadata service: {}", e)))?
+            .into_json()
+            .map_err(|e| Error::invalid_format(format!("Invalid metadata token response: {}", e)))?;
+        let token = response["access_token"].as_str()
+            .ok_or_else(|| Error::crypto("Metadata token response has no access_token"))?
+            .to_string();
+        // Azure IMDS returns expires_in as a string, GCP as a number
+        let expires_in = response["expires_in"].as_u64()
+            .or_else(|| response["expires_in"].as_str().and_then(|v| v.parse().ok()))
+            .unwrap_or(0);
+        *cached = Some((token.clone(), Instant::now() + Duration::from_secs(expires_in)));
+        Ok(token)
+    }
+}
+
+/// POSTs `body` as JSON with a bearer token and returns the parsed JSON response.
+#[cfg(any(feature = "gcp-kms", feature = "azure-keyvault"))]
+pub(crate) fn post_json(service: &str, url: &str, tokens: &TokenSource, body: Value) -> Result<Value, Error> {
+    let authorization = format!("Bearer {}", tokens.token()?);
+    post_json_with(service, url, &[("Authorization", &authorization)], body)
+}
+
+/// POSTs `body` as JSON with the given extra headers. Error responses are
+/// reported with their status and body.
+pub(crate) fn post_json_with(service: &str, url: &str, headers: &[(&str, &str)], body: Value) -> Result<Value, Error> {
+    let mut request = ureq::post(url);
+    for (name, value) in headers {
+        request = request.set(name, value);
+    }
+    match request.send_json(body) {
+        Ok(response) => response.into_json()
+            .map_err(|e| Error::crypto(format!("{}: invalid response: {}", service, e))),
+        Err(ureq::Error::Status(status, response)) => {
+            let detail = response.into_string().unwrap_or_default();
+            Err(Error::crypto(format!("{} request failed with HTTP {}: {}", service, status, detail.trim())))
+        }
+        Err(e) => Err(Error::crypto(format!("{} request failed: {}", service, e))),
+    }
+}
+
+/// Reads a string field from a JSON response.
+#[cfg(any(feature = "gcp-kms", feature = "azure-keyvault"))]
+pub(crate) fn field<'a>(service: &str, response: &'a Value, name: &str) -> Result<&'a str, Error> {
+    response[name].as_str().ok_or_else(|| Error::crypto(format!("{} response has no {}", service, name)))
+}
@@ -0,0 +1,150 @@
+use pyo3::prelude::*;
+use argon2::password_hash::rand_core::OsRng;
+use cryptoki::context::{CInitializeArgs, Pkcs11};
+use cryptoki::error::{Error as Pkcs11Error, RvError};
+use cryptoki::mechanism::aead::GcmParams;
+use cryptoki::mechanism::Mechanism;
+use cryptoki::object::{Attribute, ObjectClass, ObjectHandle};
+use cryptoki::session::{Session, UserType};
+use cryptoki::slot::Slot;
+use cryptoki::types::AuthPin;
+use rand::RngCore;
+use std::sync::{Arc, Mutex};
+
+use super::{KeyProvider, Provider};
+
+use crate::error::Error;
+
+const IV_LEN: usize = 12;
+const TAG_BITS: u64 = 128;
+
+fn pkcs11_error(operation: &str, error: Pkcs11Error) -> Error {
+    Error::crypto(format!("PKCS#11 {} failed: {}", operation, error))
+}
+
+struct Pkcs11Kek {
+    /// PKCS#11 sessions must not be shared between threads, so calls take turns.
+    session: Mutex<Session>,
+    key_label: String,
+}
+
+impl Pkcs11Kek {
+    fn find_key(session: &Session, label: &str) -> Result<ObjectHandle, Error> {
+        let template = [Attribute::Class(ObjectClass::SECRET_KEY), Attribute::Label(label.as_bytes().to_vec())];
+        let handles = session.find_objects(&template).map_err(|e| pkcs11_error("C_FindObjects", e))?;
+        match handles.as_slice() {
+            [handle] => Ok(*handle),
+            [] => Err(Error::crypto(format!("PKCS#11: no secret key labeled '{}'", label))),
+            _ => Err(Error::crypto(format!("PKCS#11: more than one secret key labeled '{}'", label))),
+        }
+    }
+}
+
+impl Provider for Pkcs11Kek {
+    fn name(&self) -> &'static str {
+        "pkcs11"
+    }
+
+    fn key_id(&self) -> &str {
+        &self.key_label
+    }
+
+    /// AES-GCM runs on the token: the KEK never leaves it. Output is `iv || ciphertext`.
+    fn wrap(&self, plaintext_key: &[u8], context: &[u8]) -> Result<(String, Vec<u8>), Error> {
+        let session = self.session.lock().unwrap_or_else(|e| e.into_inner());
+        let key = Self::find_key(&session, &self.key_label)?;
+
+        let mut iv = [0u8; IV_LEN];
+        OsRng.fill_bytes(&mut iv);
+        let mechanism = Mechanism::AesGcm(GcmParams::new(&iv, context, TAG_BITS.into()));
+        let ciphertext = session.encrypt(&mechanism, key, plaintext_key)
+            .map_err(|e| pkcs11_error("C_Encrypt", e))?;
+
+        let mut wrapped = iv.to_vec();
+        wrapped.extend_from_slice(&ciphertext);
+        Ok((self.key_label.clone(), wrapped))
+    }
+
+    fn unwrap(&self, key_id: &str, wrapped_key: &[u8], context: &[u8]) -> Result<Vec<u8>, Error> {
+        if wrapped_key.len() < IV_LEN {
+            return Err(Error::invalid_format("Invalid PKCS#11 wrapped key"));
+        }
+        let (iv, ciphertext) = wrapped_key.split_at(IV_LEN);
+
+        let session = self.session.lock().unwrap_or_else(|e| e.into_inner());
+        let key = Self::find_key(&session, key_id)?;
+        let mechanism = Mechanism::AesGcm(GcmParams::new(iv, context, TAG_BITS.into()));
+        session.decrypt(&mechanism, key, ciphertext)
+            .map_err(|e| pkcs11_error("C_Decrypt", e))
+    }
+}
+
+fn open_session(
+    module_path: &str,
+    key_label: &str,
+    pin: &str,
+    token_label: Option<&str>,
+    slot: Option<u64>
+) -> Result<Session, Error> {
+    let pkcs11 = Pkcs11::new(module_path)
+        .map_err(|e| Error::crypto(format!("PKCS#11: cannot load module '{}': {}", module_path, e)))?;
+    // Another provider in this process may already have initialized the module
+    match pkcs11.initialize(CInitializeArgs::OsThreads) {
+        Ok(()) | Err(Pkcs11Error::Pkcs11(RvError::CryptokiAlreadyInitialized, _)) => {}
+        Err(e) => return Err(pkcs11_error("C_Initialize", e)),
+    }
+
+    let slots = pkcs11.get_slots_with_token().map_err(|e| pkcs11_error("C_GetSlotList", e))?;
+    let slot: Slot = match (token_label, slot) {
+        (Some(_), Some(_)) => return Err(Error::config("Pass either token_label or slot, not both")),
+        (Some(label), None) => *slots.iter()
+            .find(|s| pkcs11.get_token_info(**s).is_ok_and(|info| info.label().trim_end() == label))
+            .ok_or_else(|| Error::crypto(format!("PKCS#11: no token labeled '{}'", label)))?,
+        (None, Some(id)) => *slots.iter()
+            .find(|s| s.id() == id)
+            .ok_or_else(|| Error::crypto(format!("PKCS#11: no token in slot {}", id)))?,
+        (None, None) => match slots.as_slice() {
+            [slot] => *slot,
+            [] => return Err(Error::crypto("PKCS#11: no token present")),
+            _ => return Err(Error::crypto("PKCS#11: several tokens present, pass token_label or slot")),
+        },
+    };
+
+    let session = pkcs11.open_rw_session(slot).map_err(|e| pkcs11_error("C_OpenSession", e))?;
+    match session.login(UserType::User, Some(&AuthPin::new(pin.to_string()))) {
+        Ok(()) | Err(Pkcs11Error::Pkcs11(RvError::UserAlreadyLoggedIn, _)) => {}
+        Err(e) => return Err(pkcs11_error("C_Login", e)),
+    }
+    // Fail now rather than on the first wrap if the label is wrong
+    Pkcs11Kek::find_key(&session, key_label)?;
+    Ok(session)
+}
+
+/// Data keys wrapped with AES-GCM by an AES key held in a PKCS#11 token (an HSM,
+/// or SoftHSM for development). `module_path` is the vendor's PKCS#11 library;
+/// `pin` defaults to `PKCS11_PIN`. The token is picked by `token_label` or `slot`,
+/// and may be left out when exactly one token is present.
+#[pyclass(extends = KeyProvider, frozen)]
+pub struct Pkcs11Provider;
+
+#[pymethods]
+impl Pkcs11Provider {
+    #[new]
+    #[pyo3(signature = (module_path, key_label, pin=None, token_label=None, slot=None))]
+    fn new(
+        py: Python<'_>,
+        module_path: &str,
+        key_label: String,
+        pin: Option<String>,
+        token_label: Option<&str>,
+        slot: Option<u64>
+    ) -> PyResult<(Self, KeyProvider)> {
+        let pin = pin.or_else(|| std::env::var("PKCS11_PIN").ok())
+            .ok_or_else(|| crate::error::to_pyerr(Error::config("PKCS#11 PIN required (pin or PKCS11_PIN)")))?;
+
+        let session = py.detach(|| open_session(module_path, &key_label, &pin, token_label, slot))
+            .map_err(crate::error::to_pyerr)?;
+        let provider = Pkcs11Kek { session: Mutex::new(session), key_label };
+        Ok((Pkcs11Provider, KeyProvider { inner: Arc::new(provider) }))
+    }
+}
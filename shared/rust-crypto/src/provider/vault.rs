@@ -0,0 +1,156 @@
+use pyo3::prelude::*;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::http::post_json_with;
+use super::{KeyProvider, Provider};
+
+use crate::error::Error;
+
+const SERVICE: &str = "Vault";
+
+/// Log in again this long before an AppRole token's lease runs out.
+const LEASE_MARGIN: Duration = Duration::from_secs(30);
+
+enum Auth {
+    Token(String),
+    AppRole {
+        mount: String,
+        role_id: String,
+        secret_id: String,
+        cached: Mutex<Option<(String, Instant)>>,
+    },
+}
+
+struct VaultTransit {
+    address: String,
+    mount: String,
+    key_name: String,
+    namespace: Option<String>,
+    auth: Auth,
+}
+
+impl VaultTransit {
+    fn post(&self, path: &str, token: Option<&str>, body: Value) -> Result<Value, Error> {
+        let url = format!("{}/v1/{}", self.address, path);
+        let mut headers = Vec::new();
+        if let Some(token) = token {
+            headers.push(("X-Vault-Token", token));
+        }
+        if let Some(namespace) = &self.namespace {
+            headers.push(("X-Vault-Namespace", namespace.as_str()));
+        }
+        post_json_with(SERVICE, &url, &headers, body)
+    }
+
+    fn token(&self) -> Result<String, Error> {
+        let (mount, role_id, secret_id, cached) = match &self.auth {
+            Auth::Token(token) => return Ok(token.clone()),
+            Auth::AppRole { mount, role_id, secret_id, cached } => (mount, role_id, secret_id, cached),
+        };
+
+        let mut cached = cached.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((token, expires)) = cached.as_ref() {
+            if Instant::now() + LEASE_MARGIN < *expires {
+                return Ok(token.clone());
+            }
+        }
+
+        let response = self.post(
+            &format!("auth/{}/login", mount), None, json!({ "role_id": role_id, "secret_id": secret_id })
+        )?;
+        let token = response["auth"]["client_token"].as_str()
+            .ok_or_else(|| Error::crypto("Vault AppRole login returned no client_token"))?
+            .to_string();
+        let lease = response["auth"]["lease_duration"].as_u64().unwrap_or(0);
+        *cached = Some((token.clone(), Instant::now() + Duration::from_secs(lease)));
+        Ok(token)
+    }
+
+    fn transit(&self, operation: &str, key_name: &str, body: Value) -> Result<Value, Error> {
+        let token = self.token()?;
+        let response = self.post(&format!("{}/{}/{}", self.mount, operation, key_name), Some(&token), body)?;
+        Ok(response["data"].clone())
+    }
+}
+
+impl Provider for VaultTransit {
+    fn name(&self) -> &'static str {
+        "vault-transit"
+    }
+
+    fn key_id(&self) -> &str {
+        &self.key_name
+    }
+
+    /// `context` is not sent: transit only accepts it for derived keys.
+    fn wrap(&self, plaintext_key: &[u8], _context: &[u8]) -> Result<(String, Vec<u8>), Error> {
+        let data = self.transit("encrypt", &self.key_name, json!({ "plaintext": BASE64.encode(plaintext_key) }))?;
+        let ciphertext = data["ciphertext"].as_str()
+            .ok_or_else(|| Error::crypto("Vault transit encrypt returned no ciphertext"))?;
+        // Stored as the `vault:v<N>:...` string, which carries the key version
+        Ok((self.key_name.clone(), ciphertext.as_bytes().to_vec()))
+    }
+
+    fn unwrap(&self, key_id: &str, wrapped_key: &[u8], _context: &[u8]) -> Result<Vec<u8>, Error> {
+        let ciphertext = std::str::from_utf8(wrapped_key)
+            .map_err(|_| Error::invalid_format("Invalid Vault transit ciphertext"))?;
+        let data = self.transit("decrypt", key_id, json!({ "ciphertext": ciphertext }))?;
+        let plaintext = data["plaintext"].as_str()
+            .ok_or_else(|| Error::crypto("Vault transit decrypt returned no plaintext"))?;
+        BASE64.decode(plaintext).map_err(|e| Error::crypto(format!("Vault transit: invalid plaintext: {}", e)))
+    }
+}
+
+/// Data keys wrapped by a key in HashiCorp Vault's transit engine. `address` and
+/// `token` default to `VAULT_ADDR` / `VAULT_TOKEN`; pass `role_id` and `secret_id`
+/// instead of a token to log in with AppRole (the token is renewed on lease expiry).
+#[pyclass(extends = KeyProvider, frozen)]
+pub struct VaultTransitProvider;
+
+#[pymethods]
+impl VaultTransitProvider {
+    #[new]
+    #[pyo3(signature = (
+        key_name, address=None, token=None, role_id=None, secret_id=None,
+        mount="transit", approle_mount="approle", namespace=None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        key_name: String,
+        address: Option<String>,
+        token: Option<String>,
+        role_id: Option<String>,
+        secret_id: Option<String>,
+        mount: &str,
+        approle_mount: &str,
+        namespace: Option<String>
+    ) -> PyResult<(Self, KeyProvider)> {
+        let address = address.or_else(|| std::env::var("VAULT_ADDR").ok())
+            .ok_or_else(|| crate::error::to_pyerr(Error::config("Vault address required (address or VAULT_ADDR)")))?;
+        let auth = match (token, role_id, secret_id) {
+            (Some(token), None, None) => Auth::Token(token),
+            (None, Some(role_id), Some(secret_id)) => Auth::AppRole {
+                mount: approle_mount.trim_matches('/').to_string(),
+                role_id,
+                secret_id,
+                cached: Mutex::new(None),
+            },
+            (None, None, None) => Auth::Token(std::env::var("VAULT_TOKEN").map_err(|_| {
+                crate::error::to_pyerr(Error::config("Vault token required (token, VAULT_TOKEN or role_id/secret_id)"))
+            })?),
+            _ => return Err(crate::error::to_pyerr(Error::config("Pass either token or both role_id and secret_id"))),
+        };
+
+        let provider = VaultTransit {
+            address: address.trim_end_matches('/').to_string(),
+            mount: mount.trim_matches('/').to_string(),
+            key_name,
+            namespace,
+            auth,
+        };
+        Ok((VaultTransitProvider, KeyProvider { inner: Arc::new(provider) }))
+    }
+}
@@ -0,0 +1,139 @@
+use pyo3::prelude::*;
+use argon2::password_hash::rand_core::OsRng;
+use crypto_box::{PublicKey, SecretKey};
+use rsa::{Oaep, RsaPrivateKey, RsaPublicKey};
+use rsa::pkcs1::{DecodeRsaPrivateKey, DecodeRsaPublicKey};
+use rsa::pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey, LineEnding};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+
+use crate::audit::Operation;
+use crate::error::Error;
+
+/// Modulus sizes accepted by `rsa_generate_keypair`.
+const RSA_KEY_SIZES: &[usize] = &[2048, 3072, 4096];
+
+const SEALED_BOX: &str = "crypto_box_seal (X25519, XSalsa20-Poly1305)";
+
+pub(crate) fn public_key(public_key: &[u8]) -> Result<PublicKey, Error> {
+    PublicKey::from_slice(public_key).map_err(|_| {
+        Error::config(format!("X25519 public key must be 32 bytes, got {}", public_key.len()))
+            .with_code("invalid_length").with_input("public_key").with_lengths(32, public_key.len())
+    })
+}
+
+pub(crate) fn secret_key(private_key: &[u8]) -> Result<SecretKey, Error> {
+    SecretKey::from_slice(private_key).map_err(|_| {
+        Error::config(format!("X25519 private key must be 32 bytes, got {}", private_key.len()))
+            .with_code("invalid_length").with_input("private_key").with_lengths(32, private_key.len())
+    })
+}
+
+/// Generates an X25519 keypair and returns `(private_key, public_key)`, both 32 bytes.
+#[pyfunction]
+pub fn x25519_generate_keypair() -> (Vec<u8>, Vec<u8>) {
+    let key = SecretKey::generate(&mut OsRng);
+    (key.to_bytes().to_vec(), key.public_key().as_bytes().to_vec())
+}
+
+/// Public key belonging to a 32-byte X25519 private key.
+#[pyfunction]
+pub fn x25519_public_key(private_key: &[u8]) -> PyResult<Vec<u8>> {
+    let key = secret_key(private_key).map_err(crate::error::to_pyerr)?;
+    Ok(key.public_key().as_bytes().to_vec())
+}
+
+/// Anonymously encrypts `plaintext` for the holder of `recipient_public_key`.
+/// Compatible with libsodium's `crypto_box_seal` (ephemeral X25519 key +
+/// XSalsa20-Poly1305); output is 48 bytes longer than the plaintext.
+#[pyfunction]
+pub fn sealed_box_encrypt(py: Python<'_>, recipient_public_key: &[u8], plaintext: &[u8]) -> PyResult<Vec<u8>> {
+    crate::fips::reject(SEALED_BOX).map_err(crate::error::to_pyerr)?;
+    let recipient = public_key(recipient_public_key).map_err(crate::error::to_pyerr)?;
+    let operation = Operation::new("encrypt", "crypto_box_seal");
+    py.detach(|| operation.run(|| {
+        recipient.seal(&mut OsRng, plaintext).map_err(|_| Error::crypto("Encryption failed"))
+    })).map_err(crate::error::to_pyerr)
+}
+
+/// Opens a sealed box with the recipient's private key (libsodium `crypto_box_seal_open`).
+#[pyfunction]
+pub fn sealed_box_decrypt(py: Python<'_>, private_key: &[u8], ciphertext: &[u8]) -> PyResult<Vec<u8>> {
+    crate::fips::reject(SEALED_BOX).map_err(crate::error::to_pyerr)?;
+    let key = secret_key(private_key).map_err(crate::error::to_pyerr)?;
+    let operation = Operation::new("decrypt", "crypto_box_seal");
+    py.detach(|| operation.run(|| {
+        key.unseal(ciphertext).map_err(|_| Error::decryption("Decryption failed: Integrity check failed or wrong key"))
+    })).map_err(crate::error::to_pyerr)
+}
+
+/// OAEP padding with the same hash for the label digest and MGF1. SHA-1 is only
+/// offered because it is the default of `openssl pkeyutl` and many partner stacks.
+fn oaep(hash: &str) -> Result<Oaep, Error> {
+    match hash.to_ascii_lowercase().as_str() {
+        "sha1" | "sha-1" => Ok(Oaep::new::<Sha1>()),
+        "sha256" | "sha-256" => Ok(Oaep::new::<Sha256>()),
+        "sha512" | "sha-512" => Ok(Oaep::new::<Sha512>()),
+        _ => Err(Error::config(format!("Unsupported OAEP hash: {}", hash))),
+    }
+}
+
+/// Accepts PKCS#8 (`BEGIN PRIVATE KEY`) or PKCS#1 (`BEGIN RSA PRIVATE KEY`) PEM.
+fn rsa_private_key(pem: &str) -> Result<RsaPrivateKey, Error> {
+    RsaPrivateKey::from_pkcs8_pem(pem)
+        .or_else(|_| RsaPrivateKey::from_pkcs1_pem(pem))
+        .map_err(|_| Error::invalid_format("Invalid RSA private key PEM"))
+}
+
+/// Accepts SubjectPublicKeyInfo (`BEGIN PUBLIC KEY`) or PKCS#1 (`BEGIN RSA PUBLIC KEY`) PEM.
+fn rsa_public_key(pem: &str) -> Result<RsaPublicKey, Error> {
+    RsaPublicKey::from_public_key_pem(pem)
+        .or_else(|_| RsaPublicKey::from_pkcs1_pem(pem))
+        .map_err(|_| Error::invalid_format("Invalid RSA public key PEM"))
+}
+
+/// Generates an RSA keypair and returns `(private_key_pem, public_key_pem)` as
+/// PKCS#8 / SubjectPublicKeyInfo. `bits` is 2048, 3072 or 4096.
+#[pyfunction]
+#[pyo3(signature = (bits=2048))]
+pub fn rsa_generate_keypair(py: Python<'_>, bits: usize) -> PyResult<(String, String)> {
+    if !RSA_KEY_SIZES.contains(&bits) {
+        return Err(crate::error::to_pyerr(Error::config("bits must be 2048, 3072 or 4096").with_input("bits")));
+    }
+    py.detach(|| -> Result<(String, String), Error> {
+        let key = RsaPrivateKey::new(&mut OsRng, bits)
+            .map_err(|e| Error::crypto(format!("Key generation failed: {}", e)))?;
+        let private_pem = key.to_pkcs8_pem(LineEnding::LF).map_err(|e| Error::crypto(e.to_string()))?;
+        let public_pem = key.to_public_key().to_public_key_pem(LineEnding::LF)
+            .map_err(|e| Error::crypto(e.to_string()))?;
+        Ok((private_pem.to_string(), public_pem))
+    }).map_err(crate::error::to_pyerr)
+}
+
+/// RSA-OAEP encryption, typically of a symmetric key for a partner. The plaintext
+/// can be at most `key_bytes - 2 * hash_len - 2` bytes (190 for RSA-2048/SHA-256).
+#[pyfunction]
+#[pyo3(signature = (public_key_pem, plaintext, hash="sha256"))]
+pub fn rsa_oaep_encrypt(public_key_pem: &str, plaintext: &[u8], hash: &str) -> PyResult<Vec<u8>> {
+    let padding = oaep(hash).map_err(crate::error::to_pyerr)?;
+    let key = rsa_public_key(public_key_pem).map_err(crate::error::to_pyerr)?;
+    let operation = Operation::new("encrypt", "rsa-oaep");
+    operation.run(|| {
+        key.encrypt(&mut OsRng, padding, plaintext).map_err(|e| Error::crypto(format!("Encryption failed: {}", e)))
+    }).map_err(crate::error::to_pyerr)
+}
+
+/// RSA-OAEP decryption; `hash` must match the one used to encrypt. Blinding is
+/// always applied, but the underlying `rsa` crate is not fully constant-time
+/// (RUSTSEC-2023-0071), so avoid exposing this as an unthrottled network oracle.
+#[pyfunction]
+#[pyo3(signature = (private_key_pem, ciphertext, hash="sha256"))]
+pub fn rsa_oaep_decrypt(private_key_pem: &str, ciphertext: &[u8], hash: &str) -> PyResult<Vec<u8>> {
+    let padding = oaep(hash).map_err(crate::error::to_pyerr)?;
+    let key = rsa_private_key(private_key_pem).map_err(crate::error::to_pyerr)?;
+    let operation = Operation::new("decrypt", "rsa-oaep");
+    operation.run(|| {
+        key.decrypt_blinded(&mut OsRng, padding, ciphertext).map_err(|_| Error::decryption("Decryption failed"))
+    })
+        .map_err(crate::error::to_pyerr)
+}
@@ -0,0 +1,168 @@
+use pyo3::prelude::*;
+use argon2::password_hash::rand_core::OsRng;
+use rand::{Rng, RngCore};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::Error;
+use crate::hash::to_hex;
+
+/// Same default as Python's `secrets` module.
+const DEFAULT_TOKEN_BYTES: usize = 32;
+
+/// EFF diceware lists (CC BY 3.0), one `<dice roll>\t<word>` per line.
+/// https://www.eff.org/deeplinks/2016/07/new-wordlists-random-passphrases
+pub(crate) const EFF_LARGE: &str = include_str!("wordlists/eff_large.txt");
+const EFF_SHORT: &str = include_str!("wordlists/eff_short.txt");
+
+/// Largest `count` for the UUID batch function, to bound a single call.
+const MAX_UUID_BATCH: usize = 1 << 20;
+
+/// Letters, digits and the shell/URL-safe symbols. ~6 bits per character.
+const DEFAULT_CHARSET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789!#%+-.:=@^_~";
+
+pub(crate) fn random_bytes(nbytes: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; nbytes];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+/// `nbytes` random bytes from the OS CSPRNG.
+#[pyfunction]
+#[pyo3(signature = (nbytes=DEFAULT_TOKEN_BYTES))]
+pub fn token_bytes(nbytes: usize) -> Vec<u8> {
+    random_bytes(nbytes)
+}
+
+/// `nbytes` random bytes as a hex string (two characters per byte).
+#[pyfunction]
+#[pyo3(signature = (nbytes=DEFAULT_TOKEN_BYTES))]
+pub fn token_hex(nbytes: usize) -> String {
+    to_hex(&random_bytes(nbytes))
+}
+
+/// `nbytes` random bytes as unpadded URL-safe base64, like `secrets.token_urlsafe`.
+#[pyfunction]
+#[pyo3(signature = (nbytes=DEFAULT_TOKEN_BYTES))]
+pub fn token_urlsafe(nbytes: usize) -> String {
+    BASE64_URL.encode(random_bytes(nbytes))
+}
+
+fn load_wordlist(name: &str) -> Result<Vec<&'static str>, Error> {
+    let source = match name.to_ascii_lowercase().as_str() {
+        "eff_large" => EFF_LARGE,
+        "eff_short" => EFF_SHORT,
+        _ => return Err(Error::config(format!("Unknown wordlist: {} (expected eff_large or eff_short)", name))),
+    };
+    Ok(source.lines().filter_map(|line| line.split('\t').nth(1)).collect())
+}
+
+/// Diceware passphrase of `words` words drawn uniformly from `wordlist`
+/// (`eff_large`: ~12.9 bits per word, `eff_short`: ~10.3 bits per word).
+#[pyfunction]
+#[pyo3(signature = (words=6, wordlist="eff_large", separator=" "))]
+pub fn generate_passphrase(words: usize, wordlist: &str, separator: &str) -> PyResult<String> {
+    if words == 0 {
+        return Err(crate::error::to_pyerr(Error::config("words must be positive").with_input("words")));
+    }
+    let list = load_wordlist(wordlist).map_err(crate::error::to_pyerr)?;
+    let chosen: Vec<&str> = (0..words).map(|_| list[OsRng.gen_range(0..list.len())]).collect();
+    Ok(chosen.join(separator))
+}
+
+/// Random password of `length` characters drawn uniformly from `charset`
+/// (duplicate characters are ignored so they don't skew the distribution).
+#[pyfunction]
+#[pyo3(signature = (length=20, charset=DEFAULT_CHARSET))]
+pub fn generate_password(length: usize, charset: &str) -> PyResult<String> {
+    let mut alphabet: Vec<char> = Vec::new();
+    for ch in charset.chars() {
+        if !alphabet.contains(&ch) {
+            alphabet.push(ch);
+        }
+    }
+    if length == 0 || alphabet.len() < 2 {
+        return Err(crate::error::to_pyerr(
+            Error::config("length must be positive and charset must contain at least two distinct characters")
+                .with_input("length")
+        ));
+    }
+    Ok((0..length).map(|_| alphabet[OsRng.gen_range(0..alphabet.len())]).collect())
+}
+
+/// Sets the version nibble and the RFC 9562 variant bits.
+fn uuid_string(mut bytes: [u8; 16], version: u8) -> String {
+    bytes[6] = bytes[6] & 0x0f | version << 4;
+    bytes[8] = bytes[8] & 0x3f | 0x80;
+    let hex = to_hex(&bytes);
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+fn new_uuid4() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    uuid_string(bytes, 4)
+}
+
+/// Last UUIDv7 timestamp (unix ms) and the 12-bit counter used with it.
+static UUID7_STATE: Mutex<(u64, u16)> = Mutex::new((0, 0));
+
+/// RFC 9562 UUIDv7: 48-bit unix milliseconds, then a 12-bit counter (method 1,
+/// randomly seeded each millisecond) and 62 random bits. The counter keeps IDs from
+/// one process strictly increasing within a millisecond and across small clock steps
+/// backwards; when it runs out the timestamp is moved a millisecond ahead.
+fn new_uuid7() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64);
+    let (ms, counter) = {
+        let mut state = UUID7_STATE.lock().unwrap();
+        let (last_ms, last_counter) = *state;
+        *state = if now > last_ms {
+            // Seeded below 2^11 so the millisecond has room for at least 2048 IDs
+            (now, OsRng.gen_range(0..1 << 11))
+        } else if last_counter < 0x0fff {
+            (last_ms, last_counter + 1)
+        } else {
+            (last_ms + 1, 0)
+        };
+        *state
+    };
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes[8..]);
+    bytes[..6].copy_from_slice(&ms.to_be_bytes()[2..]);
+    bytes[6..8].copy_from_slice(&counter.to_be_bytes());
+    uuid_string(bytes, 7)
+}
+
+/// Random (version 4) UUID as a canonical string, from the OS CSPRNG.
+#[pyfunction]
+pub fn uuid4() -> String {
+    new_uuid4()
+}
+
+/// Time-ordered (version 7) UUID as a canonical string. IDs generated by this process
+/// sort in generation order, so they index well as primary keys; they reveal the
+/// creation time to the millisecond.
+#[pyfunction]
+pub fn uuid7() -> String {
+    new_uuid7()
+}
+
+/// `count` UUIDs of `version` 4 or 7 in one call, for bulk inserts.
+#[pyfunction]
+#[pyo3(signature = (count, version=4))]
+pub fn uuid_batch(py: Python<'_>, count: usize, version: u8) -> PyResult<Vec<String>> {
+    if count > MAX_UUID_BATCH {
+        return Err(crate::error::to_pyerr(
+            Error::config(format!("count must be at most {}", MAX_UUID_BATCH)).with_input("count")
+        ));
+    }
+    let generate = match version {
+        4 => new_uuid4,
+        7 => new_uuid7,
+        _ => return Err(crate::error::to_pyerr(
+            Error::config(format!("Unsupported UUID version {} (expected 4 or 7)", version))
+        )),
+    };
+    Ok(py.detach(|| (0..count).map(|_| generate()).collect()))
+}
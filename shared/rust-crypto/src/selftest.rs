@@ -0,0 +1,128 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use argon2::{Argon2, AssociatedData, ParamsBuilder, Version};
+use std::time::{Duration, Instant};
+
+use crate::cipher::Algorithm;
+use crate::encoding::hex_decode;
+use crate::envelope::Envelope;
+use crate::error::Error;
+
+/// When set (to anything but "" or "0"), importing the module runs `self_test()` and
+/// the import fails if any check does.
+const SELF_TEST_ON_IMPORT: &str = "K_SERVICES_CRYPTO_SELF_TEST";
+
+// AES-256-GCM: test case 16 of the GCM specification (McGrew & Viega), with AAD
+const GCM_KEY: &str = "feffe9928665731c6d6a8f9467308308feffe9928665731c6d6a8f9467308308";
+const GCM_NONCE: &str = "cafebabefacedbaddecaf888";
+const GCM_AAD: &str = "feedfacedeadbeeffeedfacedeadbeefabaddad2";
+const GCM_PLAINTEXT: &str = concat!(
+    "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72",
+    "1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39"
+);
+const GCM_CIPHERTEXT: &str = concat!(
+    "522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa",
+    "8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f662",
+    "76fc6ece0f4e1768cddf8853bb2d551b"
+);
+
+// Argon2id: RFC 9106 section 5.3
+const ARGON2ID_TAG: &str = "0d640df58d78766c08c037a34a8b53c9d01ef0452d75b65eb52520e96b01e659";
+
+// Envelope: an `encrypt_to_token` token (PBKDF2 with 1000 iterations, so the check
+// stays fast), cross-checked by decrypting it with an independent implementation
+const ENVELOPE: &str = concat!(
+    "4b5343010103000003e810330c74957d0fb3fca4a638fef01265ef000b010008000000006acf6e0d",
+    "0eec2bbd92343fe527ab424cbfaa73c3002f6f1ce9631770bc6860afd5db567e52c813514cd99e81",
+    "8f8e242fccd7f4b9160aafe46ac6c11da9c0767ca70cb424c8"
+);
+const ENVELOPE_MASTER_KEY: &[u8] = b"kat-master-key";
+const ENVELOPE_CONTEXT: &[u8] = b"kat-context";
+const ENVELOPE_PLAINTEXT: &[u8] = b"k-services envelope known-answer test";
+
+type Check = fn() -> Result<(), Error>;
+
+const CHECKS: &[(&str, Check)] = &[("aes-256-gcm", aes_256_gcm), ("argon2id", argon2id), ("envelope", envelope)];
+
+fn expect(ok: bool, failure: &str) -> Result<(), Error> {
+    if ok { Ok(()) } else { Err(Error::crypto(failure)) }
+}
+
+fn aes_256_gcm() -> Result<(), Error> {
+    let key: [u8; 32] = hex_decode(GCM_KEY)?.try_into().expect("test key is 32 bytes");
+    let (nonce, aad) = (hex_decode(GCM_NONCE)?, hex_decode(GCM_AAD)?);
+    let (plaintext, ciphertext) = (hex_decode(GCM_PLAINTEXT)?, hex_decode(GCM_CIPHERTEXT)?);
+
+    let encrypted = Algorithm::AES_256_GCM.encrypt(&key, &nonce, &plaintext, &aad)?;
+    expect(encrypted == ciphertext, "ciphertext differs from the test vector")?;
+    let decrypted = Algorithm::AES_256_GCM.decrypt(&key, &nonce, &ciphertext, &aad)?;
+    expect(decrypted == plaintext, "decrypted plaintext differs from the test vector")?;
+    let mut tampered = ciphertext;
+    *tampered.last_mut().expect("test ciphertext is not empty") ^= 1;
+    expect(Algorithm::AES_256_GCM.decrypt(&key, &nonce, &tampered, &aad).is_err(), "a tampered tag was accepted")
+}
+
+fn argon2id() -> Result<(), Error> {
+    let params = ParamsBuilder::new()
+        .m_cost(32)
+        .t_cost(3)
+        .p_cost(4)
+        .output_len(32)
+        .data(AssociatedData::new(&[0x04; 12]).map_err(|e| Error::kdf(e.to_string()))?)
+        .build()
+        .map_err(|e| Error::kdf(e.to_string()))?;
+    let argon2 = Argon2::new_with_secret(&[0x03; 8], argon2::Algorithm::Argon2id, Version::V0x13, params)
+        .map_err(|e| Error::kdf(e.to_string()))?;
+    let mut tag = [0u8; 32];
+    argon2.hash_password_into(&[0x01; 32], &[0x02; 16], &mut tag).map_err(|e| Error::kdf(e.to_string()))?;
+    expect(tag.as_slice() == hex_decode(ARGON2ID_TAG)?, "tag differs from the test vector")
+}
+
+fn envelope() -> Result<(), Error> {
+    let data = hex_decode(ENVELOPE)?;
+    let envelope = Envelope::from_bytes(&data)?;
+    expect(envelope.to_bytes()? == data, "envelope does not re-serialize to the same bytes")?;
+    let plaintext = envelope.open(ENVELOPE_MASTER_KEY, ENVELOPE_CONTEXT)?;
+    expect(plaintext.as_slice() == ENVELOPE_PLAINTEXT, "decrypted plaintext differs from the test vector")
+}
+
+fn run() -> Vec<(&'static str, Result<(), Error>, Duration)> {
+    CHECKS.iter().map(|&(name, check)| {
+        let start = Instant::now();
+        (name, check(), start.elapsed())
+    }).collect()
+}
+
+/// Runs known-answer tests for AES-256-GCM (GCM spec test case 16), Argon2id
+/// (RFC 9106) and the envelope format, and returns `{passed, tests}` with one
+/// `{name, passed, error, duration_ms}` per check. Set `K_SERVICES_CRYPTO_SELF_TEST=1`
+/// to run it at import, which then fails if any check does.
+#[pyfunction]
+pub fn self_test(py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+    let results = py.detach(run);
+    let tests = PyList::empty(py);
+    for (name, result, duration) in &results {
+        let test = PyDict::new(py);
+        test.set_item("name", name)?;
+        test.set_item("passed", result.is_ok())?;
+        test.set_item("error", result.as_ref().err().map(Error::message))?;
+        test.set_item("duration_ms", duration.as_secs_f64() * 1000.0)?;
+        tests.append(test)?;
+    }
+    let report = PyDict::new(py);
+    report.set_item("passed", results.iter().all(|(_, result, _)| result.is_ok()))?;
+    report.set_item("tests", tests)?;
+    Ok(report)
+}
+
+pub(crate) fn run_on_import() -> PyResult<()> {
+    if std::env::var(SELF_TEST_ON_IMPORT).is_ok_and(|value| !value.is_empty() && value != "0") {
+        let failures: Vec<String> = run().into_iter()
+            .filter_map(|(name, result, _)| result.err().map(|e| e.context(name).message().to_string()))
+            .collect();
+        if !failures.is_empty() {
+            return Err(crate::error::to_pyerr(Error::crypto(format!("Self-test failed: {}", failures.join("; ")))));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,199 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use argon2::password_hash::rand_core::OsRng;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL};
+use rand::RngCore;
+use serde_json::{Map, Value};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::aad::AadArg;
+use crate::audit::Operation;
+use crate::cipher::Algorithm;
+use crate::error::Error;
+use crate::json::{to_json, to_python};
+use crate::locked::LockedBytes;
+use crate::master_key::MasterKeyArg;
+
+const PREFIX: &str = "kst1.";
+const SALT_LABEL: &[u8] = b"k-services/session-token";
+const DEFAULT_TTL: u64 = 3600;
+const RESERVED_CLAIMS: &[&str] = &["iat", "exp", "iss", "aud"];
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// What the cipher authenticates besides the claims: the token prefix, the cipher
+/// byte and the caller's context.
+fn token_aad(algorithm: Algorithm, aad_context: &[u8]) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(PREFIX.len() + 1 + aad_context.len());
+    aad.extend_from_slice(PREFIX.as_bytes());
+    aad.push(algorithm.envelope_id());
+    aad.extend_from_slice(aad_context);
+    aad
+}
+
+/// Mints and verifies stateless session tokens: a claims dict, encrypted and
+/// authenticated so it can round-trip through untrusted clients, who can neither
+/// read nor change it.
+///
+/// Tokens are `kst1.<base64url(cipher id | nonce | ciphertext)>`, and the claims get
+/// `iat`, `exp` and, when configured, `iss` and `aud`. `aad_context` binds a token to
+/// where it is used, as everywhere in this module. The key is derived from
+/// `master_key` once, with a fixed salt, so every server built with the same master
+/// key and KDF settings accepts the same tokens; rotating the master key logs
+/// everyone out. Revocation before `exp` needs server-side state.
+///
+/// The default cipher is XChaCha20-Poly1305, whose random nonces have no practical
+/// per-key limit; pass `algorithm="aes-256-gcm"` in FIPS mode.
+#[pyclass(frozen)]
+pub struct TokenIssuer {
+    key: LockedBytes,
+    algorithm: Algorithm,
+    issuer: Option<String>,
+    audience: Option<String>,
+    ttl: u64,
+    leeway: u64,
+}
+
+impl TokenIssuer {
+    fn key(&self) -> &[u8; 32] {
+        (*self.key).try_into().expect("derived keys are 32 bytes")
+    }
+
+    fn seal(&self, claims: &Map<String, Value>, aad_context: &[u8]) -> Result<String, Error> {
+        let plaintext = zeroize::Zeroizing::new(serde_json::to_vec(claims).map_err(|e| Error::crypto(e.to_string()))?);
+        let mut payload = vec![self.algorithm.envelope_id()];
+        let mut nonce = vec![0u8; self.algorithm.nonce_len()];
+        OsRng.fill_bytes(&mut nonce);
+        let ciphertext = self.algorithm.encrypt(self.key(), &nonce, &plaintext, &token_aad(self.algorithm, aad_context))?;
+        payload.extend_from_slice(&nonce);
+        payload.extend_from_slice(&ciphertext);
+        Ok(format!("{}{}", PREFIX, BASE64_URL.encode(payload)))
+    }
+
+    fn open(&self, token: &str, aad_context: &[u8], audience: Option<&str>) -> Result<Map<String, Value>, Error> {
+        let payload = token.trim().strip_prefix(PREFIX)
+            .ok_or_else(|| Error::invalid_format("Not a session token: expected the kst1. prefix")
+                .with_code("unrecognized_format"))?;
+        let payload = BASE64_URL.decode(payload)
+            .map_err(|e| Error::invalid_format(format!("Invalid token encoding: {}", e)))?;
+        let (&algorithm, rest) = payload.split_first()
+            .ok_or_else(|| Error::invalid_format("Invalid session token: truncated").with_code("truncated"))?;
+        // Only this issuer's cipher: a token can't pick a weaker one
+        if algorithm != self.algorithm.envelope_id() {
+            return Err(Error::decryption("Decryption failed: session token was not issued with this cipher"));
+        }
+        if rest.len() < self.algorithm.nonce_len() {
+            return Err(Error::invalid_format("Invalid session token: truncated").with_code("truncated"));
+        }
+        let (nonce, ciphertext) = rest.split_at(self.algorithm.nonce_len());
+        let plaintext = zeroize::Zeroizing::new(
+            self.algorithm.decrypt(self.key(), nonce, ciphertext, &token_aad(self.algorithm, aad_context))?
+        );
+        let Ok(Value::Object(claims)) = serde_json::from_slice(&plaintext) else {
+            return Err(Error::invalid_format("Invalid session token: claims are not a JSON object"));
+        };
+
+        let exp = claims.get("exp").and_then(Value::as_u64)
+            .ok_or_else(|| Error::invalid_format("Invalid session token: missing exp"))?;
+        if exp.saturating_add(self.leeway) <= now() {
+            return Err(Error::expired("Token has expired"));
+        }
+        if let Some(issuer) = &self.issuer {
+            if claims.get("iss").and_then(Value::as_str) != Some(issuer.as_str()) {
+                return Err(Error::crypto("Invalid issuer"));
+            }
+        }
+        match (audience.or(self.audience.as_deref()), claims.get("aud").and_then(Value::as_str)) {
+            (None, None) => {}
+            (Some(expected), Some(aud)) if expected == aud => {}
+            (None, Some(_)) => return Err(Error::config("Token has an \"aud\" claim but no audience was given")),
+            _ => return Err(Error::crypto("Invalid audience")),
+        }
+        Ok(claims)
+    }
+}
+
+#[pymethods]
+impl TokenIssuer {
+    /// `issuer` and `audience` are put in every token and required when verifying;
+    /// `ttl` is the default lifetime in seconds and `leeway` the clock skew tolerated
+    /// on `exp`. `kdf_params` / `kdf_profile` pick the one-time key derivation.
+    #[new]
+    #[pyo3(signature = (
+        master_key, issuer=None, audience=None, ttl=DEFAULT_TTL, leeway=0, algorithm="xchacha20-poly1305",
+        kdf_params=None, kdf_profile=None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        py: Python<'_>,
+        master_key: MasterKeyArg,
+        issuer: Option<String>,
+        audience: Option<String>,
+        ttl: u64,
+        leeway: u64,
+        algorithm: &str,
+        kdf_params: Option<&str>,
+        kdf_profile: Option<&str>
+    ) -> PyResult<Self> {
+        if ttl == 0 {
+            return Err(crate::error::to_pyerr(Error::config("ttl must be at least 1 second").with_input("ttl")));
+        }
+        let algorithm = Algorithm::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+        let (kdf_params, _) = crate::resolve_kdf_params(kdf_params, kdf_profile, None, None, None)?;
+        let salt = crate::deterministic::context_salt(SALT_LABEL, &[]);
+        let key = py.detach(|| kdf_params.derive(&master_key, &salt))
+            .map(|key| LockedBytes::new(key.as_slice()))
+            .map_err(crate::error::to_pyerr)?;
+        Ok(TokenIssuer { key, algorithm, issuer, audience, ttl, leeway })
+    }
+
+    /// Encrypts `claims` (a JSON-serializable dict) into a token valid for `ttl`
+    /// seconds (default: the issuer's). `iat`, `exp`, `iss` and `aud` are set here and
+    /// can't be passed in `claims`; `audience` overrides the issuer's for this token.
+    #[pyo3(signature = (claims, aad_context, ttl=None, audience=None))]
+    fn issue(
+        &self,
+        py: Python<'_>,
+        claims: &Bound<'_, PyDict>,
+        aad_context: AadArg,
+        ttl: Option<u64>,
+        audience: Option<&str>
+    ) -> PyResult<String> {
+        let Value::Object(mut claims) = to_json(claims.as_any())? else { unreachable!("dicts convert to objects") };
+        if let Some(reserved) = RESERVED_CLAIMS.iter().find(|name| claims.contains_key(**name)) {
+            return Err(crate::error::to_pyerr(
+                Error::config(format!("claims must not contain \"{}\": TokenIssuer sets it", reserved))
+                    .with_input("claims")
+            ));
+        }
+        let iat = now();
+        claims.insert("iat".to_string(), iat.into());
+        claims.insert("exp".to_string(), iat.saturating_add(ttl.unwrap_or(self.ttl)).into());
+        if let Some(issuer) = &self.issuer {
+            claims.insert("iss".to_string(), issuer.as_str().into());
+        }
+        if let Some(audience) = audience.or(self.audience.as_deref()) {
+            claims.insert("aud".to_string(), audience.into());
+        }
+        let operation = Operation::new("encrypt", self.algorithm.name()).with_aad(&aad_context);
+        py.detach(|| operation.run(|| self.seal(&claims, &aad_context))).map_err(crate::error::to_pyerr)
+    }
+
+    /// Decrypts `token` and returns its claims, after checking `exp`, `iss` and `aud`
+    /// (`audience` overrides the issuer's). Tampered tokens, or ones used with another
+    /// `aad_context`, raise `DecryptionError`.
+    #[pyo3(signature = (token, aad_context, audience=None))]
+    fn verify<'py>(&self, py: Python<'py>, token: &str, aad_context: AadArg, audience: Option<&str>) -> PyResult<Bound<'py, PyAny>> {
+        let operation = Operation::new("decrypt", self.algorithm.name()).with_aad(&aad_context);
+        let claims = py.detach(|| operation.run(|| self.open(token, &aad_context, audience)))
+            .map_err(crate::error::to_pyerr)?;
+        to_python(py, &Value::Object(claims))
+    }
+
+    #[getter]
+    fn algorithm(&self) -> &'static str {
+        self.algorithm.name()
+    }
+}
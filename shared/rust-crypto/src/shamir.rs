@@ -0,0 +1,262 @@
+use pyo3::prelude::*;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+use crate::encoding::Encoding;
+use crate::envelope::Reader;
+use crate::error::Error;
+use crate::master_key::{MasterKey, MasterKeyArg};
+use crate::random::random_bytes;
+
+pub(crate) const MAGIC: &[u8; 3] = b"KSS";
+const VERSION: u8 = 1;
+const SPLIT_ID_LEN: usize = 4;
+const CHECKSUM_LEN: usize = 4;
+
+/// Multiplication in GF(2^8) with the AES polynomial, without data-dependent branches.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    for _ in 0..8 {
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        let carry = 0u8.wrapping_sub(a >> 7);
+        a = (a << 1) ^ (carry & 0x1b);
+        b >>= 1;
+    }
+    product
+}
+
+/// a^254 = a^-1 for every non-zero a.
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1u8;
+    let mut base = a;
+    let mut exponent = 254u8;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// One share of a split secret.
+///
+/// ```text
+/// magic "KSS" | version u8 | split_id (4) | threshold u8 | x u8 | y
+/// ```
+///
+/// `y` covers the secret followed by the first 4 bytes of its SHA-256, so combining
+/// the wrong shares is reported instead of silently yielding a different key.
+struct Share {
+    split_id: [u8; SPLIT_ID_LEN],
+    threshold: u8,
+    x: u8,
+    y: Zeroizing<Vec<u8>>,
+}
+
+impl Share {
+    fn encode(&self) -> String {
+        let mut data = Zeroizing::new(Vec::with_capacity(MAGIC.len() + SPLIT_ID_LEN + 3 + self.y.len()));
+        data.extend_from_slice(MAGIC);
+        data.push(VERSION);
+        data.extend_from_slice(&self.split_id);
+        data.push(self.threshold);
+        data.push(self.x);
+        data.extend_from_slice(&self.y);
+        BASE64_URL.encode(&data)
+    }
+
+    fn decode(share: &str) -> Result<Share, Error> {
+        let data = Encoding::Base64Url.decode_secret_text(share, "share")?;
+        let mut reader = Reader { data: &data, pos: 0 };
+        if reader.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+            return Err(Error::invalid_format("Invalid share: bad magic").with_code("unrecognized_format"));
+        }
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(
+                Error::invalid_format(format!("Unsupported share version: {}", version))
+                    .with_code("unsupported_version")
+            );
+        }
+        let split_id = reader.take(SPLIT_ID_LEN)?.try_into().unwrap();
+        let threshold = reader.u8()?;
+        let x = reader.u8()?;
+        let y = Zeroizing::new(data[reader.pos..].to_vec());
+        if x == 0 || threshold < 2 || y.len() <= CHECKSUM_LEN {
+            return Err(Error::invalid_format("Invalid share"));
+        }
+        Ok(Share { split_id, threshold, x, y })
+    }
+}
+
+fn checksum(secret: &[u8]) -> [u8; CHECKSUM_LEN] {
+    Sha256::digest(secret)[..CHECKSUM_LEN].try_into().unwrap()
+}
+
+fn split(secret: &[u8], threshold: u8, shares: u8) -> Vec<Share> {
+    let mut payload = Zeroizing::new(secret.to_vec());
+    payload.extend_from_slice(&checksum(secret));
+
+    let split_id: [u8; SPLIT_ID_LEN] = random_bytes(SPLIT_ID_LEN).try_into().unwrap();
+    let mut result: Vec<Share> = (1..=shares)
+        .map(|x| Share { split_id, threshold, x, y: Zeroizing::new(Vec::with_capacity(payload.len())) })
+        .collect();
+
+    // One random polynomial of degree threshold - 1 per byte, constant term = the byte
+    for &byte in payload.iter() {
+        let coefficients = Zeroizing::new(random_bytes(threshold as usize - 1));
+        for share in result.iter_mut() {
+            // Horner's rule, highest coefficient first
+            let mut y = 0u8;
+            for &coefficient in coefficients.iter().rev() {
+                y = gf_mul(y, share.x) ^ coefficient;
+            }
+            share.y.push(gf_mul(y, share.x) ^ byte);
+        }
+    }
+    result
+}
+
+fn combine(shares: &[String]) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let shares = shares.iter().map(|s| Share::decode(s)).collect::<Result<Vec<_>, _>>()?;
+    let first = shares.first().ok_or_else(|| Error::config("No shares given"))?;
+    if shares.iter().any(|s| s.split_id != first.split_id || s.threshold != first.threshold || s.y.len() != first.y.len()) {
+        return Err(Error::crypto("Shares come from different splits"));
+    }
+    for (i, share) in shares.iter().enumerate() {
+        if shares[..i].iter().any(|other| other.x == share.x) {
+            return Err(Error::crypto("Duplicate share"));
+        }
+    }
+    let threshold = first.threshold as usize;
+    if shares.len() < threshold {
+        return Err(Error::config(format!("Need at least {} shares, got {}", threshold, shares.len())));
+    }
+    let shares = &shares[..threshold];
+
+    // Lagrange basis polynomials evaluated at x = 0
+    let weights: Vec<u8> = shares.iter().map(|share| {
+        shares.iter().filter(|other| other.x != share.x).fold(1u8, |weight, other| {
+            gf_mul(weight, gf_mul(other.x, gf_inv(other.x ^ share.x)))
+        })
+    }).collect();
+
+    let mut payload = Zeroizing::new(vec![0u8; first.y.len()]);
+    for (share, &weight) in shares.iter().zip(&weights) {
+        for (byte, &y) in payload.iter_mut().zip(share.y.iter()) {
+            *byte ^= gf_mul(y, weight);
+        }
+    }
+
+    let (secret, check) = payload.split_at(payload.len() - CHECKSUM_LEN);
+    if !bool::from(checksum(secret).ct_eq(check)) {
+        return Err(Error::crypto("Shares do not combine to a valid secret"));
+    }
+    Ok(Zeroizing::new(secret.to_vec()))
+}
+
+/// Splits `master_key` into `shares` Shamir shares, any `threshold` of which rebuild
+/// it with `combine_shares`; fewer reveal nothing about it. Shares are URL-safe strings
+/// meant to be handed to different custodians.
+#[pyfunction]
+pub fn split_secret(master_key: MasterKeyArg, threshold: u8, shares: u8) -> PyResult<Vec<String>> {
+    if threshold < 2 || threshold > shares {
+        return Err(crate::error::to_pyerr(Error::config("Need 2 <= threshold <= shares <= 255")));
+    }
+    if master_key.is_empty() {
+        return Err(crate::error::to_pyerr(Error::config("master_key must not be empty").with_input("master_key")));
+    }
+    Ok(split(&master_key, threshold, shares).iter().map(Share::encode).collect())
+}
+
+/// Rebuilds the master key from at least `threshold` shares of the same split.
+/// Returns a `MasterKey`, so the reassembled key stays out of Python strings.
+#[pyfunction]
+pub fn combine_shares(shares: Vec<String>) -> PyResult<MasterKey> {
+    let secret = combine(&shares).map_err(crate::error::to_pyerr)?;
+    Ok(MasterKey::from_secret(secret))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Kind;
+
+    fn shares(secret: &[u8], threshold: u8, count: u8) -> Vec<String> {
+        split(secret, threshold, count).iter().map(Share::encode).collect()
+    }
+
+    #[test]
+    fn gf_inverse() {
+        for a in 1..=255u8 {
+            assert_eq!(gf_mul(a, gf_inv(a)), 1, "{}", a);
+        }
+        assert_eq!(gf_mul(0x57, 0x83), 0xc1);
+    }
+
+    #[test]
+    fn any_threshold_shares_combine() {
+        let secret = b"correct horse battery staple";
+        let all = shares(secret, 3, 5);
+        for a in 0..5 {
+            for b in 0..5 {
+                for c in 0..5 {
+                    if a == b || b == c || a == c {
+                        continue;
+                    }
+                    let subset = [all[a].clone(), all[b].clone(), all[c].clone()];
+                    assert_eq!(combine(&subset).unwrap().as_slice(), secret, "shares {}, {}, {}", a, b, c);
+                }
+            }
+        }
+        assert_eq!(combine(&all).unwrap().as_slice(), secret);
+    }
+
+    #[test]
+    fn too_few_shares() {
+        let all = shares(b"secret", 3, 5);
+        assert_eq!(combine(&all[..2]).unwrap_err().kind(), Kind::Config);
+        assert_eq!(combine(&[]).unwrap_err().kind(), Kind::Config);
+    }
+
+    #[test]
+    fn duplicate_shares() {
+        let all = shares(b"secret", 2, 3);
+        let error = combine(&[all[0].clone(), all[0].clone()]).unwrap_err();
+        assert_eq!(error.message(), "Duplicate share");
+    }
+
+    #[test]
+    fn shares_of_different_splits() {
+        let first = shares(b"secret", 2, 3);
+        let second = shares(b"secret", 2, 3);
+        let error = combine(&[first[0].clone(), second[1].clone()]).unwrap_err();
+        assert_eq!(error.message(), "Shares come from different splits");
+    }
+
+    #[test]
+    fn altered_share() {
+        let all = shares(b"secret", 2, 3);
+        let mut share = Share::decode(&all[1]).unwrap();
+        share.y[0] ^= 1;
+        let error = combine(&[all[0].clone(), share.encode()]).unwrap_err();
+        assert_eq!(error.message(), "Shares do not combine to a valid secret");
+    }
+
+    #[test]
+    fn malformed_shares() {
+        let share = shares(b"secret", 2, 3).remove(0);
+        assert_eq!(Share::decode("not a share!").err().unwrap().code(), "invalid_encoding");
+        assert_eq!(Share::decode(&BASE64_URL.encode(b"XYZ\x01")).err().unwrap().code(), "unrecognized_format");
+        let mut data = BASE64_URL.decode(&share).unwrap();
+        data[MAGIC.len()] = VERSION + 1;
+        assert_eq!(Share::decode(&BASE64_URL.encode(&data)).err().unwrap().code(), "unsupported_version");
+        data[MAGIC.len()] = VERSION;
+        data.truncate(MAGIC.len() + 1 + SPLIT_ID_LEN + 2 + CHECKSUM_LEN);
+        assert_eq!(Share::decode(&BASE64_URL.encode(&data)).err().unwrap().kind(), Kind::InvalidFormat);
+    }
+}
@@ -0,0 +1,222 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyString};
+use argon2::password_hash::rand_core::OsRng;
+use rand::RngCore;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+use crate::aad::AadArg;
+use crate::audit::Operation;
+use crate::buffer::BytesLike;
+use crate::cipher::Algorithm;
+use crate::datakey::KeyWrapper;
+use crate::error::Error;
+use crate::kdf::KdfParams;
+use crate::locked::LockedBytes;
+use crate::master_key::MasterKeyArg;
+use crate::provider::{KeyProvider, Provider};
+
+/// Store value that replaces a shredded subject's wrapped key, followed by the unix time.
+const TOMBSTONE_PREFIX: &str = "shredded:";
+const ALGORITHM: Algorithm = Algorithm::AES_256_GCM;
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+fn tombstone_time(value: &str) -> Option<u64> {
+    value.strip_prefix(TOMBSTONE_PREFIX).map(|time| time.parse().unwrap_or(0))
+}
+
+/// Binds ciphertexts to their subject, so one subject's record can't be passed off as another's.
+fn subject_aad(subject_id: &str, aad_context: &[u8]) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(4 + subject_id.len() + aad_context.len());
+    aad.extend_from_slice(&(subject_id.len() as u32).to_be_bytes());
+    aad.extend_from_slice(subject_id.as_bytes());
+    aad.extend_from_slice(aad_context);
+    aad
+}
+
+/// Per-subject data keys for crypto-shredding (e.g. GDPR erasure). Each subject's
+/// data is encrypted under its own random key, kept in `store` wrapped by
+/// `master_key` or `provider`; `shred()` replaces the wrapped key with a tombstone,
+/// after which nothing encrypted for that subject can be decrypted by anyone.
+///
+/// `store` is any mutable mapping of subject ID to `str` (a `dict` by default, or a
+/// database-backed mapping); it must support `get`, `setdefault` and item assignment.
+/// Persisting it is up to the caller. Copies of the store (backups, replicas) still
+/// hold wrapped keys, so erasure is only complete once those age out.
+///
+/// Ciphertexts are `nonce || ciphertext` (AES-256-GCM) and authenticate the subject ID
+/// along with `aad_context`. Unwrapped keys are cached in locked memory per instance.
+#[pyclass]
+pub struct SubjectKeyRegistry {
+    master_key: Option<MasterKeyArg>,
+    provider: Option<Arc<dyn Provider>>,
+    kdf_params: KdfParams,
+    store: Py<PyAny>,
+    /// Unwrapped keys by subject, with the wrapped key they came from.
+    keys: Mutex<HashMap<String, (String, Arc<LockedBytes>)>>,
+}
+
+impl SubjectKeyRegistry {
+    fn wrapper(&self) -> KeyWrapper<'_> {
+        match (&self.master_key, &self.provider) {
+            (Some(master_key), _) => KeyWrapper::MasterKey(master_key),
+            (None, Some(provider)) => KeyWrapper::Provider(provider.clone()),
+            (None, None) => unreachable!("checked in new()"),
+        }
+    }
+
+    fn cache(&self, subject_id: &str, wrapped_key: &str, key: Arc<LockedBytes>) {
+        self.keys.lock().unwrap().insert(subject_id.to_string(), (wrapped_key.to_string(), key));
+    }
+
+    /// The subject's stored value: a wrapped key, a tombstone, or `None`.
+    fn stored(&self, py: Python<'_>, subject_id: &str) -> PyResult<Option<String>> {
+        let value = self.store.bind(py).call_method1("get", (subject_id,))?;
+        if value.is_none() { Ok(None) } else { value.extract().map(Some) }
+    }
+
+    fn unwrap_key(&self, py: Python<'_>, subject_id: &str, wrapped_key: &str, create: bool) -> PyResult<Arc<LockedBytes>> {
+        if tombstone_time(wrapped_key).is_some() {
+            self.keys.lock().unwrap().remove(subject_id);
+            return Err(crate::error::to_pyerr(if create {
+                Error::config(format!("Subject {:?} has been shredded; encrypt new data under a new subject ID", subject_id))
+                    .with_code("subject_shredded")
+            } else {
+                Error::decryption(format!("Decryption failed: subject {:?} has been shredded", subject_id))
+                    .with_code("subject_shredded")
+            }));
+        }
+        let wrapper = self.wrapper();
+        let key = py.detach(|| wrapper.unwrap(wrapped_key))
+            .map(|(key, _)| Arc::new(LockedBytes::new(&key)))
+            .map_err(crate::error::to_pyerr)?;
+        self.cache(subject_id, wrapped_key, key.clone());
+        Ok(key)
+    }
+
+    /// The subject's data key, created and stored on first use when `create` is set.
+    /// The store is read every time, so a shred by another instance or process takes
+    /// effect here immediately; the cache only saves the unwrap.
+    fn key(&self, py: Python<'_>, subject_id: &str, create: bool) -> PyResult<Arc<LockedBytes>> {
+        if let Some(wrapped_key) = self.stored(py, subject_id)? {
+            let cached = self.keys.lock().unwrap().get(subject_id)
+                .filter(|(cached_wrapped_key, _)| *cached_wrapped_key == wrapped_key)
+                .map(|(_, key)| key.clone());
+            return match cached {
+                Some(key) => Ok(key),
+                None => self.unwrap_key(py, subject_id, &wrapped_key, create),
+            };
+        }
+        if !create {
+            return Err(crate::error::to_pyerr(
+                Error::decryption(format!("Decryption failed: no key stored for subject {:?}", subject_id))
+            ));
+        }
+
+        let mut key = Zeroizing::new([0u8; 32]);
+        OsRng.fill_bytes(key.as_mut());
+        let key = LockedBytes::new(key.as_slice());
+        let (wrapper, kdf_params) = (self.wrapper(), self.kdf_params);
+        let wrapped_key = py.detach(|| wrapper.wrap(&key, None, kdf_params))
+            .map_err(crate::error::to_pyerr)?;
+        // Another thread may have created the key while the GIL was released; whichever
+        // stored first wins, so no data ends up under a key the store forgot
+        let stored: String = self.store.bind(py).call_method1("setdefault", (subject_id, &wrapped_key))?.extract()?;
+        if stored != wrapped_key {
+            return self.unwrap_key(py, subject_id, &stored, create);
+        }
+        let key = Arc::new(key);
+        self.cache(subject_id, &wrapped_key, key.clone());
+        Ok(key)
+    }
+}
+
+#[pymethods]
+impl SubjectKeyRegistry {
+    /// Exactly one of `master_key` / `provider` wraps the subject keys; `kdf_profile`
+    /// picks the KDF cost for master-key wrapping.
+    #[new]
+    #[pyo3(signature = (master_key=None, provider=None, store=None, kdf_profile=None))]
+    fn new(
+        py: Python<'_>,
+        master_key: Option<MasterKeyArg>,
+        provider: Option<Bound<'_, KeyProvider>>,
+        store: Option<Py<PyAny>>,
+        kdf_profile: Option<&str>
+    ) -> PyResult<Self> {
+        if master_key.is_some() == provider.is_some() {
+            return Err(crate::error::to_pyerr(Error::config("Exactly one of master_key or provider is required")));
+        }
+        let (kdf_params, _) = crate::resolve_kdf_params(None, kdf_profile, None, None, None)?;
+        Ok(SubjectKeyRegistry {
+            master_key,
+            provider: provider.map(|provider| provider.get().inner.clone()),
+            kdf_params,
+            store: store.unwrap_or_else(|| PyDict::new(py).into_any().unbind()),
+            keys: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// The mapping holding wrapped keys and tombstones, to persist.
+    #[getter]
+    fn store(&self, py: Python<'_>) -> Py<PyAny> {
+        self.store.clone_ref(py)
+    }
+
+    /// Encrypts `plaintext` for `subject_id`, creating the subject's key on first use.
+    /// Fails for a shredded subject; new data for the same person needs a new ID.
+    fn encrypt(&self, py: Python<'_>, subject_id: &str, plaintext: BytesLike, aad_context: AadArg) -> PyResult<Vec<u8>> {
+        let key = self.key(py, subject_id, true)?;
+        let key: &[u8; 32] = key[..].try_into().expect("subject keys are 32 bytes");
+        py.detach(|| -> Result<Vec<u8>, Error> {
+            let mut blob = vec![0u8; ALGORITHM.nonce_len()];
+            OsRng.fill_bytes(&mut blob);
+            let operation = Operation::new("encrypt", ALGORITHM.name()).with_aad(&aad_context);
+            let ciphertext = operation.run(|| {
+                ALGORITHM.encrypt(key, &blob, &plaintext, &subject_aad(subject_id, &aad_context))
+            })?;
+            blob.extend_from_slice(&ciphertext);
+            Ok(blob)
+        }).map_err(crate::error::to_pyerr)
+    }
+
+    /// Decrypts what `encrypt` produced for `subject_id`; raises `DecryptionError` once
+    /// the subject is shredded.
+    fn decrypt(&self, py: Python<'_>, subject_id: &str, ciphertext: BytesLike, aad_context: AadArg) -> PyResult<Vec<u8>> {
+        let key = self.key(py, subject_id, false)?;
+        let key: &[u8; 32] = key[..].try_into().expect("subject keys are 32 bytes");
+        py.detach(|| {
+            if ciphertext.len() < ALGORITHM.nonce_len() {
+                return Err(Error::invalid_format("Ciphertext too short").with_code("truncated"));
+            }
+            let (nonce, ciphertext) = ciphertext.split_at(ALGORITHM.nonce_len());
+            let operation = Operation::new("decrypt", ALGORITHM.name()).with_aad(&aad_context);
+            operation.run(|| ALGORITHM.decrypt(key, nonce, ciphertext, &subject_aad(subject_id, &aad_context)))
+        }).map_err(crate::error::to_pyerr)
+    }
+
+    /// Destroys the subject's key: the wrapped key in `store` is overwritten by a
+    /// tombstone recording when, and the cached copy is wiped. Irreversible. Shredding
+    /// a subject without a key still records the tombstone.
+    fn shred(&self, py: Python<'_>, subject_id: &str) -> PyResult<()> {
+        let tombstone = format!("{}{}", TOMBSTONE_PREFIX, now());
+        self.store.bind(py).set_item(subject_id, PyString::new(py, &tombstone))?;
+        self.keys.lock().unwrap().remove(subject_id);
+        crate::logging::info(|| "Shredded a subject key".to_string());
+        Ok(())
+    }
+
+    fn is_shredded(&self, py: Python<'_>, subject_id: &str) -> PyResult<bool> {
+        Ok(self.shredded_at(py, subject_id)?.is_some())
+    }
+
+    /// Unix time of the subject's tombstone, or `None` if it wasn't shredded.
+    fn shredded_at(&self, py: Python<'_>, subject_id: &str) -> PyResult<Option<u64>> {
+        Ok(self.stored(py, subject_id)?.as_deref().and_then(tombstone_time))
+    }
+}
@@ -0,0 +1,58 @@
+use pyo3::prelude::*;
+use argon2::password_hash::rand_core::OsRng;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+
+use crate::audit::Operation;
+use crate::error::Error;
+
+const SIGN: Operation<'static> = Operation::new("sign", "ed25519");
+const VERIFY: Operation<'static> = Operation::new("verify", "ed25519");
+
+pub(crate) fn signing_key(private_key: &[u8]) -> Result<SigningKey, Error> {
+    let seed: &[u8; 32] = private_key.try_into()
+        .map_err(|_| {
+            Error::config(format!("Ed25519 private key must be 32 bytes, got {}", private_key.len()))
+                .with_code("invalid_length").with_input("private_key").with_lengths(32, private_key.len())
+        })?;
+    Ok(SigningKey::from_bytes(seed))
+}
+
+/// Generates an Ed25519 keypair and returns `(private_key, public_key)`,
+/// both 32 bytes (the private key is the RFC 8032 seed).
+#[pyfunction]
+pub fn ed25519_generate_keypair() -> (Vec<u8>, Vec<u8>) {
+    let key = SigningKey::generate(&mut OsRng);
+    (key.to_bytes().to_vec(), key.verifying_key().to_bytes().to_vec())
+}
+
+/// Public key belonging to a 32-byte private key.
+#[pyfunction]
+pub fn ed25519_public_key(private_key: &[u8]) -> PyResult<Vec<u8>> {
+    let key = signing_key(private_key).map_err(crate::error::to_pyerr)?;
+    Ok(key.verifying_key().to_bytes().to_vec())
+}
+
+/// Signs `message` and returns the 64-byte signature.
+#[pyfunction]
+pub fn ed25519_sign(private_key: &[u8], message: &[u8]) -> PyResult<Vec<u8>> {
+    SIGN.run(|| Ok(signing_key(private_key)?.sign(message).to_bytes().to_vec())).map_err(crate::error::to_pyerr)
+}
+
+/// Checks a signature (strict RFC 8032 verification, rejecting malleable and
+/// small-order encodings). Returns `False` for any bad signature; raises only
+/// when the public key itself is malformed.
+#[pyfunction]
+pub fn ed25519_verify(public_key: &[u8], message: &[u8], signature: &[u8]) -> PyResult<bool> {
+    let public_key: &[u8; 32] = public_key.try_into()
+        .map_err(|_| crate::error::to_pyerr(
+            Error::config(format!("Ed25519 public key must be 32 bytes, got {}", public_key.len()))
+                .with_code("invalid_length").with_input("public_key").with_lengths(32, public_key.len())
+        ))?;
+    let key = VerifyingKey::from_bytes(public_key)
+        .map_err(|_| crate::error::to_pyerr(Error::invalid_format("Invalid Ed25519 public key")))?;
+    let valid = VERIFY.run_checked(
+        || Ok(Signature::from_slice(signature).is_ok_and(|signature| key.verify_strict(message, &signature).is_ok())),
+        |valid| (!valid).then_some("invalid_signature"),
+    );
+    valid.map_err(crate::error::to_pyerr)
+}
@@ -0,0 +1,267 @@
+use pyo3::prelude::*;
+use chacha20::cipher::{consts::U10, KeyIvInit, StreamCipher, StreamCipherSeek};
+use chacha20::{hchacha, ChaCha20};
+use crypto_secretbox::aead::{Aead, KeyInit};
+use crypto_secretbox::XSalsa20Poly1305;
+use poly1305::Poly1305;
+use subtle::ConstantTimeEq;
+
+use crate::audit::Operation;
+use crate::error::Error;
+use crate::random::random_bytes;
+
+const SECRETBOX_KEY_LEN: usize = 32;
+const SECRETBOX_NONCE_LEN: usize = 24;
+const SECRETBOX_MAC_LEN: usize = 16;
+
+const SECRETSTREAM_KEY_LEN: usize = 32;
+const SECRETSTREAM_HEADER_LEN: usize = 24;
+/// Encrypted tag byte + Poly1305 MAC added to every message.
+const SECRETSTREAM_ABYTES: usize = 1 + 16;
+const SECRETSTREAM: &str = "secretstream-xchacha20poly1305";
+const TAG_MESSAGE: u8 = 0;
+const TAG_PUSH: u8 = 1;
+const TAG_REKEY: u8 = 2;
+const TAG_FINAL: u8 = TAG_PUSH | TAG_REKEY;
+
+fn secretbox_cipher(key: &[u8]) -> Result<XSalsa20Poly1305, Error> {
+    crate::fips::reject("secretbox (XSalsa20-Poly1305)")?;
+    XSalsa20Poly1305::new_from_slice(key)
+        .map_err(|_| {
+            Error::config(format!("secretbox key must be {} bytes, got {}", SECRETBOX_KEY_LEN, key.len()))
+                .with_code("invalid_length").with_input("key").with_lengths(SECRETBOX_KEY_LEN, key.len())
+        })
+}
+
+/// `crypto_secretbox_easy` (XSalsa20-Poly1305) with a random nonce. Returns
+/// `nonce || mac || ciphertext`, the layout PyNaCl's `SecretBox.encrypt` and most
+/// Node wrappers use; the part after the 24-byte nonce is exactly libsodium's output.
+#[pyfunction]
+pub fn secretbox_encrypt(py: Python<'_>, key: &[u8], plaintext: &[u8]) -> PyResult<Vec<u8>> {
+    let cipher = secretbox_cipher(key).map_err(crate::error::to_pyerr)?;
+    py.detach(|| Operation::new("encrypt", "secretbox").run(|| {
+        let mut blob = random_bytes(SECRETBOX_NONCE_LEN);
+        let boxed = cipher.encrypt(blob.as_slice().into(), plaintext)
+            .map_err(|_| Error::crypto("secretbox encryption failed"))?;
+        blob.extend_from_slice(&boxed);
+        Ok(blob)
+    })).map_err(crate::error::to_pyerr)
+}
+
+/// `crypto_secretbox_open_easy`. Without `nonce`, `ciphertext` must start with it
+/// (as produced by `secretbox_encrypt`); with it, `ciphertext` is the bare `mac || ciphertext`.
+#[pyfunction]
+#[pyo3(signature = (key, ciphertext, nonce=None))]
+pub fn secretbox_decrypt(py: Python<'_>, key: &[u8], ciphertext: &[u8], nonce: Option<&[u8]>) -> PyResult<Vec<u8>> {
+    let cipher = secretbox_cipher(key).map_err(crate::error::to_pyerr)?;
+    py.detach(|| Operation::new("decrypt", "secretbox").run(|| {
+        let (nonce, boxed) = match nonce {
+            Some(nonce) => (nonce, ciphertext),
+            None if ciphertext.len() >= SECRETBOX_NONCE_LEN => ciphertext.split_at(SECRETBOX_NONCE_LEN),
+            None => return Err(Error::invalid_format("Ciphertext too short").with_code("truncated")),
+        };
+        if nonce.len() != SECRETBOX_NONCE_LEN {
+            return Err(crate::error::wrong_length("nonce", SECRETBOX_NONCE_LEN, nonce.len()));
+        }
+        if boxed.len() < SECRETBOX_MAC_LEN {
+            return Err(Error::invalid_format("Ciphertext too short").with_code("truncated"));
+        }
+        cipher.decrypt(nonce.into(), boxed).map_err(|_| Error::decryption("Decryption failed: Integrity check failed"))
+    })).map_err(crate::error::to_pyerr)
+}
+
+/// State of `crypto_secretstream_xchacha20poly1305`: a subkey and a 96-bit nonce made
+/// of a little-endian 32-bit counter followed by 8 bytes mixed with every MAC.
+struct SecretStreamState {
+    key: [u8; 32],
+    nonce: [u8; 12],
+    finished: bool,
+}
+
+impl SecretStreamState {
+    fn new(key: &[u8], header: &[u8]) -> Result<Self, Error> {
+        crate::fips::reject("secretstream (XChaCha20-Poly1305)")?;
+        let key: &[u8; SECRETSTREAM_KEY_LEN] = key.try_into()
+            .map_err(|_| {
+                Error::config(format!("secretstream key must be {} bytes, got {}", SECRETSTREAM_KEY_LEN, key.len()))
+                    .with_code("invalid_length").with_input("key").with_lengths(SECRETSTREAM_KEY_LEN, key.len())
+            })?;
+        if header.len() != SECRETSTREAM_HEADER_LEN {
+            return Err(crate::error::wrong_length("header", SECRETSTREAM_HEADER_LEN, header.len()));
+        }
+        let mut nonce = [0u8; 12];
+        nonce[..4].copy_from_slice(&1u32.to_le_bytes());
+        nonce[4..].copy_from_slice(&header[16..]);
+        Ok(SecretStreamState {
+            key: hchacha::<U10>(key.into(), header[..16].into()).into(),
+            nonce,
+            finished: false,
+        })
+    }
+
+    /// ChaCha20 keystream positioned at 64-byte block `block`.
+    fn cipher(&self, block: u64) -> ChaCha20 {
+        let mut cipher = ChaCha20::new(&self.key.into(), &self.nonce.into());
+        cipher.seek(block * 64);
+        cipher
+    }
+
+    fn mac(&self, ad: &[u8], block: &[u8; 64], ciphertext: &[u8]) -> [u8; 16] {
+        let mut poly_key = [0u8; 32];
+        self.cipher(0).apply_keystream(&mut poly_key);
+
+        // libsodium pads the ciphertext with `(0x10 - 64 + mlen) & 0xf` zero bytes,
+        // i.e. `mlen % 16` rather than up to the next block, so the input isn't block
+        // aligned and has to be assembled before hashing
+        let ad_pad = (16 - ad.len() % 16) % 16;
+        let mut input = Vec::with_capacity(ad.len() + ad_pad + block.len() + 2 * ciphertext.len() + 16);
+        input.extend_from_slice(ad);
+        input.resize(input.len() + ad_pad, 0);
+        input.extend_from_slice(block);
+        input.extend_from_slice(ciphertext);
+        input.resize(input.len() + ciphertext.len() % 16, 0);
+        input.extend_from_slice(&(ad.len() as u64).to_le_bytes());
+        input.extend_from_slice(&((block.len() + ciphertext.len()) as u64).to_le_bytes());
+        Poly1305::new(&poly_key.into()).compute_unpadded(&input).into()
+    }
+
+    fn advance(&mut self, mac: &[u8; 16], tag: u8) {
+        for (byte, m) in self.nonce[4..].iter_mut().zip(mac) {
+            *byte ^= m;
+        }
+        let counter = u32::from_le_bytes(self.nonce[..4].try_into().unwrap()).wrapping_add(1);
+        self.nonce[..4].copy_from_slice(&counter.to_le_bytes());
+        if tag & TAG_REKEY != 0 || counter == 0 {
+            self.rekey();
+        }
+        if tag == TAG_FINAL {
+            self.finished = true;
+        }
+    }
+
+    fn rekey(&mut self) {
+        let mut next = [0u8; 40];
+        next[..32].copy_from_slice(&self.key);
+        next[32..].copy_from_slice(&self.nonce[4..]);
+        self.cipher(0).apply_keystream(&mut next);
+        self.key.copy_from_slice(&next[..32]);
+        self.nonce[..4].copy_from_slice(&1u32.to_le_bytes());
+        self.nonce[4..].copy_from_slice(&next[32..]);
+    }
+
+    fn push(&mut self, message: &[u8], ad: &[u8], tag: u8) -> Result<Vec<u8>, Error> {
+        if self.finished {
+            return Err(Error::crypto("Stream already finalized"));
+        }
+        let mut block = [0u8; 64];
+        block[0] = tag;
+        self.cipher(1).apply_keystream(&mut block);
+        let mut ciphertext = message.to_vec();
+        self.cipher(2).apply_keystream(&mut ciphertext);
+        let mac = self.mac(ad, &block, &ciphertext);
+
+        let mut out = Vec::with_capacity(message.len() + SECRETSTREAM_ABYTES);
+        out.push(block[0]);
+        out.extend_from_slice(&ciphertext);
+        out.extend_from_slice(&mac);
+        self.advance(&mac, tag);
+        Ok(out)
+    }
+
+    fn pull(&mut self, input: &[u8], ad: &[u8]) -> Result<(Vec<u8>, u8), Error> {
+        if self.finished {
+            return Err(Error::crypto("Stream already finalized"));
+        }
+        if input.len() < SECRETSTREAM_ABYTES {
+            return Err(Error::invalid_format("Ciphertext too short").with_code("truncated"));
+        }
+        let mut block = [0u8; 64];
+        block[0] = input[0];
+        self.cipher(1).apply_keystream(&mut block);
+        let tag = block[0];
+        block[0] = input[0];
+
+        let (ciphertext, expected) = input[1..].split_at(input.len() - SECRETSTREAM_ABYTES);
+        let mac = self.mac(ad, &block, ciphertext);
+        if !bool::from(mac.ct_eq(expected)) {
+            return Err(Error::decryption("Decryption failed: Integrity check failed"));
+        }
+        let mut message = ciphertext.to_vec();
+        self.cipher(2).apply_keystream(&mut message);
+        self.advance(&mac, tag);
+        Ok((message, tag))
+    }
+}
+
+/// Encrypting side of libsodium's `crypto_secretstream_xchacha20poly1305`. Send
+/// `header` first, then every `push()` output as a separate message; mark the last
+/// one with `TAG_FINAL` so the reader can tell the stream wasn't truncated.
+#[pyclass]
+pub struct SecretStreamPush {
+    state: SecretStreamState,
+    header: Vec<u8>,
+}
+
+#[pymethods]
+impl SecretStreamPush {
+    #[classattr]
+    const TAG_MESSAGE: u8 = TAG_MESSAGE;
+    #[classattr]
+    const TAG_PUSH: u8 = TAG_PUSH;
+    #[classattr]
+    const TAG_REKEY: u8 = TAG_REKEY;
+    #[classattr]
+    const TAG_FINAL: u8 = TAG_FINAL;
+
+    #[new]
+    fn new(key: &[u8]) -> PyResult<Self> {
+        let header = random_bytes(SECRETSTREAM_HEADER_LEN);
+        let state = SecretStreamState::new(key, &header).map_err(crate::error::to_pyerr)?;
+        Ok(SecretStreamPush { state, header })
+    }
+
+    /// The 24-byte stream header (`crypto_secretstream_xchacha20poly1305_init_push`).
+    #[getter]
+    fn header(&self) -> Vec<u8> {
+        self.header.clone()
+    }
+
+    /// Encrypts one message; the output is `len(message) + 17` bytes.
+    #[pyo3(signature = (message, tag=TAG_MESSAGE, ad=b"".as_slice()))]
+    fn push(&mut self, py: Python<'_>, message: &[u8], tag: u8, ad: &[u8]) -> PyResult<Vec<u8>> {
+        if tag > TAG_FINAL {
+            return Err(crate::error::to_pyerr(Error::invalid_format("Invalid secretstream tag")));
+        }
+        let operation = Operation::new("encrypt", SECRETSTREAM).with_aad(ad);
+        py.detach(|| operation.run(|| self.state.push(message, ad, tag))).map_err(crate::error::to_pyerr)
+    }
+}
+
+/// Decrypting side of `crypto_secretstream_xchacha20poly1305`. Messages must be
+/// pulled in the order they were pushed.
+#[pyclass]
+pub struct SecretStreamPull {
+    state: SecretStreamState,
+}
+
+#[pymethods]
+impl SecretStreamPull {
+    #[new]
+    fn new(key: &[u8], header: &[u8]) -> PyResult<Self> {
+        let state = SecretStreamState::new(key, header).map_err(crate::error::to_pyerr)?;
+        Ok(SecretStreamPull { state })
+    }
+
+    /// Whether the message tagged `TAG_FINAL` has been pulled.
+    #[getter]
+    fn finished(&self) -> bool {
+        self.state.finished
+    }
+
+    /// Decrypts one message and returns `(message, tag)`.
+    #[pyo3(signature = (ciphertext, ad=b"".as_slice()))]
+    fn pull(&mut self, py: Python<'_>, ciphertext: &[u8], ad: &[u8]) -> PyResult<(Vec<u8>, u8)> {
+        let operation = Operation::new("decrypt", SECRETSTREAM).with_aad(ad);
+        py.detach(|| operation.run(|| self.state.pull(ciphertext, ad))).map_err(crate::error::to_pyerr)
+    }
+}
@@ -0,0 +1,541 @@
+use pyo3::prelude::*;
+use argon2::password_hash::rand_core::OsRng;
+use rand::RngCore;
+use zeroize::Zeroizing;
+
+use crate::aad::AadArg;
+use crate::audit::Operation;
+use crate::buffer::BytesLike;
+use crate::cipher::{Algorithm, TAG_LEN};
+use crate::envelope::Reader;
+use crate::error::{Error, Kind};
+use crate::kdf::{DerivedKey, KdfParams};
+use crate::master_key::MasterKeyArg;
+use crate::progress::{CancellationToken, Progress, DEFAULT_PROGRESS_INTERVAL};
+
+const MAGIC: &[u8; 3] = b"KSS";
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+/// STREAM-BE32 ends every nonce with a 4-byte chunk counter and a 1-byte "last chunk"
+/// flag; the rest of the algorithm's nonce is a random prefix.
+const NONCE_SUFFIX_LEN: usize = 5;
+/// Nonce prefix of headers without the magic, which are always AES-256-GCM.
+const LEGACY_NONCE_PREFIX_LEN: usize = 7;
+/// Longest possible header (XChaCha20-Poly1305 with Argon2id), rounded up.
+pub(crate) const MAX_HEADER_LEN: usize = 64;
+pub(crate) const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+/// Largest chunk size accepted, also from a stream header: a decryptor buffers a
+/// whole chunk, so the header must not be able to make it hold gigabytes.
+const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+/// Stream header (all integers big-endian):
+///
+/// ```text
+/// magic "KSS" | version u8 | algorithm u8 | kdf u8 | kdf params | salt (16) | nonce prefix | chunk size u32
+/// ```
+///
+/// The algorithm and KDF are recorded as in an `Envelope` header, and the nonce prefix
+/// is the algorithm's nonce minus the 5 bytes STREAM-BE32 appends. Streams written
+/// before the magic was added have just `salt | nonce prefix (7) | chunk size u32`
+/// and are AES-256-GCM under the default KDF parameters; they are still read (unless
+/// their random salt happens to start with the magic and version).
+struct StreamHeader {
+    algorithm: Algorithm,
+    kdf_params: KdfParams,
+    salt: [u8; SALT_LEN],
+    nonce_prefix: Vec<u8>,
+    chunk_size: usize,
+}
+
+impl StreamHeader {
+    fn new(algorithm: Algorithm, kdf_params: KdfParams, chunk_size: usize) -> Self {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let mut nonce_prefix = vec![0u8; algorithm.nonce_len() - NONCE_SUFFIX_LEN];
+        OsRng.fill_bytes(&mut nonce_prefix);
+        StreamHeader { algorithm, kdf_params, salt, nonce_prefix, chunk_size }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(MAX_HEADER_LEN);
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.push(self.algorithm.envelope_id());
+        self.kdf_params.write(&mut out);
+        out.extend_from_slice(&self.salt);
+        out.extend_from_slice(&self.nonce_prefix);
+        out.extend_from_slice(&(self.chunk_size as u32).to_be_bytes());
+        out
+    }
+
+    /// Parses the header at the start of `data` and returns it with its length, or
+    /// `None` if `data` ends before the header does.
+    fn parse(data: &[u8]) -> Result<Option<(Self, usize)>, Error> {
+        let mut reader = Reader { data, pos: 0 };
+        match Self::read(&mut reader) {
+            Ok(header) => Ok(Some((header, reader.pos))),
+            Err(error) if error.code() == "truncated" => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn read(reader: &mut Reader<'_>) -> Result<Self, Error> {
+        let tagged = reader.take(MAGIC.len())? == MAGIC && reader.u8()? == VERSION;
+        let (algorithm, kdf_params, nonce_prefix_len) = if tagged {
+            let algorithm = Algorithm::from_envelope_id(reader.u8()?)?;
+            (algorithm, KdfParams::read(reader)?, algorithm.nonce_len() - NONCE_SUFFIX_LEN)
+        } else {
+            reader.pos = 0;
+            (Algorithm::AES_256_GCM, KdfParams::default(), LEGACY_NONCE_PREFIX_LEN)
+        };
+        let salt = reader.take(SALT_LEN)?.try_into().unwrap();
+        let nonce_prefix = reader.take(nonce_prefix_len)?.to_vec();
+        let chunk_size = reader.u32()? as usize;
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(Error::invalid_format("Invalid stream header: bad chunk size"));
+        }
+        Ok(StreamHeader { algorithm, kdf_params, salt, nonce_prefix, chunk_size })
+    }
+
+    /// Derives the stream key, ready to seal or open chunk 0.
+    fn cipher(&self, master_key: &[u8]) -> Result<ChunkCipher, Error> {
+        Ok(ChunkCipher {
+            algorithm: self.algorithm,
+            key: self.kdf_params.derive(master_key, &self.salt)?,
+            nonce_prefix: self.nonce_prefix.clone(),
+            counter: 0,
+        })
+    }
+}
+
+/// Algorithm of the stream whose first bytes are `head`, for audit records made
+/// before the header is parsed (AES-256-GCM if `head` isn't a header).
+pub(crate) fn recorded_algorithm(head: &[u8]) -> Algorithm {
+    match StreamHeader::parse(head) {
+        Ok(Some((header, _))) => header.algorithm,
+        _ => Algorithm::AES_256_GCM,
+    }
+}
+
+/// STREAM-BE32 (the `aead` crate's `stream` construction) over a registry AEAD:
+/// chunk `i` is sealed under `nonce prefix | i u32 | last u8`, so chunks can't be
+/// reordered, dropped or moved past the last one.
+struct ChunkCipher {
+    algorithm: Algorithm,
+    key: DerivedKey,
+    nonce_prefix: Vec<u8>,
+    counter: u32,
+}
+
+impl ChunkCipher {
+    fn next_nonce(&mut self, last: bool) -> Result<Vec<u8>, Error> {
+        if !last && self.counter == u32::MAX {
+            return Err(Error::crypto("Stream too long: chunk counter exhausted"));
+        }
+        let mut nonce = self.nonce_prefix.clone();
+        nonce.extend_from_slice(&self.counter.to_be_bytes());
+        nonce.push(last as u8);
+        self.counter += 1;
+        Ok(nonce)
+    }
+
+    fn seal(&mut self, chunk: &[u8], aad: &[u8], last: bool) -> Result<Vec<u8>, Error> {
+        let nonce = self.next_nonce(last)?;
+        self.algorithm.encrypt(&self.key, &nonce, chunk, aad)
+    }
+
+    fn open(&mut self, chunk: &[u8], aad: &[u8], last: bool) -> Result<Zeroizing<Vec<u8>>, Error> {
+        let nonce = self.next_nonce(last)?;
+        self.algorithm.decrypt(&self.key, &nonce, chunk, aad).map(Zeroizing::new)
+    }
+}
+
+/// Every chunk authenticates the stream header together with the caller's AAD,
+/// so tampering with any header field breaks the whole stream.
+fn chunk_aad(header: &[u8], aad_context: &[u8]) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(header.len() + aad_context.len());
+    aad.extend_from_slice(header);
+    aad.extend_from_slice(aad_context);
+    aad
+}
+
+/// A stream is audited as one operation: `finalize()` reports it, `update()` only
+/// when it fails.
+pub(crate) fn operation<'a>(name: &'static str, algorithm: Algorithm, aad_context: &'a [u8]) -> Operation<'a> {
+    Operation::new(name, algorithm.name()).with_aad(aad_context)
+}
+
+/// Chunked AEAD encryptor (STREAM construction) for payloads too large to hold in memory.
+///
+/// Output layout: `header || chunk_0 || ... || chunk_last`, where every chunk except the
+/// last one is exactly `chunk_size + 16` bytes (`chunk_size` is at most 16 MiB). Feed
+/// data with `update()` and write whatever it returns; `finalize()` emits the last
+/// (flagged) chunk and must always be called.
+///
+/// `algorithm` (default AES-256-GCM) and the KDF, a `kdf_profile` or encoded
+/// `kdf_params` as for `encrypt_to_token` (default Argon2id), are recorded in the
+/// header, so `StreamDecryptor` needs only the master key and AAD. In FIPS mode pass
+/// PBKDF2 parameters, e.g. `kdf_params="pbkdf2-sha256:i=600000"`.
+///
+/// `progress(bytes_processed, total)` is called every `progress_interval` bytes passed
+/// to `update()`, and once from `finalize()`; `total` is passed through as given.
+/// Once `cancel` is cancelled, `update()` raises `OperationCancelled`.
+#[pyclass]
+pub struct StreamEncryptor {
+    cipher: Option<ChunkCipher>,
+    algorithm: Algorithm,
+    header: Vec<u8>,
+    header_sent: bool,
+    aad: Vec<u8>,
+    chunk_size: usize,
+    /// Plaintext not yet encrypted; wiped as it is consumed
+    buffer: Zeroizing<Vec<u8>>,
+    progress: Progress,
+}
+
+impl StreamEncryptor {
+    pub(crate) fn create(
+        master_key: &[u8],
+        aad_context: &[u8],
+        chunk_size: usize,
+        algorithm: Algorithm,
+        kdf_params: KdfParams
+    ) -> Result<Self, Error> {
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(
+                Error::config(format!("chunk_size must be between 1 and {}", MAX_CHUNK_SIZE)).with_input("chunk_size")
+            );
+        }
+        let header = StreamHeader::new(algorithm, kdf_params, chunk_size);
+        let cipher = header.cipher(master_key)?;
+        let header = header.to_bytes();
+
+        Ok(StreamEncryptor {
+            cipher: Some(cipher),
+            algorithm,
+            aad: chunk_aad(&header, aad_context),
+            header,
+            header_sent: false,
+            chunk_size,
+            buffer: Zeroizing::new(Vec::new()),
+            progress: Progress::new(None, None, DEFAULT_PROGRESS_INTERVAL),
+        })
+    }
+
+    fn aad_context(&self) -> Vec<u8> {
+        self.aad[self.header.len()..].to_vec()
+    }
+
+    pub(crate) fn push(&mut self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let cipher = self.cipher.as_mut().ok_or_else(|| Error::crypto("Stream already finalized"))?;
+
+        let mut out = Vec::new();
+        if !self.header_sent {
+            out.extend_from_slice(&self.header);
+            self.header_sent = true;
+        }
+
+        self.buffer.extend_from_slice(data);
+        // Strictly greater: the final chunk is held back so finish() can flag it as last
+        while self.buffer.len() > self.chunk_size {
+            let chunk: Zeroizing<Vec<u8>> = Zeroizing::new(self.buffer.drain(..self.chunk_size).collect());
+            out.extend_from_slice(&cipher.seal(&chunk, &self.aad, false)?);
+        }
+
+        Ok(out)
+    }
+
+    pub(crate) fn finish(&mut self) -> Result<Vec<u8>, Error> {
+        let mut cipher = self.cipher.take().ok_or_else(|| Error::crypto("Stream already finalized"))?;
+
+        let mut out = Vec::new();
+        if !self.header_sent {
+            out.extend_from_slice(&self.header);
+            self.header_sent = true;
+        }
+
+        let chunk = std::mem::take(&mut self.buffer);
+        out.extend_from_slice(&cipher.seal(&chunk, &self.aad, true)?);
+
+        Ok(out)
+    }
+}
+
+#[pymethods]
+impl StreamEncryptor {
+    #[new]
+    #[pyo3(signature = (
+        master_key, aad_context, chunk_size=DEFAULT_CHUNK_SIZE,
+        progress=None, total=None, progress_interval=DEFAULT_PROGRESS_INTERVAL, cancel=None,
+        algorithm=Algorithm::DEFAULT_NAME, kdf_profile=None, kdf_params=None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        py: Python<'_>,
+        master_key: MasterKeyArg,
+        aad_context: AadArg,
+        chunk_size: usize,
+        progress: Option<Py<PyAny>>,
+        total: Option<u64>,
+        progress_interval: u64,
+        cancel: Option<PyRef<'_, CancellationToken>>,
+        algorithm: &str,
+        kdf_profile: Option<&str>,
+        kdf_params: Option<&str>
+    ) -> PyResult<Self> {
+        let algorithm = Algorithm::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+        let (kdf_params, _) = crate::resolve_kdf_params(kdf_params, kdf_profile, None, None, None)?;
+        let mut encryptor = py.detach(|| Self::create(&master_key, &aad_context, chunk_size, algorithm, kdf_params))
+            .map_err(crate::error::to_pyerr)?;
+        encryptor.progress = Progress::new(progress, total, progress_interval).with_cancel(cancel.as_deref());
+        Ok(encryptor)
+    }
+
+    /// Buffers `data` and returns the encrypted bytes for every chunk completed so far.
+    fn update(&mut self, py: Python<'_>, data: BytesLike) -> PyResult<Vec<u8>> {
+        let aad_context = self.aad_context();
+        let out = py.detach(|| operation("encrypt", self.algorithm, &aad_context).run_step(|| self.push(&data)))
+            .map_err(crate::error::to_pyerr)?;
+        self.progress.advance(data.len()).map_err(|e| self.progress.to_pyerr(e))?;
+        Ok(out)
+    }
+
+    /// Encrypts the remaining buffered data as the last chunk, closing the stream.
+    fn finalize(&mut self, py: Python<'_>) -> PyResult<Vec<u8>> {
+        let aad_context = self.aad_context();
+        let out = py.detach(|| operation("encrypt", self.algorithm, &aad_context).run(|| self.finish()))
+            .map_err(crate::error::to_pyerr)?;
+        self.progress.finish().map_err(|e| self.progress.to_pyerr(e))?;
+        Ok(out)
+    }
+}
+
+/// Counterpart of `StreamEncryptor`. The algorithm and KDF are read from the stream
+/// header. Chunks are verified in order; a stream that was truncated, reordered or
+/// extended fails in `update()` or `finalize()`.
+///
+/// Plaintext returned by `update()` is already authenticated, but the stream as a whole
+/// is only proven complete once `finalize()` succeeds. `progress` and `cancel` work
+/// as in `StreamEncryptor`, counting the encrypted bytes passed to `update()`.
+#[pyclass]
+pub struct StreamDecryptor {
+    cipher: Option<ChunkCipher>,
+    /// What the header records once parsed; until then what audit records report
+    algorithm: Algorithm,
+    master_key: Zeroizing<Vec<u8>>,
+    aad_context: Vec<u8>,
+    aad: Vec<u8>,
+    chunk_size: usize,
+    buffer: Vec<u8>,
+    finished: bool,
+    progress: Progress,
+}
+
+impl StreamDecryptor {
+    pub(crate) fn create(master_key: &[u8], aad_context: &[u8]) -> Self {
+        StreamDecryptor {
+            cipher: None,
+            algorithm: Algorithm::AES_256_GCM,
+            master_key: Zeroizing::new(master_key.to_vec()),
+            aad_context: aad_context.to_vec(),
+            aad: Vec::new(),
+            chunk_size: 0,
+            buffer: Vec::new(),
+            finished: false,
+            progress: Progress::new(None, None, DEFAULT_PROGRESS_INTERVAL),
+        }
+    }
+
+    /// Parses the stream header once it is fully buffered and sets up the cipher.
+    fn init_from_header(&mut self) -> Result<bool, Error> {
+        let Some((header, len)) = StreamHeader::parse(&self.buffer)? else {
+            return Ok(false);
+        };
+        self.cipher = Some(header.cipher(&self.master_key)?);
+        self.algorithm = header.algorithm;
+        self.aad = chunk_aad(&self.buffer[..len], &self.aad_context);
+        self.chunk_size = header.chunk_size;
+        self.buffer.drain(..len);
+        Ok(true)
+    }
+
+    pub(crate) fn push(&mut self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        if self.finished {
+            return Err(Error::crypto("Stream already finalized"));
+        }
+        self.buffer.extend_from_slice(data);
+
+        if self.cipher.is_none() && !self.init_from_header()? {
+            return Ok(Vec::new());
+        }
+
+        let cipher = self.cipher.as_mut().unwrap();
+        let encrypted_chunk_size = self.chunk_size + TAG_LEN;
+
+        let mut out = Vec::new();
+        // Strictly greater: the trailing chunk might be the last one, which only finish() may open
+        while self.buffer.len() > encrypted_chunk_size {
+            let chunk: Vec<u8> = self.buffer.drain(..encrypted_chunk_size).collect();
+            out.extend_from_slice(&cipher.open(&chunk, &self.aad, false)?);
+        }
+
+        Ok(out)
+    }
+
+    pub(crate) fn finish(&mut self) -> Result<Vec<u8>, Error> {
+        if self.finished {
+            return Err(Error::crypto("Stream already finalized"));
+        }
+        self.finished = true;
+
+        if self.cipher.is_none() && !self.init_from_header()? {
+            return Err(Error::invalid_format("Truncated stream: header missing").with_code("truncated"));
+        }
+        let mut cipher = self.cipher.take().unwrap();
+
+        let chunk = std::mem::take(&mut self.buffer);
+        let plaintext = cipher.open(&chunk, &self.aad, true).map_err(|error| match error.kind() {
+            Kind::Decryption => Error::decryption("Decryption failed: stream truncated, tampered or context mismatch")
+                .with_code("stream_truncated"),
+            _ => error,
+        })?;
+        Ok(plaintext.to_vec())
+    }
+}
+
+#[pymethods]
+impl StreamDecryptor {
+    #[new]
+    #[pyo3(signature = (
+        master_key, aad_context, progress=None, total=None, progress_interval=DEFAULT_PROGRESS_INTERVAL, cancel=None
+    ))]
+    fn new(
+        master_key: MasterKeyArg,
+        aad_context: AadArg,
+        progress: Option<Py<PyAny>>,
+        total: Option<u64>,
+        progress_interval: u64,
+        cancel: Option<PyRef<'_, CancellationToken>>
+    ) -> Self {
+        let mut decryptor = Self::create(&master_key, &aad_context);
+        decryptor.progress = Progress::new(progress, total, progress_interval).with_cancel(cancel.as_deref());
+        decryptor
+    }
+
+    /// Buffers `data` and returns the plaintext of every non-final chunk received so far.
+    fn update(&mut self, py: Python<'_>, data: BytesLike) -> PyResult<Vec<u8>> {
+        let aad_context = self.aad_context.clone();
+        let out = py.detach(|| operation("decrypt", self.algorithm, &aad_context).run_step(|| self.push(&data)))
+            .map_err(crate::error::to_pyerr)?;
+        self.progress.advance(data.len()).map_err(|e| self.progress.to_pyerr(e))?;
+        Ok(out)
+    }
+
+    /// Decrypts the last chunk. Fails if the stream was truncated or has trailing data.
+    fn finalize(&mut self, py: Python<'_>) -> PyResult<Vec<u8>> {
+        let aad_context = self.aad_context.clone();
+        let out = py.detach(|| operation("decrypt", self.algorithm, &aad_context).run(|| self.finish()))
+            .map_err(crate::error::to_pyerr)?;
+        self.progress.finish().map_err(|e| self.progress.to_pyerr(e))?;
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes_gcm::aead::{stream::EncryptorBE32, KeyInit, Payload};
+    use aes_gcm::Aes256Gcm;
+
+    const KEY: &[u8] = b"stream test master key";
+    const CHUNK: usize = 10;
+
+    fn kdf_params() -> KdfParams {
+        KdfParams::new(Some(8), Some(1), Some(1)).unwrap()
+    }
+
+    /// Header and chunks of a stream over `CHUNK`-byte chunks.
+    fn encrypt(algorithm: Algorithm, plaintext: &[u8]) -> (Vec<u8>, Vec<Vec<u8>>) {
+        let mut encryptor = StreamEncryptor::create(KEY, b"ctx", CHUNK, algorithm, kdf_params()).unwrap();
+        let mut out = encryptor.push(plaintext).unwrap();
+        out.extend(encryptor.finish().unwrap());
+        let body = out.split_off(encryptor.header.len());
+        (out, body.chunks(CHUNK + TAG_LEN).map(<[u8]>::to_vec).collect())
+    }
+
+    fn decrypt(data: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut decryptor = StreamDecryptor::create(KEY, b"ctx");
+        let mut out = Vec::new();
+        for piece in data.chunks(7) {
+            out.extend(decryptor.push(piece)?);
+        }
+        out.extend(decryptor.finish()?);
+        Ok(out)
+    }
+
+    #[test]
+    fn round_trips_with_every_algorithm() {
+        for algorithm in [Algorithm::AES_256_GCM, Algorithm::XCHACHA20_POLY1305, Algorithm::AES_256_GCM_SIV] {
+            for len in [0, 1, CHUNK, CHUNK + 1, 3 * CHUNK] {
+                let plaintext: Vec<u8> = (0..len as u8).collect();
+                let (header, chunks) = encrypt(algorithm, &plaintext);
+                assert_eq!(recorded_algorithm(&header).name(), algorithm.name());
+                assert_eq!(decrypt(&[header, chunks.concat()].concat()).unwrap(), plaintext, "{}", algorithm.name());
+            }
+        }
+    }
+
+    #[test]
+    fn reads_streams_without_the_magic() {
+        // The layout before the algorithm and KDF were recorded, made with the `aead` crate
+        let (salt, nonce_prefix) = ([3u8; SALT_LEN], [4u8; LEGACY_NONCE_PREFIX_LEN]);
+        let mut header = [&salt[..], &nonce_prefix, &(CHUNK as u32).to_be_bytes()].concat();
+        let key = KdfParams::default().derive(KEY, &salt).unwrap();
+        let cipher = Aes256Gcm::new(key.as_slice().into());
+        let mut encryptor = EncryptorBE32::from_aead(cipher, nonce_prefix.as_slice().into());
+        let aad = chunk_aad(&header, b"ctx");
+        header.extend(encryptor.encrypt_next(Payload { msg: b"0123456789", aad: &aad }).unwrap());
+        header.extend(encryptor.encrypt_last(Payload { msg: b"abc", aad: &aad }).unwrap());
+        assert_eq!(decrypt(&header).unwrap(), b"0123456789abc");
+    }
+
+    #[test]
+    fn rejects_truncated_streams() {
+        let (header, chunks) = encrypt(Algorithm::AES_256_GCM, &[1; 3 * CHUNK + 5]);
+        let error = decrypt(&[header.clone(), chunks[..3].concat()].concat()).unwrap_err();
+        assert_eq!(error.code(), "stream_truncated");
+        let mut stream = [header.clone(), chunks.concat()].concat();
+        stream.pop();
+        assert_eq!(decrypt(&stream).unwrap_err().code(), "stream_truncated");
+        assert_eq!(decrypt(&header[..header.len() - 1]).unwrap_err().code(), "truncated");
+    }
+
+    #[test]
+    fn rejects_reordered_chunks() {
+        let (header, mut chunks) = encrypt(Algorithm::AES_256_GCM, &[1; 3 * CHUNK + 5]);
+        chunks.swap(0, 1);
+        assert_eq!(decrypt(&[header, chunks.concat()].concat()).unwrap_err().kind(), Kind::Decryption);
+    }
+
+    #[test]
+    fn only_the_flagged_chunk_ends_the_stream() {
+        // Full chunks only: the last one is held back so it can be flagged
+        let (header, chunks) = encrypt(Algorithm::AES_256_GCM, &[1; 2 * CHUNK]);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1].len(), CHUNK + TAG_LEN);
+        // The last chunk can't go first, nor can anything follow it
+        let last_first = [header.clone(), chunks[1].clone(), chunks[0].clone()].concat();
+        assert_eq!(decrypt(&last_first).unwrap_err().kind(), Kind::Decryption);
+        let extended = [header.clone(), chunks.concat(), vec![0; CHUNK + TAG_LEN]].concat();
+        assert_eq!(decrypt(&extended).unwrap_err().kind(), Kind::Decryption);
+        assert_eq!(decrypt(&[header, chunks.concat()].concat()).unwrap(), [1; 2 * CHUNK]);
+    }
+
+    #[test]
+    fn authenticates_the_header() {
+        let (header, chunks) = encrypt(Algorithm::AES_256_GCM, b"payload");
+        let mut stream = [header.clone(), chunks.concat()].concat();
+        stream[header.len() - 5] ^= 1;
+        assert_eq!(decrypt(&stream).unwrap_err().kind(), Kind::Decryption);
+    }
+}
@@ -0,0 +1,560 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Most common leaked passwords, most common first.
+const COMMON_PASSWORDS: &str = include_str!("wordlists/common_passwords.txt");
+/// Only this many characters are analysed; anything longer is strong regardless.
+const MAX_LEN: usize = 100;
+const MAX_WORD_LEN: usize = 30;
+
+// Guess model constants, as in zxcvbn
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+const MIN_GUESSES_SINGLE_CHAR: f64 = 10.0;
+const MIN_GUESSES_MULTI_CHAR: f64 = 50.0;
+/// Extra guesses charged per additional match, so many small matches don't beat bruteforce.
+const MIN_GUESSES_BEFORE_GROWING_SEQUENCE: f64 = 10_000.0;
+const MIN_YEAR_SPACE: f64 = 20.0;
+/// Starting keys and average neighbours on a QWERTY keyboard, for straight key runs.
+const KEYBOARD_STARTS: f64 = 94.0;
+const KEYBOARD_DEGREE: f64 = 4.6;
+const KEYBOARD_ROWS: &[&str] = &["1234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm"];
+/// Common substitutions: symbol, the letter it usually stands for, and the other one.
+const L33T: &[(char, char, char)] = &[
+    ('4', 'a', 'a'), ('@', 'a', 'a'), ('8', 'b', 'b'), ('(', 'c', 'c'), ('{', 'c', 'c'), ('<', 'c', 'c'),
+    ('3', 'e', 'e'), ('6', 'g', 'g'), ('9', 'g', 'g'), ('1', 'i', 'l'), ('!', 'i', 'i'), ('|', 'i', 'l'),
+    ('0', 'o', 'o'), ('$', 's', 's'), ('5', 's', 's'), ('+', 't', 't'), ('7', 't', 'l'), ('%', 'x', 'x'),
+    ('2', 'z', 'z'),
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Source {
+    Common,
+    English,
+    UserInputs,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Pattern {
+    Dictionary { source: Source, rank: f64, l33t: bool, reversed: bool },
+    Spatial,
+    Sequence,
+    Repeat { chunk_len: usize },
+    Year,
+    Date,
+    Bruteforce,
+}
+
+impl Pattern {
+    fn name(self) -> &'static str {
+        match self {
+            Pattern::Dictionary { .. } => "dictionary",
+            Pattern::Spatial => "spatial",
+            Pattern::Sequence => "sequence",
+            Pattern::Repeat { .. } => "repeat",
+            Pattern::Year => "year",
+            Pattern::Date => "date",
+            Pattern::Bruteforce => "bruteforce",
+        }
+    }
+}
+
+/// A guessable span `i..=j` of the password (in characters).
+#[derive(Clone)]
+struct Match {
+    i: usize,
+    j: usize,
+    token: String,
+    pattern: Pattern,
+    guesses: f64,
+}
+
+type Dictionary = HashMap<String, f64>;
+
+/// Common passwords ranked by position; English words (the EFF list) all cost the
+/// list's size, since it isn't ranked by frequency.
+fn dictionaries() -> &'static [(Source, Dictionary)] {
+    static DICTIONARIES: OnceLock<Vec<(Source, Dictionary)>> = OnceLock::new();
+    DICTIONARIES.get_or_init(|| {
+        let mut common = Dictionary::new();
+        for (rank, word) in COMMON_PASSWORDS.lines().enumerate() {
+            common.entry(word.to_string()).or_insert((rank + 1) as f64);
+        }
+        let words: Vec<&str> = crate::random::EFF_LARGE.lines().filter_map(|line| line.split('\t').nth(1)).collect();
+        let english = words.iter().map(|word| (word.to_string(), words.len() as f64)).collect();
+        vec![(Source::Common, common), (Source::English, english)]
+    })
+}
+
+/// `user_inputs` (names, email, site name) and their alphanumeric pieces, ranked by position.
+fn user_dictionary(user_inputs: &[String]) -> Dictionary {
+    let mut dictionary = Dictionary::new();
+    for (rank, input) in user_inputs.iter().enumerate() {
+        let input = input.to_lowercase();
+        let pieces = input.split(|c: char| !c.is_alphanumeric()).filter(|piece| piece.chars().count() >= 3);
+        for word in std::iter::once(input.as_str()).chain(pieces) {
+            dictionary.entry(word.to_string()).or_insert((rank + 1) as f64);
+        }
+    }
+    dictionary
+}
+
+fn n_choose_k(n: usize, k: usize) -> f64 {
+    (1..=k).fold(1.0, |acc, i| acc * (n + 1 - i) as f64 / i as f64)
+}
+
+fn factorial(n: usize) -> f64 {
+    (1..=n).fold(1.0, |acc, i| acc * i as f64)
+}
+
+/// Ways to capitalize a word like `word` is: 1 for lower case, 2 for the usual
+/// forms (Capitalized, ALL CAPS, lasT), and so on.
+fn uppercase_variations(word: &[char]) -> f64 {
+    let upper = word.iter().filter(|c| c.is_uppercase()).count();
+    let lower = word.iter().filter(|c| c.is_lowercase()).count();
+    if upper == 0 {
+        return 1.0;
+    }
+    let first_only = word[0].is_uppercase() && upper == 1;
+    let last_only = word[word.len() - 1].is_uppercase() && upper == 1;
+    if lower == 0 || first_only || last_only {
+        return 2.0;
+    }
+    (1..=upper.min(lower)).map(|k| n_choose_k(upper + lower, k)).sum()
+}
+
+/// Ways to apply the substitutions `original` has relative to `translated`.
+fn l33t_variations(original: &[char], translated: &[char]) -> f64 {
+    let mut variations = 1.0;
+    let mut letters: Vec<char> = translated.iter().zip(original)
+        .filter(|(t, o)| t != &&o.to_ascii_lowercase())
+        .map(|(t, _)| *t)
+        .collect();
+    letters.dedup();
+    for letter in letters {
+        let subbed = translated.iter().zip(original).filter(|(t, o)| **t == letter && o.to_ascii_lowercase() != letter).count();
+        let unsubbed = original.iter().filter(|o| o.to_ascii_lowercase() == letter).count();
+        variations *= if unsubbed == 0 {
+            2.0
+        } else {
+            (1..=subbed.min(unsubbed)).map(|k| n_choose_k(subbed + unsubbed, k)).sum()
+        };
+    }
+    variations
+}
+
+fn translate(lower: &[char], primary: bool) -> Vec<char> {
+    lower.iter().map(|&c| match L33T.iter().find(|(symbol, _, _)| *symbol == c) {
+        Some(&(_, first, second)) => if primary { first } else { second },
+        None => c,
+    }).collect()
+}
+
+fn dictionary_matches(chars: &[char], lower: &[char], user: &Dictionary, out: &mut Vec<Match>) {
+    let dictionaries = dictionaries().iter().map(|(source, words)| (*source, words))
+        .chain(std::iter::once((Source::UserInputs, user)));
+    let dictionaries: Vec<(Source, &Dictionary)> = dictionaries.collect();
+    let n = chars.len();
+    for i in 0..n {
+        for j in i + 2..n.min(i + MAX_WORD_LEN) {
+            let original = &chars[i..=j];
+            let plain = &lower[i..=j];
+            let reversed: Vec<char> = plain.iter().rev().copied().collect();
+            let mut candidates = vec![(plain.to_vec(), false, false)];
+            for primary in [true, false] {
+                let translated = translate(plain, primary);
+                if translated != plain && !candidates.iter().any(|(word, _, _)| *word == translated) {
+                    candidates.push((translated, true, false));
+                }
+            }
+            if reversed != plain {
+                candidates.push((reversed, false, true));
+            }
+            for (word, l33t, reversed) in candidates {
+                let key: String = word.iter().collect();
+                for (source, dictionary) in &dictionaries {
+                    let Some(&rank) = dictionary.get(&key) else { continue };
+                    let mut guesses = rank * uppercase_variations(original);
+                    if l33t {
+                        guesses *= l33t_variations(original, &word);
+                    }
+                    if reversed {
+                        guesses *= 2.0;
+                    }
+                    let pattern = Pattern::Dictionary { source: *source, rank, l33t, reversed };
+                    out.push(Match { i, j, token: original.iter().collect(), pattern, guesses });
+                }
+            }
+        }
+    }
+}
+
+/// Runs of three or more neighbouring keys along a keyboard row, either direction.
+fn spatial_matches(chars: &[char], lower: &[char], out: &mut Vec<Match>) {
+    for row in KEYBOARD_ROWS {
+        let row: Vec<char> = row.chars().collect();
+        for step in [1isize, -1] {
+            let adjacent = |a: char, b: char| {
+                let (Some(a), Some(b)) = (row.iter().position(|&k| k == a), row.iter().position(|&k| k == b)) else {
+                    return false;
+                };
+                b as isize - a as isize == step
+            };
+            let mut i = 0;
+            while i < lower.len() {
+                let mut j = i;
+                while j + 1 < lower.len() && adjacent(lower[j], lower[j + 1]) {
+                    j += 1;
+                }
+                if j - i >= 2 {
+                    let guesses = (j - i) as f64 * KEYBOARD_STARTS * KEYBOARD_DEGREE;
+                    out.push(Match { i, j, token: chars[i..=j].iter().collect(), pattern: Pattern::Spatial, guesses });
+                }
+                i = j + 1;
+            }
+        }
+    }
+}
+
+/// Runs with a constant step between code points (`abc`, `7531`, `ZYX`).
+fn sequence_matches(chars: &[char], out: &mut Vec<Match>) {
+    let mut push = |i: usize, j: usize, delta: i64| {
+        if j - i < 2 || delta == 0 || delta.abs() > 5 {
+            return;
+        }
+        let token = &chars[i..=j];
+        let base = if token.iter().all(char::is_ascii_lowercase) || token.iter().all(char::is_ascii_uppercase) {
+            26.0
+        } else if token.iter().all(char::is_ascii_digit) {
+            10.0
+        } else {
+            return;
+        };
+        let base = if ['a', 'A', 'z', 'Z', '0', '1', '9'].contains(&token[0]) { 4.0 } else { base };
+        let guesses = base * token.len() as f64 * if delta < 0 { 2.0 } else { 1.0 };
+        out.push(Match { i, j, token: token.iter().collect(), pattern: Pattern::Sequence, guesses });
+    };
+    let (mut i, mut last_delta) = (0, None);
+    for k in 1..chars.len() {
+        let delta = chars[k] as i64 - chars[k - 1] as i64;
+        match last_delta {
+            None => last_delta = Some(delta),
+            Some(last) if last == delta => {}
+            Some(last) => {
+                push(i, k - 1, last);
+                (i, last_delta) = (k - 1, Some(delta));
+            }
+        }
+    }
+    if let Some(last) = last_delta {
+        push(i, chars.len() - 1, last);
+    }
+}
+
+/// The longest repetition of a chunk starting at each position (`aaa`, `abcabc`),
+/// costed as guessing the chunk times the repeat count.
+fn repeat_matches(chars: &[char], user: &Dictionary, out: &mut Vec<Match>) {
+    let n = chars.len();
+    let mut i = 0;
+    while i < n {
+        let mut best: Option<(usize, usize)> = None;
+        for chunk_len in 1..=(n - i) / 2 {
+            let chunk = &chars[i..i + chunk_len];
+            let count = 1 + chars[i + chunk_len..].chunks(chunk_len).take_while(|next| next == &chunk).count();
+            if count >= 2 && best.is_none_or(|(len, best_count)| chunk_len * count > len * best_count) {
+                best = Some((chunk_len, count));
+            }
+        }
+        let Some((chunk_len, count)) = best else {
+            i += 1;
+            continue;
+        };
+        let j = i + chunk_len * count - 1;
+        let (base_guesses, _) = most_guessable(&chars[i..i + chunk_len], user);
+        let pattern = Pattern::Repeat { chunk_len };
+        out.push(Match { i, j, token: chars[i..=j].iter().collect(), pattern, guesses: base_guesses * count as f64 });
+        i = j + 1;
+    }
+}
+
+fn current_year() -> i64 {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    1970 + (secs / 31_556_952) as i64
+}
+
+fn year_space(year: i64) -> f64 {
+    ((year - current_year()).abs() as f64).max(MIN_YEAR_SPACE)
+}
+
+/// The year of a plausible date written as `token` (`13.05.1987`, `870513`), if any.
+fn date_year(token: &str) -> Option<i64> {
+    let separator = token.chars().find(|c| !c.is_ascii_digit());
+    let parts: Vec<&str> = match separator {
+        Some(separator) if "/\\-._ ".contains(separator) => token.split(separator).collect(),
+        Some(_) => return None,
+        None if token.len() == 6 => vec![&token[..2], &token[2..4], &token[4..]],
+        None if token.len() == 8 => {
+            // yyyymmdd, or ddmmyyyy / mmddyyyy
+            if let Some(year) = date_year(&format!("{}-{}-{}", &token[..4], &token[4..6], &token[6..])) {
+                return Some(year);
+            }
+            vec![&token[..2], &token[2..4], &token[4..]]
+        }
+        None => return None,
+    };
+    let [a, b, c] = parts.as_slice() else { return None };
+    let numbers: Vec<i64> = [a, b, c].iter().map(|part| part.parse().ok()).collect::<Option<_>>()?;
+    let day_month = |x: i64, y: i64| (1..=31).contains(&x) && (1..=12).contains(&y) || (1..=12).contains(&x) && (1..=31).contains(&y);
+    let full_year = |part: &str, year: i64| match part.len() {
+        2 => Some(if year > 50 { 1900 + year } else { 2000 + year }),
+        4 if (1000..=2050).contains(&year) => Some(year),
+        _ => None,
+    };
+    if a.len() == 4 && day_month(numbers[1], numbers[2]) {
+        return full_year(a, numbers[0]);
+    }
+    if day_month(numbers[0], numbers[1]) && a.len() <= 2 && b.len() <= 2 {
+        return full_year(c, numbers[2]);
+    }
+    if separator.is_none() && day_month(numbers[1], numbers[2]) {
+        return full_year(a, numbers[0]);
+    }
+    None
+}
+
+fn date_matches(chars: &[char], out: &mut Vec<Match>) {
+    let n = chars.len();
+    for i in 0..n {
+        for j in i + 3..n.min(i + 10) {
+            let token: String = chars[i..=j].iter().collect();
+            if token.len() == 4 && token.bytes().all(|b| b.is_ascii_digit()) {
+                let year: i64 = token.parse().unwrap_or(0);
+                if (1900..=2050).contains(&year) {
+                    out.push(Match { i, j, token, pattern: Pattern::Year, guesses: year_space(year) });
+                }
+                continue;
+            }
+            if let Some(year) = date_year(&token) {
+                let separated = !token.bytes().all(|b| b.is_ascii_digit());
+                let guesses = year_space(year) * 365.0 * if separated { 4.0 } else { 1.0 };
+                out.push(Match { i, j, token, pattern: Pattern::Date, guesses });
+            }
+        }
+    }
+}
+
+fn bruteforce(chars: &[char], i: usize, j: usize) -> Match {
+    let len = j - i + 1;
+    let min = if len == 1 { MIN_GUESSES_SINGLE_CHAR } else { MIN_GUESSES_MULTI_CHAR } + 1.0;
+    let guesses = BRUTEFORCE_CARDINALITY.powi(len as i32).max(min);
+    Match { i, j, token: chars[i..=j].iter().collect(), pattern: Pattern::Bruteforce, guesses }
+}
+
+/// zxcvbn's search: the sequence of non-overlapping matches (gaps filled by
+/// bruteforce) minimizing `l! * product(guesses) + D^(l-1)` for `l` matches.
+/// Returns the guesses for the whole of `chars` and the sequence.
+fn most_guessable(chars: &[char], user: &Dictionary) -> (f64, Vec<Match>) {
+    let n = chars.len();
+    if n == 0 {
+        return (1.0, Vec::new());
+    }
+    let lower: Vec<char> = chars.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+    let mut matches = Vec::new();
+    dictionary_matches(chars, &lower, user, &mut matches);
+    spatial_matches(chars, &lower, &mut matches);
+    sequence_matches(chars, &mut matches);
+    repeat_matches(chars, user, &mut matches);
+    date_matches(chars, &mut matches);
+    let min_guesses = |m: &Match| if m.j == m.i { MIN_GUESSES_SINGLE_CHAR } else { MIN_GUESSES_MULTI_CHAR };
+    let mut by_end: Vec<Vec<Match>> = vec![Vec::new(); n];
+    for mut m in matches {
+        m.guesses = m.guesses.max(min_guesses(&m));
+        by_end[m.j].push(m);
+    }
+
+    // optimal[k][l]: best sequence of l matches covering 0..=k, as (last match, product, total)
+    let mut optimal: Vec<HashMap<usize, (Match, f64, f64)>> = vec![HashMap::new(); n];
+    let update = |optimal: &mut Vec<HashMap<usize, (Match, f64, f64)>>, m: Match, l: usize| {
+        let k = m.j;
+        let mut pi = m.guesses;
+        if l > 1 {
+            pi *= optimal[m.i - 1][&(l - 1)].1;
+        }
+        let g = factorial(l) * pi + MIN_GUESSES_BEFORE_GROWING_SEQUENCE.powi(l as i32 - 1);
+        if optimal[k].iter().any(|(&other_l, (_, _, other_g))| other_l <= l && *other_g <= g) {
+            return;
+        }
+        optimal[k].insert(l, (m, pi, g));
+    };
+    for (k, ending) in by_end.into_iter().enumerate() {
+        for m in ending {
+            if m.i == 0 {
+                update(&mut optimal, m, 1);
+                continue;
+            }
+            let lengths: Vec<usize> = optimal[m.i - 1].keys().copied().collect();
+            for l in lengths {
+                update(&mut optimal, m.clone(), l + 1);
+            }
+        }
+        update(&mut optimal, bruteforce(chars, 0, k), 1);
+        for i in 1..=k {
+            let previous: Vec<(usize, bool)> = optimal[i - 1].iter()
+                .map(|(&l, (last, _, _))| (l, last.pattern == Pattern::Bruteforce))
+                .collect();
+            // Adjacent bruteforce spans would just be one longer span
+            for (l, _) in previous.into_iter().filter(|(_, bruteforce)| !bruteforce) {
+                update(&mut optimal, bruteforce(chars, i, k), l + 1);
+            }
+        }
+    }
+
+    let (mut l, guesses) = optimal[n - 1].iter()
+        .map(|(&l, (_, _, g))| (l, *g))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .expect("bruteforce always covers the whole password");
+    let mut sequence = Vec::with_capacity(l);
+    let mut k = n;
+    while k > 0 {
+        let m = optimal[k - 1][&l].0.clone();
+        k = m.i;
+        l -= 1;
+        sequence.push(m);
+    }
+    sequence.reverse();
+    (guesses, sequence)
+}
+
+fn score(guesses: f64) -> u8 {
+    const DELTA: f64 = 5.0;
+    match guesses {
+        g if g < 1e3 + DELTA => 0,
+        g if g < 1e6 + DELTA => 1,
+        g if g < 1e8 + DELTA => 2,
+        g if g < 1e10 + DELTA => 3,
+        _ => 4,
+    }
+}
+
+/// A warning about the weakest part of the password, and suggestions.
+fn feedback(score: u8, sequence: &[Match], empty: bool) -> (Option<&'static str>, Vec<&'static str>) {
+    if empty {
+        return (None, vec!["Use a few words, avoid common phrases", "No need for symbols, digits, or uppercase letters"]);
+    }
+    if score > 2 {
+        return (None, Vec::new());
+    }
+    let mut suggestions = vec!["Add another word or two. Uncommon words are better."];
+    let Some(longest) = sequence.iter().max_by_key(|m| m.j - m.i) else { return (None, suggestions) };
+    let warning = match longest.pattern {
+        Pattern::Dictionary { source, rank, l33t, reversed } => {
+            let chars: Vec<char> = longest.token.chars().collect();
+            if chars.iter().all(|c| c.is_uppercase()) {
+                suggestions.push("All-uppercase is almost as easy to guess as all-lowercase");
+            } else if chars[0].is_uppercase() {
+                suggestions.push("Capitalization doesn't help very much");
+            }
+            if reversed && chars.len() >= 4 {
+                suggestions.push("Reversed words aren't much harder to guess");
+            }
+            if l33t {
+                suggestions.push("Predictable substitutions like '@' instead of 'a' don't help very much");
+            }
+            match source {
+                Source::Common if rank <= 10.0 => Some("This is a top-10 common password"),
+                Source::Common if rank <= 100.0 => Some("This is a top-100 common password"),
+                Source::Common => Some("This is a very common password"),
+                Source::UserInputs => Some("This is similar to your personal information"),
+                Source::English if sequence.len() == 1 => Some("A word by itself is easy to guess"),
+                Source::English => None,
+            }
+        }
+        Pattern::Spatial => {
+            suggestions.push("Use a longer keyboard pattern with more turns");
+            Some("Straight rows of keys are easy to guess")
+        }
+        Pattern::Sequence => {
+            suggestions.push("Avoid sequences");
+            Some("Sequences like abc or 6543 are easy to guess")
+        }
+        Pattern::Repeat { chunk_len } => {
+            suggestions.push("Avoid repeated words and characters");
+            Some(if chunk_len == 1 {
+                "Repeats like \"aaa\" are easy to guess"
+            } else {
+                "Repeats like \"abcabcabc\" are only slightly harder to guess than \"abc\""
+            })
+        }
+        Pattern::Year => {
+            suggestions.push("Avoid recent years");
+            suggestions.push("Avoid years that are associated with you");
+            Some("Recent years are easy to guess")
+        }
+        Pattern::Date => {
+            suggestions.push("Avoid dates and years that are associated with you");
+            Some("Dates are often easy to guess")
+        }
+        Pattern::Bruteforce => None,
+    };
+    (warning, suggestions)
+}
+
+/// Score (0-4) and guesses for `password`, for checks that don't need the rest.
+pub(crate) fn estimate(password: &str, user_inputs: &[String]) -> (u8, f64, Option<&'static str>) {
+    let chars: Vec<char> = password.chars().take(MAX_LEN).collect();
+    let (guesses, sequence) = most_guessable(&chars, &user_dictionary(user_inputs));
+    let score = score(guesses);
+    (score, guesses, feedback(score, &sequence, chars.is_empty()).0)
+}
+
+/// Estimates how hard `password` is to guess, zxcvbn style: it is split into the
+/// likeliest patterns an attacker would try (common passwords, English words and
+/// `user_inputs` such as the user's name or email, with capitalization, l33t
+/// substitutions and reversal; keyboard rows, sequences, repeats, years and dates),
+/// and the guesses needed to get through them are added up.
+///
+/// Returns `{score, guesses, guesses_log10, crack_times_seconds, feedback, sequence}`:
+/// `score` runs from 0 (under 10^3 guesses) to 4 (over 10^10); `crack_times_seconds`
+/// has `online_throttled` (100/hour), `online_unthrottled` (10/s), `offline_slow_hash`
+/// (10^4/s) and `offline_fast_hash` (10^10/s); `feedback` is `{warning, suggestions}`
+/// for showing at signup, empty once the score is 3 or more; `sequence` lists the
+/// matched `{pattern, token, guesses}`. Only the first 100 characters are analysed.
+#[pyfunction]
+#[pyo3(signature = (password, user_inputs=Vec::new()))]
+pub fn estimate_password_strength<'py>(py: Python<'py>, password: &str, user_inputs: Vec<String>) -> PyResult<Bound<'py, PyDict>> {
+    let chars: Vec<char> = password.chars().take(MAX_LEN).collect();
+    let (guesses, sequence) = py.detach(|| most_guessable(&chars, &user_dictionary(&user_inputs)));
+    let score = score(guesses);
+    let (warning, suggestions) = feedback(score, &sequence, chars.is_empty());
+
+    let result = PyDict::new(py);
+    result.set_item("score", score)?;
+    result.set_item("guesses", guesses)?;
+    result.set_item("guesses_log10", guesses.log10())?;
+    let crack_times = PyDict::new(py);
+    for (scenario, per_second) in [
+        ("online_throttled", 100.0 / 3600.0),
+        ("online_unthrottled", 10.0),
+        ("offline_slow_hash", 1e4),
+        ("offline_fast_hash", 1e10),
+    ] {
+        crack_times.set_item(scenario, guesses / per_second)?;
+    }
+    result.set_item("crack_times_seconds", crack_times)?;
+    let feedback = PyDict::new(py);
+    feedback.set_item("warning", warning)?;
+    feedback.set_item("suggestions", suggestions)?;
+    result.set_item("feedback", feedback)?;
+    let matches = PyList::empty(py);
+    for m in &sequence {
+        let entry = PyDict::new(py);
+        entry.set_item("pattern", m.pattern.name())?;
+        entry.set_item("token", &m.token)?;
+        entry.set_item("guesses", m.guesses)?;
+        matches.append(entry)?;
+    }
+    result.set_item("sequence", matches)?;
+    Ok(result)
+}
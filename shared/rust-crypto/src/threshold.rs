@@ -0,0 +1,290 @@
+use pyo3::prelude::*;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL};
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::Identity;
+use hkdf::Hkdf;
+use sha2::{Digest, Sha256};
+
+use crate::aad::AadArg;
+use crate::audit::Operation;
+use crate::cipher::Algorithm;
+use crate::encoding::Encoding;
+use crate::envelope::Reader;
+use crate::error::Error;
+use crate::random::random_bytes;
+
+pub(crate) const MAGIC: &[u8; 3] = b"KST";
+const SHARE_MAGIC: &[u8; 3] = b"KSH";
+const PARTIAL_MAGIC: &[u8; 3] = b"KSD";
+const VERSION: u8 = 1;
+const FINGERPRINT_LEN: usize = 8;
+const KEY_INFO: &[u8] = b"k-services/threshold";
+
+fn random_scalar() -> Scalar {
+    Scalar::from_bytes_mod_order_wide(&random_bytes(64).try_into().unwrap())
+}
+
+fn read_point(reader: &mut Reader) -> Result<RistrettoPoint, Error> {
+    let point = CompressedRistretto::from_slice(reader.take(32)?)
+        .ok()
+        .and_then(|p| p.decompress())
+        .ok_or_else(|| Error::invalid_format("Invalid group element"))?;
+    if point == RistrettoPoint::identity() {
+        return Err(Error::invalid_format("Invalid group element"));
+    }
+    Ok(point)
+}
+
+fn read_magic(reader: &mut Reader, magic: &[u8; 3], what: &str) -> Result<(), Error> {
+    if reader.take(magic.len()).ok() != Some(magic.as_slice()) {
+        return Err(Error::invalid_format(format!("Invalid {}: bad magic", what)).with_code("unrecognized_format"));
+    }
+    let version = reader.u8()?;
+    if version != VERSION {
+        return Err(
+            Error::invalid_format(format!("Unsupported {} version: {}", what, version)).with_code("unsupported_version")
+        );
+    }
+    Ok(())
+}
+
+fn decode(encoded: &str, what: &str) -> Result<Vec<u8>, Error> {
+    BASE64_URL.decode(encoded.trim()).map_err(|e| Error::invalid_format(format!("Invalid {} encoding: {}", what, e)))
+}
+
+/// Ties shares, ciphertexts and partial decryptions to the key they belong to.
+fn fingerprint(public_key: &RistrettoPoint) -> [u8; FINGERPRINT_LEN] {
+    Sha256::digest(public_key.compress().as_bytes())[..FINGERPRINT_LEN].try_into().unwrap()
+}
+
+/// Lagrange coefficient at x = 0 for share `index` among `indices`.
+fn lagrange(index: u8, indices: &[u8]) -> Scalar {
+    let x_i = Scalar::from(index as u64);
+    indices.iter().filter(|&&j| j != index).fold(Scalar::ONE, |lambda, &j| {
+        let x_j = Scalar::from(j as u64);
+        lambda * x_j * (x_j - x_i).invert()
+    })
+}
+
+fn content_key(shared: &RistrettoPoint, ephemeral: &RistrettoPoint) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(ephemeral.compress().as_bytes()), shared.compress().as_bytes())
+        .expand(KEY_INFO, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    key
+}
+
+/// One operator's piece of the decryption key.
+///
+/// ```text
+/// magic "KSH" | version u8 | threshold u8 | index u8 | public key (32) | secret scalar (32)
+/// ```
+struct KeyShare {
+    threshold: u8,
+    index: u8,
+    public_key: RistrettoPoint,
+    secret: Scalar,
+}
+
+impl KeyShare {
+    fn encode(&self) -> String {
+        let mut data = SHARE_MAGIC.to_vec();
+        data.push(VERSION);
+        data.push(self.threshold);
+        data.push(self.index);
+        data.extend_from_slice(self.public_key.compress().as_bytes());
+        data.extend_from_slice(self.secret.as_bytes());
+        BASE64_URL.encode(data)
+    }
+
+    fn decode(encoded: &str) -> Result<KeyShare, Error> {
+        let data = Encoding::Base64Url.decode_secret_text(encoded, "key share")?;
+        let mut reader = Reader { data: &data, pos: 0 };
+        read_magic(&mut reader, SHARE_MAGIC, "key share")?;
+        let threshold = reader.u8()?;
+        let index = reader.u8()?;
+        let public_key = read_point(&mut reader)?;
+        let secret = Option::from(Scalar::from_canonical_bytes(reader.take(32)?.try_into().unwrap()))
+            .ok_or_else(|| Error::invalid_format("Invalid key share"))?;
+        if index == 0 || reader.pos != data.len() {
+            return Err(Error::invalid_format("Invalid key share"));
+        }
+        Ok(KeyShare { threshold, index, public_key, secret })
+    }
+}
+
+/// Ciphertext that needs `threshold` key-share holders to open: ElGamal over
+/// Ristretto255 to agree on a content key, then the usual AEAD for the payload.
+///
+/// ```text
+/// magic "KST" | version u8 | algorithm u8 | key fingerprint (8)
+/// | ephemeral point (32) | nonce | ciphertext || tag
+/// ```
+///
+/// Everything before the nonce is authenticated together with `aad_context`.
+struct ThresholdEnvelope {
+    algorithm: Algorithm,
+    fingerprint: [u8; FINGERPRINT_LEN],
+    ephemeral: RistrettoPoint,
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+impl ThresholdEnvelope {
+    fn header(&self) -> Vec<u8> {
+        let mut header = MAGIC.to_vec();
+        header.push(VERSION);
+        header.push(self.algorithm.envelope_id());
+        header.extend_from_slice(&self.fingerprint);
+        header.extend_from_slice(self.ephemeral.compress().as_bytes());
+        header
+    }
+
+    fn aad(&self, aad_context: &[u8]) -> Vec<u8> {
+        let mut aad = self.header();
+        aad.extend_from_slice(aad_context);
+        aad
+    }
+
+    fn from_token(token: &str) -> Result<ThresholdEnvelope, Error> {
+        let data = decode(token, "token")?;
+        let mut reader = Reader { data: &data, pos: 0 };
+        read_magic(&mut reader, MAGIC, "threshold token")?;
+        let algorithm = Algorithm::from_envelope_id(reader.u8()?)?;
+        let fingerprint = reader.take(FINGERPRINT_LEN)?.try_into().unwrap();
+        let ephemeral = read_point(&mut reader)?;
+        let nonce = reader.take(algorithm.nonce_len())?.to_vec();
+        let ciphertext = data[reader.pos..].to_vec();
+        Ok(ThresholdEnvelope { algorithm, fingerprint, ephemeral, nonce, ciphertext })
+    }
+}
+
+/// Creates a threshold key with a trusted dealer: returns the public key (32 bytes)
+/// and `shares` key shares, any `threshold` of which can jointly decrypt. The full
+/// private key only exists for the duration of this call.
+#[pyfunction]
+pub fn threshold_generate_key(threshold: u8, shares: u8) -> PyResult<(Vec<u8>, Vec<String>)> {
+    if threshold < 2 || threshold > shares {
+        return Err(crate::error::to_pyerr(Error::config("Need 2 <= threshold <= shares <= 255")));
+    }
+
+    // f(x) = a0 + a1 x + ... ; the private key is f(0), share i is f(i)
+    let coefficients: Vec<Scalar> = (0..threshold).map(|_| random_scalar()).collect();
+    let public_key = RistrettoPoint::mul_base(&coefficients[0]);
+    let key_shares = (1..=shares).map(|index| {
+        let x = Scalar::from(index as u64);
+        let secret = coefficients.iter().rev().fold(Scalar::ZERO, |y, a| y * x + a);
+        KeyShare { threshold, index, public_key, secret }.encode()
+    }).collect();
+    Ok((public_key.compress().as_bytes().to_vec(), key_shares))
+}
+
+/// Encrypts for a threshold key. Only the public key is needed.
+#[pyfunction]
+#[pyo3(signature = (public_key, plaintext, aad_context, algorithm=Algorithm::DEFAULT_NAME))]
+pub fn threshold_encrypt(
+    py: Python<'_>,
+    public_key: &[u8],
+    plaintext: &[u8],
+    aad_context: AadArg,
+    algorithm: &str
+) -> PyResult<String> {
+    let algorithm = Algorithm::from_name(algorithm).map_err(crate::error::to_pyerr)?;
+    let public_key = read_point(&mut Reader { data: public_key, pos: 0 })
+        .map_err(|_| crate::error::to_pyerr(Error::invalid_format("Invalid threshold public key")))?;
+
+    let operation = Operation::new("encrypt", "threshold").with_aad(&aad_context);
+    py.detach(|| operation.run(|| {
+        let r = random_scalar();
+        let mut envelope = ThresholdEnvelope {
+            algorithm,
+            fingerprint: fingerprint(&public_key),
+            ephemeral: RistrettoPoint::mul_base(&r),
+            nonce: random_bytes(algorithm.nonce_len()),
+            ciphertext: Vec::new(),
+        };
+        let key = content_key(&(public_key * r), &envelope.ephemeral);
+        envelope.ciphertext = algorithm.encrypt(&key, &envelope.nonce, plaintext, &envelope.aad(&aad_context))?;
+
+        let mut data = envelope.header();
+        data.extend_from_slice(&envelope.nonce);
+        data.extend_from_slice(&envelope.ciphertext);
+        Ok(BASE64_URL.encode(data))
+    })).map_err(crate::error::to_pyerr)
+}
+
+/// One share holder's contribution to decrypting `token`. It reveals nothing about
+/// the key share or the plaintext, so it can be sent to whoever runs `threshold_combine`.
+///
+/// ```text
+/// magic "KSD" | version u8 | threshold u8 | index u8 | key fingerprint (8) | partial point (32)
+/// ```
+#[pyfunction]
+pub fn threshold_partial_decrypt(key_share: &str, token: &str) -> PyResult<String> {
+    let share = KeyShare::decode(key_share).map_err(crate::error::to_pyerr)?;
+    let envelope = ThresholdEnvelope::from_token(token).map_err(crate::error::to_pyerr)?;
+    let fingerprint = fingerprint(&share.public_key);
+    if fingerprint != envelope.fingerprint {
+        return Err(crate::error::to_pyerr(
+            Error::decryption("Key share does not belong to the key this token was encrypted for")
+                .with_code("wrong_key_share")
+        ));
+    }
+
+    let mut data = PARTIAL_MAGIC.to_vec();
+    data.push(VERSION);
+    data.push(share.threshold);
+    data.push(share.index);
+    data.extend_from_slice(&fingerprint);
+    data.extend_from_slice((envelope.ephemeral * share.secret).compress().as_bytes());
+    Ok(BASE64_URL.encode(data))
+}
+
+/// Decrypts `token` from at least `threshold` partial decryptions by distinct share
+/// holders. A wrong or tampered partial makes decryption fail.
+#[pyfunction]
+pub fn threshold_combine(py: Python<'_>, token: &str, partials: Vec<String>, aad_context: AadArg) -> PyResult<Vec<u8>> {
+    let operation = Operation::new("decrypt", "threshold").with_aad(&aad_context);
+    py.detach(|| operation.run(|| {
+        let envelope = ThresholdEnvelope::from_token(token)?;
+
+        let mut threshold = None;
+        let mut indices = Vec::with_capacity(partials.len());
+        let mut points = Vec::with_capacity(partials.len());
+        for partial in &partials {
+            let data = decode(partial, "partial decryption")?;
+            let mut reader = Reader { data: &data, pos: 0 };
+            read_magic(&mut reader, PARTIAL_MAGIC, "partial decryption")?;
+            let partial_threshold = reader.u8()?;
+            if *threshold.get_or_insert(partial_threshold) != partial_threshold {
+                return Err(Error::crypto("Partial decryptions come from different key splits"));
+            }
+            let index = reader.u8()?;
+            if reader.take(FINGERPRINT_LEN)? != envelope.fingerprint {
+                return Err(
+                    Error::decryption("Partial decryption belongs to a different key").with_code("wrong_key_share")
+                );
+            }
+            if index == 0 || indices.contains(&index) {
+                return Err(Error::crypto("Duplicate or invalid partial decryption"));
+            }
+            indices.push(index);
+            points.push(read_point(&mut reader)?);
+        }
+        let threshold = threshold.ok_or_else(|| Error::config("No partial decryptions given"))? as usize;
+        if indices.len() < threshold {
+            return Err(
+                Error::config(format!("Need at least {} partial decryptions, got {}", threshold, indices.len()))
+            );
+        }
+
+        // Lagrange interpolation in the exponent: sum(lambda_i * s_i * R) = s * R
+        let shared = indices.iter().zip(&points)
+            .map(|(&index, point)| point * lagrange(index, &indices))
+            .sum::<RistrettoPoint>();
+        let key = content_key(&shared, &envelope.ephemeral);
+        envelope.algorithm.decrypt(&key, &envelope.nonce, &envelope.ciphertext, &envelope.aad(&aad_context))
+            .map_err(|_| Error::decryption("Decryption failed: invalid partial decryption or wrong aad_context"))
+    })).map_err(crate::error::to_pyerr)
+}
@@ -0,0 +1,76 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::cell::Cell;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Longest delay a hook can impose per failure.
+const MAX_DELAY: f64 = 60.0;
+
+static HOOK: Mutex<Option<Py<PyAny>>> = Mutex::new(None);
+
+thread_local! {
+    /// Set while the hook runs, so failures it causes itself don't call it again.
+    static IN_HOOK: Cell<bool> = const { Cell::new(false) };
+}
+
+/// What the hook is told: the labels `to_pyerr` put on the exception, never key
+/// material, plaintext or AAD.
+fn event<'py>(py: Python<'py>, err: &PyErr) -> PyResult<Bound<'py, PyDict>> {
+    let value = err.value(py);
+    let event = PyDict::new(py);
+    for label in ["code", "input", "item", "field"] {
+        event.set_item(label, value.getattr(label)?)?;
+    }
+    event.set_item("message", value.str()?)?;
+    Ok(event)
+}
+
+/// Reports an authentication failure to the hook and waits as long as it asks.
+/// Returns the error to raise: `err`, or what the hook raised instead.
+pub(crate) fn on_decryption_failure(err: PyErr) -> PyErr {
+    Python::attach(|py| {
+        let Some(hook) = HOOK.lock().unwrap().as_ref().map(|hook| hook.clone_ref(py)) else { return err };
+        if IN_HOOK.get() {
+            return err;
+        }
+        IN_HOOK.set(true);
+        let outcome = event(py, &err)
+            .and_then(|event| hook.call1(py, (event,)))
+            .and_then(|delay| delay.extract::<Option<f64>>(py));
+        IN_HOOK.set(false);
+        match outcome {
+            Ok(Some(delay)) if delay > 0.0 => {
+                let delay = Duration::from_secs_f64(delay.min(MAX_DELAY));
+                py.detach(|| std::thread::sleep(delay));
+                err
+            }
+            Ok(_) => err,
+            Err(raised) => {
+                raised.set_cause(py, Some(err));
+                raised
+            }
+        }
+    })
+}
+
+/// Installs `hook(event)`, called on every authentication failure anywhere in the
+/// module (wrong key or passphrase, wrong AAD, tampered data) just before the
+/// `DecryptionError` is raised, so guessing attacks on passphrase-derived ciphertexts
+/// can be throttled in one place. `event` is a dict of labels only: `code`,
+/// `message`, and `input` / `item` / `field` as on the exception.
+///
+/// The hook may return a number of seconds to wait before raising (capped at 60;
+/// the GIL is released meanwhile), or raise to replace the `DecryptionError`, e.g.
+/// with a rate-limit error once a budget is spent; the original becomes its
+/// `__cause__`. It runs in the failing call's thread and must not block on locks
+/// held by crypto calls. `None` removes the hook.
+#[pyfunction]
+#[pyo3(signature = (hook=None))]
+pub fn set_decrypt_failure_hook(py: Python<'_>, hook: Option<Py<PyAny>>) -> PyResult<()> {
+    if hook.as_ref().is_some_and(|hook| !hook.bind(py).is_callable()) {
+        return Err(pyo3::exceptions::PyTypeError::new_err("hook must be callable"));
+    }
+    *HOOK.lock().unwrap() = hook;
+    Ok(())
+}
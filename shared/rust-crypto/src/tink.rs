@@ -0,0 +1,291 @@
+use pyo3::prelude::*;
+use aes_gcm::{aead::{Aead, KeyInit, Payload}, Aes128Gcm, Aes256Gcm};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use serde_json::{json, Value};
+
+use crate::audit::Operation;
+use crate::encoding::Encoding;
+use crate::error::Error;
+use crate::random::random_bytes;
+
+const AES_GCM_TYPE_URL: &str = "type.googleapis.com/google.crypto.tink.AesGcmKey";
+const IV_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const PREFIX_LEN: usize = 5;
+
+/// Ciphertext prefix identifying the key, per the key's `outputPrefixType`.
+fn prefix(output_prefix_type: &str, key_id: u32) -> Result<Vec<u8>, Error> {
+    let start = match output_prefix_type {
+        "TINK" => 0x01,
+        "LEGACY" | "CRUNCHY" => 0x00,
+        "RAW" => return Ok(Vec::new()),
+        other => return Err(Error::config(format!("Unsupported Tink outputPrefixType: {}", other))),
+    };
+    let mut prefix = vec![start];
+    prefix.extend_from_slice(&key_id.to_be_bytes());
+    Ok(prefix)
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Result<u64, Error> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *data.get(*pos)
+            .ok_or_else(|| Error::invalid_format("Invalid Tink key: truncated protobuf").with_code("truncated"))?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(Error::invalid_format("Invalid Tink key: bad varint"))
+}
+
+/// Pulls `key_value` (field 3) out of a serialized `AesGcmKey` protobuf.
+fn parse_aes_gcm_key(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut pos = 0;
+    let mut key_value = None;
+    while pos < data.len() {
+        let tag = read_varint(data, &mut pos)?;
+        match tag & 7 {
+            0 => { read_varint(data, &mut pos)?; }
+            1 => pos += 8,
+            2 => {
+                let len = read_varint(data, &mut pos)? as usize;
+                let field = data.get(pos..pos.saturating_add(len))
+                    .ok_or_else(|| Error::invalid_format("Invalid Tink key: truncated protobuf")
+                        .with_code("truncated"))?;
+                if tag >> 3 == 3 {
+                    key_value = Some(field.to_vec());
+                }
+                pos += len;
+            }
+            5 => pos += 4,
+            _ => return Err(Error::invalid_format("Invalid Tink key: bad protobuf wire type")),
+        }
+    }
+    let key_value = key_value.ok_or_else(|| Error::invalid_format("Invalid Tink key: AesGcmKey has no key_value"))?;
+    if key_value.len() != 16 && key_value.len() != 32 {
+        return Err(Error::invalid_format("Invalid Tink key: AES-GCM key must be 16 or 32 bytes"));
+    }
+    Ok(key_value)
+}
+
+/// Serialized `AesGcmKey { version: 0, key_value }`.
+fn serialize_aes_gcm_key(key_value: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x08, 0x00, 0x1a, key_value.len() as u8];
+    out.extend_from_slice(key_value);
+    out
+}
+
+fn gcm_seal(key: &[u8], iv: &[u8], msg: &[u8], aad: &[u8]) -> Result<Vec<u8>, Error> {
+    let payload = Payload { msg, aad };
+    match key.len() {
+        16 => Aes128Gcm::new(key.into()).encrypt(iv.into(), payload),
+        _ => Aes256Gcm::new(key.into()).encrypt(iv.into(), payload),
+    }.map_err(|e| Error::crypto(format!("Encryption failed: {}", e)))
+}
+
+fn gcm_open(key: &[u8], iv: &[u8], msg: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+    let payload = Payload { msg, aad };
+    match key.len() {
+        16 => Aes128Gcm::new(key.into()).decrypt(iv.into(), payload),
+        _ => Aes256Gcm::new(key.into()).decrypt(iv.into(), payload),
+    }.ok()
+}
+
+struct TinkKey {
+    key_id: u32,
+    enabled: bool,
+    /// Empty for RAW keys.
+    prefix: Vec<u8>,
+    /// AES-GCM key bytes; `None` for key types this module can't use, which are
+    /// still kept so that `to_json()` writes them back unchanged.
+    aes_gcm: Option<Vec<u8>>,
+    json: Value,
+}
+
+impl TinkKey {
+    fn parse(entry: &Value) -> Result<TinkKey, Error> {
+        let key_id = entry.get("keyId").and_then(Value::as_u64).and_then(|id| u32::try_from(id).ok())
+            .ok_or_else(|| Error::invalid_format("Invalid Tink keyset: key without a valid keyId"))?;
+        let status = entry.get("status").and_then(Value::as_str).unwrap_or("UNKNOWN_STATUS");
+        let output_prefix_type = entry.get("outputPrefixType").and_then(Value::as_str).unwrap_or("UNKNOWN_PREFIX");
+        let key_data = entry.get("keyData");
+        let type_url = key_data.and_then(|d| d.get("typeUrl")).and_then(Value::as_str);
+
+        let (aes_gcm, prefix) = if type_url == Some(AES_GCM_TYPE_URL) {
+            let value = key_data.and_then(|d| d.get("value")).and_then(Value::as_str)
+                .ok_or_else(|| Error::invalid_format("Invalid Tink keyset: AesGcmKey without a value"))?;
+            let value = Encoding::Base64.decode_secret_text(value, "key value")
+                .map_err(|_| Error::invalid_format("Invalid Tink keyset: key value is not base64"))?;
+            (Some(parse_aes_gcm_key(&value)?), prefix(output_prefix_type, key_id)?)
+        } else {
+            (None, prefix(output_prefix_type, key_id).unwrap_or_default())
+        };
+        Ok(TinkKey { key_id, enabled: status == "ENABLED", prefix, aes_gcm, json: entry.clone() })
+    }
+
+    fn generate(key_id: u32, key_size: usize) -> TinkKey {
+        let key_value = random_bytes(key_size);
+        let json = json!({
+            "keyData": {
+                "typeUrl": AES_GCM_TYPE_URL,
+                "value": BASE64.encode(serialize_aes_gcm_key(&key_value)),
+                "keyMaterialType": "SYMMETRIC",
+            },
+            "status": "ENABLED",
+            "keyId": key_id,
+            "outputPrefixType": "TINK",
+        });
+        TinkKey { key_id, enabled: true, prefix: prefix("TINK", key_id).unwrap(), aes_gcm: Some(key_value), json }
+    }
+}
+
+/// Cleartext Google Tink keyset (the JSON written by `tinkey --out-format json` or
+/// `CleartextKeysetHandle.write`). `encrypt()`/`decrypt()` speak Tink's AEAD wire
+/// format for AES-GCM keys: `prefix (5, none for RAW) | iv (12) | ciphertext | tag`.
+/// Keys of other types are carried through `to_json()` but never used.
+#[pyclass]
+pub struct TinkKeyset {
+    primary_key_id: u32,
+    keys: Vec<TinkKey>,
+}
+
+impl TinkKeyset {
+    fn new_key_id(&self) -> u32 {
+        loop {
+            let id = u32::from_be_bytes(random_bytes(4).try_into().unwrap());
+            if id != 0 && self.keys.iter().all(|k| k.key_id != id) {
+                return id;
+            }
+        }
+    }
+
+    fn primary(&self) -> Result<(&TinkKey, &[u8]), Error> {
+        let key = self.keys.iter().find(|k| k.key_id == self.primary_key_id && k.enabled)
+            .ok_or_else(|| Error::crypto("Tink keyset has no enabled primary key"))?;
+        let material = key.aes_gcm.as_deref().ok_or_else(|| Error::crypto("Tink primary key is not an AesGcmKey"))?;
+        Ok((key, material))
+    }
+
+    fn seal(&self, plaintext: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+        let (key, material) = self.primary()?;
+        let iv = random_bytes(IV_LEN);
+        let mut out = key.prefix.clone();
+        out.extend_from_slice(&iv);
+        out.extend_from_slice(&gcm_seal(material, &iv, plaintext, associated_data)?);
+        Ok(out)
+    }
+
+    fn open(&self, ciphertext: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+        let usable = || self.keys.iter().filter(|k| k.enabled)
+            .filter_map(|k| k.aes_gcm.as_deref().map(|material| (k, material)));
+        let attempt = |material: &[u8], data: &[u8]| {
+            if data.len() < IV_LEN + TAG_LEN {
+                return None;
+            }
+            let (iv, sealed) = data.split_at(IV_LEN);
+            gcm_open(material, iv, sealed, associated_data)
+        };
+
+        // Keys whose prefix matches first, then RAW keys, like Tink's primitive set
+        if ciphertext.len() >= PREFIX_LEN {
+            let (prefix, rest) = ciphertext.split_at(PREFIX_LEN);
+            for (_, material) in usable().filter(|(k, _)| k.prefix == prefix) {
+                if let Some(plaintext) = attempt(material, rest) {
+                    return Ok(plaintext);
+                }
+            }
+        }
+        for (_, material) in usable().filter(|(k, _)| k.prefix.is_empty()) {
+            if let Some(plaintext) = attempt(material, ciphertext) {
+                return Ok(plaintext);
+            }
+        }
+        Err(Error::decryption("Decryption failed: no key in the Tink keyset matches").with_code("no_matching_key"))
+    }
+}
+
+#[pymethods]
+impl TinkKeyset {
+    /// New keyset holding one fresh AES-GCM key (`key_size` 16 or 32) with TINK prefix.
+    #[staticmethod]
+    #[pyo3(signature = (key_size=32))]
+    fn generate(key_size: usize) -> PyResult<Self> {
+        let mut keyset = TinkKeyset { primary_key_id: 0, keys: Vec::new() };
+        keyset.add_key(key_size, true)?;
+        Ok(keyset)
+    }
+
+    /// Parses a cleartext JSON keyset. Encrypted keysets (`encryptedKeyset`) must be
+    /// decrypted with Tink first.
+    #[staticmethod]
+    fn from_json(keyset: &str) -> PyResult<Self> {
+        let parse = || -> Result<TinkKeyset, Error> {
+            let value: Value = serde_json::from_str(keyset)
+                .map_err(|e| Error::invalid_format(format!("Invalid Tink keyset JSON: {}", e)))?;
+            if value.get("encryptedKeyset").is_some() {
+                return Err(Error::crypto("Encrypted Tink keysets are not supported; export a cleartext keyset"));
+            }
+            let primary_key_id = value.get("primaryKeyId").and_then(Value::as_u64)
+                .and_then(|id| u32::try_from(id).ok())
+                .ok_or_else(|| Error::invalid_format("Invalid Tink keyset: missing primaryKeyId"))?;
+            let keys = value.get("key").and_then(Value::as_array)
+                .ok_or_else(|| Error::invalid_format("Invalid Tink keyset: missing key list"))?
+                .iter().map(TinkKey::parse).collect::<Result<Vec<_>, _>>()?;
+            Ok(TinkKeyset { primary_key_id, keys })
+        };
+        parse().map_err(crate::error::to_pyerr)
+    }
+
+    /// The keyset as Tink JSON, loadable with `tink.json_proto_keyset_format.parse`.
+    fn to_json(&self) -> String {
+        json!({
+            "primaryKeyId": self.primary_key_id,
+            "key": self.keys.iter().map(|k| k.json.clone()).collect::<Vec<_>>(),
+        }).to_string()
+    }
+
+    /// Adds a fresh AES-GCM key and returns its key ID. With `primary=True` new
+    /// ciphertexts use it; older ones still decrypt with the previous keys.
+    #[pyo3(signature = (key_size=32, primary=false))]
+    fn add_key(&mut self, key_size: usize, primary: bool) -> PyResult<u32> {
+        if key_size != 16 && key_size != 32 {
+            return Err(crate::error::to_pyerr(Error::config("key_size must be 16 or 32").with_input("key_size")));
+        }
+        let key_id = self.new_key_id();
+        self.keys.push(TinkKey::generate(key_id, key_size));
+        if primary {
+            self.primary_key_id = key_id;
+        }
+        Ok(key_id)
+    }
+
+    #[getter]
+    fn primary_key_id(&self) -> u32 {
+        self.primary_key_id
+    }
+
+    #[getter]
+    fn key_ids(&self) -> Vec<u32> {
+        self.keys.iter().map(|k| k.key_id).collect()
+    }
+
+    /// Encrypts with the primary key, as Tink's `Aead.encrypt(plaintext, associated_data)`.
+    #[pyo3(signature = (plaintext, associated_data=b"".as_slice()))]
+    fn encrypt(&self, py: Python<'_>, plaintext: &[u8], associated_data: &[u8]) -> PyResult<Vec<u8>> {
+        let operation = Operation::new("encrypt", "tink-aes-gcm").with_aad(associated_data);
+        py.detach(|| operation.run(|| self.seal(plaintext, associated_data))).map_err(crate::error::to_pyerr)
+    }
+
+    /// Decrypts a Tink AEAD ciphertext made with any enabled AES-GCM key in the keyset.
+    #[pyo3(signature = (ciphertext, associated_data=b"".as_slice()))]
+    fn decrypt(&self, py: Python<'_>, ciphertext: &[u8], associated_data: &[u8]) -> PyResult<Vec<u8>> {
+        let operation = Operation::new("decrypt", "tink-aes-gcm").with_aad(associated_data);
+        py.detach(|| operation.run(|| self.open(ciphertext, associated_data))).map_err(crate::error::to_pyerr)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("TinkKeyset(primary_key_id={}, keys={})", self.primary_key_id, self.keys.len())
+    }
+}
@@ -0,0 +1,61 @@
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL};
+use hmac::{digest::KeyInit, Hmac, Mac};
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+use crate::cipher::Algorithm;
+use crate::deterministic::context_salt;
+use crate::envelope::{decode_token, default_extensions, encode_token, Envelope};
+use crate::error::Error;
+use crate::kdf::KdfParams;
+use crate::master_key::MasterKeyArg;
+
+const TOKEN_LABEL: &[u8] = b"k-services/tokenize";
+const TOKEN_PREFIX: &str = "tok_";
+/// 128 bits: collisions between distinct values are not a practical concern.
+const TOKEN_BYTES: usize = 16;
+
+fn surrogate(value: &[u8], master_key: &[u8], context: &[u8]) -> Result<String, Error> {
+    let key = KdfParams::default().derive(master_key, &context_salt(TOKEN_LABEL, context))?;
+    let mut mac = <Hmac<Sha256> as KeyInit>::new_from_slice(key.as_slice()).expect("HMAC accepts any key length");
+    mac.update(value);
+    Ok(format!("{}{}", TOKEN_PREFIX, BASE64_URL.encode(&mac.finalize().into_bytes()[..TOKEN_BYTES])))
+}
+
+/// The encrypted original is bound to its surrogate, so it can't be swapped under another token.
+fn record_aad(context: &[u8], token: &str) -> Vec<u8> {
+    [context, b"\0", token.as_bytes()].concat()
+}
+
+/// Replaces `value` with a stable surrogate token and returns `(token, encrypted)`:
+/// the same value under the same master key and `context` always gets the same
+/// token (so joins and counts on tokenized data still work), while `encrypted` is
+/// a regular, randomized envelope token of the original. Store `token -> encrypted`
+/// in the vault table and hand out only the token.
+#[pyfunction]
+pub fn tokenize(py: Python<'_>, value: &[u8], master_key: MasterKeyArg, context: &str) -> PyResult<(String, String)> {
+    py.detach(|| -> Result<(String, String), Error> {
+        let token = surrogate(value, &master_key, context.as_bytes())?;
+        let envelope = Envelope::seal(
+            Algorithm::AES_256_GCM, KdfParams::default(), default_extensions(None),
+            value, &master_key, &record_aad(context.as_bytes(), &token)
+        )?;
+        Ok((token, encode_token(&envelope)?))
+    }).map_err(crate::error::to_pyerr)
+}
+
+/// Returns the original value for `token`, given the `encrypted` record stored with
+/// it by `tokenize`. Fails if the record belongs to a different token or context.
+#[pyfunction]
+pub fn detokenize<'py>(
+    py: Python<'py>, token: &str, encrypted: &str, master_key: MasterKeyArg, context: &str
+) -> PyResult<Bound<'py, PyBytes>> {
+    py.detach(|| -> Result<Zeroizing<Vec<u8>>, Error> {
+        if !token.starts_with(TOKEN_PREFIX) {
+            return Err(Error::invalid_format("Not a token produced by tokenize()").with_code("unrecognized_format"));
+        }
+        decode_token(encrypted)?.open(&master_key, &record_aad(context.as_bytes(), token))
+    }).map(|plaintext| PyBytes::new(py, &plaintext)).map_err(crate::error::to_pyerr)
+}